@@ -0,0 +1,210 @@
+// ============================================
+// BLOCKY: Generate Vertices + Generate Faces
+// ============================================
+// Alternative to kernels 1 and 4 drawing every solid voxel (negative density)
+// as a unit cube, voxel p spanning p..p + 1. The prefix sum and compaction
+// kernels are shared with surface nets.
+//
+// Exposed faces are merged greedily within each slice: into runs along the
+// face's u axis, then runs of equal extent in consecutive rows along v into
+// rectangles. A rectangle belongs to the voxel at its minimum corner, which
+// each thread can decide from the field alone.
+//
+// Each voxel owns one quad slot per face direction (+X, -X, +Y, -Y, +Z, -Z)
+// and 4 vertex slots per quad, so vertices aren't shared and normals stay flat.
+
+#import sculpter::density::{DensityDecode, decode_density, density_word, source_index, source_uvw}
+
+// STEP 1: Define the bind group layout (shared by both entry points)
+// With the DENSITY_TEXTURE shader def the field is a 3D texture instead (plus a sampler at 9)
+#ifdef DENSITY_TEXTURE
+@group(0) @binding(0)
+var density_texture: texture_3d<f32>;  // Input scalar field (red channel)
+
+@group(0) @binding(9)
+var density_sampler: sampler;  // Linear sampler for hardware trilinear filtering
+#else
+@group(0) @binding(0)
+var<storage, read> density_field: array<u32>;  // Input scalar field (f32 bits or 4 packed bytes per u32)
+#endif
+
+@group(0) @binding(1)
+var<storage, read_write> vertices: array<f32>;  // Output quad corners, 4 per face slot
+
+@group(0) @binding(2)
+var<storage, read_write> vertex_valid: array<u32>;  // Output validity flags per vertex slot
+
+@group(0) @binding(3)
+var<uniform> dimensions: vec3<u32>;  // Grid dimensions (x, y, z)
+
+@group(0) @binding(4)
+var<uniform> density_decode: DensityDecode;
+
+// Cells this dispatch covers (min inclusive, max exclusive)
+struct MeshingRegion {
+    min: vec3<u32>,
+    max: vec3<u32>,
+}
+
+@group(0) @binding(5)
+var<uniform> region: MeshingRegion;
+
+// Must match SurfaceNetsParams in generate_vertices.wgsl (unused by this algorithm)
+struct SurfaceNetsParams {
+    vertex_placement: u32,
+    relaxation_iterations: u32,
+    relaxation_strength: f32,
+    occlusion_rays: u32,
+    occlusion_distance: f32,
+    materials: u32,
+    stride: u32,
+    source_dimensions: vec3<u32>,
+}
+
+@group(0) @binding(6)
+var<uniform> params: SurfaceNetsParams;
+
+@group(0) @binding(7)
+var<storage, read_write> faces: array<u32>;  // Output: 4 vertex slots per quad
+
+@group(0) @binding(8)
+var<storage, read_write> face_valid: array<u32>;  // Output: which voxels own a quad
+
+// Quads owned by each voxel, one per face direction
+const FACES_PER_VOXEL: u32 = 6u;
+
+// Corners per quad
+const QUAD_CORNERS: u32 = 4u;
+
+// ===========================================================
+// Helper functions MUST be at global scope in WGSL
+// ===========================================================
+#ifdef DENSITY_TEXTURE
+    fn sample_density(p: vec3<u32>) -> f32 {
+        let uvw = source_uvw(p, params.stride, params.source_dimensions);
+        return textureSampleLevel(density_texture, density_sampler, uvw, 0.0).r;
+    }
+#else
+    fn sample_density(p: vec3<u32>) -> f32 {
+        let index = source_index(p, params.stride, params.source_dimensions);
+        return decode_density(density_field[density_word(index, density_decode)], index, density_decode);
+    }
+#endif
+
+fn grid_index(p: vec3<u32>) -> u32 {
+    return p.x + p.y * dimensions.x + p.z * dimensions.x * dimensions.y;
+}
+
+fn solid(p: vec3<i32>) -> bool {
+    // Everything outside the grid is empty, so the border is closed
+    if (any(p < vec3<i32>(0)) || any(p >= vec3<i32>(dimensions))) {
+        return false;
+    }
+    return sample_density(vec3<u32>(p)) < 0.0;
+}
+
+fn exposed(p: vec3<i32>, normal: vec3<i32>) -> bool {
+    return solid(p) && !solid(p + normal);
+}
+
+// Length along u of the run of exposed faces starting at p, 0 if p doesn't start one
+fn run_length(p: vec3<i32>, normal: vec3<i32>, u: vec3<i32>) -> i32 {
+    if (!exposed(p, normal) || exposed(p - u, normal)) {
+        return 0;
+    }
+    var width = 1;
+    while (exposed(p + u * width, normal)) {
+        width = width + 1;
+    }
+    return width;
+}
+
+fn unit_axis(axis: u32) -> vec3<i32> {
+    return select(vec3<i32>(0), vec3<i32>(1), vec3<bool>(axis == 0u, axis == 1u, axis == 2u));
+}
+
+// Normal, u and v of face direction d, with u x v along the axis
+// (must match face_frame in sculpter-core's blocky.rs)
+struct FaceFrame {
+    normal: vec3<i32>,
+    u: vec3<i32>,
+    v: vec3<i32>,
+}
+
+fn face_frame(d: u32) -> FaceFrame {
+    let axis = d / 2u;
+    let sign = select(-1, 1, (d & 1u) == 0u);
+    return FaceFrame(unit_axis(axis) * sign, unit_axis((axis + 1u) % 3u), unit_axis((axis + 2u) % 3u));
+}
+
+// STEP 2: Write the corners of the quad each voxel owns in each direction
+// (must match owned_quad in sculpter-core's blocky.rs)
+// Dispatched over the voxels of the region, one more than its cells
+@compute @workgroup_size(8, 8, 8)
+fn generate_blocky_vertices(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let voxel = region.min + global_id;
+    if (any(voxel > region.max) || any(voxel >= dimensions)) {
+        return;  // Outside the region being regenerated
+    }
+
+    let p = vec3<i32>(voxel);
+    for (var d = 0u; d < FACES_PER_VOXEL; d = d + 1u) {
+        let base_slot = (grid_index(voxel) * FACES_PER_VOXEL + d) * QUAD_CORNERS;
+        let frame = face_frame(d);
+        let width = run_length(p, frame.normal, frame.u);
+        if (width == 0 || run_length(p - frame.v, frame.normal, frame.u) == width) {
+            // Not the first row of a rectangle, clear old corners
+            for (var c = 0u; c < QUAD_CORNERS; c = c + 1u) {
+                vertex_valid[base_slot + c] = 0u;
+            }
+            continue;
+        }
+        var height = 1;
+        while (run_length(p + frame.v * height, frame.normal, frame.u) == width) {
+            height = height + 1;
+        }
+
+        // Faces on the positive side lie one voxel further along the normal
+        let base = vec3<f32>(p + max(frame.normal, vec3<i32>(0)));
+        let u = vec3<f32>(frame.u * width);
+        let v = vec3<f32>(frame.v * height);
+        var corners = array<vec3<f32>, 4>(base, base + u, base + u + v, base + v);
+        if ((d & 1u) == 1u) {
+            // Reverse the winding of faces looking down their axis
+            corners = array<vec3<f32>, 4>(base, base + v, base + u + v, base + u);
+        }
+        for (var c = 0u; c < QUAD_CORNERS; c = c + 1u) {
+            let slot = base_slot + c;
+            vertices[slot * 3u + 0u] = corners[c].x;
+            vertices[slot * 3u + 1u] = corners[c].y;
+            vertices[slot * 3u + 2u] = corners[c].z;
+            vertex_valid[slot] = 1u;
+        }
+    }
+}
+
+// STEP 3: Emit a quad for every direction the voxel owns corners in
+@compute @workgroup_size(8, 8, 8)
+fn generate_blocky_faces(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let voxel = region.min + global_id;
+    if (any(voxel > region.max) || any(voxel >= dimensions)) {
+        return;  // Outside the region being regenerated
+    }
+
+    for (var d = 0u; d < FACES_PER_VOXEL; d = d + 1u) {
+        let face_slot = grid_index(voxel) * FACES_PER_VOXEL + d;
+        let base_slot = face_slot * QUAD_CORNERS;
+        if (vertex_valid[base_slot] == 0u) {
+            face_valid[face_slot] = 0u;
+            continue;
+        }
+        for (var c = 0u; c < QUAD_CORNERS; c = c + 1u) {
+            faces[face_slot * 4u + c] = base_slot + c;
+        }
+        face_valid[face_slot] = 1u;
+    }
+}
@@ -4,7 +4,7 @@
 // This shader removes invalid faces from the array, creating a dense
 // packed array. Similar to compact vertices, but operates on quads (4 indices).
 
-// STEP 1: Define bind group
+// STEP 1: Define bind group (0-4)
 @group(0) @binding(0)
 var<storage, read> faces: array<u32>;  // Input: sparse faces (4 vertex indices per face, with gaps)
 
@@ -17,6 +17,9 @@ var<storage, read> face_indices: array<u32>;  // Input: compacted indices from p
 @group(0) @binding(3)
 var<storage, read_write> compacted_faces: array<u32>;  // Output: dense face array
 
+@group(0) @binding(4)
+var<storage, read> vertex_indices: array<u32>;  // Input: compacted vertex index of each cell
+
 // STEP 2: Define workgroup size
 // Using 256 threads for 1D processing of the face array
 @compute @workgroup_size(256, 1, 1)
@@ -44,11 +47,11 @@ fn compact_faces(
         let src_base = thread_idx * 4u;      // Source position in sparse array
         let dst_base = compacted_idx * 4u;   // Destination position in dense array
         
-        // Copy all four vertex indices that define this quad face
-        compacted_faces[dst_base + 0u] = faces[src_base + 0u];  // Vertex 0 (bottom-left)
-        compacted_faces[dst_base + 1u] = faces[src_base + 1u];  // Vertex 1 (bottom-right)
-        compacted_faces[dst_base + 2u] = faces[src_base + 2u];  // Vertex 2 (top-right)
-        compacted_faces[dst_base + 3u] = faces[src_base + 3u];  // Vertex 3 (top-left)
+        // Faces hold cell indices, translate them to compacted vertex indices
+        compacted_faces[dst_base + 0u] = vertex_indices[faces[src_base + 0u]];  // Vertex 0 (bottom-left)
+        compacted_faces[dst_base + 1u] = vertex_indices[faces[src_base + 1u]];  // Vertex 1 (bottom-right)
+        compacted_faces[dst_base + 2u] = vertex_indices[faces[src_base + 2u]];  // Vertex 2 (top-right)
+        compacted_faces[dst_base + 3u] = vertex_indices[faces[src_base + 3u]];  // Vertex 3 (top-left)
     }
     // STEP 8: Invalid faces are simply skipped
     // No else clause needed - invalid entries are not copied
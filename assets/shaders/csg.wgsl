@@ -0,0 +1,103 @@
+// ============================================
+// KERNEL 0b: CSG
+// ============================================
+// This shader composites a signed distance primitive or another field into the
+// density buffer of a `GpuDensity` volume, after it is generated and before it
+// is meshed, as `sculpter_core::composite` does on the CPU.
+
+#import sculpter::sdf::{EMPTY, SdfPart, combine, combine_part}
+
+// STEP 1: Define the bind group layout
+// These match the Rust side BindGroupLayoutEntries in order (0, 1, 2, 3)
+@group(0) @binding(0)
+var<storage, read_write> density_field: array<f32>;  // Densities composited into
+
+// The source and where it lies, built from CompositeDensity
+struct CsgParams {
+    inverse: mat4x4<f32>,  // Grid space to the source's space
+    region_min: vec3<u32>,  // First sample the shape can change
+    region_size: vec3<u32>,  // Samples the kernel is dispatched over
+    dimensions: vec3<u32>,  // Grid dimensions (x, y, z)
+    source_dimensions: vec3<u32>,  // Grid dimensions of a field source
+    source: u32,
+    part_count: u32,  // Parts of a shape source
+    operation: u32,
+    radius: f32,  // Blend radius, 0 = sharp
+    scale: f32,  // Source distances to samples
+}
+
+@group(0) @binding(1)
+var<uniform> params: CsgParams;
+
+@group(0) @binding(2)
+var<storage, read> source_field: array<f32>;  // Densities of a field source
+
+@group(0) @binding(3)
+var<storage, read> parts: array<SdfPart>;  // Primitives of a shape source
+
+// Must match the SOURCE_* constants in csg.rs
+const SOURCE_FIELD: u32 = 0u;
+const SOURCE_SHAPE: u32 = 1u;
+
+// ===========================================================
+// Helper function MUST be at global scope in WGSL
+// ===========================================================
+
+fn grid_index(p: vec3<u32>) -> u32 {
+    return p.z * params.dimensions.y * params.dimensions.x + p.y * params.dimensions.x + p.x;
+}
+
+fn sample_source(x: u32, y: u32, z: u32) -> f32 {
+    let dimensions = params.source_dimensions;
+    return source_field[z * dimensions.y * dimensions.x + y * dimensions.x + x];
+}
+
+// Trilinear interpolation of the source field, clamped to its grid
+fn sample_trilinear(p: vec3<f32>) -> f32 {
+    let dimensions = params.source_dimensions;
+    let q = clamp(p, vec3<f32>(0.0), vec3<f32>(dimensions - 1u));
+    let base = min(vec3<u32>(floor(q)), dimensions - 2u);
+    let f = q - vec3<f32>(base);
+    let c00 = mix(sample_source(base.x, base.y,      base.z),      sample_source(base.x + 1u, base.y,      base.z),      f.x);
+    let c10 = mix(sample_source(base.x, base.y + 1u, base.z),      sample_source(base.x + 1u, base.y + 1u, base.z),      f.x);
+    let c01 = mix(sample_source(base.x, base.y,      base.z + 1u), sample_source(base.x + 1u, base.y,      base.z + 1u), f.x);
+    let c11 = mix(sample_source(base.x, base.y + 1u, base.z + 1u), sample_source(base.x + 1u, base.y + 1u, base.z + 1u), f.x);
+    return mix(mix(c00, c10, f.y), mix(c01, c11, f.y), f.z);
+}
+
+// Density of the source at p in its own space, negative inside
+fn source_density(p: vec3<f32>) -> f32 {
+    if params.source == SOURCE_SHAPE {
+        var density = EMPTY;
+        for (var i = 0u; i < params.part_count; i++) {
+            density = combine_part(parts[i], density, p);
+        }
+        return density;
+    }
+    // Beyond its samples the field is at least as far out as its box
+    let density = sample_trilinear(p);
+    let outside = distance(p, clamp(p, vec3<f32>(0.0), vec3<f32>(params.source_dimensions - 1u)));
+    if outside > 0.0 {
+        return max(density, outside);
+    }
+    return density;
+}
+
+// ===========================================================
+// STEP 2: Main compute shader entry point
+// ===========================================================
+@compute @workgroup_size(8, 8, 8)
+fn csg(
+    @builtin(global_invocation_id) global_id: vec3<u32>,  // Unique thread ID across all workgroups
+) {
+    // Each thread composites one grid point of the region
+    if any(global_id >= params.region_size) {
+        return;
+    }
+    let sample = params.region_min + global_id;
+    let p = (params.inverse * vec4<f32>(vec3<f32>(sample), 1.0)).xyz;
+    let shape = source_density(p) * params.scale;
+
+    let index = grid_index(sample);
+    density_field[index] = combine(params.operation, density_field[index], shape, params.radius);
+}
@@ -0,0 +1,63 @@
+// ============================================
+// Density input
+// ============================================
+// Reading a volume's densities, shared by the meshing kernels. Each kernel
+// binds the field itself (a buffer, or a texture with the DENSITY_TEXTURE
+// shader def) and wraps these in its own sample_density:
+//
+//     #import sculpter::density::{DensityDecode, decode_density, density_word, source_index}
+//
+// LOD levels read every `stride`-th sample of a field `size` samples across.
+#define_import_path sculpter::density
+
+// How the density buffer is encoded
+// Quantized values decode as: density = byte * scale + offset
+struct DensityDecode {
+    format: u32,
+    scale: f32,
+    offset: f32,
+}
+
+// Must match the DENSITY_FORMAT_* constants in quantize.rs
+const DENSITY_FORMAT_F32: u32 = 0u;
+const DENSITY_FORMAT_U8: u32 = 1u;
+const DENSITY_FORMAT_I8: u32 = 2u;
+
+// Index of grid point p in the bound field
+fn source_index(p: vec3<u32>, stride: u32, size: vec3<u32>) -> u32 {
+    let source = p * stride;
+    return source.x + source.y * size.x + source.z * size.x * size.y;
+}
+
+// Texture coordinates of grid point p, at the texel centre so grid points map
+// exactly onto texels
+fn source_uvw(p: vec3<u32>, stride: u32, size: vec3<u32>) -> vec3<f32> {
+    return (vec3<f32>(p * stride) + 0.5) / vec3<f32>(size);
+}
+
+// Word of the density buffer holding sample `index`
+fn density_word(index: u32, decode: DensityDecode) -> u32 {
+    if (decode.format == DENSITY_FORMAT_F32) {
+        return index;
+    }
+    // 4 bytes per u32, little endian
+    return index / 4u;
+}
+
+// Density of sample `index` out of the word holding it
+fn decode_density(word: u32, index: u32, decode: DensityDecode) -> f32 {
+    switch decode.format {
+        case DENSITY_FORMAT_U8: {
+            let byte = extractBits(word, (index % 4u) * 8u, 8u);
+            return f32(byte) * decode.scale + decode.offset;
+        }
+        case DENSITY_FORMAT_I8: {
+            // extractBits on i32 sign-extends the byte
+            let byte = extractBits(bitcast<i32>(word), (index % 4u) * 8u, 8u);
+            return f32(byte) * decode.scale + decode.offset;
+        }
+        default: {
+            return bitcast<f32>(word);
+        }
+    }
+}
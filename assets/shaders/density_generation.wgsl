@@ -0,0 +1,44 @@
+// ============================================
+// Density generation bindings
+// ============================================
+// The density buffer and parameters every density generation kernel binds
+// first, shared by the built-in generator and compiled `SdfGraph`s.
+#define_import_path sculpter::density_generation
+
+// Where the samples lie and the noise stack, built from GpuDensity
+struct DensityGenerationParams {
+    origin: vec3<f32>,  // Position of the first sample
+    spacing: vec3<f32>,  // Distance between neighbouring samples
+    dimensions: vec3<u32>,  // Grid dimensions (x, y, z)
+    noise: u32,
+    basis: u32,  // NOISE_BASIS_* of sculpter::noise
+    terrain: u32,  // 1 = densities are the height above the ground
+    seed: u32,
+    octaves: u32,
+    frequency: f32,
+    lacunarity: f32,
+    gain: f32,
+    amplitude: f32,
+    warp: f32,  // Domain warp distance, 0 = off
+    ground: f32,  // Height of the surface where the noise is zero
+    vertical_scale: f32,  // Height of a full heightmap texel
+    sdf_parts: u32,  // Parts of the signed distance shape
+}
+
+@group(0) @binding(0)
+var<storage, read_write> density_field: array<f32>;  // Output scalar field
+
+@group(0) @binding(1)
+var<uniform> params: DensityGenerationParams;
+
+// Position of the grid point a thread fills
+fn sample_position(global_id: vec3<u32>) -> vec3<f32> {
+    return params.origin + vec3<f32>(global_id) * params.spacing;
+}
+
+fn store_density(global_id: vec3<u32>, value: f32) {
+    let index = global_id.z * params.dimensions.y * params.dimensions.x
+        + global_id.y * params.dimensions.x
+        + global_id.x;
+    density_field[index] = value;
+}
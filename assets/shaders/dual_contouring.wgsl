@@ -0,0 +1,242 @@
+// ============================================
+// DUAL CONTOURING: Generate Vertices
+// ============================================
+// Alternative to kernel 1 with the same bindings and output. Each surface cell
+// gets one vertex minimising the quadric error function (QEF) of its Hermite
+// data: the edge crossings and the field normals there. Vertices land on the
+// intersection of the tangent planes, so sharp edges and corners stay crisp.
+// Faces come from the surface nets generate_faces kernel.
+
+#import sculpter::density::{DensityDecode, decode_density, density_word, source_index, source_uvw}
+
+// STEP 1: Define the bind group layout
+// These match the Rust side BindGroupLayoutEntries in order (0, 1, 2, 3, 4, 5, 6)
+// With the DENSITY_TEXTURE shader def the field is a 3D texture instead (plus a sampler at 7)
+#ifdef DENSITY_TEXTURE
+@group(0) @binding(0)
+var density_texture: texture_3d<f32>;  // Input scalar field (red channel)
+
+@group(0) @binding(7)
+var density_sampler: sampler;  // Linear sampler for hardware trilinear filtering
+#else
+@group(0) @binding(0)
+var<storage, read> density_field: array<u32>;  // Input scalar field (f32 bits or 4 packed bytes per u32)
+#endif
+
+@group(0) @binding(1)
+var<storage, read_write> vertices: array<f32>;  // Output vertex positions (x,y,z packed)
+
+@group(0) @binding(2)
+var<storage, read_write> vertex_valid: array<u32>;  // Output validity flags (1 = valid vertex)
+
+@group(0) @binding(3)
+var<uniform> dimensions: vec3<u32>;  // Grid dimensions (x, y, z)
+
+@group(0) @binding(4)
+var<uniform> density_decode: DensityDecode;
+
+// Cells this dispatch covers (min inclusive, max exclusive)
+// Cells outside keep the vertices written by earlier dispatches
+struct MeshingRegion {
+    min: vec3<u32>,
+    max: vec3<u32>,
+}
+
+@group(0) @binding(5)
+var<uniform> region: MeshingRegion;
+
+// Must match SurfaceNetsParams in generate_vertices.wgsl (unused by this algorithm)
+struct SurfaceNetsParams {
+    vertex_placement: u32,
+    relaxation_iterations: u32,  // Extra gradient steps after placement, 0 = off
+    relaxation_strength: f32,  // Fraction of each step taken
+    occlusion_rays: u32,  // Ambient occlusion rays per vertex, 0 = off
+    occlusion_distance: f32,  // How far each ray marches, in voxels
+    materials: u32,  // 1 when the volume has a MaterialField, 0 = off
+    stride: u32,  // Samples skipped per grid step, 1 = full resolution (LOD levels)
+    source_dimensions: vec3<u32>,  // Dimensions of the bound field, stride times the grid
+}
+
+@group(0) @binding(6)
+var<uniform> params: SurfaceNetsParams;
+
+// Eigenvalues below this are treated as zero when inverting the QEF, so
+// directions the normals don't constrain stay at the mass point
+// Must match QEF_THRESHOLD in dual_contouring.rs
+const QEF_THRESHOLD: f32 = 0.1;
+
+// Jacobi sweeps of the eigen solve, must match QEF_SWEEPS in dual_contouring.rs
+const QEF_SWEEPS: u32 = 4u;
+
+// ===========================================================
+// Helper function MUST be at global scope in WGSL
+// ===========================================================
+#ifdef DENSITY_TEXTURE
+    fn sample_density(x: u32, y: u32, z: u32) -> f32 {
+        let uvw = source_uvw(vec3<u32>(x, y, z), params.stride, params.source_dimensions);
+        return textureSampleLevel(density_texture, density_sampler, uvw, 0.0).r;
+    }
+#else
+    fn sample_density(x: u32, y: u32, z: u32) -> f32 {
+        let index = source_index(vec3<u32>(x, y, z), params.stride, params.source_dimensions);
+        return decode_density(density_field[density_word(index, density_decode)], index, density_decode);
+    }
+#endif
+
+// Trilinear interpolation of the grid at any point inside the volume
+fn sample_trilinear(p: vec3<f32>) -> f32 {
+    let q = clamp(p, vec3<f32>(0.0), vec3<f32>(dimensions - 1u));
+    let base = min(vec3<u32>(floor(q)), dimensions - 2u);
+    let f = q - vec3<f32>(base);
+    let c00 = mix(sample_density(base.x, base.y,      base.z),      sample_density(base.x + 1u, base.y,      base.z),      f.x);
+    let c10 = mix(sample_density(base.x, base.y + 1u, base.z),      sample_density(base.x + 1u, base.y + 1u, base.z),      f.x);
+    let c01 = mix(sample_density(base.x, base.y,      base.z + 1u), sample_density(base.x + 1u, base.y,      base.z + 1u), f.x);
+    let c11 = mix(sample_density(base.x, base.y + 1u, base.z + 1u), sample_density(base.x + 1u, base.y + 1u, base.z + 1u), f.x);
+    return mix(mix(c00, c10, f.y), mix(c01, c11, f.y), f.z);
+}
+
+// Central differences of the interpolated field, half a voxel either side
+fn density_gradient(p: vec3<f32>) -> vec3<f32> {
+    let h = 0.5;
+    return vec3<f32>(
+        sample_trilinear(p + vec3<f32>(h, 0.0, 0.0)) - sample_trilinear(p - vec3<f32>(h, 0.0, 0.0)),
+        sample_trilinear(p + vec3<f32>(0.0, h, 0.0)) - sample_trilinear(p - vec3<f32>(0.0, h, 0.0)),
+        sample_trilinear(p + vec3<f32>(0.0, 0.0, h)) - sample_trilinear(p - vec3<f32>(0.0, 0.0, h)),
+    ) / (2.0 * h);
+}
+
+// Jacobi rotation zeroing the (p, q) entry of the symmetric matrix a
+// Matrices are column major, so m[column][row]
+fn jacobi_rotation(a: mat3x3<f32>, p: u32, q: u32) -> mat3x3<f32> {
+    var j = mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    let apq = a[q][p];
+    if (abs(apq) < 1e-12) {
+        return j;  // Already diagonal here
+    }
+    let theta = (a[q][q] - a[p][p]) / (2.0 * apq);
+    let t = select(-1.0, 1.0, theta >= 0.0) / (abs(theta) + sqrt(theta * theta + 1.0));
+    let c = 1.0 / sqrt(t * t + 1.0);
+    let s = t * c;
+    j[p][p] = c;
+    j[q][q] = c;
+    j[q][p] = s;   // Row p, column q
+    j[p][q] = -s;  // Row q, column p
+    return j;
+}
+
+// Least squares solution of ata * x = atb, ignoring near-singular directions
+fn solve_qef(ata: mat3x3<f32>, atb: vec3<f32>) -> vec3<f32> {
+    // Eigen decomposition ata = v * diag(a) * transpose(v)
+    var a = ata;
+    var v = mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    for (var sweep = 0u; sweep < QEF_SWEEPS; sweep = sweep + 1u) {
+        for (var pair = 0u; pair < 3u; pair = pair + 1u) {
+            // Pairs (0, 1), (0, 2), (1, 2)
+            let p = select(0u, 1u, pair == 2u);
+            let q = select(pair + 1u, 2u, pair == 2u);
+            let j = jacobi_rotation(a, p, q);
+            a = transpose(j) * a * j;
+            v = v * j;
+        }
+    }
+
+    // Pseudo-inverse of the diagonal, applied in the eigenbasis
+    let b = transpose(v) * atb;
+    var x = vec3<f32>(0.0);
+    for (var i = 0u; i < 3u; i = i + 1u) {
+        if (a[i][i] > QEF_THRESHOLD) {
+            x[i] = b[i] / a[i][i];
+        }
+    }
+    return v * x;
+}
+
+// STEP 2: Define workgroup size
+// Must match the WORKGROUP_SIZE constant in Rust (8x8x8 = 512 threads per workgroup)
+@compute @workgroup_size(8, 8, 8)
+fn generate_dc_vertices(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let cell = region.min + global_id;
+    if (any(cell >= region.max)) {
+        return;  // Outside the region being regenerated
+    }
+    if (any(cell + 1u >= dimensions)) {
+        return;  // Cells need all 8 corners
+    }
+    let cell_index = cell.x + cell.y * dimensions.x + cell.z * dimensions.x * dimensions.y;
+
+    // Corners and edges in the same order as generate_vertices.wgsl
+    var corners: array<vec3<u32>, 8>;
+    corners[0] = cell + vec3<u32>(0u, 0u, 0u);
+    corners[1] = cell + vec3<u32>(1u, 0u, 0u);
+    corners[2] = cell + vec3<u32>(1u, 1u, 0u);
+    corners[3] = cell + vec3<u32>(0u, 1u, 0u);
+    corners[4] = cell + vec3<u32>(0u, 0u, 1u);
+    corners[5] = cell + vec3<u32>(1u, 0u, 1u);
+    corners[6] = cell + vec3<u32>(1u, 1u, 1u);
+    corners[7] = cell + vec3<u32>(0u, 1u, 1u);
+
+    var edges: array<vec2<u32>, 12>;
+    edges[0]  = vec2<u32>(0u, 1u);
+    edges[1]  = vec2<u32>(1u, 2u);
+    edges[2]  = vec2<u32>(2u, 3u);
+    edges[3]  = vec2<u32>(3u, 0u);
+    edges[4]  = vec2<u32>(4u, 5u);
+    edges[5]  = vec2<u32>(5u, 6u);
+    edges[6]  = vec2<u32>(6u, 7u);
+    edges[7]  = vec2<u32>(7u, 4u);
+    edges[8]  = vec2<u32>(0u, 4u);
+    edges[9]  = vec2<u32>(1u, 5u);
+    edges[10] = vec2<u32>(2u, 6u);
+    edges[11] = vec2<u32>(3u, 7u);
+
+    // Hermite data: crossing points and the field gradient there
+    var points: array<vec3<f32>, 12>;
+    var gradients: array<vec3<f32>, 12>;
+    var crossing_sum = vec3<f32>(0.0);
+    var crossing_count = 0u;
+    for (var i = 0u; i < 12u; i = i + 1u) {
+        let p0 = corners[edges[i].x];
+        let p1 = corners[edges[i].y];
+        let v0 = sample_density(p0.x, p0.y, p0.z);
+        let v1 = sample_density(p1.x, p1.y, p1.z);
+        if (v0 * v1 < 0.0) {
+            let t = v0 / (v0 - v1);
+            let point = vec3<f32>(p0) + t * (vec3<f32>(p1) - vec3<f32>(p0));
+            points[crossing_count] = point;
+            gradients[crossing_count] = density_gradient(point);
+            crossing_sum = crossing_sum + point;
+            crossing_count = crossing_count + 1u;
+        }
+    }
+
+    if (crossing_count == 0u) {
+        vertex_valid[cell_index] = 0u;
+        return;
+    }
+
+    // Minimise the sum of squared distances to the tangent planes, relative to
+    // the mass point so unconstrained directions stay there
+    let mass_point = crossing_sum / f32(crossing_count);
+    var ata = mat3x3<f32>(vec3<f32>(0.0), vec3<f32>(0.0), vec3<f32>(0.0));
+    var atb = vec3<f32>(0.0);
+    for (var i = 0u; i < crossing_count; i = i + 1u) {
+        let gradient = gradients[i];
+        let length_squared = dot(gradient, gradient);
+        if (length_squared < 1e-12) {
+            continue;  // Flat field at the crossing, no plane to fit
+        }
+        let n = gradient / sqrt(length_squared);
+        ata = ata + mat3x3<f32>(n * n.x, n * n.y, n * n.z);
+        atb = atb + n * dot(n, points[i] - mass_point);
+    }
+    let offset = solve_qef(ata, atb);
+    let vertex_pos = clamp(mass_point + offset, vec3<f32>(cell), vec3<f32>(cell) + 1.0);
+
+    let vertex_base_index = cell_index * 3u;
+    vertices[vertex_base_index + 0u] = vertex_pos.x;
+    vertices[vertex_base_index + 1u] = vertex_pos.y;
+    vertices[vertex_base_index + 2u] = vertex_pos.z;
+    vertex_valid[cell_index] = 1u;
+}
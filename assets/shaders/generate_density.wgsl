@@ -0,0 +1,118 @@
+// ============================================
+// KERNEL 0: Generate Density
+// ============================================
+// This shader fills the density buffer of a `GpuDensity` volume before it is
+// meshed, from the built-in noise stack or, with the CUSTOM_DENSITY shader def,
+// from the user's `density` function, with the HEIGHTMAP shader def from a
+// heightmap texture, or with the SDF_DENSITY shader def from signed distance
+// primitives.
+
+#import sculpter::density_generation::{params, sample_position, store_density}
+#import sculpter::noise::{fbm, ridged}
+#ifdef SDF_DENSITY
+#import sculpter::sdf::{EMPTY, SdfPart, combine_part}
+#endif
+#ifdef CUSTOM_DENSITY
+#import sculpter::custom_density::density
+#endif
+
+// STEP 1: Define the bind group layout
+// These match the Rust side BindGroupLayoutEntries in order (0, 1, 2, 3), the
+// density buffer and parameters coming from sculpter::density_generation
+#ifdef HEIGHTMAP
+@group(0) @binding(2)
+var heightmap: texture_2d<f32>;  // Height in the red channel
+
+@group(0) @binding(3)
+var heightmap_sampler: sampler;
+#endif
+
+#ifdef SDF_DENSITY
+@group(0) @binding(2)
+var<storage, read> sdf_parts: array<SdfPart>;  // Primitives of the shape, in order
+#endif
+
+// Must match the NOISE_* constants in gpu_density.rs
+const NOISE_FBM: u32 = 0u;
+const NOISE_RIDGED: u32 = 1u;
+
+// ===========================================================
+// Helper function MUST be at global scope in WGSL
+// ===========================================================
+
+// The noise stack at p, normalised to roughly [-1, 1] (fBm) or [0, 1] (ridged)
+fn noise_stack(p: vec3<f32>, seed: u32) -> f32 {
+    if params.noise == NOISE_RIDGED {
+        return ridged(params.basis, p, seed, params.octaves, params.frequency, params.lacunarity, params.gain);
+    }
+    return fbm(params.basis, p, seed, params.octaves, params.frequency, params.lacunarity, params.gain);
+}
+
+fn noise_density(position: vec3<f32>) -> f32 {
+    var p = position;
+    // Domain warp: offset the lookup by another noise stack
+    if params.warp > 0.0 {
+        let offset = vec3<f32>(
+            noise_stack(p, params.seed + 101u),
+            noise_stack(p + 5.2, params.seed + 211u),
+            noise_stack(p + 1.3, params.seed + 307u),
+        );
+        p += offset * params.warp;
+    }
+    let noise = params.amplitude * noise_stack(p, params.seed);
+    if params.terrain == 0u {
+        // Solid where the noise is positive
+        return -noise;
+    }
+    // Negative below the ground, raised and lowered by the noise
+    return position.y - params.ground - noise;
+}
+
+#ifdef HEIGHTMAP
+// Height above the heightmap, which spans the grid's x and z extent
+fn heightmap_density(global_id: vec3<u32>, position: vec3<f32>) -> f32 {
+    // First and last samples land on the centers of the edge texels
+    let size = vec2<f32>(textureDimensions(heightmap));
+    let grid = vec2<f32>(global_id.xz) / max(vec2<f32>(params.dimensions.xz - 1u), vec2<f32>(1.0));
+    let uv = (grid * (size - 1.0) + 0.5) / size;
+    let height = textureSampleLevel(heightmap, heightmap_sampler, uv, 0.0).r;
+    return position.y - height * params.vertical_scale;
+}
+#endif
+
+#ifdef SDF_DENSITY
+// Distance to the shape, combining its parts in order
+fn sdf_density(position: vec3<f32>) -> f32 {
+    var density = EMPTY;
+    for (var i = 0u; i < params.sdf_parts; i++) {
+        density = combine_part(sdf_parts[i], density, position);
+    }
+    return density;
+}
+#endif
+
+// ===========================================================
+// STEP 2: Main compute shader entry point
+// ===========================================================
+@compute @workgroup_size(8, 8, 8)
+fn generate_density(
+    @builtin(global_invocation_id) global_id: vec3<u32>,  // Unique thread ID across all workgroups
+) {
+    // Each thread fills one grid point
+    if any(global_id >= params.dimensions) {
+        return;
+    }
+    let position = sample_position(global_id);
+
+#ifdef CUSTOM_DENSITY
+    let value = density(position);
+#else ifdef HEIGHTMAP
+    let value = heightmap_density(global_id, position);
+#else ifdef SDF_DENSITY
+    let value = sdf_density(position);
+#else
+    let value = noise_density(position);
+#endif
+
+    store_density(global_id, value);
+}
@@ -12,7 +12,7 @@ var<storage, read> vertex_valid: array<u32>;  // Input: which cells have vertice
 var<storage, read> vertex_indices: array<u32>;  // Input: compacted vertex indices
 
 @group(0) @binding(2)
-var<storage, read_write> faces: array<u32>;  // Output: face data (4 vertex indices per face)
+var<storage, read_write> faces: array<u32>;  // Output: face data (4 cell indices per face)
 
 @group(0) @binding(3)
 var<storage, read_write> face_valid: array<u32>;  // Output: which face slots are valid
@@ -20,6 +20,16 @@ var<storage, read_write> face_valid: array<u32>;  // Output: which face slots ar
 @group(0) @binding(4)
 var<uniform> dimensions: vec3<u32>;  // Grid dimensions
 
+// Cells this dispatch covers (min inclusive, max exclusive)
+// Cells outside keep the faces written by earlier dispatches
+struct MeshingRegion {
+    min: vec3<u32>,
+    max: vec3<u32>,
+}
+
+@group(0) @binding(5)
+var<uniform> region: MeshingRegion;
+
 // ===========================================================
 // Helper function MUST be at global scope in WGSL
 // ===========================================================
@@ -34,9 +44,13 @@ fn generate_faces(
     @builtin(global_invocation_id) global_id: vec3<u32>,
 ) {
     // STEP 3: Get cell coordinates
-    let cell_x = global_id.x;
-    let cell_y = global_id.y;
-    let cell_z = global_id.z;
+    let cell = region.min + global_id;
+    if (any(cell >= region.max)) {
+        return;  // Outside the region being regenerated
+    }
+    let cell_x = cell.x;
+    let cell_y = cell.y;
+    let cell_z = cell.z;
     
     // STEP 4: Boundary check
     // We need to access neighboring cells, so we need to be within bounds
@@ -49,20 +63,24 @@ fn generate_faces(
     // STEP 5: Calculate cell index
     let cell_index = cell_x + cell_y * dimensions.x + cell_z * dimensions.x * dimensions.y;
     
-    // STEP 6: Skip if this cell has no vertex
-    // Can't make faces if there's no vertex here
+    // STEP 6: Calculate base face index for this cell
+    // Each cell can generate up to 3 faces, so we reserve 3 slots per cell
+    // Cell 0 gets face slots 0,1,2; Cell 1 gets slots 3,4,5; etc.
+    let base_face_index = cell_index * 3u;
+
+    // Skip if this cell has no vertex
+    // Can't make faces if there's no vertex here, clear slots left by earlier dispatches
     if (vertex_valid[cell_index] == 0u) {
+        for (var i = 0u; i < 3u; i = i + 1u) {
+            face_valid[base_face_index + i] = 0u;
+        }
         return;
     }
     
-    // STEP 7: Get the compacted vertex index for this cell
-    let v0 = vertex_indices[cell_index];
-   
+    // STEP 7: Faces store cell indices, compact_faces maps them to compacted vertex
+    // indices so faces outside a partial dispatch region stay valid
+    let v0 = cell_index;
     
-    // STEP 9: Calculate base face index for this cell
-    // Each cell can generate up to 3 faces, so we reserve 3 slots per cell
-    // Cell 0 gets face slots 0,1,2; Cell 1 gets slots 3,4,5; etc.
-    let base_face_index = cell_index * 3u;
     var local_face_count = 0u;  // Track how many faces we actually create
     
     // ============================================
@@ -100,10 +118,10 @@ fn generate_faces(
             vertex_valid[idx2] != 0u && 
             vertex_valid[idx3] != 0u) {
             
-            // Get compacted vertex indices for all 4 corners
-            let v1 = vertex_indices[idx1];
-            let v2 = vertex_indices[idx2];
-            let v3 = vertex_indices[idx3];
+            // Cell indices for all 4 corners
+            let v1 = idx1;
+            let v2 = idx2;
+            let v3 = idx3;
             
             // STEP 12: Write face to output
             // Each face is stored as 4 consecutive u32 values (vertex indices)
@@ -140,9 +158,9 @@ fn generate_faces(
             vertex_valid[idx2] != 0u && 
             vertex_valid[idx3] != 0u) {
             
-            let v1 = vertex_indices[idx1];
-            let v2 = vertex_indices[idx2];
-            let v3 = vertex_indices[idx3];
+            let v1 = idx1;
+            let v2 = idx2;
+            let v3 = idx3;
             
             let face_idx = base_face_index + local_face_count;
             let face_data_base = face_idx * 4u;
@@ -176,9 +194,9 @@ fn generate_faces(
             vertex_valid[idx2] != 0u && 
             vertex_valid[idx3] != 0u) {
             
-            let v1 = vertex_indices[idx1];
-            let v2 = vertex_indices[idx2];
-            let v3 = vertex_indices[idx3];
+            let v1 = idx1;
+            let v2 = idx2;
+            let v3 = idx3;
             
             let face_idx = base_face_index + local_face_count;
             let face_data_base = face_idx * 4u;
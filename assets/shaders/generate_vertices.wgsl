@@ -4,10 +4,21 @@
 // This shader generates vertices at the surface crossings in each cell
 // by finding where the isosurface (value = 0) crosses cell edges.
 
+#import sculpter::density::{DensityDecode, decode_density, density_word, source_index, source_uvw}
+
 // STEP 1: Define the bind group layout
-// These match the Rust side BindGroupLayoutEntries in order (0, 1, 2, 3)
+// These match the Rust side BindGroupLayoutEntries in order (0, 1, 2, 3, 4, 5, 6)
+// With the DENSITY_TEXTURE shader def the field is a 3D texture instead (plus a sampler at 7)
+#ifdef DENSITY_TEXTURE
+@group(0) @binding(0)
+var density_texture: texture_3d<f32>;  // Input scalar field (red channel)
+
+@group(0) @binding(7)
+var density_sampler: sampler;  // Linear sampler for hardware trilinear filtering
+#else
 @group(0) @binding(0)
-var<storage, read> density_field: array<f32>;  // Input scalar field
+var<storage, read> density_field: array<u32>;  // Input scalar field (f32 bits or 4 packed bytes per u32)
+#endif
 
 @group(0) @binding(1)
 var<storage, read_write> vertices: array<f32>;  // Output vertex positions (x,y,z packed)
@@ -18,13 +29,86 @@ var<storage, read_write> vertex_valid: array<u32>;  // Output validity flags (1
 @group(0) @binding(3)
 var<uniform> dimensions: vec3<u32>;  // Grid dimensions (x, y, z)
 
+@group(0) @binding(4)
+var<uniform> density_decode: DensityDecode;
+
+// Cells this dispatch covers (min inclusive, max exclusive)
+// Cells outside keep the vertices written by earlier dispatches
+struct MeshingRegion {
+    min: vec3<u32>,
+    max: vec3<u32>,
+}
+
+@group(0) @binding(5)
+var<uniform> region: MeshingRegion;
+
+// Per-volume meshing options, built from SculptSettings
+struct SurfaceNetsParams {
+    vertex_placement: u32,
+    relaxation_iterations: u32,  // Extra gradient steps after placement, 0 = off
+    relaxation_strength: f32,  // Fraction of each step taken
+    occlusion_rays: u32,  // Ambient occlusion rays per vertex, 0 = off
+    occlusion_distance: f32,  // How far each ray marches, in voxels
+    materials: u32,  // 1 when the volume has a MaterialField, 0 = off
+    stride: u32,  // Samples skipped per grid step, 1 = full resolution (LOD levels)
+    source_dimensions: vec3<u32>,  // Dimensions of the bound field, stride times the grid
+}
+
+@group(0) @binding(6)
+var<uniform> params: SurfaceNetsParams;
+
+// Must match the VERTEX_PLACEMENT_* constants in settings.rs
+const VERTEX_PLACEMENT_CROSSING_AVERAGE: u32 = 0u;
+const VERTEX_PLACEMENT_CELL_CENTROID: u32 = 1u;
+const VERTEX_PLACEMENT_GRADIENT_PROJECTED: u32 = 2u;
+
 // ===========================================================
 // Helper function MUST be at global scope in WGSL
 // ===========================================================
+#ifdef DENSITY_TEXTURE
+    fn sample_density(x: u32, y: u32, z: u32) -> f32 {
+        let uvw = source_uvw(vec3<u32>(x, y, z), params.stride, params.source_dimensions);
+        return textureSampleLevel(density_texture, density_sampler, uvw, 0.0).r;
+    }
+#else
     fn sample_density(x: u32, y: u32, z: u32) -> f32 {
-        let index = x + y * dimensions.x + z * dimensions.x * dimensions.y;
-        return density_field[index];
+        let index = source_index(vec3<u32>(x, y, z), params.stride, params.source_dimensions);
+        return decode_density(density_field[density_word(index, density_decode)], index, density_decode);
     }
+#endif
+
+// Trilinear interpolation of the grid at any point inside the volume
+fn sample_trilinear(p: vec3<f32>) -> f32 {
+    let q = clamp(p, vec3<f32>(0.0), vec3<f32>(dimensions - 1u));
+    let base = min(vec3<u32>(floor(q)), dimensions - 2u);
+    let f = q - vec3<f32>(base);
+    let c00 = mix(sample_density(base.x, base.y,      base.z),      sample_density(base.x + 1u, base.y,      base.z),      f.x);
+    let c10 = mix(sample_density(base.x, base.y + 1u, base.z),      sample_density(base.x + 1u, base.y + 1u, base.z),      f.x);
+    let c01 = mix(sample_density(base.x, base.y,      base.z + 1u), sample_density(base.x + 1u, base.y,      base.z + 1u), f.x);
+    let c11 = mix(sample_density(base.x, base.y + 1u, base.z + 1u), sample_density(base.x + 1u, base.y + 1u, base.z + 1u), f.x);
+    return mix(mix(c00, c10, f.y), mix(c01, c11, f.y), f.z);
+}
+
+// Central differences of the interpolated field, half a voxel either side
+fn density_gradient(p: vec3<f32>) -> vec3<f32> {
+    let h = 0.5;
+    return vec3<f32>(
+        sample_trilinear(p + vec3<f32>(h, 0.0, 0.0)) - sample_trilinear(p - vec3<f32>(h, 0.0, 0.0)),
+        sample_trilinear(p + vec3<f32>(0.0, h, 0.0)) - sample_trilinear(p - vec3<f32>(0.0, h, 0.0)),
+        sample_trilinear(p + vec3<f32>(0.0, 0.0, h)) - sample_trilinear(p - vec3<f32>(0.0, 0.0, h)),
+    ) / (2.0 * h);
+}
+
+// One (scaled) Newton step from p towards the isosurface along the gradient, kept inside the cell
+fn project_to_surface(p: vec3<f32>, cell: vec3<u32>, strength: f32) -> vec3<f32> {
+    let gradient = density_gradient(p);
+    let length_squared = dot(gradient, gradient);
+    if (length_squared < 1e-12) {
+        return p;  // Flat field, no direction to move in
+    }
+    let projected = p - strength * sample_trilinear(p) * gradient / length_squared;
+    return clamp(projected, vec3<f32>(cell), vec3<f32>(cell) + 1.0);
+}
 
 // STEP 2: Define workgroup size
 // Must match the WORKGROUP_SIZE constant in Rust (8x8x8 = 512 threads per workgroup)
@@ -33,10 +117,14 @@ fn generate_vertices(
     @builtin(global_invocation_id) global_id: vec3<u32>,  // Unique thread ID across all workgroups
 ) {
     // STEP 3: Get cell coordinates
-    // Each thread processes one cell in the 3D grid
-    let cell_x = global_id.x;
-    let cell_y = global_id.y;
-    let cell_z = global_id.z;
+    // Each thread processes one cell of the dispatch region
+    let cell = region.min + global_id;
+    if (any(cell >= region.max)) {
+        return;  // Outside the region being regenerated
+    }
+    let cell_x = cell.x;
+    let cell_y = cell.y;
+    let cell_z = cell.z;
     
     // STEP 4: Boundary check
     // We need to sample corners, so cells on the far edge can't form complete cells
@@ -136,7 +224,24 @@ fn generate_vertices(
         // STEP 14: Average all crossing positions to get the vertex position
         // This is the key idea of Surface Nets - we place the vertex at the
         // average of all edge crossings in this cell
-        let vertex_pos = crossing_sum / f32(crossing_count);
+        var vertex_pos = crossing_sum / f32(crossing_count);
+
+        // Other placement strategies start from (or replace) the average
+        switch params.vertex_placement {
+            case VERTEX_PLACEMENT_CELL_CENTROID: {
+                vertex_pos = vec3<f32>(cell) + 0.5;
+            }
+            case VERTEX_PLACEMENT_GRADIENT_PROJECTED: {
+                vertex_pos = project_to_surface(vertex_pos, cell, 1.0);
+            }
+            default: {}
+        }
+
+        // Optional relaxation: keep stepping towards the true isosurface
+        // Clamping to the cell keeps the vertex inside its cell
+        for (var i = 0u; i < params.relaxation_iterations; i = i + 1u) {
+            vertex_pos = project_to_surface(vertex_pos, cell, params.relaxation_strength);
+        }
         
         // STEP 15: Store vertex in output buffer
         // Vertices are stored as flat array: [x0, y0, z0, x1, y1, z1, ...]
@@ -0,0 +1,163 @@
+// ============================================
+// HEIGHTFIELD: Generate Vertices + Generate Faces
+// ============================================
+// Alternative to kernels 1 and 4 for fields that are heightfields, solid below
+// a single height per column. Instead of visiting every cell, each thread
+// binary searches one column for its surface, and the surface points of
+// neighbouring columns are joined into a regular grid of quads facing up.
+// The prefix sum and compaction kernels are shared with surface nets.
+//
+// Column (x, z) owns the vertex and face slots of grid point (x, 0, z), the
+// rest stay empty. Both kernels are dispatched over x and z only.
+
+#import sculpter::density::{DensityDecode, decode_density, density_word, source_index, source_uvw}
+
+// STEP 1: Define the bind group layout (shared by both entry points)
+// With the DENSITY_TEXTURE shader def the field is a 3D texture instead (plus a sampler at 9)
+#ifdef DENSITY_TEXTURE
+@group(0) @binding(0)
+var density_texture: texture_3d<f32>;  // Input scalar field (red channel)
+
+@group(0) @binding(9)
+var density_sampler: sampler;  // Linear sampler for hardware trilinear filtering
+#else
+@group(0) @binding(0)
+var<storage, read> density_field: array<u32>;  // Input scalar field (f32 bits or 4 packed bytes per u32)
+#endif
+
+@group(0) @binding(1)
+var<storage, read_write> vertices: array<f32>;  // Output surface points, one per column
+
+@group(0) @binding(2)
+var<storage, read_write> vertex_valid: array<u32>;  // Output validity flags per vertex slot
+
+@group(0) @binding(3)
+var<uniform> dimensions: vec3<u32>;  // Grid dimensions (x, y, z)
+
+@group(0) @binding(4)
+var<uniform> density_decode: DensityDecode;
+
+// Cells this dispatch covers (min inclusive, max exclusive)
+struct MeshingRegion {
+    min: vec3<u32>,
+    max: vec3<u32>,
+}
+
+@group(0) @binding(5)
+var<uniform> region: MeshingRegion;
+
+// Must match SurfaceNetsParams in generate_vertices.wgsl (unused by this algorithm)
+struct SurfaceNetsParams {
+    vertex_placement: u32,
+    relaxation_iterations: u32,
+    relaxation_strength: f32,
+    occlusion_rays: u32,
+    occlusion_distance: f32,
+    materials: u32,
+    stride: u32,
+    source_dimensions: vec3<u32>,
+}
+
+@group(0) @binding(6)
+var<uniform> params: SurfaceNetsParams;
+
+@group(0) @binding(7)
+var<storage, read_write> faces: array<u32>;  // Output: 4 vertex slots per quad
+
+@group(0) @binding(8)
+var<storage, read_write> face_valid: array<u32>;  // Output: which column cells have a quad
+
+// ===========================================================
+// Helper functions MUST be at global scope in WGSL
+// ===========================================================
+#ifdef DENSITY_TEXTURE
+    fn sample_density(p: vec3<u32>) -> f32 {
+        let uvw = source_uvw(p, params.stride, params.source_dimensions);
+        return textureSampleLevel(density_texture, density_sampler, uvw, 0.0).r;
+    }
+#else
+    fn sample_density(p: vec3<u32>) -> f32 {
+        let index = source_index(p, params.stride, params.source_dimensions);
+        return decode_density(density_field[density_word(index, density_decode)], index, density_decode);
+    }
+#endif
+
+fn grid_index(p: vec3<u32>) -> u32 {
+    return p.x + p.y * dimensions.x + p.z * dimensions.x * dimensions.y;
+}
+
+// STEP 2: Find the surface height of each column
+// (must match column_height in sculpter-core's heightfield.rs)
+// Densities are assumed to rise along y, the last solid sample is searched for
+fn column_height(x: u32, z: u32) -> f32 {
+    var low = 0u;
+    var high = dimensions.y - 1u;
+    // Solid at low, empty at high
+    while (high - low > 1u) {
+        let middle = (low + high) / 2u;
+        if (sample_density(vec3<u32>(x, middle, z)) < 0.0) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let v0 = sample_density(vec3<u32>(x, low, z));
+    let v1 = sample_density(vec3<u32>(x, high, z));
+    return f32(low) + v0 / (v0 - v1);
+}
+
+// Dispatched over the columns of the region, one more than its cells
+@compute @workgroup_size(8, 1, 8)
+fn generate_heightfield_vertices(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let column = region.min.xz + global_id.xz;
+    if (any(column > region.max.xz) || any(column >= dimensions.xz)) {
+        return;  // Outside the region being regenerated
+    }
+
+    let slot = grid_index(vec3<u32>(column.x, 0u, column.y));
+    // Columns solid or empty all the way have no surface
+    let top = max(dimensions.y, 1u) - 1u;
+    if (top == 0u
+        || sample_density(vec3<u32>(column.x, 0u, column.y)) >= 0.0
+        || sample_density(vec3<u32>(column.x, top, column.y)) < 0.0) {
+        vertex_valid[slot] = 0u;
+        return;
+    }
+
+    vertices[slot * 3u + 0u] = f32(column.x);
+    vertices[slot * 3u + 1u] = column_height(column.x, column.y);
+    vertices[slot * 3u + 2u] = f32(column.y);
+    vertex_valid[slot] = 1u;
+}
+
+// STEP 3: Join the surface points of each cell's four columns into a quad
+@compute @workgroup_size(8, 1, 8)
+fn generate_heightfield_faces(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let cell = region.min.xz + global_id.xz;
+    if (any(cell >= region.max.xz)) {
+        return;  // Outside the region being regenerated
+    }
+
+    let face_slot = grid_index(vec3<u32>(cell.x, 0u, cell.y));
+    // Wound counter-clockwise seen from above
+    let corners = array<u32, 4>(
+        face_slot,
+        grid_index(vec3<u32>(cell.x, 0u, cell.y + 1u)),
+        grid_index(vec3<u32>(cell.x + 1u, 0u, cell.y + 1u)),
+        grid_index(vec3<u32>(cell.x + 1u, 0u, cell.y)),
+    );
+    for (var c = 0u; c < 4u; c = c + 1u) {
+        if (vertex_valid[corners[c]] == 0u) {
+            face_valid[face_slot] = 0u;
+            return;
+        }
+    }
+    for (var c = 0u; c < 4u; c = c + 1u) {
+        faces[face_slot * 4u + c] = corners[c];
+    }
+    face_valid[face_slot] = 1u;
+}
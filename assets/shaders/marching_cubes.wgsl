@@ -0,0 +1,191 @@
+// ============================================
+// MARCHING CUBES: Generate Vertices + Generate Faces
+// ============================================
+// Alternative to kernels 1 and 4 emitting up to five triangles per cell from a
+// lookup table (built in marching_cubes.rs). The prefix sum and compaction
+// kernels are shared with surface nets.
+//
+// Vertices live on grid edges. Each grid point owns the 3 axis edges leaving it
+// in the positive direction, so the vertex slot of an edge is
+// grid_index * 3 + axis.
+//
+// Faces are stored like surface nets quads (4 vertex slots), each triangle
+// repeats its last index. Every cell owns MAX_TRIANGLES face slots.
+
+#import sculpter::density::{DensityDecode, decode_density, density_word, source_index, source_uvw}
+
+// STEP 1: Define the bind group layout (shared by both entry points)
+// With the DENSITY_TEXTURE shader def the field is a 3D texture instead (plus a sampler at 10)
+#ifdef DENSITY_TEXTURE
+@group(0) @binding(0)
+var density_texture: texture_3d<f32>;  // Input scalar field (red channel)
+
+@group(0) @binding(10)
+var density_sampler: sampler;  // Linear sampler for hardware trilinear filtering
+#else
+@group(0) @binding(0)
+var<storage, read> density_field: array<u32>;  // Input scalar field (f32 bits or 4 packed bytes per u32)
+#endif
+
+@group(0) @binding(1)
+var<storage, read_write> vertices: array<f32>;  // Output vertex positions, one per edge slot
+
+@group(0) @binding(2)
+var<storage, read_write> vertex_valid: array<u32>;  // Output validity flags per edge slot
+
+@group(0) @binding(3)
+var<uniform> dimensions: vec3<u32>;  // Grid dimensions (x, y, z)
+
+@group(0) @binding(4)
+var<uniform> density_decode: DensityDecode;
+
+// Cells this dispatch covers (min inclusive, max exclusive)
+struct MeshingRegion {
+    min: vec3<u32>,
+    max: vec3<u32>,
+}
+
+@group(0) @binding(5)
+var<uniform> region: MeshingRegion;
+
+// Must match SurfaceNetsParams in generate_vertices.wgsl (unused by this algorithm)
+struct SurfaceNetsParams {
+    vertex_placement: u32,
+    relaxation_iterations: u32,
+    relaxation_strength: f32,
+    occlusion_rays: u32,
+    occlusion_distance: f32,
+    materials: u32,
+    stride: u32,
+    source_dimensions: vec3<u32>,
+}
+
+@group(0) @binding(6)
+var<uniform> params: SurfaceNetsParams;
+
+@group(0) @binding(7)
+var<storage, read_write> faces: array<u32>;  // Output: 4 vertex slots per triangle
+
+@group(0) @binding(8)
+var<storage, read_write> face_valid: array<u32>;  // Output: which triangle slots are used
+
+@group(0) @binding(9)
+var<storage, read> triangle_table: array<i32>;  // 16 edge indices per case, -1 terminated
+
+// Edges owned by each grid point
+const EDGES_PER_POINT: u32 = 3u;
+
+// Must match MAX_TRIANGLES in marching_cubes.rs
+const MAX_TRIANGLES: u32 = 5u;
+
+// ===========================================================
+// Helper functions MUST be at global scope in WGSL
+// ===========================================================
+#ifdef DENSITY_TEXTURE
+    fn sample_density(p: vec3<u32>) -> f32 {
+        let uvw = source_uvw(p, params.stride, params.source_dimensions);
+        return textureSampleLevel(density_texture, density_sampler, uvw, 0.0).r;
+    }
+#else
+    fn sample_density(p: vec3<u32>) -> f32 {
+        let index = source_index(p, params.stride, params.source_dimensions);
+        return decode_density(density_field[density_word(index, density_decode)], index, density_decode);
+    }
+#endif
+
+fn grid_index(p: vec3<u32>) -> u32 {
+    return p.x + p.y * dimensions.x + p.z * dimensions.x * dimensions.y;
+}
+
+// Offset of corner c (x | y << 1 | z << 2) from the cell origin
+fn corner_offset(c: u32) -> vec3<u32> {
+    return vec3<u32>(c & 1u, (c >> 1u) & 1u, (c >> 2u) & 1u);
+}
+
+// Vertex slot of edge e of a cell, numbered axis * 4 + the coordinates of the
+// two other axes (must match edge_axis_and_offset in marching_cubes.rs)
+fn edge_slot(cell: vec3<u32>, e: u32) -> u32 {
+    let axis = e / 4u;
+    let a = e & 1u;
+    let b = (e >> 1u) & 1u;
+    var offset: vec3<u32>;
+    switch axis {
+        case 0u: { offset = vec3<u32>(0u, a, b); }
+        case 1u: { offset = vec3<u32>(a, 0u, b); }
+        default: { offset = vec3<u32>(a, b, 0u); }
+    }
+    return grid_index(cell + offset) * EDGES_PER_POINT + axis;
+}
+
+// STEP 2: Generate one vertex per crossed axis edge
+// Dispatched over the grid points of the region, which is one larger than its cells
+@compute @workgroup_size(8, 8, 8)
+fn generate_cube_vertices(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let p = region.min + global_id;
+    if (any(p > region.max) || any(p >= dimensions)) {
+        return;  // Outside the region being regenerated
+    }
+
+    let v0 = sample_density(p);
+    for (var axis = 0u; axis < 3u; axis = axis + 1u) {
+        let slot = grid_index(p) * EDGES_PER_POINT + axis;
+        let q = p + corner_offset(1u << axis);
+        if (any(q >= dimensions)) {
+            vertex_valid[slot] = 0u;  // Edge leaves the grid
+            continue;
+        }
+
+        let v1 = sample_density(q);
+        if ((v0 < 0.0) != (v1 < 0.0)) {
+            let t = v0 / (v0 - v1);
+            let crossing = vec3<f32>(p) + t * (vec3<f32>(q) - vec3<f32>(p));
+            vertices[slot * 3u + 0u] = crossing.x;
+            vertices[slot * 3u + 1u] = crossing.y;
+            vertices[slot * 3u + 2u] = crossing.z;
+            vertex_valid[slot] = 1u;
+        } else {
+            vertex_valid[slot] = 0u;
+        }
+    }
+}
+
+// STEP 3: Emit the triangles of each cell's case
+@compute @workgroup_size(8, 8, 8)
+fn generate_cube_faces(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let cell = region.min + global_id;
+    if (any(cell >= region.max) || any(cell + 1u >= dimensions)) {
+        return;  // Outside the region being regenerated
+    }
+
+    // Inside is strictly negative, matching the crossing test above
+    var case_index = 0u;
+    for (var c = 0u; c < 8u; c = c + 1u) {
+        if (sample_density(cell + corner_offset(c)) < 0.0) {
+            case_index = case_index | (1u << c);
+        }
+    }
+
+    let table_base = case_index * 16u;
+    let face_base = grid_index(cell) * MAX_TRIANGLES;
+    for (var t = 0u; t < MAX_TRIANGLES; t = t + 1u) {
+        let face_slot = face_base + t;
+        let first = triangle_table[table_base + t * 3u];
+        if (first < 0) {
+            face_valid[face_slot] = 0u;  // Case has fewer triangles, clear old ones
+            continue;
+        }
+
+        let s0 = edge_slot(cell, u32(first));
+        let s1 = edge_slot(cell, u32(triangle_table[table_base + t * 3u + 1u]));
+        let s2 = edge_slot(cell, u32(triangle_table[table_base + t * 3u + 2u]));
+        faces[face_slot * 4u + 0u] = s0;
+        faces[face_slot * 4u + 1u] = s1;
+        faces[face_slot * 4u + 2u] = s2;
+        faces[face_slot * 4u + 3u] = s2;  // Triangle marker
+        face_valid[face_slot] = 1u;
+    }
+}
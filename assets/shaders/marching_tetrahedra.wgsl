@@ -0,0 +1,268 @@
+// ============================================
+// MARCHING TETRAHEDRA: Generate Vertices + Generate Faces
+// ============================================
+// Alternative to kernels 1 and 4 that splits every cell into six tetrahedra.
+// A tetrahedron has no ambiguous cases, so the topology is always consistent.
+// The prefix sum and compaction kernels are shared with surface nets.
+//
+// Vertices live on grid edges. Each grid point owns the 7 edges leaving it in
+// the positive direction (3 axes, 3 face diagonals, 1 body diagonal), so the
+// vertex slot of an edge is grid_index * 7 + (direction bits - 1), where the
+// direction bits are x | y << 1 | z << 2.
+//
+// Faces are stored like surface nets quads (4 vertex slots). Each tetrahedron
+// makes a triangle or a quad, triangles repeat their last index.
+
+#import sculpter::density::{DensityDecode, decode_density, density_word, source_index, source_uvw}
+
+// STEP 1: Define the bind group layout (shared by both entry points)
+// With the DENSITY_TEXTURE shader def the field is a 3D texture instead (plus a sampler at 9)
+#ifdef DENSITY_TEXTURE
+@group(0) @binding(0)
+var density_texture: texture_3d<f32>;  // Input scalar field (red channel)
+
+@group(0) @binding(9)
+var density_sampler: sampler;  // Linear sampler for hardware trilinear filtering
+#else
+@group(0) @binding(0)
+var<storage, read> density_field: array<u32>;  // Input scalar field (f32 bits or 4 packed bytes per u32)
+#endif
+
+@group(0) @binding(1)
+var<storage, read_write> vertices: array<f32>;  // Output vertex positions, one per edge slot
+
+@group(0) @binding(2)
+var<storage, read_write> vertex_valid: array<u32>;  // Output validity flags per edge slot
+
+@group(0) @binding(3)
+var<uniform> dimensions: vec3<u32>;  // Grid dimensions (x, y, z)
+
+@group(0) @binding(4)
+var<uniform> density_decode: DensityDecode;
+
+// Cells this dispatch covers (min inclusive, max exclusive)
+struct MeshingRegion {
+    min: vec3<u32>,
+    max: vec3<u32>,
+}
+
+@group(0) @binding(5)
+var<uniform> region: MeshingRegion;
+
+// Must match SurfaceNetsParams in generate_vertices.wgsl (unused by this algorithm)
+struct SurfaceNetsParams {
+    vertex_placement: u32,
+    relaxation_iterations: u32,
+    relaxation_strength: f32,
+    occlusion_rays: u32,
+    occlusion_distance: f32,
+    materials: u32,
+    stride: u32,
+    source_dimensions: vec3<u32>,
+}
+
+@group(0) @binding(6)
+var<uniform> params: SurfaceNetsParams;
+
+@group(0) @binding(7)
+var<storage, read_write> faces: array<u32>;  // Output: 4 vertex slots per tetrahedron
+
+@group(0) @binding(8)
+var<storage, read_write> face_valid: array<u32>;  // Output: which tetrahedra made a face
+
+// Edges owned by each grid point
+const EDGES_PER_POINT: u32 = 7u;
+
+// Tetrahedra per cell
+const TETRAHEDRA_PER_CELL: u32 = 6u;
+
+// ===========================================================
+// Helper functions MUST be at global scope in WGSL
+// ===========================================================
+#ifdef DENSITY_TEXTURE
+    fn sample_density(p: vec3<u32>) -> f32 {
+        let uvw = source_uvw(p, params.stride, params.source_dimensions);
+        return textureSampleLevel(density_texture, density_sampler, uvw, 0.0).r;
+    }
+#else
+    fn sample_density(p: vec3<u32>) -> f32 {
+        let index = source_index(p, params.stride, params.source_dimensions);
+        return decode_density(density_field[density_word(index, density_decode)], index, density_decode);
+    }
+#endif
+
+fn grid_index(p: vec3<u32>) -> u32 {
+    return p.x + p.y * dimensions.x + p.z * dimensions.x * dimensions.y;
+}
+
+// Offset of a direction given as x | y << 1 | z << 2
+fn direction_offset(bits: u32) -> vec3<u32> {
+    return vec3<u32>(bits & 1u, (bits >> 1u) & 1u, (bits >> 2u) & 1u);
+}
+
+// Vertex slot of the edge between two grid points, one componentwise <= the other
+fn edge_slot(a: vec3<u32>, b: vec3<u32>) -> u32 {
+    let low = min(a, b);
+    let d = max(a, b) - low;
+    let bits = d.x | (d.y << 1u) | (d.z << 2u);
+    return grid_index(low) * EDGES_PER_POINT + bits - 1u;
+}
+
+// Where the isosurface crosses the edge between two grid points
+fn crossing_point(a: vec3<u32>, b: vec3<u32>) -> vec3<f32> {
+    let v0 = sample_density(a);
+    let v1 = sample_density(b);
+    let t = v0 / (v0 - v1);
+    return vec3<f32>(a) + t * (vec3<f32>(b) - vec3<f32>(a));
+}
+
+// Inside is strictly negative, matching the crossing test of both kernels
+fn is_inside(p: vec3<u32>) -> bool {
+    return sample_density(p) < 0.0;
+}
+
+// Corners of each tetrahedron: the cell origin, a step along one axis, a step
+// along a second axis, and the opposite corner (1,1,1)
+// Splitting every cube along the same diagonal makes neighbouring cells agree
+// on their shared face diagonals
+fn tetrahedron_corners(cell: vec3<u32>, tetrahedron: u32) -> array<vec3<u32>, 4> {
+    var second: vec3<u32>;
+    var third: vec3<u32>;
+    switch tetrahedron {
+        case 0u: { second = vec3<u32>(1u, 0u, 0u); third = vec3<u32>(1u, 1u, 0u); }
+        case 1u: { second = vec3<u32>(1u, 0u, 0u); third = vec3<u32>(1u, 0u, 1u); }
+        case 2u: { second = vec3<u32>(0u, 1u, 0u); third = vec3<u32>(1u, 1u, 0u); }
+        case 3u: { second = vec3<u32>(0u, 1u, 0u); third = vec3<u32>(0u, 1u, 1u); }
+        case 4u: { second = vec3<u32>(0u, 0u, 1u); third = vec3<u32>(1u, 0u, 1u); }
+        default: { second = vec3<u32>(0u, 0u, 1u); third = vec3<u32>(0u, 1u, 1u); }
+    }
+    return array<vec3<u32>, 4>(cell, cell + second, cell + third, cell + vec3<u32>(1u));
+}
+
+// STEP 2: Generate one vertex per crossed edge
+// Dispatched over the grid points of the region, which is one larger than its cells
+@compute @workgroup_size(8, 8, 8)
+fn generate_tetra_vertices(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let p = region.min + global_id;
+    if (any(p > region.max) || any(p >= dimensions)) {
+        return;  // Outside the region being regenerated
+    }
+
+    let v0 = sample_density(p);
+    for (var bits = 1u; bits < 8u; bits = bits + 1u) {
+        let slot = grid_index(p) * EDGES_PER_POINT + bits - 1u;
+        let q = p + direction_offset(bits);
+        if (any(q >= dimensions)) {
+            vertex_valid[slot] = 0u;  // Edge leaves the grid
+            continue;
+        }
+
+        let v1 = sample_density(q);
+        if ((v0 < 0.0) != (v1 < 0.0)) {
+            let t = v0 / (v0 - v1);
+            let crossing = vec3<f32>(p) + t * (vec3<f32>(q) - vec3<f32>(p));
+            vertices[slot * 3u + 0u] = crossing.x;
+            vertices[slot * 3u + 1u] = crossing.y;
+            vertices[slot * 3u + 2u] = crossing.z;
+            vertex_valid[slot] = 1u;
+        } else {
+            vertex_valid[slot] = 0u;
+        }
+    }
+}
+
+// STEP 3: Generate one triangle or quad per tetrahedron
+@compute @workgroup_size(8, 8, 8)
+fn generate_tetra_faces(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let cell = region.min + global_id;
+    if (any(cell >= region.max) || any(cell + 1u >= dimensions)) {
+        return;  // Outside the region being regenerated
+    }
+
+    for (var tetrahedron = 0u; tetrahedron < TETRAHEDRA_PER_CELL; tetrahedron = tetrahedron + 1u) {
+        let face_slot = grid_index(cell) * TETRAHEDRA_PER_CELL + tetrahedron;
+        let corners = tetrahedron_corners(cell, tetrahedron);
+
+        // Split the corners into inside and outside
+        var inside: array<vec3<u32>, 4>;
+        var outside: array<vec3<u32>, 4>;
+        var inside_count = 0u;
+        var outside_count = 0u;
+        for (var i = 0u; i < 4u; i = i + 1u) {
+            if (is_inside(corners[i])) {
+                inside[inside_count] = corners[i];
+                inside_count = inside_count + 1u;
+            } else {
+                outside[outside_count] = corners[i];
+                outside_count = outside_count + 1u;
+            }
+        }
+
+        // Crossed edges as a closed loop of 3 or 4 corner pairs
+        var loop_a: array<vec3<u32>, 4>;
+        var loop_b: array<vec3<u32>, 4>;
+        var loop_length = 0u;
+        if (inside_count == 1u) {
+            for (var i = 0u; i < 3u; i = i + 1u) {
+                loop_a[i] = inside[0];
+                loop_b[i] = outside[i];
+            }
+            loop_length = 3u;
+        } else if (inside_count == 3u) {
+            for (var i = 0u; i < 3u; i = i + 1u) {
+                loop_a[i] = inside[i];
+                loop_b[i] = outside[0];
+            }
+            loop_length = 3u;
+        } else if (inside_count == 2u) {
+            loop_a[0] = inside[0]; loop_b[0] = outside[0];
+            loop_a[1] = inside[0]; loop_b[1] = outside[1];
+            loop_a[2] = inside[1]; loop_b[2] = outside[1];
+            loop_a[3] = inside[1]; loop_b[3] = outside[0];
+            loop_length = 4u;
+        }
+
+        if (loop_length == 0u) {
+            face_valid[face_slot] = 0u;  // Tetrahedron is entirely inside or outside
+            continue;
+        }
+
+        // Wind counter-clockwise when seen from outside (towards positive density)
+        var inside_centre = vec3<f32>(0.0);
+        var outside_centre = vec3<f32>(0.0);
+        for (var i = 0u; i < inside_count; i = i + 1u) {
+            inside_centre = inside_centre + vec3<f32>(inside[i]);
+        }
+        for (var i = 0u; i < outside_count; i = i + 1u) {
+            outside_centre = outside_centre + vec3<f32>(outside[i]);
+        }
+        let outward = outside_centre / f32(outside_count) - inside_centre / f32(inside_count);
+        let p0 = crossing_point(loop_a[0], loop_b[0]);
+        let p1 = crossing_point(loop_a[1], loop_b[1]);
+        let p2 = crossing_point(loop_a[2], loop_b[2]);
+        let flip = dot(cross(p1 - p0, p2 - p0), outward) < 0.0;
+
+        var slots: array<u32, 4>;
+        for (var i = 0u; i < loop_length; i = i + 1u) {
+            // Reversing keeps the first vertex and walks the loop backwards
+            var j = i;
+            if (flip && i > 0u) {
+                j = loop_length - i;
+            }
+            slots[i] = edge_slot(loop_a[j], loop_b[j]);
+        }
+        if (loop_length == 3u) {
+            slots[3] = slots[2];  // Triangle marker
+        }
+
+        let face_base = face_slot * 4u;
+        for (var i = 0u; i < 4u; i = i + 1u) {
+            faces[face_base + i] = slots[i];
+        }
+        face_valid[face_slot] = 1u;
+    }
+}
@@ -0,0 +1,163 @@
+// ============================================
+// Noise library
+// ============================================
+// 3D noise for density generation, also importable by custom density functions:
+//
+//     #import sculpter::noise::{fbm, NOISE_BASIS_SIMPLEX}
+//
+// Every basis returns roughly [-1, 1] and differs per seed.
+#define_import_path sculpter::noise
+
+// Must match the NOISE_BASIS_* constants in gpu_density.rs
+const NOISE_BASIS_PERLIN: u32 = 0u;
+const NOISE_BASIS_SIMPLEX: u32 = 1u;
+const NOISE_BASIS_WORLEY: u32 = 2u;
+
+// PCG3D hash, three well mixed words from three
+fn pcg3d(input: vec3<u32>) -> vec3<u32> {
+    var v = input * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> vec3<u32>(16u);
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    return v;
+}
+
+// Pseudo-random point of a lattice cell, each component in [0, 1]
+fn lattice_hash(cell: vec3<i32>, seed: u32) -> vec3<f32> {
+    let hash = pcg3d(bitcast<vec3<u32>>(cell) + vec3<u32>(seed, seed * 3u, seed * 7u));
+    return vec3<f32>(hash) / 4294967295.0;
+}
+
+// Pseudo-random gradient of a lattice point, each component in [-1, 1]
+fn lattice_gradient(cell: vec3<i32>, seed: u32) -> vec3<f32> {
+    return lattice_hash(cell, seed) * 2.0 - 1.0;
+}
+
+// Perlin gradient noise
+fn perlin(p: vec3<f32>, seed: u32) -> f32 {
+    let cell = vec3<i32>(floor(p));
+    let f = fract(p);
+    // Quintic fade so the noise is smooth across cells
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+
+    var corners: array<f32, 8>;
+    for (var i = 0u; i < 8u; i++) {
+        let corner = vec3<u32>(i & 1u, (i >> 1u) & 1u, (i >> 2u) & 1u);
+        let gradient = lattice_gradient(cell + vec3<i32>(corner), seed);
+        corners[i] = dot(gradient, f - vec3<f32>(corner));
+    }
+    let x0 = mix(corners[0], corners[1], u.x);
+    let x1 = mix(corners[2], corners[3], u.x);
+    let x2 = mix(corners[4], corners[5], u.x);
+    let x3 = mix(corners[6], corners[7], u.x);
+    return mix(mix(x0, x1, u.y), mix(x2, x3, u.y), u.z);
+}
+
+// Simplex noise, summing the 4 corners of the tetrahedron p lies in
+fn simplex(p: vec3<f32>, seed: u32) -> f32 {
+    let skew = 1.0 / 3.0;
+    let unskew = 1.0 / 6.0;
+    let s = floor(p + dot(p, vec3<f32>(skew)));
+    let x0 = p - s + dot(s, vec3<f32>(unskew));
+
+    // Which of the 6 tetrahedra of the skewed cube
+    let e = step(vec3<f32>(0.0), x0 - x0.yzx);
+    let i1 = e * (1.0 - e.zxy);
+    let i2 = 1.0 - e.zxy * (1.0 - e);
+
+    let cell = vec3<i32>(s);
+    let offsets = array<vec3<f32>, 4>(vec3<f32>(0.0), i1, i2, vec3<f32>(1.0));
+    var total = 0.0;
+    for (var i = 0u; i < 4u; i++) {
+        let x = x0 - offsets[i] + f32(i) * unskew;
+        let falloff = max(0.6 - dot(x, x), 0.0);
+        let gradient = lattice_gradient(cell + vec3<i32>(offsets[i]), seed);
+        total += falloff * falloff * falloff * falloff * dot(gradient, x);
+    }
+    return 32.0 * total;
+}
+
+// Worley cellular noise: distance to the nearest feature point, one per cell
+fn worley(p: vec3<f32>, seed: u32) -> f32 {
+    let cell = vec3<i32>(floor(p));
+    let f = fract(p);
+    var nearest = 8.0;
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let offset = vec3<i32>(x, y, z);
+                let to_feature = vec3<f32>(offset) + lattice_hash(cell + offset, seed) - f;
+                nearest = min(nearest, dot(to_feature, to_feature));
+            }
+        }
+    }
+    // The distance is at most about 1 in practice
+    return min(sqrt(nearest), 1.0) * 2.0 - 1.0;
+}
+
+// One octave of the NOISE_BASIS_* basis
+fn noise(basis: u32, p: vec3<f32>, seed: u32) -> f32 {
+    switch basis {
+        case NOISE_BASIS_SIMPLEX: {
+            return simplex(p, seed);
+        }
+        case NOISE_BASIS_WORLEY: {
+            return worley(p, seed);
+        }
+        default: {
+            return perlin(p, seed);
+        }
+    }
+}
+
+// Fractal Brownian motion: octaves summed, each `lacunarity` times the frequency
+// and `gain` times the weight of the last, normalised to roughly [-1, 1]
+fn fbm(
+    basis: u32,
+    p: vec3<f32>,
+    seed: u32,
+    octaves: u32,
+    frequency: f32,
+    lacunarity: f32,
+    gain: f32,
+) -> f32 {
+    var octave_frequency = frequency;
+    var weight = 1.0;
+    var total = 0.0;
+    var weights = 0.0;
+    for (var octave = 0u; octave < octaves; octave++) {
+        total += weight * noise(basis, p * octave_frequency, seed + octave);
+        weights += weight;
+        octave_frequency *= lacunarity;
+        weight *= gain;
+    }
+    return total / max(weights, 1e-6);
+}
+
+// Like fbm with each octave folded into a crest, normalised to roughly [0, 1]
+fn ridged(
+    basis: u32,
+    p: vec3<f32>,
+    seed: u32,
+    octaves: u32,
+    frequency: f32,
+    lacunarity: f32,
+    gain: f32,
+) -> f32 {
+    var octave_frequency = frequency;
+    var weight = 1.0;
+    var total = 0.0;
+    var weights = 0.0;
+    for (var octave = 0u; octave < octaves; octave++) {
+        let ridge = 1.0 - abs(noise(basis, p * octave_frequency, seed + octave));
+        total += weight * ridge * ridge;
+        weights += weight;
+        octave_frequency *= lacunarity;
+        weight *= gain;
+    }
+    return total / max(weights, 1e-6);
+}
@@ -0,0 +1,104 @@
+// Vertex shader of `PackedVertexMaterial`, for meshes built with
+// `SculptSettings::packed_vertices`. Positions arrive as half floats packed in
+// pairs and normals octahedral encoded, everything else as in Bevy's mesh and
+// prepass vertex shaders (minus skinning and morph targets, which volumes never
+// have).
+
+#import bevy_pbr::{
+    mesh_functions,
+    view_transformations::position_world_to_clip,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::prepass_io::VertexOutput
+#else
+#import bevy_pbr::forward_io::VertexOutput
+#endif
+
+// Locations must match `PackedVertexExtension::specialize`
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) packed_position: vec2<u32>,  // x,y and z,1 as half floats
+    @location(1) packed_normal: vec2<f32>,  // Octahedral, unorm16x2
+#ifdef VERTEX_UVS_A
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_UVS_B
+    @location(3) uv_b: vec2<f32>,
+#endif
+#ifdef VERTEX_TANGENTS
+    @location(4) tangent: vec4<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    @location(5) color: vec4<f32>,
+#endif
+}
+
+// Inverse of `octahedral_encode` in sculpter_core::packing
+fn octahedral_decode(encoded: vec2<f32>) -> vec3<f32> {
+    let f = encoded * 2.0 - 1.0;
+    var n = vec3<f32>(f.x, f.y, 1.0 - abs(f.x) - abs(f.y));
+    let t = max(-n.z, 0.0);
+    n.x += select(t, -t, n.x >= 0.0);
+    n.y += select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    let position = vec3<f32>(
+        unpack2x16float(vertex.packed_position.x),
+        unpack2x16float(vertex.packed_position.y).x,
+    );
+    let normal = octahedral_decode(vertex.packed_normal);
+
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4<f32>(position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+
+#ifdef VERTEX_UVS_A
+    out.uv = vertex.uv;
+#endif
+#ifdef VERTEX_UVS_B
+    out.uv_b = vertex.uv_b;
+#endif
+#ifdef VERTEX_COLORS
+    out.color = vertex.color;
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+#ifdef VISIBILITY_RANGE_DITHER
+    out.visibility_range_dither = mesh_functions::get_visibility_range_dither_level(
+        vertex.instance_index, world_from_local[3]);
+#endif
+
+#ifdef PREPASS_PIPELINE
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+    out.unclipped_depth = out.position.z;
+    out.position.z = min(out.position.z, 1.0);  // Clamp depth to avoid clipping
+#endif
+#ifdef NORMAL_PREPASS_OR_DEFERRED_PREPASS
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = mesh_functions::mesh_tangent_local_to_world(
+        world_from_local, vertex.tangent, vertex.instance_index);
+#endif
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    let previous_world_from_local = mesh_functions::get_previous_world_from_local(vertex.instance_index);
+    out.previous_world_position = mesh_functions::mesh_position_local_to_world(
+        previous_world_from_local, vec4<f32>(position, 1.0));
+#endif
+#else
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = mesh_functions::mesh_tangent_local_to_world(
+        world_from_local, vertex.tangent, vertex.instance_index);
+#endif
+#endif
+
+    return out;
+}
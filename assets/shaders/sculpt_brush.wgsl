@@ -0,0 +1,223 @@
+// ============================================
+// KERNEL 0c: Sculpt Brush
+// ============================================
+// This shader applies a `SculptBrush` to the density buffer of a meshed volume,
+// after generation and CSG edits and before meshing. `brush` adds and
+// subtracts; `brush_smooth` and `brush_flatten` read the neighbourhood of the
+// samples they change, so `brush_snapshot` first copies the region it lies in
+// and they read it from the copy.
+
+#import sculpter::sdf::primitive_distance
+
+// STEP 1: Define the bind group layout
+// These match the Rust side BindGroupLayoutEntries in order (0, 1, 2, 3)
+@group(0) @binding(0)
+var<storage, read_write> density_field: array<f32>;  // Densities sculpted
+
+// The brush and where it lies, built from SculptBrush
+struct BrushParams {
+    inverse: mat4x4<f32>,  // Grid space to the brush's space
+    plane: vec4<f32>,  // Grid space plane flattened towards, normal and offset
+    size: vec4<f32>,  // Size of the brush shape, as in SdfPart
+    center: vec3<f32>,  // Grid space centre of the brush
+    region_min: vec3<u32>,  // First sample the brush can change
+    region_size: vec3<u32>,  // Samples the brush is dispatched over
+    snapshot_min: vec3<u32>,  // First sample copied for neighbourhood reads
+    snapshot_size: vec3<u32>,  // Samples `brush_snapshot` is dispatched over
+    dimensions: vec3<u32>,  // Grid dimensions (x, y, z)
+    shape: u32,  // SHAPE_* of sculpter::sdf
+    mode: u32,
+    falloff: u32,
+    curve_length: u32,  // Samples of a curve falloff
+    blur: u32,
+    blur_radius: u32,  // Samples averaged on each side when smoothing
+    plane_source: u32,
+    sigma: f32,  // Width of a Gaussian blur, in samples
+    radius: f32,  // Reach from the brush's centre, in its space
+    strength: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: BrushParams;
+
+@group(0) @binding(2)
+var<storage, read_write> snapshot: array<f32>;  // Densities before the brush
+
+@group(0) @binding(3)
+var<storage, read> curve: array<f32>;  // Weights from the centre to the edge
+
+// Must match the MODE_*, FALLOFF_*, BLUR_* and PLANE_* constants in brush.rs
+const MODE_SUBTRACT: u32 = 1u;
+
+const FALLOFF_LINEAR: u32 = 0u;
+const FALLOFF_SMOOTHSTEP: u32 = 1u;
+const FALLOFF_CURVE: u32 = 2u;
+
+const BLUR_BOX: u32 = 0u;
+const BLUR_GAUSSIAN: u32 = 1u;
+
+const PLANE_FIXED: u32 = 0u;
+const PLANE_SURFACE: u32 = 1u;
+
+// ===========================================================
+// Helper function MUST be at global scope in WGSL
+// ===========================================================
+
+fn grid_index(p: vec3<u32>) -> u32 {
+    return p.z * params.dimensions.y * params.dimensions.x + p.y * params.dimensions.x + p.x;
+}
+
+fn snapshot_index(p: vec3<u32>) -> u32 {
+    let q = p - params.snapshot_min;
+    return q.z * params.snapshot_size.y * params.snapshot_size.x + q.y * params.snapshot_size.x + q.x;
+}
+
+// Copied density of a neighbour, clamped to the copied region
+fn neighbour(sample: vec3<i32>, offset: vec3<i32>) -> f32 {
+    let last = vec3<i32>(params.snapshot_min + params.snapshot_size - 1u);
+    let p = clamp(sample + offset, vec3<i32>(params.snapshot_min), last);
+    return snapshot[snapshot_index(vec3<u32>(p))];
+}
+
+// Weight of the brush `t` of the way from its centre to its edge
+fn falloff(t: f32) -> f32 {
+    switch params.falloff {
+        case FALLOFF_SMOOTHSTEP: {
+            return 1.0 - smoothstep(0.0, 1.0, t);
+        }
+        case FALLOFF_CURVE: {
+            // Linear interpolation between the evenly spaced samples
+            let x = t * f32(params.curve_length - 1u);
+            let i = min(u32(x), params.curve_length - 1u);
+            let next = min(i + 1u, params.curve_length - 1u);
+            return mix(curve[i], curve[next], fract(x));
+        }
+        default: {
+            return 1.0 - t;
+        }
+    }
+}
+
+// Strength of the brush at a sample, 0 outside it
+fn brush_amount(sample: vec3<u32>) -> f32 {
+    let p = (params.inverse * vec4<f32>(vec3<f32>(sample), 1.0)).xyz;
+    let distance = primitive_distance(params.shape, params.size, p);
+    if distance >= 0.0 {
+        return 0.0;
+    }
+    let t = saturate(1.0 + distance / params.radius);
+    return params.strength * falloff(t);
+}
+
+// Mean of the copied densities within `blur_radius` samples on each axis,
+// weighted by the blur kernel
+fn blurred(sample: vec3<u32>) -> f32 {
+    let r = i32(params.blur_radius);
+    let center = vec3<i32>(sample);
+    var total = 0.0;
+    var weights = 0.0;
+    for (var z = -r; z <= r; z++) {
+        for (var y = -r; y <= r; y++) {
+            for (var x = -r; x <= r; x++) {
+                let offset = vec3<i32>(x, y, z);
+                var weight = 1.0;
+                if params.blur == BLUR_GAUSSIAN {
+                    let d = vec3<f32>(offset);
+                    weight = exp(-dot(d, d) / (2.0 * params.sigma * params.sigma));
+                }
+                total += weight * neighbour(center, offset);
+                weights += weight;
+            }
+        }
+    }
+    return total / weights;
+}
+
+// The plane flattened towards, as a normal and offset in grid space
+fn reference_plane() -> vec4<f32> {
+    if params.plane_source != PLANE_SURFACE {
+        return params.plane;
+    }
+    // Tangent plane of the copied densities at the sample nearest the centre
+    let last = vec3<f32>(params.snapshot_min + params.snapshot_size - 1u);
+    let c = vec3<i32>(clamp(round(params.center), vec3<f32>(params.snapshot_min), last));
+    let gradient = 0.5 * vec3<f32>(
+        neighbour(c, vec3<i32>(1, 0, 0)) - neighbour(c, vec3<i32>(-1, 0, 0)),
+        neighbour(c, vec3<i32>(0, 1, 0)) - neighbour(c, vec3<i32>(0, -1, 0)),
+        neighbour(c, vec3<i32>(0, 0, 1)) - neighbour(c, vec3<i32>(0, 0, -1)),
+    );
+    let slope = length(gradient);
+    if slope < 1e-6 {
+        // No surface nearby to follow
+        return params.plane;
+    }
+    let normal = gradient / slope;
+    let density = neighbour(c, vec3<i32>(0)) / slope;
+    return vec4<f32>(normal, density - dot(normal, vec3<f32>(c)));
+}
+
+// ===========================================================
+// STEP 2: Main compute shader entry points
+// ===========================================================
+@compute @workgroup_size(8, 8, 8)
+fn brush_snapshot(
+    @builtin(global_invocation_id) global_id: vec3<u32>,  // Unique thread ID across all workgroups
+) {
+    // Each thread copies one grid point of the snapshot region
+    if any(global_id >= params.snapshot_size) {
+        return;
+    }
+    let sample = params.snapshot_min + global_id;
+    snapshot[snapshot_index(sample)] = density_field[grid_index(sample)];
+}
+
+@compute @workgroup_size(8, 8, 8)
+fn brush(
+    @builtin(global_invocation_id) global_id: vec3<u32>,  // Unique thread ID across all workgroups
+) {
+    // Each thread sculpts one grid point of the region
+    if any(global_id >= params.region_size) {
+        return;
+    }
+    let sample = params.region_min + global_id;
+    let amount = brush_amount(sample);
+    if amount == 0.0 {
+        return;
+    }
+    let index = grid_index(sample);
+    density_field[index] += select(-amount, amount, params.mode == MODE_SUBTRACT);
+}
+
+@compute @workgroup_size(8, 8, 8)
+fn brush_smooth(
+    @builtin(global_invocation_id) global_id: vec3<u32>,  // Unique thread ID across all workgroups
+) {
+    if any(global_id >= params.region_size) {
+        return;
+    }
+    let sample = params.region_min + global_id;
+    let amount = brush_amount(sample);
+    if amount == 0.0 {
+        return;
+    }
+    let index = grid_index(sample);
+    density_field[index] = mix(density_field[index], blurred(sample), saturate(amount));
+}
+
+@compute @workgroup_size(8, 8, 8)
+fn brush_flatten(
+    @builtin(global_invocation_id) global_id: vec3<u32>,  // Unique thread ID across all workgroups
+) {
+    if any(global_id >= params.region_size) {
+        return;
+    }
+    let sample = params.region_min + global_id;
+    let amount = brush_amount(sample);
+    if amount == 0.0 {
+        return;
+    }
+    let plane = reference_plane();
+    let target_density = dot(plane.xyz, vec3<f32>(sample)) + plane.w;
+    let index = grid_index(sample);
+    density_field[index] = mix(density_field[index], target_density, saturate(amount));
+}
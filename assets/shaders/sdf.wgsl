@@ -0,0 +1,121 @@
+// ============================================
+// Signed distance library
+// ============================================
+// Primitives and the operations blending them, shared by density generation
+// and CSG, and importable by custom density functions:
+//
+//     #import sculpter::sdf::{primitive_distance, SHAPE_TORUS}
+//
+// Distances are negative inside, as `sculpter_core::SdfPrimitive::distance`.
+#define_import_path sculpter::sdf
+
+// Must match the SHAPE_* constants in sdf.rs
+const SHAPE_SPHERE: u32 = 0u;
+const SHAPE_CUBOID: u32 = 1u;
+const SHAPE_ROUNDED_CUBOID: u32 = 2u;
+const SHAPE_CAPSULE: u32 = 3u;
+const SHAPE_CYLINDER: u32 = 4u;
+const SHAPE_TORUS: u32 = 5u;
+const SHAPE_CONE: u32 = 6u;
+const SHAPE_PLANE: u32 = 7u;
+
+// Must match the OPERATION_* constants in sdf.rs, the smooth variants sharing them
+const OPERATION_UNION: u32 = 0u;
+const OPERATION_SUBTRACTION: u32 = 1u;
+const OPERATION_INTERSECTION: u32 = 2u;
+
+// Density of empty space, before any part is combined in
+const EMPTY: f32 = 3.4028235e38;
+
+// A primitive placed in a shape, built from `SdfPart`
+struct SdfPart {
+    inverse: mat4x4<f32>,  // Shape space to the primitive's space
+    size: vec4<f32>,  // Per shape, see primitive_distance
+    shape: u32,
+    operation: u32,
+    radius: f32,  // Blend radius, 0 = sharp
+    scale: f32,  // Primitive distances to the shape's
+}
+
+fn box_distance(p: vec3<f32>, half_extents: vec3<f32>) -> f32 {
+    let q = abs(p) - half_extents;
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+// Exact distance to a capped cone with no top radius
+fn cone_distance(p: vec3<f32>, half_height: f32, radius: f32) -> f32 {
+    let q = vec2<f32>(length(p.xz), p.y);
+    let k1 = vec2<f32>(0.0, half_height);
+    let k2 = vec2<f32>(-radius, 2.0 * half_height);
+    let cap = vec2<f32>(q.x - min(q.x, select(0.0, radius, q.y < 0.0)), abs(q.y) - half_height);
+    let side = q - k1 + k2 * clamp(dot(k1 - q, k2) / dot(k2, k2), 0.0, 1.0);
+    let s = select(1.0, -1.0, side.x < 0.0 && cap.y < 0.0);
+    return s * sqrt(min(dot(cap, cap), dot(side, side)));
+}
+
+// Signed distance from p to a primitive centered on the origin. size holds
+// the radius of spheres, the half extents (and rounding radius) of cuboids,
+// the radius and half height of capsules, cylinders and cones, and the major
+// and minor radii of tori.
+fn primitive_distance(shape: u32, size: vec4<f32>, p: vec3<f32>) -> f32 {
+    switch shape {
+        case SHAPE_CUBOID: {
+            return box_distance(p, size.xyz);
+        }
+        case SHAPE_ROUNDED_CUBOID: {
+            let radius = clamp(size.w, 0.0, min(size.x, min(size.y, size.z)));
+            return box_distance(p, size.xyz - radius) - radius;
+        }
+        case SHAPE_CAPSULE: {
+            let y = p.y - clamp(p.y, -size.y, size.y);
+            return length(vec3<f32>(p.x, y, p.z)) - size.x;
+        }
+        case SHAPE_CYLINDER: {
+            let q = vec2<f32>(length(p.xz) - size.x, abs(p.y) - size.y);
+            return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0);
+        }
+        case SHAPE_TORUS: {
+            return length(vec2<f32>(length(p.xz) - size.x, p.y)) - size.y;
+        }
+        case SHAPE_CONE: {
+            return cone_distance(p, size.y, size.x);
+        }
+        case SHAPE_PLANE: {
+            return p.y;
+        }
+        case SHAPE_SPHERE, default: {
+            return length(p) - size.x;
+        }
+    }
+}
+
+// Polynomial smooth minimum, rounding off within radius
+fn smooth_min(a: f32, b: f32, radius: f32) -> f32 {
+    if radius <= 0.0 {
+        return min(a, b);
+    }
+    let h = max(radius - abs(a - b), 0.0) / radius;
+    return min(a, b) - h * h * radius * 0.25;
+}
+
+// Combine a shape's density into another's, both negative inside
+fn combine(operation: u32, density: f32, shape: f32, radius: f32) -> f32 {
+    switch operation {
+        case OPERATION_SUBTRACTION: {
+            return -smooth_min(-density, shape, radius);
+        }
+        case OPERATION_INTERSECTION: {
+            return -smooth_min(-density, -shape, radius);
+        }
+        case OPERATION_UNION, default: {
+            return smooth_min(density, shape, radius);
+        }
+    }
+}
+
+// Combine a part into the density of the parts before it at p
+fn combine_part(part: SdfPart, density: f32, p: vec3<f32>) -> f32 {
+    let local = (part.inverse * vec4<f32>(p, 1.0)).xyz;
+    let distance = primitive_distance(part.shape, part.size, local) * part.scale;
+    return combine(part.operation, density, distance, part.radius);
+}
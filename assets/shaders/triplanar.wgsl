@@ -0,0 +1,56 @@
+// Triplanar texture splatting for TriplanarMaterial. The per-vertex material
+// weights arrive as the vertex color, see TriplanarExtension::specialize.
+
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(100)
+var layers: texture_2d_array<f32>;
+@group(#{MATERIAL_BIND_GROUP}) @binding(101)
+var layers_sampler: sampler;
+@group(#{MATERIAL_BIND_GROUP}) @binding(102)
+var<uniform> scale: f32;
+@group(#{MATERIAL_BIND_GROUP}) @binding(103)
+var<uniform> sharpness: f32;
+
+// One layer projected along the three axes, blended by the normal
+fn triplanar(position: vec3<f32>, blend: vec3<f32>, layer: i32) -> vec4<f32> {
+    let x = textureSample(layers, layers_sampler, position.zy * scale, layer);
+    let y = textureSample(layers, layers_sampler, position.xz * scale, layer);
+    let z = textureSample(layers, layers_sampler, position.xy * scale, layer);
+    return x * blend.x + y * blend.y + z * blend.z;
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+#ifdef VERTEX_COLORS
+    let weights = in.color;
+#else
+    let weights = vec4<f32>(1.0, 0.0, 0.0, 0.0);
+#endif
+
+    var blend = pow(abs(in.world_normal), vec3<f32>(sharpness));
+    blend = blend / max(blend.x + blend.y + blend.z, 1e-5);
+
+    // Every layer is sampled, keeping the samples in uniform control flow
+    let position = in.world_position.xyz;
+    let color = triplanar(position, blend, 0) * weights.x
+        + triplanar(position, blend, 1) * weights.y
+        + triplanar(position, blend, 2) * weights.z
+        + triplanar(position, blend, 3) * weights.w;
+    pbr_input.material.base_color = vec4<f32>(color.rgb, 1.0);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    return out;
+}
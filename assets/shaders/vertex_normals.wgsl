@@ -0,0 +1,254 @@
+// ============================================
+// KERNEL 7: Vertex Normals
+// ============================================
+// This shader gives every compacted vertex a smooth normal from the density
+// gradient at its position, rather than averaging the faces around it,
+// optionally bakes ambient occlusion by ray marching around that normal, and
+// takes the material id and splat weights of volumes with a material field.
+
+#import sculpter::density::{DensityDecode, decode_density, density_word, source_index, source_uvw}
+
+// STEP 1: Define bind group
+// With the DENSITY_TEXTURE shader def the field is a 3D texture instead (plus a sampler at 8)
+#ifdef DENSITY_TEXTURE
+@group(0) @binding(0)
+var density_texture: texture_3d<f32>;  // Input scalar field (red channel)
+
+@group(0) @binding(8)
+var density_sampler: sampler;  // Linear sampler for hardware trilinear filtering
+#else
+@group(0) @binding(0)
+var<storage, read> density_field: array<u32>;  // Input scalar field (f32 bits or 4 packed bytes per u32)
+#endif
+
+@group(0) @binding(1)
+var<storage, read> compacted_vertices: array<f32>;  // Input: dense vertex array (x,y,z packed)
+
+@group(0) @binding(2)
+var<storage, read> vertex_count: array<u32>;  // Input: number of compacted vertices
+
+@group(0) @binding(3)
+var<storage, read_write> compacted_normals: array<f32>;  // Output: one normal per vertex (x,y,z packed)
+
+@group(0) @binding(4)
+var<uniform> dimensions: vec3<u32>;  // Grid dimensions (x, y, z)
+
+@group(0) @binding(5)
+var<uniform> density_decode: DensityDecode;
+
+// Per-volume meshing options, built from SculptSettings
+struct SurfaceNetsParams {
+    vertex_placement: u32,
+    relaxation_iterations: u32,  // Extra gradient steps after placement, 0 = off
+    relaxation_strength: f32,  // Fraction of each step taken
+    occlusion_rays: u32,  // Ambient occlusion rays per vertex, 0 = off
+    occlusion_distance: f32,  // How far each ray marches, in voxels
+    materials: u32,  // 1 when the volume has a MaterialField, 0 = off
+    stride: u32,  // Samples skipped per grid step, 1 = full resolution (LOD levels)
+    source_dimensions: vec3<u32>,  // Dimensions of the bound field, stride times the grid
+}
+
+@group(0) @binding(6)
+var<uniform> params: SurfaceNetsParams;
+
+@group(0) @binding(7)
+var<storage, read_write> compacted_occlusion: array<f32>;  // Output: ambient occlusion per vertex, 1 = open
+
+// Texture volumes have no material field
+#ifndef DENSITY_TEXTURE
+@group(0) @binding(8)
+var<storage, read> material_field: array<u32>;  // Input: material id per sample, laid out like the densities
+
+@group(0) @binding(9)
+var<storage, read_write> compacted_material_ids: array<u32>;  // Output: material id per vertex
+
+@group(0) @binding(10)
+var<storage, read_write> compacted_material_weights: array<f32>;  // Output: weights of materials 0 to 3 per vertex (packed)
+#endif
+
+// Samples taken along each occlusion ray
+const OCCLUSION_STEPS: u32 = 4u;
+// Spreads the rays evenly around the normal
+const GOLDEN_ANGLE: f32 = 2.399963;
+
+// ===========================================================
+// Helper function MUST be at global scope in WGSL
+// ===========================================================
+#ifdef DENSITY_TEXTURE
+    fn sample_density(x: u32, y: u32, z: u32) -> f32 {
+        let uvw = source_uvw(vec3<u32>(x, y, z), params.stride, params.source_dimensions);
+        return textureSampleLevel(density_texture, density_sampler, uvw, 0.0).r;
+    }
+#else
+    fn sample_density(x: u32, y: u32, z: u32) -> f32 {
+        let index = source_index(vec3<u32>(x, y, z), params.stride, params.source_dimensions);
+        return decode_density(density_field[density_word(index, density_decode)], index, density_decode);
+    }
+#endif
+
+// Trilinear interpolation of the grid at any point inside the volume
+fn sample_trilinear(p: vec3<f32>) -> f32 {
+    let q = clamp(p, vec3<f32>(0.0), vec3<f32>(dimensions - 1u));
+    let base = min(vec3<u32>(floor(q)), dimensions - 2u);
+    let f = q - vec3<f32>(base);
+    let c00 = mix(sample_density(base.x, base.y,      base.z),      sample_density(base.x + 1u, base.y,      base.z),      f.x);
+    let c10 = mix(sample_density(base.x, base.y + 1u, base.z),      sample_density(base.x + 1u, base.y + 1u, base.z),      f.x);
+    let c01 = mix(sample_density(base.x, base.y,      base.z + 1u), sample_density(base.x + 1u, base.y,      base.z + 1u), f.x);
+    let c11 = mix(sample_density(base.x, base.y + 1u, base.z + 1u), sample_density(base.x + 1u, base.y + 1u, base.z + 1u), f.x);
+    return mix(mix(c00, c10, f.y), mix(c01, c11, f.y), f.z);
+}
+
+// Central differences of the interpolated field, half a voxel either side
+fn density_gradient(p: vec3<f32>) -> vec3<f32> {
+    let h = 0.5;
+    return vec3<f32>(
+        sample_trilinear(p + vec3<f32>(h, 0.0, 0.0)) - sample_trilinear(p - vec3<f32>(h, 0.0, 0.0)),
+        sample_trilinear(p + vec3<f32>(0.0, h, 0.0)) - sample_trilinear(p - vec3<f32>(0.0, h, 0.0)),
+        sample_trilinear(p + vec3<f32>(0.0, 0.0, h)) - sample_trilinear(p - vec3<f32>(0.0, 0.0, h)),
+    ) / (2.0 * h);
+}
+
+// Cosine weighted share of rays over the hemisphere around normal that reach
+// params.occlusion_distance without entering solid
+fn ambient_occlusion(p: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if (all(normal == vec3<f32>(0.0)) || params.occlusion_rays == 0u) {
+        return 1.0;
+    }
+    var up = vec3<f32>(0.0, 0.0, 1.0);
+    if (abs(normal.z) >= 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+
+    var total = 0.0;
+    var occluded = 0.0;
+    for (var ray = 0u; ray < params.occlusion_rays; ray++) {
+        // Evenly spaced heights over the hemisphere, turning by the golden angle
+        let cos_theta = 1.0 - (f32(ray) + 0.5) / f32(params.occlusion_rays);
+        let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        let phi = f32(ray) * GOLDEN_ANGLE;
+        let direction = tangent * (cos(phi) * sin_theta)
+            + bitangent * (sin(phi) * sin_theta)
+            + normal * cos_theta;
+
+        total += cos_theta;
+        for (var step = 1u; step <= OCCLUSION_STEPS; step++) {
+            let q = p + direction * (params.occlusion_distance * f32(step) / f32(OCCLUSION_STEPS));
+            if (sample_trilinear(q) < 0.0) {
+                occluded += cos_theta;
+                break;
+            }
+        }
+    }
+    return 1.0 - occluded / total;
+}
+
+#ifndef DENSITY_TEXTURE
+// Corner i of the cell containing p, clamped to the grid
+fn cell_corner(p: vec3<f32>, i: u32) -> vec3<u32> {
+    let cell = min(vec3<u32>(max(floor(p), vec3<f32>(0.0))), dimensions - 2u);
+    return cell + vec3<u32>(i & 1u, (i >> 1u) & 1u, (i >> 2u) & 1u);
+}
+
+// Id of the nearest inside corner of the vertex's cell, or of the nearest
+// corner when none is inside, as vertex_materials does on the CPU
+fn vertex_material(p: vec3<f32>) -> u32 {
+    var best = 0u;
+    var best_inside = false;
+    var best_distance = 3.4e38;
+    for (var i = 0u; i < 8u; i++) {
+        let corner = cell_corner(p, i);
+        let inside = sample_density(corner.x, corner.y, corner.z) < 0.0;
+        let offset = vec3<f32>(corner) - p;
+        let distance = dot(offset, offset);
+        // Inside corners win, then the nearest
+        if ((inside && !best_inside) || (inside == best_inside && distance < best_distance)) {
+            best = i;
+            best_inside = inside;
+            best_distance = distance;
+        }
+    }
+    return material_field[source_index(cell_corner(p, best), params.stride, params.source_dimensions)];
+}
+
+// Trilinear weights of the cell's inside corners (all of them when none is
+// inside) pooled by material 0 to 3 and normalized, as vertex_material_weights
+// does on the CPU
+fn vertex_material_weights(p: vec3<f32>) -> vec4<f32> {
+    var inside = vec4<f32>(0.0);
+    var all = vec4<f32>(0.0);
+    for (var i = 0u; i < 8u; i++) {
+        let corner = cell_corner(p, i);
+        let material = material_field[source_index(corner, params.stride, params.source_dimensions)];
+        if (material > 3u) {
+            continue;
+        }
+        let f = max(1.0 - abs(p - vec3<f32>(corner)), vec3<f32>(0.0));
+        var layer = vec4<f32>(0.0);
+        layer[material] = f.x * f.y * f.z;
+        all += layer;
+        if (sample_density(corner.x, corner.y, corner.z) < 0.0) {
+            inside += layer;
+        }
+    }
+    var weights = all;
+    if (dot(inside, vec4<f32>(1.0)) > 0.0) {
+        weights = inside;
+    }
+    let total = dot(weights, vec4<f32>(1.0));
+    if (total > 0.0) {
+        return weights / total;
+    }
+    return vec4<f32>(0.0);
+}
+#endif
+
+// STEP 2: Define workgroup size
+// Using 256 threads for 1D processing of the compacted vertices
+@compute @workgroup_size(256, 1, 1)
+fn vertex_normals(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    // STEP 3: Only the compacted prefix holds vertices
+    let vertex = global_id.x;
+    if (vertex >= vertex_count[0]) {
+        return;
+    }
+
+    // STEP 4: Densities grow away from solid, so the gradient points out of the surface
+    let base = vertex * 3u;
+    let p = vec3<f32>(
+        compacted_vertices[base + 0u],
+        compacted_vertices[base + 1u],
+        compacted_vertices[base + 2u],
+    );
+    let gradient = density_gradient(p);
+    let length_squared = dot(gradient, gradient);
+    // A flat field has no direction, leave a zero normal for the CPU to fill in
+    var normal = vec3<f32>(0.0);
+    if (length_squared > 1e-12) {
+        normal = gradient * inverseSqrt(length_squared);
+    }
+
+    compacted_normals[base + 0u] = normal.x;
+    compacted_normals[base + 1u] = normal.y;
+    compacted_normals[base + 2u] = normal.z;
+
+    // STEP 5: Ambient occlusion, only read back when enabled
+    if (params.occlusion_rays > 0u) {
+        compacted_occlusion[vertex] = ambient_occlusion(p, normal);
+    }
+
+    // STEP 6: Materials, only read back for volumes with a material field
+#ifndef DENSITY_TEXTURE
+    if (params.materials > 0u) {
+        compacted_material_ids[vertex] = vertex_material(p);
+        let weights = vertex_material_weights(p);
+        compacted_material_weights[vertex * 4u + 0u] = weights.x;
+        compacted_material_weights[vertex * 4u + 1u] = weights.y;
+        compacted_material_weights[vertex * 4u + 2u] = weights.z;
+        compacted_material_weights[vertex * 4u + 3u] = weights.w;
+    }
+#endif
+}
@@ -1,4 +1,7 @@
 // Example: How to use the Surface Nets plugin
+// The advanced helpers below are reference snippets and are not wired into `main`.
+#![allow(dead_code)]
+
 use bevy::prelude::*;
 use sculpter::prelude::*;
 
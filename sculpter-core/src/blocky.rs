@@ -0,0 +1,120 @@
+//! Blocky meshing: every solid voxel is a unit cube, with the exposed faces of
+//! each slice merged into larger quads.
+
+use glam::{IVec3, UVec3, Vec3};
+
+use crate::{
+    grid::{density_count, index},
+    mesh::FaceBuffers,
+};
+
+/// Quad vertices owned by each voxel, 4 for each of the 6 face directions.
+pub const VERTICES_PER_VOXEL: u32 = 24;
+
+/// Quads owned by each voxel, one per face direction.
+pub const FACES_PER_VOXEL: u32 = 6;
+
+/// Voxels as seen by the blocky mesher, solid where the density is negative.
+struct Voxels<'a> {
+    densities: &'a [f32],
+    dims: UVec3,
+}
+
+impl Voxels<'_> {
+    fn solid(&self, p: IVec3) -> bool {
+        // Everything outside the grid is empty, so the border is closed
+        if p.cmplt(IVec3::ZERO).any() || p.cmpge(self.dims.as_ivec3()).any() {
+            return false;
+        }
+        let p = p.as_uvec3();
+        self.densities[index(self.dims, p.x, p.y, p.z) as usize] < 0.0
+    }
+
+    /// Whether voxel `p` has an exposed face towards `normal`.
+    fn exposed(&self, p: IVec3, normal: IVec3) -> bool {
+        self.solid(p) && !self.solid(p + normal)
+    }
+
+    /// Length along `u` of the run of exposed faces starting at `p`, or 0 when
+    /// `p` doesn't start a run.
+    fn run(&self, p: IVec3, normal: IVec3, u: IVec3) -> i32 {
+        if !self.exposed(p, normal) || self.exposed(p - u, normal) {
+            return 0;
+        }
+        let mut width = 1;
+        while self.exposed(p + u * width, normal) {
+            width += 1;
+        }
+        width
+    }
+}
+
+/// Face direction `d` as (normal, u, v): +X, -X, +Y, -Y, +Z, -Z, with u and v
+/// the two other axes in cyclic order, so u x v points along the axis.
+/// Must match `face_frame` in blocky.wgsl.
+fn face_frame(d: u32) -> (IVec3, IVec3, IVec3) {
+    let axis = (d / 2) as usize;
+    let sign = if d & 1 == 0 { 1 } else { -1 };
+    let unit = |a: usize| IVec3::AXES[a % 3];
+    (unit(axis) * sign, unit(axis + 1), unit(axis + 2))
+}
+
+/// The merged quad owned by voxel `p` in direction `d`, as its corners wound
+/// counter-clockwise seen from outside, or `None` if it owns none.
+///
+/// Exposed faces are merged into runs along u, and runs of equal extent in
+/// consecutive rows along v into rectangles. The rectangle belongs to the voxel
+/// at its minimum corner, so every voxel decides alone, as a GPU thread does.
+/// Must match `owned_quad` in blocky.wgsl.
+fn owned_quad(voxels: &Voxels, p: IVec3, d: u32) -> Option<[Vec3; 4]> {
+    let (normal, u, v) = face_frame(d);
+    let width = voxels.run(p, normal, u);
+    if width == 0 || voxels.run(p - v, normal, u) == width {
+        return None;
+    }
+    let mut height = 1;
+    while voxels.run(p + v * height, normal, u) == width {
+        height += 1;
+    }
+
+    // Faces on the positive side lie one voxel further along the normal
+    let base = (p + normal.max(IVec3::ZERO)).as_vec3();
+    let (u, v) = ((u * width).as_vec3(), (v * height).as_vec3());
+    let corners = [base, base + u, base + u + v, base + v];
+    Some(if d & 1 == 0 {
+        corners
+    } else {
+        [corners[0], corners[3], corners[2], corners[1]]
+    })
+}
+
+/// Blocky meshing with the same slot layout and ordering as blocky.wgsl, so
+/// both backends produce identical buffers. Voxel `p` spans `p..p + 1`.
+pub fn blocky(densities: &[f32], dims: UVec3) -> FaceBuffers {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    if densities.len() < density_count(dims) as usize {
+        return FaceBuffers { vertices, faces };
+    }
+
+    let voxels = Voxels { densities, dims };
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let p = UVec3::new(x, y, z).as_ivec3();
+                for d in 0..FACES_PER_VOXEL {
+                    let Some(corners) = owned_quad(&voxels, p, d) else {
+                        continue;
+                    };
+                    let first = (vertices.len() / 3) as u32;
+                    for corner in corners {
+                        vertices.extend_from_slice(&corner.to_array());
+                    }
+                    faces.extend_from_slice(&[first, first + 1, first + 2, first + 3]);
+                }
+            }
+        }
+    }
+
+    FaceBuffers { vertices, faces }
+}
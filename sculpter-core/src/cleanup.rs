@@ -0,0 +1,126 @@
+//! Removal of degenerate triangles, which physics engines reject.
+
+use std::collections::HashMap;
+
+use glam::{IVec3, Vec3};
+
+use crate::mesh::MeshData;
+
+/// Coincident vertices whose normals differ by more than this (cosine) are
+/// intentional seams, such as blocky corners, and stay apart.
+const WELD_MIN_NORMAL_COS: f32 = 0.9;
+
+/// How degenerate triangles are filtered out of a mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DegenerateFilter {
+    /// Vertices closer than this, in voxels, are merged into one. 0 merges none.
+    pub weld_distance: f32,
+    /// Triangles whose height is less than this fraction of their longest edge
+    /// are dropped as slivers
+    pub min_aspect: f32,
+}
+
+impl Default for DegenerateFilter {
+    fn default() -> Self {
+        Self {
+            weld_distance: 1e-3,
+            min_aspect: 1e-3,
+        }
+    }
+}
+
+/// Merge coincident vertices within `filter.weld_distance`, drop the triangles
+/// this collapses along with zero-area and sliver ones, then drop the vertices
+/// no triangle uses any more. Merged vertices keep the first one's normal and
+/// attributes.
+pub fn remove_degenerate_triangles(mesh: &mut MeshData, filter: DegenerateFilter) {
+    let remap = weld_vertices(&mesh.positions, &mesh.normals, filter.weld_distance);
+
+    let mut indices = Vec::with_capacity(mesh.indices.len());
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| remap[triangle[i] as usize]);
+        if a == b || b == c || c == a {
+            continue;
+        }
+        let [pa, pb, pc] = [a, b, c].map(|i| mesh.positions[i as usize]);
+        // Twice the area over the longest edge is the height on it
+        let double_area = (pb - pa).cross(pc - pa).length();
+        let longest_squared = (pb - pa)
+            .length_squared()
+            .max((pc - pb).length_squared())
+            .max((pa - pc).length_squared());
+        if double_area <= filter.min_aspect * longest_squared || longest_squared == 0.0 {
+            continue;
+        }
+        indices.extend([a, b, c]);
+    }
+
+    compact_vertices(mesh, indices);
+}
+
+/// Replace the mesh's triangles with `indices`, dropping the vertices they no
+/// longer use. The rest keep their order.
+pub(crate) fn compact_vertices(mesh: &mut MeshData, indices: Vec<u32>) {
+    let mut used = vec![false; mesh.positions.len()];
+    for &index in &indices {
+        used[index as usize] = true;
+    }
+    let kept: Vec<u32> = (0..mesh.positions.len() as u32)
+        .filter(|&i| used[i as usize])
+        .collect();
+    let mut new_index = vec![0; mesh.positions.len()];
+    for (new, &old) in kept.iter().enumerate() {
+        new_index[old as usize] = new as u32;
+    }
+
+    mesh.positions = kept.iter().map(|&i| mesh.positions[i as usize]).collect();
+    mesh.normals = kept.iter().map(|&i| mesh.normals[i as usize]).collect();
+    for values in mesh.attributes.values_mut() {
+        *values = values.gather(&kept);
+    }
+    mesh.indices = indices
+        .into_iter()
+        .map(|index| new_index[index as usize])
+        .collect();
+}
+
+/// For every vertex, the first earlier vertex within `distance` facing the same
+/// way, or itself.
+fn weld_vertices(positions: &[Vec3], normals: &[Vec3], distance: f32) -> Vec<u32> {
+    let mut remap: Vec<u32> = (0..positions.len() as u32).collect();
+    if distance <= 0.0 {
+        return remap;
+    }
+
+    // Cells one weld distance wide, so matches are at most one cell away
+    let cell_of = |p: Vec3| (p / distance).floor().as_ivec3();
+    let mut cells: HashMap<IVec3, Vec<u32>> = HashMap::new();
+    let distance_squared = distance * distance;
+    for (vertex, &position) in positions.iter().enumerate() {
+        let cell = cell_of(position);
+        let mut found = None;
+        'search: for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let Some(others) = cells.get(&(cell + IVec3::new(x, y, z))) else {
+                        continue;
+                    };
+                    for &other in others {
+                        let other_position = positions[other as usize];
+                        if other_position.distance_squared(position) <= distance_squared
+                            && normals[other as usize].dot(normals[vertex]) >= WELD_MIN_NORMAL_COS
+                        {
+                            found = Some(other);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+        match found {
+            Some(other) => remap[vertex] = other,
+            None => cells.entry(cell).or_default().push(vertex as u32),
+        }
+    }
+    remap
+}
@@ -0,0 +1,115 @@
+//! Connected components of a field's solid, for finding parts left floating
+//! after an edit.
+
+use glam::UVec3;
+
+use crate::grid::index;
+
+/// Label of samples outside the solid.
+pub const NO_COMPONENT: u32 = u32::MAX;
+
+/// The solid samples of a field grouped into face-connected components.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SolidComponents {
+    /// Component of each sample, x fastest, or `NO_COMPONENT`
+    pub labels: Vec<u32>,
+    /// Samples in each component
+    pub sizes: Vec<u32>,
+    /// Whether each component reaches a face of the grid
+    pub touches_boundary: Vec<bool>,
+}
+
+/// Flood fill the solid samples, those with a density of 0 or less, of a `dims`
+/// field into components sharing faces. Components are numbered in the order
+/// their first sample comes in the field.
+pub fn solid_components(densities: &[f32], dims: UVec3) -> SolidComponents {
+    let mut components = SolidComponents {
+        labels: vec![NO_COMPONENT; densities.len()],
+        ..Default::default()
+    };
+    let on_boundary = |p: UVec3| p.cmpeq(UVec3::ZERO).any() || p.cmpeq(dims - 1).any();
+    let mut stack = Vec::new();
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let start = index(dims, x, y, z) as usize;
+                if densities[start] > 0.0 || components.labels[start] != NO_COMPONENT {
+                    continue;
+                }
+                let label = components.sizes.len() as u32;
+                let (mut size, mut touches_boundary) = (0, false);
+                components.labels[start] = label;
+                stack.push(UVec3::new(x, y, z));
+                while let Some(p) = stack.pop() {
+                    size += 1;
+                    touches_boundary |= on_boundary(p);
+                    for neighbour in face_neighbours(p, dims) {
+                        let i = index(dims, neighbour.x, neighbour.y, neighbour.z) as usize;
+                        if densities[i] <= 0.0 && components.labels[i] == NO_COMPONENT {
+                            components.labels[i] = label;
+                            stack.push(neighbour);
+                        }
+                    }
+                }
+                components.sizes.push(size);
+                components.touches_boundary.push(touches_boundary);
+            }
+        }
+    }
+    components
+}
+
+/// Samples reached by a flood fill.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FloodFill {
+    /// Index of each sample, x fastest, in the order they were reached
+    pub samples: Vec<u32>,
+    /// Whether the fill reaches a face of the grid
+    pub touches_boundary: bool,
+}
+
+/// Flood fill a `dims` field from `start` through samples sharing faces, the
+/// samples whose index `include` accepts. Returns `None` once more than
+/// `max_samples` are reached, and no samples when `start` isn't accepted.
+pub fn flood_fill(
+    dims: UVec3,
+    start: UVec3,
+    max_samples: usize,
+    mut include: impl FnMut(usize) -> bool,
+) -> Option<FloodFill> {
+    let mut fill = FloodFill::default();
+    if start.cmpge(dims).any() || !include(index(dims, start.x, start.y, start.z) as usize) {
+        return Some(fill);
+    }
+    let mut reached = vec![false; crate::grid::density_count(dims) as usize];
+    reached[index(dims, start.x, start.y, start.z) as usize] = true;
+    let mut stack = vec![start];
+    while let Some(p) = stack.pop() {
+        if fill.samples.len() == max_samples {
+            return None;
+        }
+        fill.samples.push(index(dims, p.x, p.y, p.z));
+        fill.touches_boundary |= p.cmpeq(UVec3::ZERO).any() || p.cmpeq(dims - 1).any();
+        for neighbour in face_neighbours(p, dims) {
+            let i = index(dims, neighbour.x, neighbour.y, neighbour.z) as usize;
+            if !reached[i] && include(i) {
+                reached[i] = true;
+                stack.push(neighbour);
+            }
+        }
+    }
+    Some(fill)
+}
+
+/// The up to six samples sharing a face with `p` within the grid.
+fn face_neighbours(p: UVec3, dims: UVec3) -> impl Iterator<Item = UVec3> {
+    (0..3).flat_map(move |axis| {
+        let mut lower = p;
+        let mut upper = p;
+        lower[axis] = lower[axis].wrapping_sub(1);
+        upper[axis] += 1;
+        [lower, upper]
+            .into_iter()
+            .filter(move |neighbour| neighbour[axis] < dims[axis])
+    })
+}
@@ -0,0 +1,149 @@
+//! Axis conventions of volume and mesh file formats, and conversions between
+//! them and Bevy's (Y-up, right-handed, -Z forward).
+
+use glam::{IVec3, UVec3, Vec3};
+
+use crate::mesh::MeshData;
+
+/// Which axis of a file format points up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Handedness {
+    #[default]
+    Right,
+    Left,
+}
+
+/// Axis conventions of a volume or mesh format.
+///
+/// Importers convert into Bevy's convention (Y-up, right-handed, -Z forward) and
+/// exporters back out of it, so volumes and meshes aren't mirrored or rotated.
+/// Each format has a default that can be overridden for files that don't follow it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoordinateSystem {
+    pub up: UpAxis,
+    pub handedness: Handedness,
+}
+
+impl CoordinateSystem {
+    /// Bevy's own convention, also used by glTF and OBJ.
+    pub const Y_UP_RIGHT_HANDED: Self = Self::new(UpAxis::Y, Handedness::Right);
+    /// Used by MagicaVoxel (.vox), Blender and most CAD and medical (NRRD) data.
+    pub const Z_UP_RIGHT_HANDED: Self = Self::new(UpAxis::Z, Handedness::Right);
+    /// Used by Unity and DirectX-style tools.
+    pub const Y_UP_LEFT_HANDED: Self = Self::new(UpAxis::Y, Handedness::Left);
+    /// Used by Unreal.
+    pub const Z_UP_LEFT_HANDED: Self = Self::new(UpAxis::Z, Handedness::Left);
+
+    pub const fn new(up: UpAxis, handedness: Handedness) -> Self {
+        Self { up, handedness }
+    }
+
+    /// For each Bevy axis, the axis of this system it comes from and whether it
+    /// is negated.
+    fn axes(self) -> [(usize, bool); 3] {
+        match (self.up, self.handedness) {
+            (UpAxis::Y, Handedness::Right) => [(0, false), (1, false), (2, false)],
+            // Mirror forward
+            (UpAxis::Y, Handedness::Left) => [(0, false), (1, false), (2, true)],
+            // Rotate -90 degrees about X, forward (+Y) becomes -Z
+            (UpAxis::Z, Handedness::Right) => [(0, false), (2, false), (1, true)],
+            // Swapping Y and Z both mirrors and rotates
+            (UpAxis::Z, Handedness::Left) => [(0, false), (2, false), (1, false)],
+        }
+    }
+
+    /// Whether converting to or from Bevy mirrors geometry, which reverses
+    /// triangle winding.
+    pub fn flips_winding(self) -> bool {
+        self.handedness == Handedness::Left
+    }
+
+    /// Convert a position or direction in this system to Bevy's.
+    pub fn to_bevy(self, p: Vec3) -> Vec3 {
+        let p = p.to_array();
+        Vec3::from_array(
+            self.axes()
+                .map(|(axis, negate)| if negate { -p[axis] } else { p[axis] }),
+        )
+    }
+
+    /// Convert a position or direction in Bevy's system to this one.
+    pub fn from_bevy(self, p: Vec3) -> Vec3 {
+        let mut out = [0.0; 3];
+        for (i, (axis, negate)) in self.axes().into_iter().enumerate() {
+            out[axis] = if negate { -p[i] } else { p[i] };
+        }
+        Vec3::from_array(out)
+    }
+
+    /// Convert a grid sample in this system to Bevy's. Negated axes mirror the
+    /// sample about zero, for unbounded grids such as sparse volumes.
+    pub fn sample_to_bevy(self, p: IVec3) -> IVec3 {
+        self.to_bevy(p.as_vec3()).as_ivec3()
+    }
+
+    /// Convert a grid sample in Bevy's system to this one.
+    pub fn sample_from_bevy(self, p: IVec3) -> IVec3 {
+        self.from_bevy(p.as_vec3()).as_ivec3()
+    }
+
+    /// Convert a mesh in Bevy's system to this one, positions and normals alike,
+    /// keeping its faces pointing the same way.
+    pub fn mesh_from_bevy(self, mesh: &MeshData) -> MeshData {
+        let mut converted = mesh.clone();
+        for position in &mut converted.positions {
+            *position = self.from_bevy(*position);
+        }
+        for normal in &mut converted.normals {
+            *normal = self.from_bevy(*normal);
+        }
+        if self.flips_winding() {
+            converted.invert_winding();
+        }
+        converted
+    }
+
+    /// Grid dimensions of a volume in this system once converted to Bevy's.
+    pub fn dims_to_bevy(self, dims: UVec3) -> UVec3 {
+        let dims = dims.to_array();
+        UVec3::from_array(self.axes().map(|(axis, _)| dims[axis]))
+    }
+
+    /// Reorder an x-fastest volume in this system into Bevy's axes.
+    ///
+    /// Negated axes are reversed rather than moved below zero, so the volume
+    /// stays in positive grid space. Returns the densities and new dimensions.
+    pub fn volume_to_bevy<T: Copy>(self, values: &[T], dims: UVec3) -> (Vec<T>, UVec3) {
+        let axes = self.axes();
+        let src_dims = dims.to_array();
+        let dst_dims = self.dims_to_bevy(dims);
+        let mut out = Vec::with_capacity(values.len());
+        for z in 0..dst_dims.z {
+            for y in 0..dst_dims.y {
+                for x in 0..dst_dims.x {
+                    let mut src = [0; 3];
+                    for (dst, (axis, negate)) in [x, y, z].into_iter().zip(axes) {
+                        src[axis] = if negate {
+                            src_dims[axis] - 1 - dst
+                        } else {
+                            dst
+                        };
+                    }
+                    let index = src[0] + src[1] * src_dims[0] + src[2] * src_dims[0] * src_dims[1];
+                    out.push(values[index as usize]);
+                }
+            }
+        }
+        (out, dst_dims)
+    }
+}
@@ -0,0 +1,170 @@
+//! Boolean operations compositing signed distance primitives or other fields
+//! into a field, to merge and cut structures.
+
+use glam::{Affine3A, UVec3, Vec3, uvec3};
+
+use crate::{
+    grid::index,
+    sdf::{SdfPrimitive, SdfShape, transform_bounds},
+    surface_nets::sample_trilinear,
+};
+
+/// Samples further than this many voxels outside a shape keep their density.
+/// The surface only moves within it, and meshing reads no further.
+pub const CSG_BAND: f32 = 3.0;
+
+/// How a shape's density is combined with a field's.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CsgOperation {
+    /// Add the shape's solid to the field's
+    Union,
+    /// Cut the shape out of the field
+    Subtraction,
+    /// Keep only the solid inside both
+    Intersection,
+    /// `Union` blended over `radius` samples, filleting the seam
+    SmoothUnion { radius: f32 },
+    /// `Subtraction` blended over `radius` samples
+    SmoothSubtraction { radius: f32 },
+    /// `Intersection` blended over `radius` samples
+    SmoothIntersection { radius: f32 },
+}
+
+impl CsgOperation {
+    /// Combine a field's `density` with a `shape`'s, both negative inside.
+    pub fn apply(self, density: f32, shape: f32) -> f32 {
+        match self {
+            Self::Union => density.min(shape),
+            Self::Subtraction => density.max(-shape),
+            Self::Intersection => density.max(shape),
+            Self::SmoothUnion { radius } => smooth_min(density, shape, radius),
+            Self::SmoothSubtraction { radius } => -smooth_min(-density, shape, radius),
+            Self::SmoothIntersection { radius } => -smooth_min(-density, -shape, radius),
+        }
+    }
+
+    /// Blend radius in samples, 0 for the sharp operations.
+    pub fn radius(self) -> f32 {
+        match self {
+            Self::Union | Self::Subtraction | Self::Intersection => 0.0,
+            Self::SmoothUnion { radius }
+            | Self::SmoothSubtraction { radius }
+            | Self::SmoothIntersection { radius } => radius.max(0.0),
+        }
+    }
+
+    /// Whether samples away from the shape change too, emptying outside it.
+    pub fn is_intersection(self) -> bool {
+        matches!(self, Self::Intersection | Self::SmoothIntersection { .. })
+    }
+}
+
+/// Polynomial smooth minimum of `a` and `b`, rounding off where they are
+/// within `radius` of each other.
+pub fn smooth_min(a: f32, b: f32, radius: f32) -> f32 {
+    if radius <= 0.0 {
+        return a.min(b);
+    }
+    let h = (radius - (a - b).abs()).max(0.0) / radius;
+    a.min(b) - h * h * radius * 0.25
+}
+
+/// What is composited into a field.
+#[derive(Clone, Copy, Debug)]
+pub enum CsgShape<'a> {
+    Primitive(SdfPrimitive),
+    Sdf(&'a SdfShape),
+    /// Another field, sample `(x, y, z)` at `(x, y, z)` in its space. Needs at
+    /// least two samples along each axis.
+    Field {
+        densities: &'a [f32],
+        dims: UVec3,
+    },
+}
+
+impl CsgShape<'_> {
+    /// Density of the shape at `p` in its own space, negative inside.
+    pub fn density(&self, p: Vec3) -> f32 {
+        match *self {
+            Self::Primitive(primitive) => primitive.distance(p),
+            Self::Sdf(shape) => shape.distance(p),
+            Self::Field { densities, dims } => {
+                let density = sample_trilinear(densities, dims, p);
+                // Beyond its samples the field is at least as far out as its box
+                let outside = p.distance(p.clamp(Vec3::ZERO, (dims - 1).as_vec3()));
+                if outside > 0.0 {
+                    density.max(outside)
+                } else {
+                    density
+                }
+            }
+        }
+    }
+
+    /// Corners of the box around the shape in its own space, or `None` when
+    /// it has no solid.
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        match *self {
+            Self::Primitive(primitive) => {
+                Some((-primitive.half_extents(), primitive.half_extents()))
+            }
+            Self::Sdf(shape) => shape.bounds(),
+            Self::Field { dims, .. } => Some((Vec3::ZERO, (dims - 1).as_vec3())),
+        }
+    }
+}
+
+/// Average scale of a shape's `transform`, turning distances in its space into
+/// samples of the field. Shapes should be scaled uniformly to stay distances.
+pub fn csg_scale(transform: Affine3A) -> f32 {
+    transform.matrix3.determinant().abs().cbrt()
+}
+
+/// Samples of a `dims` field that compositing `shape`, placed in the field's
+/// grid space by `transform`, can change: from the first corner inclusive to the
+/// second exclusive, or `None` when it misses the field. Intersections change
+/// every sample.
+pub fn csg_region(
+    dims: UVec3,
+    shape: &CsgShape,
+    transform: Affine3A,
+    operation: CsgOperation,
+) -> Option<(UVec3, UVec3)> {
+    if operation.is_intersection() {
+        return Some((UVec3::ZERO, dims));
+    }
+    let (local_min, local_max) = shape.bounds()?;
+    let (min, max) = transform_bounds(local_min, local_max, transform);
+    let margin = CSG_BAND + operation.radius();
+    let min = (min - margin).ceil().max(Vec3::ZERO);
+    let max = ((max + margin).floor() + 1.0).min(dims.as_vec3());
+    min.cmplt(max)
+        .all()
+        .then(|| (min.as_uvec3(), max.as_uvec3()))
+}
+
+/// Composite `shape`, placed in the `dims` field's grid space by `transform`,
+/// into its densities with `operation`. Returns the region changed, as
+/// `csg_region`.
+pub fn composite(
+    densities: &mut [f32],
+    dims: UVec3,
+    shape: &CsgShape,
+    transform: Affine3A,
+    operation: CsgOperation,
+) -> Option<(UVec3, UVec3)> {
+    let (min, max) = csg_region(dims, shape, transform, operation)?;
+    let inverse = transform.inverse();
+    let scale = csg_scale(transform);
+    for z in min.z..max.z {
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let i = index(dims, x, y, z) as usize;
+                let p = inverse.transform_point3(uvec3(x, y, z).as_vec3());
+                densities[i] = operation.apply(densities[i], shape.density(p) * scale);
+            }
+        }
+    }
+    Some((min, max))
+}
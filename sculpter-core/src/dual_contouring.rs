@@ -0,0 +1,148 @@
+//! Dual contouring: surface nets topology with vertices placed by a quadric
+//! error solve, which keeps sharp features.
+
+use glam::{Mat3, UVec3, Vec3};
+
+use crate::{
+    mesh::FaceBuffers,
+    surface_nets::{density_gradient, dual_mesh},
+};
+
+// Must match QEF_THRESHOLD in dual_contouring.wgsl
+pub const QEF_THRESHOLD: f32 = 0.1;
+
+// Jacobi sweeps of the 3x3 eigen solve, must match QEF_SWEEPS in dual_contouring.wgsl
+pub const QEF_SWEEPS: u32 = 4;
+
+/// Dual contouring as dual_contouring.wgsl: surface nets topology with each
+/// vertex at the minimiser of the cell's quadric error.
+pub fn dual_contouring(densities: &[f32], dims: UVec3) -> FaceBuffers {
+    dual_mesh(densities, dims, |cell, crossings| {
+        let mass_point = crossings.iter().sum::<Vec3>() / crossings.len() as f32;
+        let mut ata = Mat3::ZERO;
+        let mut atb = Vec3::ZERO;
+        for &point in crossings {
+            let gradient = density_gradient(densities, dims, point);
+            let length_squared = gradient.length_squared();
+            if length_squared < 1e-12 {
+                continue;
+            }
+            let n = gradient / length_squared.sqrt();
+            ata += Mat3::from_cols(n * n.x, n * n.y, n * n.z);
+            atb += n * n.dot(point - mass_point);
+        }
+        (mass_point + solve_qef(ata, atb)).clamp(cell.as_vec3(), cell.as_vec3() + 1.0)
+    })
+}
+
+/// Least squares solution of `ata * x = atb` through a Jacobi eigen decomposition,
+/// ignoring directions with eigenvalues below `QEF_THRESHOLD`.
+/// Must match `solve_qef` in dual_contouring.wgsl.
+pub fn solve_qef(ata: Mat3, atb: Vec3) -> Vec3 {
+    let mut a = ata;
+    let mut v = Mat3::IDENTITY;
+    for _ in 0..QEF_SWEEPS {
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            let j = jacobi_rotation(a, p, q);
+            a = j.transpose() * a * j;
+            v *= j;
+        }
+    }
+
+    // Pseudo-inverse of the diagonal, applied in the eigenbasis
+    let b = v.transpose() * atb;
+    let mut x = Vec3::ZERO;
+    for i in 0..3 {
+        if a.col(i)[i] > QEF_THRESHOLD {
+            x[i] = b[i] / a.col(i)[i];
+        }
+    }
+    v * x
+}
+
+/// Rotation zeroing the (p, q) entry of the symmetric matrix `a`.
+fn jacobi_rotation(a: Mat3, p: usize, q: usize) -> Mat3 {
+    let mut j = Mat3::IDENTITY;
+    let apq = a.col(q)[p];
+    if apq.abs() < 1e-12 {
+        return j;
+    }
+    let theta = (a.col(q)[q] - a.col(p)[p]) / (2.0 * apq);
+    let t = if theta >= 0.0 { 1.0 } else { -1.0 } / (theta.abs() + (theta * theta + 1.0).sqrt());
+    let c = 1.0 / (t * t + 1.0).sqrt();
+    let s = t * c;
+    j.col_mut(p)[p] = c;
+    j.col_mut(q)[q] = c;
+    // Row p column q, and row q column p
+    j.col_mut(q)[p] = s;
+    j.col_mut(p)[q] = -s;
+    j
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::uvec3;
+
+    use super::*;
+    use crate::{
+        grid::density_count,
+        surface_nets::{
+            VertexPlacement, VertexRelaxation, surface_nets,
+            tests::{boundary_edges, oriented, sphere},
+        },
+    };
+
+    #[test]
+    fn sphere_is_closed() {
+        let dims = UVec3::splat(16);
+        let densities = sphere(dims, 5.0);
+        let mesh = oriented(&dual_contouring(&densities, dims), &densities, dims);
+        assert!(!mesh.indices.is_empty());
+        assert_eq!(boundary_edges(&mesh), 0);
+    }
+
+    #[test]
+    fn uniform_fields_have_no_faces() {
+        let dims = UVec3::splat(8);
+        let count = density_count(dims) as usize;
+        for density in [1.0, -1.0] {
+            assert!(
+                dual_contouring(&vec![density; count], dims)
+                    .faces
+                    .is_empty()
+            );
+        }
+    }
+
+    #[test]
+    fn box_corner_is_sharper_than_surface_nets() {
+        // Solid where x, y and z are all below 4.5: one corner of a box
+        let dims = UVec3::splat(8);
+        let densities: Vec<f32> = (0..density_count(dims))
+            .map(|i| (uvec3(i % 8, i / 8 % 8, i / 64).as_vec3() - 4.5).max_element())
+            .collect();
+        let nearest = |faces: FaceBuffers| {
+            faces
+                .vertices
+                .chunks_exact(3)
+                .map(|v| Vec3::from_slice(v).distance(Vec3::splat(4.5)))
+                .fold(f32::MAX, f32::min)
+        };
+        let smooth = surface_nets(
+            &densities,
+            dims,
+            VertexPlacement::default(),
+            VertexRelaxation::default(),
+        );
+        assert!(nearest(dual_contouring(&densities, dims)) < nearest(smooth));
+    }
+
+    #[test]
+    fn qef_ignores_unconstrained_directions() {
+        // A single plane x = 0.25: only x is constrained
+        let n = Vec3::X;
+        let ata = Mat3::from_cols(n * n.x, n * n.y, n * n.z);
+        let solution = solve_qef(ata, n * 0.25);
+        assert!((solution - Vec3::new(0.25, 0.0, 0.0)).length() < 1e-5);
+    }
+}
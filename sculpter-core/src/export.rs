@@ -0,0 +1,101 @@
+//! Mesh export to Wavefront OBJ and binary STL, for 3D printing and other tools.
+//!
+//! Both write positions in the given `CoordinateSystem`, unscaled, so scale the
+//! mesh to the unit the reader expects first: slicers read STL and OBJ in
+//! millimetres, and most expect them Z-up.
+
+use std::{collections::HashMap, io};
+
+use glam::{UVec3, Vec3};
+
+use crate::{coords::CoordinateSystem, mesh::MeshData};
+
+/// Surround a `dims` grid with a layer of `outside` samples, x fastest, so
+/// surfaces cut off by the grid's faces close over them and mesh watertight.
+///
+/// Returns the padded densities and their dimensions, 2 larger along each axis.
+/// Positions meshed from them are one sample further from the origin.
+pub fn pad_densities(densities: &[f32], dims: UVec3, outside: f32) -> (Vec<f32>, UVec3) {
+    let padded = dims + 2;
+    let mut output = vec![outside; padded.element_product() as usize];
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            let from = ((z * dims.y + y) * dims.x) as usize;
+            let to = (((z + 1) * padded.y + y + 1) * padded.x + 1) as usize;
+            output[to..to + dims.x as usize]
+                .copy_from_slice(&densities[from..from + dims.x as usize]);
+        }
+    }
+    (output, padded)
+}
+
+/// Write the mesh as a Wavefront OBJ file of positions, normals and triangles.
+///
+/// Vertices at the same position become one `v`, so surfaces split only for
+/// their normals, such as blocky corners, still read as closed.
+pub fn write_obj(
+    mesh: &MeshData,
+    coordinate_system: CoordinateSystem,
+    mut writer: impl io::Write,
+) -> io::Result<()> {
+    let mesh = coordinate_system.mesh_from_bevy(mesh);
+    let mut positions: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut position_index = Vec::with_capacity(mesh.positions.len());
+    for position in &mesh.positions {
+        let next = positions.len() + 1;
+        let index = *positions
+            .entry(position.to_array().map(f32::to_bits))
+            .or_insert_with(|| next);
+        if index == next {
+            writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+        }
+        position_index.push(index);
+    }
+    for normal in &mesh.normals {
+        writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        writeln!(
+            writer,
+            "f {}//{} {}//{} {}//{}",
+            position_index[a],
+            a + 1,
+            position_index[b],
+            b + 1,
+            position_index[c],
+            c + 1
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the mesh as a binary STL file, each triangle with its face normal.
+pub fn write_stl(
+    mesh: &MeshData,
+    coordinate_system: CoordinateSystem,
+    mut writer: impl io::Write,
+) -> io::Result<()> {
+    let mesh = coordinate_system.mesh_from_bevy(mesh);
+    let mut header = [0; 80];
+    header[..8].copy_from_slice(b"sculpter");
+    writer.write_all(&header)?;
+    writer.write_all(&((mesh.indices.len() / 3) as u32).to_le_bytes())?;
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[triangle[i] as usize]);
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        for vector in [normal, a, b, c] {
+            write_vec3(&mut writer, vector)?;
+        }
+        // Attribute byte count, unused
+        writer.write_all(&[0; 2])?;
+    }
+    Ok(())
+}
+
+fn write_vec3(writer: &mut impl io::Write, vector: Vec3) -> io::Result<()> {
+    for component in vector.to_array() {
+        writer.write_all(&component.to_le_bytes())?;
+    }
+    Ok(())
+}
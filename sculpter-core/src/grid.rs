@@ -0,0 +1,51 @@
+//! Layout of a density grid stored as a flat, x-fastest array.
+
+use glam::UVec3;
+
+/// Number of density values in a grid.
+pub fn density_count(dims: UVec3) -> u32 {
+    dims.x * dims.y * dims.z
+}
+
+/// Position of the value at `(x, y, z)` in the flat array.
+pub fn index(dims: UVec3, x: u32, y: u32, z: u32) -> u32 {
+    z * dims.y * dims.x + y * dims.x + x
+}
+
+/// Number of cells, the cubes between eight neighbouring values.
+pub fn cell_count(dims: UVec3) -> u32 {
+    (dims.x.saturating_sub(1)) * (dims.y.saturating_sub(1)) * (dims.z.saturating_sub(1))
+}
+
+/// Every `factor`-th value of a grid along each axis, and the dimensions of the
+/// result. Its grid unit is `factor` of the original's, starting at the same
+/// corner. Leftover values past the last multiple of `factor` are dropped.
+pub fn downsample(densities: &[f32], dims: UVec3, factor: u32) -> (Vec<f32>, UVec3) {
+    let factor = factor.max(1);
+    let low = (dims.saturating_sub(UVec3::ONE)) / factor + UVec3::ONE;
+    let mut values = Vec::with_capacity(density_count(low) as usize);
+    for z in 0..low.z {
+        for y in 0..low.y {
+            for x in 0..low.x {
+                values.push(densities[index(dims, x * factor, y * factor, z * factor) as usize]);
+            }
+        }
+    }
+    (values, low)
+}
+
+/// A grid of `new_dims` holding `values` from its minimum corner, values past
+/// `new_dims` cut off and the rest filled with `fill`.
+pub fn resize<T: Copy>(values: &[T], dims: UVec3, new_dims: UVec3, fill: T) -> Vec<T> {
+    let mut resized = vec![fill; density_count(new_dims) as usize];
+    let copied = dims.min(new_dims);
+    for z in 0..copied.z {
+        for y in 0..copied.y {
+            let from = index(dims, 0, y, z) as usize;
+            let to = index(new_dims, 0, y, z) as usize;
+            resized[to..to + copied.x as usize]
+                .copy_from_slice(&values[from..from + copied.x as usize]);
+        }
+    }
+    resized
+}
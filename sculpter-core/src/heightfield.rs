@@ -0,0 +1,77 @@
+//! Heightfield meshing: one vertex per column of the grid, at the height where
+//! it turns from solid to empty, joined into a regular grid of quads.
+//!
+//! Each column is binary searched for its surface instead of visiting every
+//! cell, so only fields that are solid below a single height per column, such
+//! as terrain without overhangs or caves, mesh correctly.
+
+use glam::UVec3;
+
+use crate::{
+    grid::{density_count, index},
+    mesh::FaceBuffers,
+};
+
+/// Height of the surface in column `(x, z)`, in grid units, or `None` if the
+/// column is solid or empty all the way.
+///
+/// Assumes the densities rise along y and searches for the last solid sample.
+/// Must match `column_height` in heightfield.wgsl.
+pub fn column_height(densities: &[f32], dims: UVec3, x: u32, z: u32) -> Option<f32> {
+    let density = |y: u32| densities[index(dims, x, y, z) as usize];
+    let (mut low, mut high) = (0, dims.y.checked_sub(1)?);
+    if density(low) >= 0.0 || density(high) < 0.0 {
+        return None;
+    }
+    // Solid at low, empty at high
+    while high - low > 1 {
+        let middle = (low + high) / 2;
+        if density(middle) < 0.0 {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let (v0, v1) = (density(low), density(high));
+    Some(low as f32 + v0 / (v0 - v1))
+}
+
+/// Heightfield meshing with the same slot layout and ordering as
+/// heightfield.wgsl, so both backends produce identical buffers. Quads face up.
+pub fn heightfield(densities: &[f32], dims: UVec3) -> FaceBuffers {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    if dims.min_element() < 2 || densities.len() < density_count(dims) as usize {
+        return FaceBuffers { vertices, faces };
+    }
+
+    // Vertices: one per column with a surface, compacted in slot order
+    let mut vertex_indices = vec![None; (dims.x * dims.z) as usize];
+    for z in 0..dims.z {
+        for x in 0..dims.x {
+            let Some(height) = column_height(densities, dims, x, z) else {
+                continue;
+            };
+            vertex_indices[(z * dims.x + x) as usize] = Some((vertices.len() / 3) as u32);
+            vertices.extend_from_slice(&[x as f32, height, z as f32]);
+        }
+    }
+
+    // Faces: one per cell between four columns with a surface
+    let column = |x: u32, z: u32| vertex_indices[(z * dims.x + x) as usize];
+    for z in 0..dims.z - 1 {
+        for x in 0..dims.x - 1 {
+            let corners = [
+                column(x, z),
+                column(x, z + 1),
+                column(x + 1, z + 1),
+                column(x + 1, z),
+            ];
+            if let [Some(a), Some(b), Some(c), Some(d)] = corners {
+                faces.extend_from_slice(&[a, b, c, d]);
+            }
+        }
+    }
+
+    FaceBuffers { vertices, faces }
+}
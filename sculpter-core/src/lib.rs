@@ -0,0 +1,92 @@
+//! The meshing math behind `sculpter` with no Bevy dependency: the density grid
+//! layout, the CPU isosurface extractors and mesh post-processing. For offline
+//! bakers, tests and other engines.
+//!
+//! Densities are x-fastest and negative inside the surface. Every mesher emits
+//! [`FaceBuffers`] laid out like the GPU pipeline's readback buffers, which
+//! [`MeshData`] turns into an indexed triangle mesh.
+
+pub mod blocky;
+pub mod cleanup;
+pub mod components;
+pub mod coords;
+pub mod csg;
+pub mod dual_contouring;
+pub mod export;
+pub mod grid;
+pub mod heightfield;
+#[cfg(feature = "lightmap")]
+pub mod lightmap;
+pub mod marching_cubes;
+pub mod marching_tetrahedra;
+pub mod mass;
+pub mod material;
+pub mod mesh;
+pub mod occlusion;
+pub mod octree;
+#[cfg(feature = "meshopt")]
+pub mod optimize;
+pub mod orientation;
+pub mod overlap;
+pub mod packing;
+pub mod raycast;
+pub mod scatter;
+pub mod sdf;
+pub mod simplify;
+pub mod skirt;
+pub mod smooth;
+#[cfg(feature = "sparse")]
+pub mod sparse;
+pub mod surface_nets;
+pub mod transition;
+pub mod uv;
+pub mod voxelize;
+pub mod walkable;
+
+pub use glam;
+
+#[cfg(feature = "lightmap")]
+pub use crate::lightmap::{UV_1_ATTRIBUTE, lightmap_unwrap};
+#[cfg(feature = "meshopt")]
+pub use crate::optimize::optimize_vertex_order;
+#[cfg(feature = "sparse")]
+pub use crate::sparse::{LEAF_SIZE, SparseVolume, SparseVolumeError};
+pub use crate::{
+    blocky::blocky,
+    cleanup::{DegenerateFilter, remove_degenerate_triangles},
+    components::{FloodFill, NO_COMPONENT, SolidComponents, flood_fill, solid_components},
+    coords::{CoordinateSystem, Handedness, UpAxis},
+    csg::{CsgOperation, CsgShape, composite, csg_region, csg_scale, smooth_min},
+    dual_contouring::dual_contouring,
+    export::{pad_densities, write_obj, write_stl},
+    heightfield::heightfield,
+    marching_cubes::marching_cubes,
+    marching_tetrahedra::marching_tetrahedra,
+    mass::{MassProperties, mass_properties},
+    material::{
+        COLOR_ATTRIBUTE, MATERIAL_ID_ATTRIBUTE, MATERIAL_WEIGHTS_ATTRIBUTE, split_by_material,
+        vertex_material_weights, vertex_materials, vertex_palette_colors,
+    },
+    mesh::{
+        FaceBuffers, MeshData, VertexAttributeData, compute_flat_normals, quads_from_triangles,
+        split_creases, triangulate_quad, wireframe_edges,
+    },
+    occlusion::{AMBIENT_OCCLUSION_ATTRIBUTE, AmbientOcclusion, vertex_ambient_occlusion},
+    octree::adaptive_dual_contouring,
+    orientation::{gradient_normals, orient_to_gradient},
+    overlap::{aabb_overlaps, capsule_penetration_depth, sphere_overlaps},
+    packing::{f32_to_f16_bits, octahedral_decode, octahedral_encode, pack_half2},
+    raycast::{RayHit, raycast},
+    scatter::{ScatterDistribution, SurfacePoint, scatter_points},
+    sdf::{SdfPart, SdfPrimitive, SdfShape, evaluate_sdf, transform_bounds},
+    simplify::{Simplification, simplify},
+    skirt::add_skirts,
+    smooth::{Smoothing, smooth},
+    surface_nets::{
+        VertexPlacement, VertexRelaxation, density_gradient, sample_trilinear, surface_nets,
+    },
+    transition::transition_faces,
+    uv::{UV_0_ATTRIBUTE, box_atlas_unwrap, planar_uvs},
+    voxelize::{voxelize, voxelize_slab},
+    walkable::walkable_triangles,
+};
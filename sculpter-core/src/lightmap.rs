@@ -0,0 +1,249 @@
+//! Non-overlapping lightmap UVs, the second UV channel, for baking lighting.
+
+use std::collections::HashMap;
+
+use glam::{IVec2, Vec2, Vec3};
+
+use crate::{
+    mesh::{MeshData, VertexAttributeData},
+    uv::box_face,
+};
+
+/// Name of the `Float32x2` vertex attribute holding the second UV channel.
+pub const UV_1_ATTRIBUTE: &str = "Vertex_Uv_1";
+
+/// Gap kept around every chart, in lightmap texels, so bilinear filtering doesn't
+/// bleed between charts.
+const PADDING_TEXELS: f32 = 2.0;
+
+/// Connected triangles facing the same side of the box, flattened together.
+struct Chart {
+    /// Index into the box faces of `box_face`
+    face: usize,
+    triangles: Vec<usize>,
+    min: Vec2,
+    max: Vec2,
+    /// Projected triangles bucketed in cells about one triangle wide
+    cells: HashMap<IVec2, Vec<[Vec2; 3]>>,
+    cell_size: f32,
+}
+
+impl Chart {
+    fn add(&mut self, triangle: usize, projected: [Vec2; 3]) {
+        self.triangles.push(triangle);
+        for p in projected {
+            self.min = self.min.min(p);
+            self.max = self.max.max(p);
+        }
+        for cell in covered_cells(projected, self.cell_size) {
+            self.cells.entry(cell).or_default().push(projected);
+        }
+    }
+
+    fn overlaps(&self, projected: [Vec2; 3]) -> bool {
+        covered_cells(projected, self.cell_size).any(|cell| {
+            self.cells.get(&cell).is_some_and(|placed| {
+                placed
+                    .iter()
+                    .any(|&other| triangles_overlap(projected, other))
+            })
+        })
+    }
+}
+
+/// Unwrap a mesh into charts packed without overlap in the unit square, for a
+/// lightmap `resolution` texels wide, and write them to [`UV_1_ATTRIBUTE`].
+///
+/// Charts grow from a seed triangle over shared edges while the triangles face
+/// the same side of the box and don't overlap the chart once projected onto it.
+/// Vertices shared by several charts are duplicated.
+pub fn lightmap_unwrap(mesh: &mut MeshData, resolution: u32) {
+    let triangle_count = mesh.indices.len() / 3;
+    let corners = |triangle: usize| {
+        [0, 1, 2].map(|i| mesh.positions[mesh.indices[triangle * 3 + i] as usize])
+    };
+    let faces: Vec<usize> = (0..triangle_count)
+        .map(|triangle| {
+            let [a, b, c] = corners(triangle);
+            box_face((b - a).cross(c - a))
+        })
+        .collect();
+
+    // Triangles around each edge
+    let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for triangle in 0..triangle_count {
+        for i in 0..3 {
+            let a = mesh.indices[triangle * 3 + i];
+            let b = mesh.indices[triangle * 3 + (i + 1) % 3];
+            edges
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push(triangle);
+        }
+    }
+
+    let edge_length = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| mesh.positions[t[0] as usize].distance(mesh.positions[t[1] as usize]))
+        .sum::<f32>()
+        / triangle_count.max(1) as f32;
+    let cell_size = edge_length.max(1e-6);
+
+    let mut charts: Vec<Chart> = Vec::new();
+    let mut chart_of = vec![usize::MAX; triangle_count];
+    for seed in 0..triangle_count {
+        if chart_of[seed] != usize::MAX {
+            continue;
+        }
+        let face = faces[seed];
+        let chart_index = charts.len();
+        let mut chart = Chart {
+            face,
+            triangles: vec![],
+            min: Vec2::MAX,
+            max: Vec2::MIN,
+            cells: HashMap::new(),
+            cell_size,
+        };
+        chart_of[seed] = chart_index;
+        chart.add(seed, corners(seed).map(|p| project(p, face)));
+        let mut queue = vec![seed];
+        while let Some(triangle) = queue.pop() {
+            for i in 0..3 {
+                let a = mesh.indices[triangle * 3 + i];
+                let b = mesh.indices[triangle * 3 + (i + 1) % 3];
+                for &neighbor in &edges[&(a.min(b), a.max(b))] {
+                    if chart_of[neighbor] != usize::MAX || faces[neighbor] != face {
+                        continue;
+                    }
+                    let projected = corners(neighbor).map(|p| project(p, face));
+                    if !chart.overlaps(projected) {
+                        chart_of[neighbor] = chart_index;
+                        chart.add(neighbor, projected);
+                        queue.push(neighbor);
+                    }
+                }
+            }
+        }
+        charts.push(chart);
+    }
+
+    // Padding depends on the atlas size it's packed into, so settle it in a few passes
+    let sizes: Vec<Vec2> = charts.iter().map(|chart| chart.max - chart.min).collect();
+    let mut padding = 0.0;
+    let mut packed = pack(&sizes, padding);
+    for _ in 0..3 {
+        padding = PADDING_TEXELS * packed.1 / resolution.max(1) as f32;
+        packed = pack(&sizes, padding);
+    }
+    let (offsets, side) = packed;
+
+    // Give each chart its own copy of the vertices it shares with earlier ones
+    let mut vertex_chart: Vec<Option<usize>> = vec![None; mesh.positions.len()];
+    let mut copies: HashMap<(u32, usize), u32> = HashMap::new();
+    for (chart_index, chart) in charts.iter().enumerate() {
+        for &triangle in &chart.triangles {
+            for corner in triangle * 3..triangle * 3 + 3 {
+                let vertex = mesh.indices[corner];
+                match vertex_chart[vertex as usize] {
+                    Some(existing) if existing != chart_index => {
+                        let copy = *copies.entry((vertex, chart_index)).or_insert_with(|| {
+                            vertex_chart.push(Some(chart_index));
+                            mesh.duplicate_vertex(vertex)
+                        });
+                        mesh.indices[corner] = copy;
+                    }
+                    Some(_) => {}
+                    None => vertex_chart[vertex as usize] = Some(chart_index),
+                }
+            }
+        }
+    }
+
+    let side = side.max(f32::EPSILON);
+    let uvs = mesh
+        .positions
+        .iter()
+        .zip(&vertex_chart)
+        .map(|(&position, chart)| match *chart {
+            Some(chart_index) => {
+                let chart = &charts[chart_index];
+                (project(position, chart.face) - chart.min + offsets[chart_index]) / side
+            }
+            None => Vec2::ZERO,
+        })
+        .collect();
+    mesh.attributes
+        .insert(UV_1_ATTRIBUTE, VertexAttributeData::Float32x2(uvs));
+}
+
+/// `p` on the plane of box face `face`, dropping the axis it faces.
+fn project(p: Vec3, face: usize) -> Vec2 {
+    let axis = face / 2;
+    Vec2::new(p[(axis + 1) % 3], p[(axis + 2) % 3])
+}
+
+/// Cells of size `cell_size` overlapping the bounds of `triangle`.
+fn covered_cells(triangle: [Vec2; 3], cell_size: f32) -> impl Iterator<Item = IVec2> {
+    let min = (triangle[0].min(triangle[1]).min(triangle[2]) / cell_size)
+        .floor()
+        .as_ivec2();
+    let max = (triangle[0].max(triangle[1]).max(triangle[2]) / cell_size)
+        .floor()
+        .as_ivec2();
+    (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+}
+
+/// Whether two triangles' interiors overlap. Triangles only touching along an
+/// edge or at a corner don't.
+fn triangles_overlap(a: [Vec2; 3], b: [Vec2; 3]) -> bool {
+    // Separating axis test on the edge normals of both triangles
+    let epsilon = 1e-5;
+    for triangle in [a, b] {
+        for i in 0..3 {
+            let edge = triangle[(i + 1) % 3] - triangle[i];
+            let axis = edge.perp();
+            if axis.length_squared() < 1e-12 {
+                continue;
+            }
+            let axis = axis.normalize();
+            let range = |t: [Vec2; 3]| {
+                let d = t.map(|p| p.dot(axis));
+                (d[0].min(d[1]).min(d[2]), d[0].max(d[1]).max(d[2]))
+            };
+            let ((a_min, a_max), (b_min, b_max)) = (range(a), range(b));
+            if a_max <= b_min + epsilon || b_max <= a_min + epsilon {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Shelf pack rectangles of `sizes` with `padding` around each, tallest first.
+/// Returns each rectangle's offset and the side of the square holding them all.
+fn pack(sizes: &[Vec2], padding: f32) -> (Vec<Vec2>, f32) {
+    let padded: Vec<Vec2> = sizes.iter().map(|&size| size + 2.0 * padding).collect();
+    let area: f32 = padded.iter().map(|size| size.x * size.y).sum();
+    let widest = padded.iter().map(|size| size.x).fold(0.0, f32::max);
+    let shelf_width = (area.sqrt() * 1.1).max(widest);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| padded[b].y.total_cmp(&padded[a].y));
+
+    let mut offsets = vec![Vec2::ZERO; sizes.len()];
+    let (mut x, mut y, mut shelf_height, mut width) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
+    for i in order {
+        if x > 0.0 && x + padded[i].x > shelf_width {
+            y += shelf_height;
+            x = 0.0;
+            shelf_height = 0.0;
+        }
+        offsets[i] = Vec2::new(x, y) + padding;
+        x += padded[i].x;
+        width = width.max(x);
+        shelf_height = shelf_height.max(padded[i].y);
+    }
+    (offsets, width.max(y + shelf_height))
+}
@@ -0,0 +1,276 @@
+//! Marching cubes with a watertight triangle table built on first use.
+
+use std::sync::OnceLock;
+
+use glam::{UVec3, Vec3, uvec3, vec3};
+
+use crate::{
+    grid::{density_count, index},
+    mesh::FaceBuffers,
+};
+
+// Must match MAX_TRIANGLES in marching_cubes.wgsl
+pub const MAX_TRIANGLES: usize = 5;
+
+/// Edge indices of each case's triangles, -1 terminated. A case has a bit set
+/// for every corner inside the surface, with corners numbered x | y << 1 | z << 2.
+pub type TriangleTable = [[i32; 16]; 256];
+
+/// Marching cubes with the same slot layout, table and ordering as
+/// marching_cubes.wgsl, so both backends produce identical buffers.
+pub fn marching_cubes(densities: &[f32], dims: UVec3) -> FaceBuffers {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    if dims.min_element() < 2 || densities.len() < density_count(dims) as usize {
+        return FaceBuffers { vertices, faces };
+    }
+
+    let density = |p: UVec3| densities[index(dims, p.x, p.y, p.z) as usize];
+
+    // Vertices: one per crossed axis edge, compacted in slot order
+    let mut vertex_indices = vec![None; (density_count(dims) * 3) as usize];
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let p = uvec3(x, y, z);
+                for axis in 0..3 {
+                    let q = p + corner_offset(1 << axis);
+                    if q.cmpge(dims).any() {
+                        continue;
+                    }
+                    let (v0, v1) = (density(p), density(q));
+                    if (v0 < 0.0) == (v1 < 0.0) {
+                        continue;
+                    }
+                    let t = v0 / (v0 - v1);
+                    let position = p.as_vec3() + t * (q.as_vec3() - p.as_vec3());
+                    vertex_indices[(index(dims, x, y, z) * 3) as usize + axis] =
+                        Some((vertices.len() / 3) as u32);
+                    vertices.extend_from_slice(&[position.x, position.y, position.z]);
+                }
+            }
+        }
+    }
+
+    // Faces: the case's triangles, in slot order
+    let table = triangle_table();
+    for z in 0..dims.z - 1 {
+        for y in 0..dims.y - 1 {
+            for x in 0..dims.x - 1 {
+                let cell = uvec3(x, y, z);
+                let case = (0..8)
+                    .filter(|&c| density(cell + corner_offset(c)) < 0.0)
+                    .fold(0, |case, c| case | 1 << c);
+                let vertex = |e: i32| {
+                    let (axis, offset) = edge_axis_and_offset(e as usize);
+                    let p = cell + offset;
+                    vertex_indices[(index(dims, p.x, p.y, p.z) * 3) as usize + axis]
+                        .unwrap_or_default()
+                };
+                for triangle in table[case].chunks_exact(3).take(MAX_TRIANGLES) {
+                    if triangle[0] < 0 {
+                        break;
+                    }
+                    let (a, b, c) = (
+                        vertex(triangle[0]),
+                        vertex(triangle[1]),
+                        vertex(triangle[2]),
+                    );
+                    faces.extend_from_slice(&[a, b, c, c]);
+                }
+            }
+        }
+    }
+
+    FaceBuffers { vertices, faces }
+}
+
+/// Offset of corner `c` (x | y << 1 | z << 2) from the cell origin.
+pub fn corner_offset(c: usize) -> UVec3 {
+    uvec3(c as u32 & 1, (c as u32 >> 1) & 1, (c as u32 >> 2) & 1)
+}
+
+/// Axis and lower corner of edge `e`. Edges are numbered axis * 4 + the
+/// coordinates of the two other axes, the lower one first.
+/// Must match `edge_slot` in marching_cubes.wgsl.
+pub fn edge_axis_and_offset(e: usize) -> (usize, UVec3) {
+    let axis = e / 4;
+    let (a, b) = ((e & 1) as u32, ((e >> 1) & 1) as u32);
+    let offset = match axis {
+        0 => uvec3(0, a, b),
+        1 => uvec3(a, 0, b),
+        _ => uvec3(a, b, 0),
+    };
+    (axis, offset)
+}
+
+/// The triangle table, built on first use.
+pub fn triangle_table() -> &'static TriangleTable {
+    static TABLE: OnceLock<TriangleTable> = OnceLock::new();
+    TABLE.get_or_init(build_triangle_table)
+}
+
+/// Build the table by tracing the surface around each case.
+///
+/// On every cube face the crossed edges are joined in pairs, separating diagonal
+/// inside corners when a face is ambiguous. The decision only depends on the
+/// face's own corners, so neighbouring cells agree and the mesh is watertight.
+/// The joined edges form closed loops, which are fanned into triangles wound
+/// counter-clockwise when seen from outside (positive density).
+fn build_triangle_table() -> TriangleTable {
+    let corners_of = |e: usize| {
+        let (axis, offset) = edge_axis_and_offset(e);
+        let low = (offset.x | offset.y << 1 | offset.z << 2) as usize;
+        (low, low | 1 << axis)
+    };
+
+    let mut table = [[-1; 16]; 256];
+    for (case, triangles) in table.iter_mut().enumerate() {
+        let inside = |c: usize| case >> c & 1 == 1;
+        let crossed = |e: usize| {
+            let (a, b) = corners_of(e);
+            inside(a) != inside(b)
+        };
+
+        // Each crossed edge is joined to one edge on each of its two faces
+        let mut links: [Vec<usize>; 12] = Default::default();
+        for axis in 0..3 {
+            for side in 0..2 {
+                let on_face = |c: usize| (c >> axis & 1) == side;
+                let edges: Vec<usize> = (0..12)
+                    .filter(|&e| {
+                        let (a, b) = corners_of(e);
+                        crossed(e) && on_face(a) && on_face(b)
+                    })
+                    .collect();
+                let mut link = |a: usize, b: usize| {
+                    links[a].push(b);
+                    links[b].push(a);
+                };
+                match edges.len() {
+                    2 => link(edges[0], edges[1]),
+                    // Ambiguous face: cut off each inside corner on its own
+                    4 => {
+                        for c in (0..8).filter(|&c| on_face(c) && inside(c)) {
+                            let around: Vec<usize> = edges
+                                .iter()
+                                .copied()
+                                .filter(|&e| {
+                                    let (a, b) = corners_of(e);
+                                    a == c || b == c
+                                })
+                                .collect();
+                            link(around[0], around[1]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Walk the links into loops and fan them into triangles
+        let mut visited = [false; 12];
+        let mut count = 0;
+        for start in (0..12).filter(|&e| crossed(e)) {
+            if visited[start] {
+                continue;
+            }
+            let mut polygon = vec![start];
+            visited[start] = true;
+            let (mut previous, mut current) = (usize::MAX, start);
+            loop {
+                let next = if links[current][0] != previous {
+                    links[current][0]
+                } else {
+                    links[current][1]
+                };
+                if next == start {
+                    break;
+                }
+                polygon.push(next);
+                visited[next] = true;
+                (previous, current) = (current, next);
+            }
+
+            // Orient by the polygon normal through the edge midpoints against
+            // the direction from the inside corners to the outside ones
+            let midpoint = |e: usize| {
+                let (a, b) = corners_of(e);
+                (corner_offset(a) + corner_offset(b)).as_vec3() * 0.5
+            };
+            let mut normal = Vec3::ZERO;
+            let mut outward = Vec3::ZERO;
+            for (i, &e) in polygon.iter().enumerate() {
+                let (p, q) = (midpoint(e), midpoint(polygon[(i + 1) % polygon.len()]));
+                normal += vec3(
+                    (p.y - q.y) * (p.z + q.z),
+                    (p.z - q.z) * (p.x + q.x),
+                    (p.x - q.x) * (p.y + q.y),
+                );
+                let (a, b) = corners_of(e);
+                let (inner, outer) = if inside(a) { (a, b) } else { (b, a) };
+                outward += corner_offset(outer).as_vec3() - corner_offset(inner).as_vec3();
+            }
+            if normal.dot(outward) < 0.0 {
+                polygon.reverse();
+            }
+
+            for i in 1..polygon.len() - 1 {
+                for e in [polygon[0], polygon[i], polygon[i + 1]] {
+                    triangles[count] = e as i32;
+                    count += 1;
+                }
+            }
+        }
+        debug_assert!(count <= MAX_TRIANGLES * 3);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::surface_nets::tests::{boundary_edges, oriented, sphere};
+
+    #[test]
+    fn sphere_is_closed() {
+        let dims = UVec3::splat(16);
+        let densities = sphere(dims, 5.0);
+        let mesh = oriented(&marching_cubes(&densities, dims), &densities, dims);
+        assert!(!mesh.indices.is_empty());
+        assert_eq!(boundary_edges(&mesh), 0);
+        // Vertices are edge crossings, on the surface up to interpolation
+        let centre = (dims - 1).as_vec3() / 2.0;
+        for position in &mesh.positions {
+            assert!((position.distance(centre) - 5.0).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn uniform_fields_have_no_faces() {
+        let dims = UVec3::splat(8);
+        let count = density_count(dims) as usize;
+        for density in [1.0, -1.0] {
+            let faces = marching_cubes(&vec![density; count], dims);
+            assert!(faces.faces.is_empty());
+        }
+    }
+
+    #[test]
+    fn complementary_cases_cross_the_same_edges() {
+        let table = triangle_table();
+        let edges = |case: usize| {
+            let mut edges: Vec<i32> = table[case]
+                .iter()
+                .copied()
+                .take_while(|&e| e >= 0)
+                .collect();
+            edges.sort_unstable();
+            edges.dedup();
+            edges
+        };
+        for case in 0..256 {
+            assert_eq!(edges(case), edges(255 - case), "case {case}");
+        }
+    }
+}
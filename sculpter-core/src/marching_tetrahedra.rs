@@ -0,0 +1,122 @@
+//! Marching tetrahedra: six tetrahedra per cell, free of ambiguous cases.
+
+use glam::{UVec3, Vec3, uvec3};
+
+use crate::{
+    grid::{density_count, index},
+    mesh::FaceBuffers,
+};
+
+// Must match tetrahedron_corners in marching_tetrahedra.wgsl: the second and third
+// corners of each tetrahedron, which all start at the cell origin and end at (1,1,1)
+const TETRAHEDRA: [(UVec3, UVec3); 6] = [
+    (uvec3(1, 0, 0), uvec3(1, 1, 0)),
+    (uvec3(1, 0, 0), uvec3(1, 0, 1)),
+    (uvec3(0, 1, 0), uvec3(1, 1, 0)),
+    (uvec3(0, 1, 0), uvec3(0, 1, 1)),
+    (uvec3(0, 0, 1), uvec3(1, 0, 1)),
+    (uvec3(0, 0, 1), uvec3(0, 1, 1)),
+];
+
+// Edges owned by each grid point, in direction bits x | y << 1 | z << 2
+const EDGES_PER_POINT: u32 = 7;
+
+/// Marching tetrahedra with the same slot layout and ordering as
+/// marching_tetrahedra.wgsl, so both backends produce identical buffers.
+pub fn marching_tetrahedra(densities: &[f32], dims: UVec3) -> FaceBuffers {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    if dims.min_element() < 2 || densities.len() < density_count(dims) as usize {
+        return FaceBuffers { vertices, faces };
+    }
+
+    let density = |p: UVec3| densities[index(dims, p.x, p.y, p.z) as usize];
+    let crossing = |a: UVec3, b: UVec3| {
+        let (v0, v1) = (density(a), density(b));
+        let t = v0 / (v0 - v1);
+        a.as_vec3() + t * (b.as_vec3() - a.as_vec3())
+    };
+    let edge_slot = |a: UVec3, b: UVec3| {
+        let low = a.min(b);
+        let d = a.max(b) - low;
+        let bits = d.x | (d.y << 1) | (d.z << 2);
+        (index(dims, low.x, low.y, low.z) * EDGES_PER_POINT + bits - 1) as usize
+    };
+
+    // Vertices: one per crossed edge, compacted in slot order
+    let mut vertex_indices = vec![None; (density_count(dims) * EDGES_PER_POINT) as usize];
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let p = uvec3(x, y, z);
+                for bits in 1..8u32 {
+                    let q = p + uvec3(bits & 1, (bits >> 1) & 1, (bits >> 2) & 1);
+                    if q.cmpge(dims).any() || (density(p) < 0.0) == (density(q) < 0.0) {
+                        continue;
+                    }
+                    let position = crossing(p, q);
+                    vertex_indices[edge_slot(p, q)] = Some((vertices.len() / 3) as u32);
+                    vertices.extend_from_slice(&[position.x, position.y, position.z]);
+                }
+            }
+        }
+    }
+
+    // Faces: one triangle or quad per tetrahedron, in slot order
+    let vertex = |a: UVec3, b: UVec3| vertex_indices[edge_slot(a, b)].unwrap_or_default();
+    for z in 0..dims.z - 1 {
+        for y in 0..dims.y - 1 {
+            for x in 0..dims.x - 1 {
+                let cell = uvec3(x, y, z);
+                for (second, third) in TETRAHEDRA {
+                    let corners = [cell, cell + second, cell + third, cell + UVec3::ONE];
+                    let (inside, outside): (Vec<UVec3>, Vec<UVec3>) =
+                        corners.into_iter().partition(|&p| density(p) < 0.0);
+
+                    // Crossed edges as a closed loop
+                    let edges = match inside.len() {
+                        1 => vec![
+                            (inside[0], outside[0]),
+                            (inside[0], outside[1]),
+                            (inside[0], outside[2]),
+                        ],
+                        3 => vec![
+                            (inside[0], outside[0]),
+                            (inside[1], outside[0]),
+                            (inside[2], outside[0]),
+                        ],
+                        2 => vec![
+                            (inside[0], outside[0]),
+                            (inside[0], outside[1]),
+                            (inside[1], outside[1]),
+                            (inside[1], outside[0]),
+                        ],
+                        _ => continue,
+                    };
+
+                    // Wind counter-clockwise when seen from outside
+                    let centre = |points: &[UVec3]| {
+                        points.iter().map(|p| p.as_vec3()).sum::<Vec3>() / points.len() as f32
+                    };
+                    let outward = centre(&outside) - centre(&inside);
+                    let p0 = crossing(edges[0].0, edges[0].1);
+                    let p1 = crossing(edges[1].0, edges[1].1);
+                    let p2 = crossing(edges[2].0, edges[2].1);
+                    let flip = (p1 - p0).cross(p2 - p0).dot(outward) < 0.0;
+
+                    let mut face = [0; 4];
+                    for (i, slot) in face.iter_mut().enumerate().take(edges.len()) {
+                        let j = if flip && i > 0 { edges.len() - i } else { i };
+                        *slot = vertex(edges[j].0, edges[j].1);
+                    }
+                    if edges.len() == 3 {
+                        face[3] = face[2];
+                    }
+                    faces.extend_from_slice(&face);
+                }
+            }
+        }
+    }
+
+    FaceBuffers { vertices, faces }
+}
@@ -0,0 +1,73 @@
+//! Volume, center of mass and inertia of the solid a density field encloses,
+//! integrated over its samples rather than its surface mesh.
+
+use glam::{DMat3, DVec3, Mat3, UVec3, Vec3};
+
+/// Mass properties of a solid of unit density, in the space meshes of the field
+/// are built in: sample `(x, y, z)` at `(x, y, z) * spacing`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MassProperties {
+    pub volume: f32,
+    pub center_of_mass: Vec3,
+    /// Inertia tensor about the center of mass
+    pub inertia: Mat3,
+}
+
+/// Integrate the mass properties of a `dims` field, x fastest, with samples
+/// `spacing` apart.
+///
+/// Each sample stands for the cell around it, filled by `0.5 - density` clamped
+/// to [0, 1]: whole inside and out, and in proportion near the surface for
+/// fields holding distances in voxels. An empty field has all zero properties.
+pub fn mass_properties(densities: &[f32], dims: UVec3, spacing: Vec3) -> MassProperties {
+    let spacing = spacing.as_dvec3();
+    // Filled cells and their first and second moments, in samples
+    let mut cells = 0.0;
+    let mut first = DVec3::ZERO;
+    let mut second = DMat3::ZERO;
+    let mut densities = densities.iter();
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let fill = f64::from((0.5 - densities.next().unwrap()).clamp(0.0, 1.0));
+                if fill == 0.0 {
+                    continue;
+                }
+                let position = UVec3::new(x, y, z).as_dvec3() * spacing;
+                cells += fill;
+                first += fill * position;
+                second += outer(position, position) * fill;
+            }
+        }
+    }
+    if cells == 0.0 {
+        return MassProperties::default();
+    }
+
+    let cell_volume = spacing.element_product();
+    let volume = cells * cell_volume;
+    let center_of_mass = first / cells;
+    // Spread of the cell centers about the center of mass, and of each cell
+    // about its own center
+    let covariance = (second / cells - outer(center_of_mass, center_of_mass)) * volume;
+    let squared = spacing * spacing;
+    let own = DVec3::new(
+        squared.y + squared.z,
+        squared.x + squared.z,
+        squared.x + squared.y,
+    ) * (volume / 12.0);
+    let inertia = DMat3::from_diagonal(DVec3::splat(
+        covariance.x_axis.x + covariance.y_axis.y + covariance.z_axis.z,
+    )) - covariance
+        + DMat3::from_diagonal(own);
+
+    MassProperties {
+        volume: volume as f32,
+        center_of_mass: center_of_mass.as_vec3(),
+        inertia: inertia.as_mat3(),
+    }
+}
+
+fn outer(a: DVec3, b: DVec3) -> DMat3 {
+    DMat3::from_cols(a * b.x, a * b.y, a * b.z)
+}
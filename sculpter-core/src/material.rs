@@ -0,0 +1,158 @@
+//! Per-vertex material ids from a material channel alongside the densities.
+
+use std::collections::{BTreeMap, HashMap};
+
+use glam::{UVec3, Vec3, Vec4};
+
+use crate::{
+    grid::index,
+    mesh::{MeshData, VertexAttributeData},
+};
+
+/// Name of the `Uint32` vertex attribute holding material ids.
+pub const MATERIAL_ID_ATTRIBUTE: &str = "Vertex_MaterialId";
+
+/// Name of the `Float32x4` vertex attribute holding splat weights.
+pub const MATERIAL_WEIGHTS_ATTRIBUTE: &str = "Vertex_MaterialWeights";
+
+/// Name of the `Float32x4` vertex attribute holding linear RGBA vertex colors.
+pub const COLOR_ATTRIBUTE: &str = "Vertex_Color";
+
+/// Corners of the cell containing `position`, clamped to the grid.
+fn cell_corners(position: Vec3, dims: UVec3) -> impl Iterator<Item = UVec3> {
+    let max_cell = dims.saturating_sub(UVec3::splat(2));
+    let cell = position.floor().max(Vec3::ZERO).as_uvec3().min(max_cell);
+    (0..8).map(move |i| cell + UVec3::new(i & 1, i >> 1 & 1, i >> 2 & 1))
+}
+
+/// Material id of each vertex: that of the nearest inside sample among the
+/// corners of its cell, or of the nearest corner when none is inside.
+///
+/// `materials` is laid out like `densities`, one id per sample.
+pub fn vertex_materials(
+    positions: &[Vec3],
+    densities: &[f32],
+    materials: &[u32],
+    dims: UVec3,
+) -> Vec<u32> {
+    positions
+        .iter()
+        .map(|&position| {
+            // Inside corners sort first, then by distance
+            let nearest = cell_corners(position, dims).min_by(|&a, &b| {
+                let key = |p: UVec3| {
+                    let inside = densities[index(dims, p.x, p.y, p.z) as usize] < 0.0;
+                    (!inside, p.as_vec3().distance_squared(position))
+                };
+                key(a)
+                    .partial_cmp(&key(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            nearest.map_or(0, |p| materials[index(dims, p.x, p.y, p.z) as usize])
+        })
+        .collect()
+}
+
+/// Splat weights of each vertex for materials 0 to 3, one per component, summing
+/// to 1: the trilinear weights of the inside corners of its cell (all corners
+/// when none is inside), pooled by material. Other ids don't count.
+pub fn vertex_material_weights(
+    positions: &[Vec3],
+    densities: &[f32],
+    materials: &[u32],
+    dims: UVec3,
+) -> Vec<Vec4> {
+    positions
+        .iter()
+        .map(|&position| {
+            let mut inside = Vec4::ZERO;
+            let mut all = Vec4::ZERO;
+            for corner in cell_corners(position, dims) {
+                let i = index(dims, corner.x, corner.y, corner.z) as usize;
+                let Some(layer) = Vec4::AXES.get(materials[i] as usize) else {
+                    continue;
+                };
+                let weight = (1.0 - (position - corner.as_vec3()).abs())
+                    .max(Vec3::ZERO)
+                    .element_product();
+                all += *layer * weight;
+                if densities[i] < 0.0 {
+                    inside += *layer * weight;
+                }
+            }
+            let weights = if inside.element_sum() > 0.0 {
+                inside
+            } else {
+                all
+            };
+            let total = weights.element_sum();
+            if total > 0.0 {
+                weights / total
+            } else {
+                Vec4::ZERO
+            }
+        })
+        .collect()
+}
+
+/// Color of each vertex's material id looked up in `palette`, white for ids past
+/// its end.
+pub fn vertex_palette_colors(ids: &[u32], palette: &[Vec4]) -> Vec<Vec4> {
+    ids.iter()
+        .map(|&id| palette.get(id as usize).copied().unwrap_or(Vec4::ONE))
+        .collect()
+}
+
+/// The material id most of the triangle's vertices have, or its first vertex's
+/// when they all differ.
+pub(crate) fn triangle_material(ids: &[u32], triangle: &[u32]) -> u32 {
+    let [a, b, c] = [0, 1, 2].map(|i| ids[triangle[i] as usize]);
+    if b == c { b } else { a }
+}
+
+/// Old vertex indices, old-to-new lookup and remapped indices of one submesh.
+type Submesh = (Vec<u32>, HashMap<u32, u32>, Vec<u32>);
+
+/// Split a mesh into one mesh per material, each triangle going to the id most
+/// of its vertices have (its first vertex's when they all differ). Vertices are
+/// copied into every mesh using them. A mesh without material ids is returned
+/// whole, as material 0.
+pub fn split_by_material(mesh: &MeshData) -> BTreeMap<u32, MeshData> {
+    let Some(VertexAttributeData::Uint32(ids)) = mesh.attributes.get(MATERIAL_ID_ATTRIBUTE) else {
+        return BTreeMap::from([(0, mesh.clone())]);
+    };
+
+    // Old indices of each material's vertices, and its remapped triangles
+    let mut groups: BTreeMap<u32, Submesh> = BTreeMap::new();
+    for triangle in mesh.indices.chunks_exact(3) {
+        let material = triangle_material(ids, triangle);
+        let (vertices, remap, indices) = groups.entry(material).or_default();
+        for &old in triangle {
+            let new = *remap.entry(old).or_insert_with(|| {
+                vertices.push(old);
+                vertices.len() as u32 - 1
+            });
+            indices.push(new);
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(material, (vertices, _, indices))| {
+            let submesh = MeshData {
+                positions: vertices
+                    .iter()
+                    .map(|&i| mesh.positions[i as usize])
+                    .collect(),
+                normals: vertices.iter().map(|&i| mesh.normals[i as usize]).collect(),
+                indices,
+                attributes: mesh
+                    .attributes
+                    .iter()
+                    .map(|(&name, values)| (name, values.gather(&vertices)))
+                    .collect(),
+            };
+            (material, submesh)
+        })
+        .collect()
+}
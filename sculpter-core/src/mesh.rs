@@ -0,0 +1,355 @@
+//! Mesher output and the indexed triangle mesh built from it.
+
+use std::collections::{BTreeMap, HashSet};
+
+use glam::{Vec2, Vec3, Vec4, vec3};
+
+/// Compacted mesher output, laid out exactly like the GPU readback buffers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaceBuffers {
+    /// Packed x,y,z grid-space positions
+    pub vertices: Vec<f32>,
+    /// Packed quads, 4 vertex indices each. Triangles repeat their last index.
+    pub faces: Vec<u32>,
+}
+
+impl FaceBuffers {
+    /// Triangulate the faces with [`triangulate_quad`] and compute normals.
+    pub fn to_mesh_data(&self) -> MeshData {
+        MeshData::from_faces(&self.vertices, &self.faces, triangulate_quad)
+    }
+}
+
+/// A triangle mesh as plain data, independent of any engine's mesh type.
+///
+/// Both the GPU readback and the CPU mesher produce one, in grid units, and it
+/// is what mesh building, collider output and the plain-data API work on.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    /// Unit normals, one per position
+    pub normals: Vec<Vec3>,
+    /// Counter-clockwise triangles, 3 indices each
+    pub indices: Vec<u32>,
+    /// Extra per-vertex attributes by name. Bevy's own names (such as
+    /// `Mesh::ATTRIBUTE_UV_0.name`) become those attributes when converted to a `Mesh`.
+    pub attributes: BTreeMap<&'static str, VertexAttributeData>,
+}
+
+/// Values of an extra per-vertex attribute, one per position.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VertexAttributeData {
+    Float32(Vec<f32>),
+    Float32x2(Vec<Vec2>),
+    Float32x3(Vec<Vec3>),
+    Float32x4(Vec<Vec4>),
+    Uint32(Vec<u32>),
+}
+
+impl VertexAttributeData {
+    /// The values at `indices`, in order.
+    pub fn gather(&self, indices: &[u32]) -> Self {
+        fn pick<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+            indices.iter().map(|&i| values[i as usize]).collect()
+        }
+        match self {
+            Self::Float32(values) => Self::Float32(pick(values, indices)),
+            Self::Float32x2(values) => Self::Float32x2(pick(values, indices)),
+            Self::Float32x3(values) => Self::Float32x3(pick(values, indices)),
+            Self::Float32x4(values) => Self::Float32x4(pick(values, indices)),
+            Self::Uint32(values) => Self::Uint32(pick(values, indices)),
+        }
+    }
+
+    /// Append a copy of the value at `index`.
+    pub fn duplicate(&mut self, index: usize) {
+        match self {
+            Self::Float32(values) => values.push(values[index]),
+            Self::Float32x2(values) => values.push(values[index]),
+            Self::Float32x3(values) => values.push(values[index]),
+            Self::Float32x4(values) => values.push(values[index]),
+            Self::Uint32(values) => values.push(values[index]),
+        }
+    }
+}
+
+impl MeshData {
+    /// Triangulate compacted mesher output (packed x,y,z positions and 4-index
+    /// faces) with `triangulate`, and compute normals.
+    pub fn from_faces(
+        vertices: &[f32],
+        faces: &[u32],
+        mut triangulate: impl FnMut(&[u32], &mut Vec<u32>),
+    ) -> Self {
+        let positions: Vec<Vec3> = vertices
+            .chunks_exact(3)
+            .map(|v| vec3(v[0], v[1], v[2]))
+            .collect();
+        let mut indices = Vec::with_capacity(faces.len() / 4 * 6);
+        for face in faces.chunks_exact(4) {
+            triangulate(face, &mut indices);
+        }
+        let normals = compute_flat_normals(&positions, &indices);
+        Self {
+            positions,
+            normals,
+            indices,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Add `other`'s triangles after this mesh's. Attributes missing from either
+    /// mesh are dropped, since they would no longer cover every position.
+    pub fn append(&mut self, other: MeshData) {
+        let offset = self.positions.len() as u32;
+        self.positions.extend(other.positions);
+        self.normals.extend(other.normals);
+        self.indices
+            .extend(other.indices.into_iter().map(|index| index + offset));
+        let mut others = other.attributes;
+        self.attributes.retain(|name, values| {
+            match (values, others.remove(name)) {
+                (VertexAttributeData::Float32(a), Some(VertexAttributeData::Float32(b))) => {
+                    a.extend(b)
+                }
+                (VertexAttributeData::Float32x2(a), Some(VertexAttributeData::Float32x2(b))) => {
+                    a.extend(b)
+                }
+                (VertexAttributeData::Float32x3(a), Some(VertexAttributeData::Float32x3(b))) => {
+                    a.extend(b)
+                }
+                (VertexAttributeData::Float32x4(a), Some(VertexAttributeData::Float32x4(b))) => {
+                    a.extend(b)
+                }
+                (VertexAttributeData::Uint32(a), Some(VertexAttributeData::Uint32(b))) => {
+                    a.extend(b)
+                }
+                _ => return false,
+            }
+            true
+        });
+    }
+
+    /// Turn the mesh inside out: reverse every triangle and its normals.
+    pub fn invert_winding(&mut self) {
+        for triangle in self.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        for normal in &mut self.normals {
+            *normal = -*normal;
+        }
+    }
+
+    /// Append an inverted copy of every triangle, with its own vertices, so the
+    /// surface can be seen from both sides with back-face culling on.
+    pub fn make_double_sided(&mut self) {
+        let mut back = self.clone();
+        back.invert_winding();
+        self.append(back);
+    }
+
+    /// Append a copy of vertex `index`, with its normal and attributes, and
+    /// return the copy's index.
+    pub fn duplicate_vertex(&mut self, index: u32) -> u32 {
+        let i = index as usize;
+        self.positions.push(self.positions[i]);
+        self.normals.push(self.normals[i]);
+        for values in self.attributes.values_mut() {
+            values.duplicate(i);
+        }
+        self.positions.len() as u32 - 1
+    }
+
+    /// Scale positions by `scale`, keeping normals perpendicular to the surface.
+    pub fn scale(&mut self, scale: Vec3) {
+        for position in &mut self.positions {
+            *position *= scale;
+        }
+        // A uniform scale leaves the directions alone, otherwise normals take the
+        // inverse scale
+        if scale.x != scale.y || scale.y != scale.z {
+            for normal in &mut self.normals {
+                *normal = (*normal / scale).normalize_or_zero();
+            }
+        }
+    }
+}
+
+/// Split a face into one triangle, when its last two indices match, or two.
+pub fn triangulate_quad(face: &[u32], indices: &mut Vec<u32>) {
+    let (v0, v1, v2, v3) = (face[0], face[1], face[2], face[3]);
+    indices.extend([v0, v1, v2]);
+    if v3 != v2 {
+        indices.extend([v0, v2, v3]);
+    }
+}
+
+/// Pair consecutive triangles back into the quads they were split from, as
+/// [`triangulate_quad`] and the meshers emit them: neighbours sharing an edge
+/// that together form a convex quad. Returns the counter-clockwise quads and the
+/// triangles left over.
+pub fn quads_from_triangles(positions: &[Vec3], indices: &[u32]) -> (Vec<[u32; 4]>, Vec<[u32; 3]>) {
+    let triangles: Vec<[u32; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .collect();
+    let mut quads = Vec::new();
+    let mut leftover = Vec::new();
+    let mut i = 0;
+    while i < triangles.len() {
+        let quad = triangles
+            .get(i + 1)
+            .and_then(|&next| merge_triangles(positions, triangles[i], next));
+        match quad {
+            Some(quad) => {
+                quads.push(quad);
+                i += 2;
+            }
+            None => {
+                leftover.push(triangles[i]);
+                i += 1;
+            }
+        }
+    }
+    (quads, leftover)
+}
+
+/// Every edge of the mesh once, leaving out the diagonals splitting quads (see
+/// [`quads_from_triangles`]), so surface nets show their cell-dual edges.
+pub fn wireframe_edges(positions: &[Vec3], indices: &[u32]) -> Vec<[u32; 2]> {
+    let (quads, triangles) = quads_from_triangles(positions, indices);
+    let faces = quads
+        .iter()
+        .map(|quad| quad.as_slice())
+        .chain(triangles.iter().map(|triangle| triangle.as_slice()));
+    let mut seen = HashSet::new();
+    let mut edges = Vec::new();
+    for face in faces {
+        for (i, &a) in face.iter().enumerate() {
+            let b = face[(i + 1) % face.len()];
+            if seen.insert((a.min(b), a.max(b))) {
+                edges.push([a, b]);
+            }
+        }
+    }
+    edges
+}
+
+/// The convex quad made of `first` and `second`, if they share an edge.
+fn merge_triangles(positions: &[Vec3], first: [u32; 3], second: [u32; 3]) -> Option<[u32; 4]> {
+    let normal = |[a, b, c]: [u32; 3]| {
+        let [a, b, c] = [a, b, c].map(|i| positions[i as usize]);
+        (b - a).cross(c - a)
+    };
+    let facing = normal(first);
+    for rotation in 0..3 {
+        // `first` is (x, y, z), so a neighbour runs the shared edge as y to x
+        let [x, y, z] = [0, 1, 2].map(|i| first[(rotation + i) % 3]);
+        let Some(w) = (0..3).find_map(|i| {
+            (second[i] == y && second[(i + 1) % 3] == x).then_some(second[(i + 2) % 3])
+        }) else {
+            continue;
+        };
+        // Convex exactly when the other diagonal splits it the same way round
+        let convex =
+            w != z && normal([z, x, w]).dot(facing) > 0.0 && normal([w, y, z]).dot(facing) > 0.0;
+        return convex.then_some([y, z, x, w]);
+    }
+    None
+}
+
+/// Average of the normals of the triangles around each vertex.
+pub fn compute_flat_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    let mut normal_counts = vec![0u32; positions.len()];
+
+    // For each triangle, compute its normal and add to vertices
+    for triangle in indices.chunks_exact(3) {
+        let i0 = triangle[0] as usize;
+        let i1 = triangle[1] as usize;
+        let i2 = triangle[2] as usize;
+
+        if i0 >= positions.len() || i1 >= positions.len() || i2 >= positions.len() {
+            continue;
+        }
+
+        let v0 = positions[i0];
+        let v1 = positions[i1];
+        let v2 = positions[i2];
+
+        // Compute face normal using cross product
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let normal = edge1.cross(edge2).normalize_or_zero();
+
+        // Add to each vertex of the triangle
+        for &idx in &[i0, i1, i2] {
+            normals[idx] += normal;
+            normal_counts[idx] += 1;
+        }
+    }
+
+    // Average the normals
+    for (normal, &count) in normals.iter_mut().zip(&normal_counts) {
+        if count > 0 {
+            *normal = (*normal / count as f32).normalize_or_zero();
+        }
+    }
+
+    normals
+}
+
+/// Split vertices along creases: where the faces around a vertex meet at more
+/// than `crease_angle` (radians), each group of faces within the angle of each
+/// other gets its own copy of the vertex with the average normal of the group.
+/// Vertices on smooth stretches keep their normal.
+pub fn split_creases(mesh: &mut MeshData, crease_angle: f32) {
+    let min_cos = crease_angle.cos();
+    let face_normals: Vec<Vec3> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[t[i] as usize]);
+            (b - a).cross(c - a).normalize_or_zero()
+        })
+        .collect();
+
+    // Triangle corners using each vertex
+    let mut corners = vec![Vec::new(); mesh.positions.len()];
+    for (corner, &vertex) in mesh.indices.iter().enumerate() {
+        corners[vertex as usize].push(corner);
+    }
+
+    for (vertex, corners) in corners.into_iter().enumerate() {
+        // Faces join the first group whose first face is within the angle,
+        // degenerate ones the first group
+        let mut groups: Vec<(Vec3, Vec3, Vec<usize>)> = Vec::new();
+        for corner in corners {
+            let normal = face_normals[corner / 3];
+            let group = groups.iter_mut().find(|(first, ..)| {
+                normal == Vec3::ZERO || *first == Vec3::ZERO || first.dot(normal) >= min_cos
+            });
+            match group {
+                Some((_, sum, corners)) => {
+                    *sum += normal;
+                    corners.push(corner);
+                }
+                None => groups.push((normal, normal, vec![corner])),
+            }
+        }
+        if groups.len() < 2 {
+            continue;
+        }
+
+        for (i, (_, sum, corners)) in groups.into_iter().enumerate() {
+            let target = match i {
+                0 => vertex as u32,
+                _ => mesh.duplicate_vertex(vertex as u32),
+            };
+            mesh.normals[target as usize] = sum.normalize_or_zero();
+            for corner in corners {
+                mesh.indices[corner] = target;
+            }
+        }
+    }
+}
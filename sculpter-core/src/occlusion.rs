@@ -0,0 +1,91 @@
+//! Per-vertex ambient occlusion, ray marched through the density field.
+
+use glam::{UVec3, Vec3};
+
+use crate::surface_nets::{density_gradient, sample_trilinear};
+
+/// Name of the `Float32` vertex attribute holding ambient occlusion, from 0
+/// (fully occluded) to 1 (open sky).
+pub const AMBIENT_OCCLUSION_ATTRIBUTE: &str = "Vertex_AmbientOcclusion";
+
+/// Samples taken along each ray.
+const STEPS: u32 = 4;
+
+/// Golden angle in radians, spreading the rays evenly around the normal.
+const GOLDEN_ANGLE: f32 = 2.399_963;
+
+/// How ambient occlusion is baked into each vertex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AmbientOcclusion {
+    /// Rays cast over the hemisphere around the surface normal
+    pub rays: u32,
+    /// How far each ray marches, in voxels
+    pub distance: f32,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self {
+            rays: 8,
+            distance: 4.0,
+        }
+    }
+}
+
+/// Ambient occlusion of each vertex: the cosine weighted share of rays over the
+/// hemisphere around the density gradient that reach `distance` without entering
+/// solid, as `ambient_occlusion` in vertex_normals.wgsl.
+pub fn vertex_ambient_occlusion(
+    positions: &[Vec3],
+    densities: &[f32],
+    dims: UVec3,
+    occlusion: AmbientOcclusion,
+) -> Vec<f32> {
+    positions
+        .iter()
+        .map(|&p| {
+            let normal = density_gradient(densities, dims, p).normalize_or_zero();
+            ambient_occlusion(densities, dims, p, normal, occlusion)
+        })
+        .collect()
+}
+
+fn ambient_occlusion(
+    densities: &[f32],
+    dims: UVec3,
+    p: Vec3,
+    normal: Vec3,
+    occlusion: AmbientOcclusion,
+) -> f32 {
+    if normal == Vec3::ZERO || occlusion.rays == 0 {
+        return 1.0;
+    }
+    let up = if normal.z.abs() < 0.999 {
+        Vec3::Z
+    } else {
+        Vec3::X
+    };
+    let tangent = up.cross(normal).normalize();
+    let bitangent = normal.cross(tangent);
+
+    let (mut total, mut occluded) = (0.0, 0.0);
+    for ray in 0..occlusion.rays {
+        // Evenly spaced heights over the hemisphere, turning by the golden angle
+        let cos_theta = 1.0 - (ray as f32 + 0.5) / occlusion.rays as f32;
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = ray as f32 * GOLDEN_ANGLE;
+        let direction = tangent * (phi.cos() * sin_theta)
+            + bitangent * (phi.sin() * sin_theta)
+            + normal * cos_theta;
+
+        total += cos_theta;
+        let blocked = (1..=STEPS).any(|step| {
+            let q = p + direction * (occlusion.distance * step as f32 / STEPS as f32);
+            sample_trilinear(densities, dims, q) < 0.0
+        });
+        if blocked {
+            occluded += cos_theta;
+        }
+    }
+    1.0 - occluded / total
+}
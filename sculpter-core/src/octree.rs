@@ -0,0 +1,483 @@
+//! Adaptive dual contouring over an octree (Ju et al. 2002): cells whose merged
+//! quadric error stays under a threshold collapse into one larger cell with a
+//! single vertex, so flat stretches of a surface take a handful of triangles.
+
+use glam::{Mat3, UVec3, Vec3};
+
+use crate::{
+    dual_contouring::solve_qef,
+    grid::{density_count, index},
+    mesh::FaceBuffers,
+    surface_nets::density_gradient,
+};
+
+/// Corner `i` of an octree cell sits at `(i >> 2 & 1, i >> 1 & 1, i & 1)`, and
+/// child `i` in the same octant.
+fn corner_offset(i: usize) -> UVec3 {
+    UVec3::new((i >> 2 & 1) as u32, (i >> 1 & 1) as u32, (i & 1) as u32)
+}
+
+/// Corner pairs of the cell edges, 4 along x, then y, then z.
+const EDGE_CORNERS: [[usize; 2]; 12] = [
+    [0, 4],
+    [1, 5],
+    [2, 6],
+    [3, 7],
+    [0, 2],
+    [1, 3],
+    [4, 6],
+    [5, 7],
+    [0, 1],
+    [2, 3],
+    [4, 5],
+    [6, 7],
+];
+
+/// Children sharing each of the 12 faces inside a cell, and the face axis.
+const CELL_FACES: [[usize; 3]; 12] = [
+    [0, 4, 0],
+    [1, 5, 0],
+    [2, 6, 0],
+    [3, 7, 0],
+    [0, 2, 1],
+    [4, 6, 1],
+    [1, 3, 1],
+    [5, 7, 1],
+    [0, 1, 2],
+    [2, 3, 2],
+    [4, 5, 2],
+    [6, 7, 2],
+];
+
+/// Children around each of the 6 edges inside a cell, and the edge axis.
+const CELL_EDGES: [[usize; 5]; 6] = [
+    [0, 1, 2, 3, 0],
+    [4, 5, 6, 7, 0],
+    [0, 4, 1, 5, 1],
+    [2, 6, 3, 7, 1],
+    [0, 2, 4, 6, 2],
+    [1, 3, 5, 7, 2],
+];
+
+/// Child pairs across a face between two cells, by face axis.
+const FACE_FACES: [[[usize; 3]; 4]; 3] = [
+    [[4, 0, 0], [5, 1, 0], [6, 2, 0], [7, 3, 0]],
+    [[2, 0, 1], [6, 4, 1], [3, 1, 1], [7, 5, 1]],
+    [[1, 0, 2], [3, 2, 2], [5, 4, 2], [7, 6, 2]],
+];
+
+/// Edges inside a face between two cells, by face axis: which cell each of the
+/// four children comes from (see `FACE_EDGE_ORDERS`), the children, and the
+/// edge axis.
+const FACE_EDGES: [[[usize; 6]; 4]; 3] = [
+    [
+        [1, 4, 0, 5, 1, 1],
+        [1, 6, 2, 7, 3, 1],
+        [0, 4, 6, 0, 2, 2],
+        [0, 5, 7, 1, 3, 2],
+    ],
+    [
+        [0, 2, 3, 0, 1, 0],
+        [0, 6, 7, 4, 5, 0],
+        [1, 2, 0, 6, 4, 2],
+        [1, 3, 1, 7, 5, 2],
+    ],
+    [
+        [1, 1, 0, 3, 2, 0],
+        [1, 5, 4, 7, 6, 0],
+        [0, 1, 5, 0, 4, 1],
+        [0, 3, 7, 2, 6, 1],
+    ],
+];
+const FACE_EDGE_ORDERS: [[usize; 4]; 2] = [[0, 0, 1, 1], [0, 1, 0, 1]];
+
+/// Children along an edge between four cells, by edge axis, for each half of
+/// the edge, and the axis again.
+const EDGE_EDGES: [[[usize; 5]; 2]; 3] = [
+    [[3, 2, 1, 0, 0], [7, 6, 5, 4, 0]],
+    [[5, 1, 4, 0, 1], [7, 3, 6, 2, 1]],
+    [[6, 4, 2, 0, 2], [7, 5, 3, 1, 2]],
+];
+
+/// The edge of each of the four cells around an edge along each axis.
+const AROUND_EDGE: [[usize; 4]; 3] = [[3, 2, 1, 0], [7, 5, 6, 4], [11, 10, 9, 8]];
+
+/// Quadric error of a set of planes, in a form that can be summed.
+#[derive(Clone, Copy)]
+struct Qef {
+    ata: Mat3,
+    atb: Vec3,
+    btb: f32,
+    mass: Vec3,
+    count: u32,
+}
+
+impl Default for Qef {
+    // `Mat3::default()` is the identity
+    fn default() -> Self {
+        Self {
+            ata: Mat3::ZERO,
+            atb: Vec3::ZERO,
+            btb: 0.0,
+            mass: Vec3::ZERO,
+            count: 0,
+        }
+    }
+}
+
+impl Qef {
+    fn add_plane(&mut self, point: Vec3, normal: Vec3) {
+        let b = normal.dot(point);
+        self.ata += Mat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z);
+        self.atb += normal * b;
+        self.btb += b * b;
+        self.mass += point;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Qef) {
+        self.ata += other.ata;
+        self.atb += other.atb;
+        self.btb += other.btb;
+        self.mass += other.mass;
+        self.count += other.count;
+    }
+
+    fn mass_point(&self) -> Vec3 {
+        self.mass / self.count.max(1) as f32
+    }
+
+    /// Minimiser, solved around the mass point like `dual_contouring`, and the
+    /// error there.
+    fn solve(&self) -> (Vec3, f32) {
+        let mass_point = self.mass_point();
+        let x = mass_point + solve_qef(self.ata, self.atb - self.ata * mass_point);
+        let error = x.dot(self.ata * x) - 2.0 * x.dot(self.atb) + self.btb;
+        (x, error.max(0.0))
+    }
+}
+
+enum Kind {
+    Internal([Option<usize>; 8]),
+    /// A surface cell, or a collapsed subtree standing in for one
+    Leaf {
+        /// Bit `i` set when corner `i` is inside
+        corners: u8,
+        qef: Qef,
+        position: Vec3,
+    },
+}
+
+struct Node {
+    size: u32,
+    kind: Kind,
+}
+
+struct Octree<'a> {
+    densities: &'a [f32],
+    dims: UVec3,
+    threshold: f32,
+    nodes: Vec<Node>,
+}
+
+impl Octree<'_> {
+    fn inside(&self, p: UVec3) -> bool {
+        self.densities[index(self.dims, p.x, p.y, p.z) as usize] < 0.0
+    }
+
+    /// Surface cell at `min`, if the cell is in the grid and the surface crosses it.
+    fn leaf(&self, min: UVec3) -> Option<Node> {
+        if min.cmpge(self.dims - 1).any() {
+            return None;
+        }
+        let density = |p: UVec3| self.densities[index(self.dims, p.x, p.y, p.z) as usize];
+        let corners = (0..8).fold(0u8, |bits, i| {
+            bits | (self.inside(min + corner_offset(i)) as u8) << i
+        });
+        if corners == 0 || corners == 0xff {
+            return None;
+        }
+
+        let mut qef = Qef::default();
+        for [a, b] in EDGE_CORNERS {
+            if (corners >> a & 1) == (corners >> b & 1) {
+                continue;
+            }
+            let (pa, pb) = (min + corner_offset(a), min + corner_offset(b));
+            let (da, db) = (density(pa), density(pb));
+            let point = pa.as_vec3() + da / (da - db) * (pb.as_vec3() - pa.as_vec3());
+            let normal = density_gradient(self.densities, self.dims, point).normalize_or_zero();
+            qef.add_plane(point, normal);
+        }
+        let position = qef.solve().0.clamp(min.as_vec3(), min.as_vec3() + 1.0);
+        Some(Node {
+            size: 1,
+            kind: Kind::Leaf {
+                corners,
+                qef,
+                position,
+            },
+        })
+    }
+
+    /// Build the subtree of the cube at `min`, collapsing it into one leaf when
+    /// all its children are leaves and their merged error is small enough.
+    fn build(&mut self, min: UVec3, size: u32) -> Option<usize> {
+        if min.cmpge(self.dims - 1).any() {
+            return None;
+        }
+        if size == 1 {
+            let leaf = self.leaf(min)?;
+            self.nodes.push(leaf);
+            return Some(self.nodes.len() - 1);
+        }
+
+        let half = size / 2;
+        let children: [Option<usize>; 8] =
+            std::array::from_fn(|i| self.build(min + corner_offset(i) * half, half));
+        if children.iter().all(Option::is_none) {
+            return None;
+        }
+
+        let collapsed = self.collapse(min, size, &children);
+        self.nodes.push(Node {
+            size,
+            kind: collapsed.unwrap_or(Kind::Internal(children)),
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    fn collapse(&self, min: UVec3, size: u32, children: &[Option<usize>; 8]) -> Option<Kind> {
+        let mut qef = Qef::default();
+        let mut corners = 0u8;
+        let mut known = 0u8;
+        let mut middle = false;
+        for (i, child) in children.iter().enumerate() {
+            let Some(child) = child else { continue };
+            let Kind::Leaf {
+                corners: child_corners,
+                qef: child_qef,
+                ..
+            } = &self.nodes[*child].kind
+            else {
+                return None;
+            };
+            qef.merge(child_qef);
+            // Each child holds the parent's corner in its octant, and the centre
+            middle = child_corners >> (7 - i) & 1 == 1;
+            corners |= (child_corners >> i & 1) << i;
+            known |= 1 << i;
+        }
+
+        let (mut position, error) = qef.solve();
+        if error > self.threshold {
+            return None;
+        }
+        let bounds_max = (min + size).min(self.dims - 1).as_vec3();
+        if position.cmplt(min.as_vec3()).any() || position.cmpgt(bounds_max).any() {
+            position = qef.mass_point();
+        }
+        // Corners of empty children are on the same side as the centre
+        if middle {
+            corners |= !known;
+        }
+        Some(Kind::Leaf {
+            corners,
+            qef,
+            position,
+        })
+    }
+
+    fn leaf_corners(&self, node: usize) -> Option<u8> {
+        match self.nodes[node].kind {
+            Kind::Leaf { corners, .. } => Some(corners),
+            Kind::Internal(_) => None,
+        }
+    }
+
+    /// Child `i` of `node`, or the node itself once it is a leaf.
+    fn descend(&self, node: usize, i: usize) -> Option<usize> {
+        match &self.nodes[node].kind {
+            Kind::Internal(children) => children[i],
+            Kind::Leaf { .. } => Some(node),
+        }
+    }
+
+    fn contour_cell(&self, node: usize, faces: &mut Vec<u32>) {
+        let Kind::Internal(children) = self.nodes[node].kind else {
+            return;
+        };
+        for child in children.into_iter().flatten() {
+            self.contour_cell(child, faces);
+        }
+        for [a, b, axis] in CELL_FACES {
+            if let (Some(a), Some(b)) = (children[a], children[b]) {
+                self.contour_face([a, b], axis, faces);
+            }
+        }
+        for [a, b, c, d, axis] in CELL_EDGES {
+            let around = [children[a], children[b], children[c], children[d]];
+            if let [Some(a), Some(b), Some(c), Some(d)] = around {
+                self.contour_edge([a, b, c, d], axis, faces);
+            }
+        }
+    }
+
+    fn contour_face(&self, nodes: [usize; 2], axis: usize, faces: &mut Vec<u32>) {
+        if nodes.iter().all(|&node| self.leaf_corners(node).is_some()) {
+            return;
+        }
+        for [a, b, face_axis] in FACE_FACES[axis] {
+            if let (Some(a), Some(b)) = (self.descend(nodes[0], a), self.descend(nodes[1], b)) {
+                self.contour_face([a, b], face_axis, faces);
+            }
+        }
+        for [order, a, b, c, d, edge_axis] in FACE_EDGES[axis] {
+            let order = FACE_EDGE_ORDERS[order];
+            let around = [a, b, c, d]
+                .into_iter()
+                .zip(order)
+                .map(|(child, side)| self.descend(nodes[side], child))
+                .collect::<Option<Vec<_>>>();
+            if let Some(&[a, b, c, d]) = around.as_deref() {
+                self.contour_edge([a, b, c, d], edge_axis, faces);
+            }
+        }
+    }
+
+    fn contour_edge(&self, nodes: [usize; 4], axis: usize, faces: &mut Vec<u32>) {
+        if nodes.iter().all(|&node| self.leaf_corners(node).is_some()) {
+            self.emit_edge(nodes, axis, faces);
+            return;
+        }
+        for half in EDGE_EDGES[axis] {
+            let around = [0, 1, 2, 3]
+                .map(|j| self.descend(nodes[j], half[j]))
+                .into_iter()
+                .collect::<Option<Vec<_>>>();
+            if let Some(&[a, b, c, d]) = around.as_deref() {
+                self.contour_edge([a, b, c, d], half[4], faces);
+            }
+        }
+    }
+
+    /// Join the vertices of the four leaves around an edge the surface crosses,
+    /// deciding the crossing and orientation from the smallest one.
+    fn emit_edge(&self, nodes: [usize; 4], axis: usize, faces: &mut Vec<u32>) {
+        let Some(smallest) = (0..4).min_by_key(|&i| self.nodes[nodes[i]].size) else {
+            return;
+        };
+        let corners = self.leaf_corners(nodes[smallest]).unwrap_or_default();
+        let [a, b] = EDGE_CORNERS[AROUND_EDGE[axis][smallest]];
+        let (inside_a, inside_b) = (corners >> a & 1 == 1, corners >> b & 1 == 1);
+        if inside_a == inside_b {
+            return;
+        }
+
+        let v = nodes.map(|node| node as u32);
+        let triangles = if inside_a {
+            [[v[0], v[3], v[1]], [v[0], v[2], v[3]]]
+        } else {
+            [[v[0], v[1], v[3]], [v[0], v[3], v[2]]]
+        };
+        for [a, b, c] in triangles {
+            if a != b && b != c && c != a {
+                faces.extend_from_slice(&[a, b, c, c]);
+            }
+        }
+    }
+}
+
+/// Dual contouring on an octree of the field, merging surface cells while the
+/// quadric error of the merged cell stays at most `error_threshold` (squared
+/// grid units). 0 merges only where the surface is exactly flat.
+///
+/// Triangles repeat their last index; vertices are the leaves' positions.
+pub fn adaptive_dual_contouring(
+    densities: &[f32],
+    dims: UVec3,
+    error_threshold: f32,
+) -> FaceBuffers {
+    let mut output = FaceBuffers::default();
+    if dims.min_element() < 2 || densities.len() < density_count(dims) as usize {
+        return output;
+    }
+
+    let mut octree = Octree {
+        densities,
+        dims,
+        threshold: error_threshold,
+        nodes: Vec::new(),
+    };
+    let size = (dims - 1).max_element().next_power_of_two();
+    let Some(root) = octree.build(UVec3::ZERO, size) else {
+        return output;
+    };
+
+    // Faces come out indexed by node, number the leaves they use as vertices.
+    // Leaves of collapsed subtrees are left behind unused.
+    octree.contour_cell(root, &mut output.faces);
+    let mut vertex_indices = vec![u32::MAX; octree.nodes.len()];
+    for index in &mut output.faces {
+        let node = *index as usize;
+        if vertex_indices[node] == u32::MAX
+            && let Kind::Leaf { position, .. } = octree.nodes[node].kind
+        {
+            vertex_indices[node] = (output.vertices.len() / 3) as u32;
+            output.vertices.extend_from_slice(&position.to_array());
+        }
+        *index = vertex_indices[node];
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::uvec3;
+
+    use super::*;
+    use crate::{
+        dual_contouring::dual_contouring,
+        surface_nets::tests::{boundary_edges, oriented, sphere},
+    };
+
+    #[test]
+    fn sphere_is_closed() {
+        let dims = UVec3::splat(16);
+        let densities = sphere(dims, 5.0);
+        for threshold in [0.0, 0.1] {
+            let faces = adaptive_dual_contouring(&densities, dims, threshold);
+            let mesh = oriented(&faces, &densities, dims);
+            assert!(!mesh.indices.is_empty());
+            assert_eq!(boundary_edges(&mesh), 0, "threshold {threshold}");
+        }
+    }
+
+    #[test]
+    fn uniform_fields_have_no_faces() {
+        let dims = UVec3::splat(9);
+        let count = density_count(dims) as usize;
+        for density in [1.0, -1.0] {
+            let faces = adaptive_dual_contouring(&vec![density; count], dims, 0.0);
+            assert!(faces.faces.is_empty());
+        }
+    }
+
+    #[test]
+    fn flat_faces_merge() {
+        // A box 9 samples across in the middle of the grid
+        let dims = UVec3::splat(17);
+        let densities: Vec<f32> = (0..density_count(dims))
+            .map(|i| {
+                let p = uvec3(i % 17, i / 17 % 17, i / 289).as_vec3();
+                ((p - 8.0).abs() - 4.5).max_element()
+            })
+            .collect();
+        let adaptive = adaptive_dual_contouring(&densities, dims, 0.0);
+        let uniform = dual_contouring(&densities, dims);
+        assert!(!adaptive.faces.is_empty());
+        assert!(adaptive.faces.len() < uniform.faces.len());
+        let mesh = oriented(&adaptive, &densities, dims);
+        assert_eq!(boundary_edges(&mesh), 0);
+    }
+}
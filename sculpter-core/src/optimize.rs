@@ -0,0 +1,47 @@
+//! Index and vertex reordering for GPU efficiency, through meshoptimizer.
+
+use glam::Vec3;
+use meshopt::DecodePosition;
+
+use crate::mesh::MeshData;
+
+/// How much the overdraw pass may degrade vertex cache efficiency, 1.05 being
+/// up to 5%.
+const OVERDRAW_THRESHOLD: f32 = 1.05;
+
+struct Position(Vec3);
+
+impl DecodePosition for Position {
+    fn decode_position(&self) -> [f32; 3] {
+        self.0.to_array()
+    }
+}
+
+/// Reorder the triangles of `mesh` for the post-transform vertex cache and
+/// less overdraw, then its vertices in the order the triangles first use them,
+/// for fetch locality. Vertices no triangle uses are dropped. The triangles
+/// themselves are unchanged.
+pub fn optimize_vertex_order(mesh: &mut MeshData) {
+    let vertex_count = mesh.positions.len();
+    let mut indices = meshopt::optimize_vertex_cache(&mesh.indices, vertex_count);
+    let positions: Vec<Position> = mesh.positions.iter().copied().map(Position).collect();
+    meshopt::optimize_overdraw_in_place_decoder(&mut indices, &positions, OVERDRAW_THRESHOLD);
+
+    let mut new_index = vec![u32::MAX; vertex_count];
+    let mut order = Vec::with_capacity(vertex_count);
+    for index in &mut indices {
+        let slot = &mut new_index[*index as usize];
+        if *slot == u32::MAX {
+            *slot = order.len() as u32;
+            order.push(*index);
+        }
+        *index = *slot;
+    }
+
+    mesh.positions = order.iter().map(|&i| mesh.positions[i as usize]).collect();
+    mesh.normals = order.iter().map(|&i| mesh.normals[i as usize]).collect();
+    for values in mesh.attributes.values_mut() {
+        *values = values.gather(&order);
+    }
+    mesh.indices = indices;
+}
@@ -0,0 +1,32 @@
+//! Outward facing triangles, whatever winding the mesher emitted.
+
+use glam::{UVec3, Vec3};
+
+use crate::{
+    mesh::{MeshData, compute_flat_normals},
+    surface_nets::density_gradient,
+};
+
+/// Unit density gradient at each position, pointing out of the surface, or zero
+/// where the field is flat.
+pub fn gradient_normals(positions: &[Vec3], densities: &[f32], dims: UVec3) -> Vec<Vec3> {
+    positions
+        .iter()
+        .map(|&p| density_gradient(densities, dims, p).normalize_or_zero())
+        .collect()
+}
+
+/// Flip every triangle facing against the `outward` directions of its corners,
+/// such as [`gradient_normals`], then recompute the normals. Triangles whose
+/// corners have no direction keep their winding.
+pub fn orient_to_gradient(mesh: &mut MeshData, outward: &[Vec3]) {
+    for triangle in mesh.indices.chunks_exact_mut(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let face =
+            (mesh.positions[b] - mesh.positions[a]).cross(mesh.positions[c] - mesh.positions[a]);
+        if face.dot(outward[a] + outward[b] + outward[c]) < 0.0 {
+            triangle.swap(1, 2);
+        }
+    }
+    mesh.normals = compute_flat_normals(&mesh.positions, &mesh.indices);
+}
@@ -0,0 +1,88 @@
+//! Overlap tests of simple shapes against the solid of a density field, read
+//! straight from its samples.
+//!
+//! Shapes are in grid space, where sample `(x, y, z)` sits at `(x, y, z)`, and
+//! only see the part of the field inside the grid.
+
+use glam::{UVec3, Vec3};
+
+use crate::{grid::index, surface_nets::sample_trilinear};
+
+/// Spacing of the points a capsule's segment is sampled at, in samples.
+const SEGMENT_STEP: f32 = 0.25;
+
+/// Whether any solid sample lies within the sphere, or the field is solid at its
+/// center, which catches spheres smaller than a sample.
+pub fn sphere_overlaps(densities: &[f32], dims: UVec3, center: Vec3, radius: f32) -> bool {
+    let radius_squared = radius * radius;
+    any_solid_sample(densities, dims, center - radius, center + radius, |p| {
+        p.distance_squared(center) <= radius_squared
+    }) || solid_at(densities, dims, center)
+}
+
+/// Whether any solid sample lies within the box from `min` to `max`, or the
+/// field is solid at its center, which catches boxes smaller than a sample.
+pub fn aabb_overlaps(densities: &[f32], dims: UVec3, min: Vec3, max: Vec3) -> bool {
+    any_solid_sample(densities, dims, min, max, |_| true)
+        || solid_at(densities, dims, (min + max) * 0.5)
+}
+
+/// How deep the capsule around the segment from `a` to `b` sinks into the
+/// solid, in samples, or 0 if it stays clear.
+///
+/// Densities are taken as distances to the surface in samples, so this is the
+/// capsule's radius less the lowest density along the segment.
+pub fn capsule_penetration_depth(
+    densities: &[f32],
+    dims: UVec3,
+    a: Vec3,
+    b: Vec3,
+    radius: f32,
+) -> f32 {
+    let steps = (a.distance(b) / SEGMENT_STEP).ceil().max(1.0) as u32;
+    let lowest = (0..=steps)
+        .map(|step| a.lerp(b, step as f32 / steps as f32))
+        .filter(|&p| in_grid(dims, p))
+        .map(|p| sample_trilinear(densities, dims, p))
+        .fold(f32::INFINITY, f32::min);
+    (radius - lowest).max(0.0)
+}
+
+fn in_grid(dims: UVec3, p: Vec3) -> bool {
+    p.cmpge(Vec3::ZERO).all() && p.cmple((dims - 1).as_vec3()).all()
+}
+
+/// Whether the interpolated field is solid at `p`, which outside the grid it
+/// never is.
+fn solid_at(densities: &[f32], dims: UVec3, p: Vec3) -> bool {
+    in_grid(dims, p) && sample_trilinear(densities, dims, p) <= 0.0
+}
+
+/// Whether any sample within the box from `min` to `max` and accepted by
+/// `inside` is solid.
+fn any_solid_sample(
+    densities: &[f32],
+    dims: UVec3,
+    min: Vec3,
+    max: Vec3,
+    inside: impl Fn(Vec3) -> bool,
+) -> bool {
+    let last = (dims - 1).as_vec3();
+    if max.cmplt(Vec3::ZERO).any() || min.cmpgt(last).any() {
+        return false;
+    }
+    let from = min.max(Vec3::ZERO).ceil().as_uvec3();
+    let to = max.min(last).floor().as_uvec3();
+    for z in from.z..=to.z {
+        for y in from.y..=to.y {
+            for x in from.x..=to.x {
+                if densities[index(dims, x, y, z) as usize] <= 0.0
+                    && inside(UVec3::new(x, y, z).as_vec3())
+                {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
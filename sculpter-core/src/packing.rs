@@ -0,0 +1,77 @@
+//! Compact vertex encodings: half precision positions and octahedral normals.
+
+use glam::{Vec2, Vec3};
+
+/// Octahedral encoding of a unit normal as two unorm16 values: the normal is
+/// projected onto an octahedron, whose lower half is folded over the upper one,
+/// then flattened onto a square.
+pub fn octahedral_encode(normal: Vec3) -> [u16; 2] {
+    let sum = normal.x.abs() + normal.y.abs() + normal.z.abs();
+    if sum == 0.0 {
+        return [32768; 2];
+    }
+    let n = normal / sum;
+    let mut e = Vec2::new(n.x, n.y);
+    if n.z < 0.0 {
+        let sign = Vec2::new(
+            if e.x >= 0.0 { 1.0 } else { -1.0 },
+            if e.y >= 0.0 { 1.0 } else { -1.0 },
+        );
+        e = (Vec2::ONE - Vec2::new(e.y, e.x).abs()) * sign;
+    }
+    (e * 0.5 + 0.5)
+        .to_array()
+        .map(|v| (v.clamp(0.0, 1.0) * 65535.0).round() as u16)
+}
+
+/// The unit normal encoded by [`octahedral_encode`], as `octahedral_decode` in
+/// packed_vertex.wgsl.
+pub fn octahedral_decode(encoded: [u16; 2]) -> Vec3 {
+    let f = Vec2::from_array(encoded.map(|v| v as f32 / 65535.0)) * 2.0 - 1.0;
+    let mut n = Vec3::new(f.x, f.y, 1.0 - f.x.abs() - f.y.abs());
+    let t = (-n.z).max(0.0);
+    n.x += if n.x >= 0.0 { -t } else { t };
+    n.y += if n.y >= 0.0 { -t } else { t };
+    n.normalize_or_zero()
+}
+
+/// Two half precision floats packed into a `u32`, `a` in the low bits, the way
+/// WGSL's `unpack2x16float` reads them.
+pub fn pack_half2(a: f32, b: f32) -> u32 {
+    f32_to_f16_bits(a) as u32 | (f32_to_f16_bits(b) as u32) << 16
+}
+
+/// IEEE 754 half precision bits of `value`, rounded to nearest even. Values
+/// out of range become infinity.
+pub fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // Infinity and NaN, keeping NaN quiet
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // Subnormal halves shift the implicit leading bit into the mantissa
+    let (half, shift, significand) = if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        let shift = (14 - half_exponent) as u32;
+        (0, shift, mantissa | 0x80_0000)
+    } else {
+        ((half_exponent as u32) << 10, 13, mantissa)
+    };
+    let truncated = half | significand >> shift;
+    let remainder = significand & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let round_up = remainder > halfway || (remainder == halfway && truncated & 1 == 1);
+    // A carry out of the mantissa bumps the exponent, which is still correct
+    sign | (truncated + round_up as u32) as u16
+}
@@ -0,0 +1,156 @@
+//! Ray casts against the density field, sphere tracing up to the surface and
+//! bisecting where the ray crosses it.
+
+use glam::{UVec3, Vec3};
+
+use crate::surface_nets::{density_gradient, sample_trilinear};
+
+/// Shortest step, in samples, so rays grazing the surface still advance.
+const MIN_STEP: f32 = 0.05;
+/// Longest step, in samples, so fields that aren't distances can't be stepped
+/// over.
+const MAX_STEP: f32 = 1.0;
+/// Halvings of the step that crossed the surface.
+const BISECTIONS: u32 = 16;
+
+/// Where a ray first meets the surface.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub position: Vec3,
+    /// Out of the solid, along the density gradient
+    pub normal: Vec3,
+    /// Along the ray from its origin
+    pub distance: f32,
+}
+
+/// Cast a ray in grid space against the zero surface of a `dims` field, x
+/// fastest, up to `max_distance` samples along `direction`.
+///
+/// The ray only sees the field within the grid, and hits at once when it starts
+/// inside the solid. Densities are taken as distances in samples to step
+/// through empty space, but steps stay below a sample.
+pub fn raycast(
+    densities: &[f32],
+    dims: UVec3,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+) -> Option<RayHit> {
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+    let (enter, exit) = clip_to_box(origin, direction, (dims - 1).as_vec3())?;
+    let end = exit.min(max_distance);
+    let mut near = enter.max(0.0);
+    if near > end {
+        return None;
+    }
+
+    let at = |t: f32| origin + direction * t;
+    let sample = |t: f32| sample_trilinear(densities, dims, at(t));
+    let hit = |t: f32| {
+        let position = at(t);
+        Some(RayHit {
+            position,
+            normal: density_gradient(densities, dims, position).normalize_or_zero(),
+            distance: t,
+        })
+    };
+
+    let mut density = sample(near);
+    if density <= 0.0 {
+        return hit(near);
+    }
+    while near < end {
+        let far = (near + density.clamp(MIN_STEP, MAX_STEP)).min(end);
+        let far_density = sample(far);
+        if far_density <= 0.0 {
+            // Outside at `near`, not at `far`
+            let mut far = far;
+            for _ in 0..BISECTIONS {
+                let middle = (near + far) * 0.5;
+                if sample(middle) <= 0.0 {
+                    far = middle;
+                } else {
+                    near = middle;
+                }
+            }
+            return hit(far);
+        }
+        near = far;
+        density = far_density;
+    }
+    None
+}
+
+/// Distances along the ray at which it enters and leaves the box from the origin
+/// to `max`, or `None` if it misses it.
+fn clip_to_box(origin: Vec3, direction: Vec3, max: Vec3) -> Option<(f32, f32)> {
+    let mut enter = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            if origin[axis] < 0.0 || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let a = -origin[axis] / direction[axis];
+        let b = (max[axis] - origin[axis]) / direction[axis];
+        enter = enter.max(a.min(b));
+        exit = exit.min(a.max(b));
+    }
+    (enter <= exit).then_some((enter, exit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::surface_nets::tests::sphere;
+
+    // Odd, so the sphere's centre and rays through it lie on samples
+    const DIMS: UVec3 = UVec3::splat(15);
+    const CENTRE: Vec3 = Vec3::splat(7.0);
+
+    #[test]
+    fn hits_the_near_side_of_a_sphere() {
+        let densities = sphere(DIMS, 5.0);
+        let hit = raycast(&densities, DIMS, Vec3::new(-4.0, 7.0, 7.0), Vec3::X, 100.0).unwrap();
+        assert!((hit.position.x - 2.0).abs() < 1e-3);
+        assert!((hit.distance - 6.0).abs() < 1e-3);
+        assert!(hit.normal.dot(-Vec3::X) > 0.99);
+    }
+
+    #[test]
+    fn misses() {
+        let densities = sphere(DIMS, 5.0);
+        // Passing beside the sphere, pointing away, and stopping short
+        let beside = raycast(&densities, DIMS, Vec3::new(0.0, 13.0, 7.0), Vec3::X, 100.0);
+        let away = raycast(&densities, DIMS, Vec3::new(1.0, 7.0, 7.0), -Vec3::X, 100.0);
+        let short = raycast(&densities, DIMS, Vec3::new(0.0, 7.0, 7.0), Vec3::X, 1.5);
+        assert_eq!((beside, away, short), (None, None, None));
+        assert_eq!(
+            raycast(&densities, DIMS, Vec3::ZERO, Vec3::ZERO, 100.0),
+            None
+        );
+    }
+
+    #[test]
+    fn starting_inside_hits_at_once() {
+        let densities = sphere(DIMS, 5.0);
+        let hit = raycast(&densities, DIMS, CENTRE, Vec3::Y, 100.0).unwrap();
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.position, CENTRE);
+    }
+
+    #[test]
+    fn empty_fields_are_never_hit() {
+        let densities = vec![1.0; DIMS.element_product() as usize];
+        let direction = Vec3::new(1.0, 0.5, 0.25);
+        assert_eq!(
+            raycast(&densities, DIMS, Vec3::ZERO, direction, 100.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Random points on a mesh's surface, for scattering grass, rocks and decals.
+
+use std::collections::HashMap;
+
+use glam::{IVec3, Vec3};
+
+use crate::{
+    material::{MATERIAL_ID_ATTRIBUTE, triangle_material},
+    mesh::{MeshData, VertexAttributeData},
+};
+
+/// Candidates tried per requested point before blue noise sampling gives up.
+const BLUE_NOISE_ATTEMPTS: usize = 30;
+
+/// A point on the surface of a mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfacePoint {
+    pub position: Vec3,
+    /// Interpolated from the triangle's vertex normals
+    pub normal: Vec3,
+    /// Material of the triangle the point lies on, 0 without material ids
+    pub material: u32,
+}
+
+/// How surface points are spread.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ScatterDistribution {
+    /// Independent points, each triangle getting its share by area
+    #[default]
+    AreaWeighted,
+    /// Points at least `min_distance` apart, in the mesh's units, for even
+    /// cover without clumps. Fewer are returned when the surface is full.
+    BlueNoise { min_distance: f32 },
+}
+
+/// Up to `count` random points on the mesh's surface, the same for the same
+/// `seed` and mesh.
+pub fn scatter_points(
+    mesh: &MeshData,
+    count: usize,
+    distribution: ScatterDistribution,
+    seed: u64,
+) -> Vec<SurfacePoint> {
+    let triangles: Vec<[u32; 3]> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect();
+    // Running total of the triangles' areas, to pick them in proportion
+    let mut total = 0.0;
+    let cumulative: Vec<f32> = triangles
+        .iter()
+        .map(|triangle| {
+            let [a, b, c] = triangle.map(|i| mesh.positions[i as usize]);
+            total += (b - a).cross(c - a).length() * 0.5;
+            total
+        })
+        .collect();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+
+    let ids = match mesh.attributes.get(MATERIAL_ID_ATTRIBUTE) {
+        Some(VertexAttributeData::Uint32(ids)) => Some(ids),
+        _ => None,
+    };
+    let mut random = Random(seed);
+    let mut candidate = || {
+        let target = random.next() * total;
+        let picked = cumulative
+            .partition_point(|&area| area < target)
+            .min(triangles.len() - 1);
+        let triangle = triangles[picked];
+        // Uniform over the triangle
+        let (u, v) = (random.next().sqrt(), random.next());
+        let weights = [1.0 - u, u * (1.0 - v), u * v];
+        let [a, b, c] = triangle.map(|i| mesh.positions[i as usize]);
+        let [na, nb, nc] = triangle.map(|i| mesh.normals[i as usize]);
+        let normal = (na * weights[0] + nb * weights[1] + nc * weights[2]).normalize_or_zero();
+        SurfacePoint {
+            position: a * weights[0] + b * weights[1] + c * weights[2],
+            normal: if normal == Vec3::ZERO {
+                (b - a).cross(c - a).normalize_or_zero()
+            } else {
+                normal
+            },
+            material: ids.map_or(0, |ids| triangle_material(ids, &triangle)),
+        }
+    };
+
+    match distribution {
+        ScatterDistribution::BlueNoise { min_distance } if min_distance > 0.0 => {
+            // Dart throwing, with accepted points bucketed in cells of the
+            // minimum distance so only neighbouring cells need checking
+            let cell_of = |p: Vec3| (p / min_distance).floor().as_ivec3();
+            let mut cells: HashMap<IVec3, Vec<Vec3>> = HashMap::new();
+            let mut points = Vec::with_capacity(count);
+            for _ in 0..count * BLUE_NOISE_ATTEMPTS {
+                if points.len() == count {
+                    break;
+                }
+                let point = candidate();
+                let cell = cell_of(point.position);
+                let crowded = (-1..=1).any(|z| {
+                    (-1..=1).any(|y| {
+                        (-1..=1).any(|x| {
+                            cells
+                                .get(&(cell + IVec3::new(x, y, z)))
+                                .is_some_and(|others| {
+                                    others
+                                        .iter()
+                                        .any(|other| other.distance(point.position) < min_distance)
+                                })
+                        })
+                    })
+                });
+                if !crowded {
+                    cells.entry(cell).or_default().push(point.position);
+                    points.push(point);
+                }
+            }
+            points
+        }
+        _ => (0..count).map(|_| candidate()).collect(),
+    }
+}
+
+/// SplitMix64, uniform floats in [0, 1).
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
@@ -0,0 +1,256 @@
+//! Signed distance primitives, and shapes blended together from them, for
+//! authoring base shapes without writing density functions.
+
+use glam::{Affine3A, BVec3, UVec3, Vec2, Vec3, Vec3Swizzles, vec2, vec3};
+
+use crate::{
+    csg::{CsgOperation, csg_scale},
+    grid::density_count,
+};
+
+/// Signed distance shape centered on the origin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SdfPrimitive {
+    Sphere {
+        radius: f32,
+    },
+    Cuboid {
+        half_extents: Vec3,
+    },
+    /// `Cuboid` with its edges rounded off by `radius`, within the same box
+    RoundedCuboid {
+        half_extents: Vec3,
+        radius: f32,
+    },
+    /// Rounded segment along y
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+    /// Along y
+    Cylinder {
+        half_height: f32,
+        radius: f32,
+    },
+    /// Ring around y, in the xz plane
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    /// Along y, its base of `radius` at `-half_height` and its tip at
+    /// `half_height`
+    Cone {
+        half_height: f32,
+        radius: f32,
+    },
+    /// Solid below the xz plane, oriented by the transform it is placed with
+    Plane,
+}
+
+impl SdfPrimitive {
+    /// Signed distance from `p` to the surface, negative inside.
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match *self {
+            Self::Sphere { radius } => p.length() - radius,
+            Self::Cuboid { half_extents } => box_distance(p, half_extents),
+            Self::RoundedCuboid {
+                half_extents,
+                radius,
+            } => {
+                let radius = radius.clamp(0.0, half_extents.min_element());
+                box_distance(p, half_extents - radius) - radius
+            }
+            Self::Capsule {
+                half_height,
+                radius,
+            } => vec3(p.x, p.y - p.y.clamp(-half_height, half_height), p.z).length() - radius,
+            Self::Cylinder {
+                half_height,
+                radius,
+            } => {
+                let q = vec2(p.xz().length() - radius, p.y.abs() - half_height);
+                q.max(Vec2::ZERO).length() + q.max_element().min(0.0)
+            }
+            Self::Torus {
+                major_radius,
+                minor_radius,
+            } => vec2(p.xz().length() - major_radius, p.y).length() - minor_radius,
+            Self::Cone {
+                half_height,
+                radius,
+            } => cone_distance(p, half_height, radius),
+            Self::Plane => p.y,
+        }
+    }
+
+    /// Half extents of the box around it, infinite for planes.
+    pub fn half_extents(&self) -> Vec3 {
+        match *self {
+            Self::Sphere { radius } => Vec3::splat(radius),
+            Self::Cuboid { half_extents } | Self::RoundedCuboid { half_extents, .. } => {
+                half_extents
+            }
+            Self::Capsule {
+                half_height,
+                radius,
+            } => vec3(radius, half_height + radius, radius),
+            Self::Cylinder {
+                half_height,
+                radius,
+            }
+            | Self::Cone {
+                half_height,
+                radius,
+            } => vec3(radius, half_height, radius),
+            Self::Torus {
+                major_radius,
+                minor_radius,
+            } => vec3(
+                major_radius + minor_radius,
+                minor_radius,
+                major_radius + minor_radius,
+            ),
+            Self::Plane => Vec3::INFINITY,
+        }
+    }
+}
+
+fn box_distance(p: Vec3, half_extents: Vec3) -> f32 {
+    let q = p.abs() - half_extents;
+    q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+}
+
+// Exact distance to a capped cone with no top radius
+fn cone_distance(p: Vec3, half_height: f32, radius: f32) -> f32 {
+    let q = vec2(p.xz().length(), p.y);
+    let k1 = vec2(0.0, half_height);
+    let k2 = vec2(-radius, 2.0 * half_height);
+    let cap = vec2(
+        q.x - q.x.min(if q.y < 0.0 { radius } else { 0.0 }),
+        q.y.abs() - half_height,
+    );
+    let side = q - k1 + k2 * ((k1 - q).dot(k2) / k2.length_squared()).clamp(0.0, 1.0);
+    let sign = if side.x < 0.0 && cap.y < 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+    sign * cap.length_squared().min(side.length_squared()).sqrt()
+}
+
+/// A primitive of an `SdfShape`, placed by `transform` and combined with the
+/// parts before it by `operation`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SdfPart {
+    pub primitive: SdfPrimitive,
+    pub transform: Affine3A,
+    pub operation: CsgOperation,
+}
+
+/// Primitives combined in order, starting from empty space: a union of two
+/// spheres smoothly blended, then a box cut out of them, and so on.
+///
+/// Parts should be scaled uniformly for their densities to stay distances.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SdfShape {
+    pub parts: Vec<SdfPart>,
+}
+
+impl SdfShape {
+    /// A shape of a single primitive.
+    pub fn new(primitive: SdfPrimitive, transform: Affine3A) -> Self {
+        Self::default().with(CsgOperation::Union, primitive, transform)
+    }
+
+    /// Combine a primitive into the shape.
+    pub fn with(
+        mut self,
+        operation: CsgOperation,
+        primitive: SdfPrimitive,
+        transform: Affine3A,
+    ) -> Self {
+        self.parts.push(SdfPart {
+            primitive,
+            transform,
+            operation,
+        });
+        self
+    }
+
+    /// Signed distance from `p` to the surface, negative inside, and
+    /// `f32::MAX` for a shape without parts.
+    pub fn distance(&self, p: Vec3) -> f32 {
+        combine_parts(&self.placed_parts(), p)
+    }
+
+    // Parts with the inverse and scale of their transform
+    fn placed_parts(&self) -> Vec<(&SdfPart, Affine3A, f32)> {
+        self.parts
+            .iter()
+            .map(|part| (part, part.transform.inverse(), csg_scale(part.transform)))
+            .collect()
+    }
+
+    /// Corners of the box around the solid, which only unions grow, or `None`
+    /// for a shape without any.
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        self.parts
+            .iter()
+            .filter(|part| {
+                matches!(
+                    part.operation,
+                    CsgOperation::Union | CsgOperation::SmoothUnion { .. }
+                )
+            })
+            .map(|part| {
+                let half_extents = part.primitive.half_extents();
+                let (min, max) = transform_bounds(-half_extents, half_extents, part.transform);
+                let margin = part.operation.radius();
+                (min - margin, max + margin)
+            })
+            .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.min(b_min), a_max.max(b_max)))
+    }
+}
+
+fn combine_parts(parts: &[(&SdfPart, Affine3A, f32)], p: Vec3) -> f32 {
+    parts
+        .iter()
+        .fold(f32::MAX, |density, (part, inverse, scale)| {
+            let distance = part.primitive.distance(inverse.transform_point3(p)) * scale;
+            part.operation.apply(density, distance)
+        })
+}
+
+/// Box around the box from `min` to `max` placed by `transform`, infinite
+/// where it is.
+pub fn transform_bounds(min: Vec3, max: Vec3, transform: Affine3A) -> (Vec3, Vec3) {
+    if !(min.is_finite() && max.is_finite()) {
+        return (Vec3::NEG_INFINITY, Vec3::INFINITY);
+    }
+    (0..8)
+        .map(|corner| {
+            let pick = |axis: u32| corner >> axis & 1 == 1;
+            let corner = Vec3::select(BVec3::new(pick(0), pick(1), pick(2)), max, min);
+            transform.transform_point3(corner)
+        })
+        .fold((Vec3::MAX, Vec3::MIN), |(min, max), p| {
+            (min.min(p), max.max(p))
+        })
+}
+
+/// Distances from the samples of a `dims` grid to `shape`, x fastest, with
+/// sample `(x, y, z)` at `origin + (x, y, z) * spacing`.
+pub fn evaluate_sdf(shape: &SdfShape, dims: UVec3, origin: Vec3, spacing: Vec3) -> Vec<f32> {
+    let parts = shape.placed_parts();
+    let mut densities = Vec::with_capacity(density_count(dims) as usize);
+    for z in 0..dims.z {
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let p = origin + UVec3::new(x, y, z).as_vec3() * spacing;
+                densities.push(combine_parts(&parts, p));
+            }
+        }
+    }
+    densities
+}
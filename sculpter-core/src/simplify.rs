@@ -0,0 +1,352 @@
+//! Quadric error mesh decimation, merging the many near-coplanar faces of flat
+//! stretches.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use glam::{DMat3, DVec3};
+
+use crate::{cleanup::compact_vertices, mesh::MeshData};
+
+/// How far a mesh is decimated: edges are collapsed, cheapest first, until the
+/// triangle count reaches `target_ratio` of the original or the next collapse
+/// would move the surface more than `max_error`, whichever comes first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Simplification {
+    /// Share of the triangles to keep, 0 to 1
+    pub target_ratio: f32,
+    /// Largest error a collapse may add: the summed squared distances, in
+    /// voxels, from the moved vertex to the planes of the faces it merges
+    pub max_error: f32,
+}
+
+impl Default for Simplification {
+    fn default() -> Self {
+        Self {
+            target_ratio: 0.25,
+            max_error: 0.01,
+        }
+    }
+}
+
+/// Sum of squared distances to a set of planes, as `p·Ap + 2b·p + c`.
+#[derive(Clone, Copy)]
+struct Quadric {
+    a: DMat3,
+    b: DVec3,
+    c: f64,
+}
+
+impl Quadric {
+    // Not `Default`, which would start `a` at the identity
+    const ZERO: Self = Self {
+        a: DMat3::ZERO,
+        b: DVec3::ZERO,
+        c: 0.0,
+    };
+
+    /// The plane through `point` facing the unit `normal`.
+    fn plane(point: DVec3, normal: DVec3) -> Self {
+        let d = -normal.dot(point);
+        Self {
+            a: DMat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z),
+            b: normal * d,
+            c: d * d,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.a += other.a;
+        self.b += other.b;
+        self.c += other.c;
+    }
+
+    fn error(&self, p: DVec3) -> f64 {
+        (p.dot(self.a * p) + 2.0 * self.b.dot(p) + self.c).max(0.0)
+    }
+}
+
+/// Moving `from` onto `to`, valid while both vertices are at `versions`.
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // Reversed, so the max-heap pops the cheapest collapse
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Decimate `mesh` by quadric error edge collapses. Each collapse moves one
+/// vertex onto a neighbour, so the survivors keep their positions, normals and
+/// attributes exactly. Vertices on open edges, such as the volume's borders, never
+/// move, so neighbouring volumes still meet without cracks. Collapses that would
+/// flip a triangle or pinch the surface are skipped.
+pub fn simplify(mesh: &mut MeshData, options: Simplification) {
+    let positions: Vec<DVec3> = mesh.positions.iter().map(|p| p.as_dvec3()).collect();
+    let mut triangles: Vec<Option<[u32; 3]>> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| Some([t[0], t[1], t[2]]))
+        .collect();
+    let target = (triangles.len() as f32 * options.target_ratio.clamp(0.0, 1.0)) as usize;
+
+    // Triangles around each vertex, and how many triangles use each edge
+    let mut around = vec![Vec::new(); positions.len()];
+    let mut edge_uses: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut quadrics = vec![Quadric::ZERO; positions.len()];
+    for (index, triangle) in triangles.iter().enumerate() {
+        let [a, b, c] = triangle.expect("all triangles are alive before collapsing");
+        let [pa, pb, pc] = [a, b, c].map(|i| positions[i as usize]);
+        let plane = Quadric::plane(pa, (pb - pa).cross(pc - pa).normalize_or_zero());
+        for (i, &vertex) in [a, b, c].iter().enumerate() {
+            around[vertex as usize].push(index);
+            quadrics[vertex as usize].add(&plane);
+            let next = [a, b, c][(i + 1) % 3];
+            *edge_uses
+                .entry((vertex.min(next), vertex.max(next)))
+                .or_default() += 1;
+        }
+    }
+    let mut locked = vec![false; positions.len()];
+    for (&(a, b), &uses) in &edge_uses {
+        if uses != 2 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+
+    let mut versions = vec![0_u32; positions.len()];
+    let mut heap = BinaryHeap::new();
+    let push = |heap: &mut BinaryHeap<Collapse>,
+                quadrics: &[Quadric],
+                versions: &[u32],
+                from: u32,
+                to: u32| {
+        if locked[from as usize] {
+            return;
+        }
+        let mut quadric = quadrics[from as usize];
+        quadric.add(&quadrics[to as usize]);
+        heap.push(Collapse {
+            cost: quadric.error(positions[to as usize]),
+            from,
+            to,
+            versions: (versions[from as usize], versions[to as usize]),
+        });
+    };
+    for &(a, b) in edge_uses.keys() {
+        push(&mut heap, &quadrics, &versions, a, b);
+        push(&mut heap, &quadrics, &versions, b, a);
+    }
+
+    let mut alive = triangles.len();
+    while alive > target {
+        let Some(collapse) = heap.pop() else {
+            break;
+        };
+        if collapse.cost > options.max_error as f64 {
+            break;
+        }
+        let (from, to) = (collapse.from as usize, collapse.to as usize);
+        if collapse.versions != (versions[from], versions[to])
+            || !can_collapse(&positions, &triangles, &around, collapse.from, collapse.to)
+        {
+            continue;
+        }
+
+        // Triangles on the edge vanish, the rest of `from`'s move onto `to`
+        for index in std::mem::take(&mut around[from]) {
+            let Some(triangle) = triangles[index] else {
+                continue;
+            };
+            if triangle.contains(&collapse.to) {
+                triangles[index] = None;
+                alive -= 1;
+                for vertex in triangle {
+                    around[vertex as usize].retain(|&other| other != index);
+                }
+            } else {
+                triangles[index] =
+                    Some(triangle.map(|v| if v == collapse.from { collapse.to } else { v }));
+                around[to].push(index);
+            }
+        }
+        let from_quadric = quadrics[from];
+        quadrics[to].add(&from_quadric);
+        versions[to] += 1;
+
+        // Every edge at `to` now costs something else
+        let mut neighbours: Vec<u32> = around[to]
+            .iter()
+            .filter_map(|&index| triangles[index])
+            .flatten()
+            .filter(|&vertex| vertex != collapse.to)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        for neighbour in neighbours {
+            push(&mut heap, &quadrics, &versions, neighbour, collapse.to);
+            push(&mut heap, &quadrics, &versions, collapse.to, neighbour);
+        }
+    }
+
+    let indices = triangles.into_iter().flatten().flatten().collect();
+    compact_vertices(mesh, indices);
+}
+
+/// Whether `from` can move onto `to` without flipping or flattening one of the
+/// triangles that stay, or joining the surface to itself: the two may only share
+/// the neighbours of the triangles on their edge.
+fn can_collapse(
+    positions: &[DVec3],
+    triangles: &[Option<[u32; 3]>],
+    around: &[Vec<usize>],
+    from: u32,
+    to: u32,
+) -> bool {
+    let neighbours = |vertex: u32| {
+        let mut neighbours: Vec<u32> = around[vertex as usize]
+            .iter()
+            .filter_map(|&index| triangles[index])
+            .flatten()
+            .filter(|&other| other != vertex)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    };
+    let to_neighbours = neighbours(to);
+    let shared = neighbours(from)
+        .into_iter()
+        .filter(|vertex| to_neighbours.binary_search(vertex).is_ok())
+        .count();
+    let on_edge = around[from as usize]
+        .iter()
+        .filter_map(|&index| triangles[index])
+        .filter(|triangle| triangle.contains(&to))
+        .count();
+    if on_edge == 0 || shared != on_edge {
+        return false;
+    }
+
+    let target = positions[to as usize];
+    around[from as usize]
+        .iter()
+        .filter_map(|&index| triangles[index])
+        .filter(|triangle| !triangle.contains(&to))
+        .all(|triangle| {
+            let [a, b, c] = triangle.map(|v| positions[v as usize]);
+            let [na, nb, nc] = triangle.map(|v| {
+                if v == from {
+                    target
+                } else {
+                    positions[v as usize]
+                }
+            });
+            let before = (b - a).cross(c - a);
+            let after = (nb - na).cross(nc - na);
+            after.dot(before) > 0.0 && after.length_squared() > before.length_squared() * 1e-6
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{UVec3, Vec3, uvec3};
+
+    use super::*;
+    use crate::{
+        grid::density_count,
+        surface_nets::{
+            VertexPlacement, VertexRelaxation, surface_nets,
+            tests::{boundary_edges, oriented, sphere},
+        },
+    };
+
+    fn mesh(densities: &[f32], dims: UVec3) -> MeshData {
+        let faces = surface_nets(
+            densities,
+            dims,
+            VertexPlacement::default(),
+            VertexRelaxation::default(),
+        );
+        oriented(&faces, densities, dims)
+    }
+
+    fn triangles(mesh: &MeshData) -> usize {
+        mesh.indices.len() / 3
+    }
+
+    #[test]
+    fn sphere_stays_closed() {
+        let dims = UVec3::splat(16);
+        let mut mesh = mesh(&sphere(dims, 5.0), dims);
+        let before = triangles(&mesh);
+        simplify(
+            &mut mesh,
+            Simplification {
+                target_ratio: 0.25,
+                max_error: 1.0,
+            },
+        );
+        assert!(triangles(&mesh) < before);
+        assert!(triangles(&mesh) >= before / 4);
+        assert_eq!(boundary_edges(&mesh), 0);
+        assert_eq!(mesh.normals.len(), mesh.positions.len());
+    }
+
+    #[test]
+    fn keeping_every_triangle_changes_nothing() {
+        let dims = UVec3::splat(12);
+        let original = mesh(&sphere(dims, 4.0), dims);
+        let mut mesh = original.clone();
+        simplify(
+            &mut mesh,
+            Simplification {
+                target_ratio: 1.0,
+                max_error: 1.0,
+            },
+        );
+        assert_eq!(mesh, original);
+    }
+
+    #[test]
+    fn flat_ground_keeps_its_border() {
+        // Ground below y = 3.5, meeting the sides of the grid
+        let dims = UVec3::splat(12);
+        let densities: Vec<f32> = (0..density_count(dims))
+            .map(|i| uvec3(i % 12, i / 12 % 12, i / 144).y as f32 - 3.5)
+            .collect();
+        let mut mesh = mesh(&densities, dims);
+        let before = triangles(&mesh);
+        let on_border = |p: &Vec3| p.x < 1.0 || p.z < 1.0 || p.x > 10.0 || p.z > 10.0;
+        let border: Vec<Vec3> = mesh.positions.iter().copied().filter(on_border).collect();
+
+        simplify(&mut mesh, Simplification::default());
+        assert!(triangles(&mesh) < before);
+        for position in border {
+            assert!(mesh.positions.contains(&position));
+        }
+        assert!(mesh.positions.iter().all(|p| p.y == 3.5));
+    }
+}
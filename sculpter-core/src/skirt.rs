@@ -0,0 +1,71 @@
+//! Skirts hiding the cracks between neighbouring volumes meshed at different
+//! resolutions.
+
+use std::collections::HashMap;
+
+use glam::{UVec3, Vec3};
+
+use crate::mesh::MeshData;
+
+/// Whether a grid-space vertex lies in the outermost layer of cells.
+fn on_border(position: Vec3, dims: UVec3) -> bool {
+    let max = dims.saturating_sub(UVec3::splat(2)).as_vec3();
+    position.cmple(Vec3::ONE).any() || position.cmpge(max).any()
+}
+
+/// Hang a wall of `depth` grid units below every open edge along the border of
+/// the volume, pushed against the vertex normals into the solid side.
+///
+/// Cheaper than transition cells and works with any mesher whose triangles share
+/// vertices, but only hides cracks: the neighbouring surfaces still don't join.
+/// Skirt vertices copy the normals and attributes of the edge they hang from.
+pub fn add_skirts(mesh: &mut MeshData, dims: UVec3, depth: f32) {
+    if depth <= 0.0 {
+        return;
+    }
+
+    // Uses of each edge, and the direction its first triangle runs along it
+    let mut edge_uses: HashMap<(u32, u32), (u32, (u32, u32))> = HashMap::new();
+    for triangle in mesh.indices.chunks_exact(3) {
+        for (a, b) in [
+            (triangle[0], triangle[1]),
+            (triangle[1], triangle[2]),
+            (triangle[2], triangle[0]),
+        ] {
+            edge_uses
+                .entry((a.min(b), a.max(b)))
+                .or_insert((0, (a, b)))
+                .0 += 1;
+        }
+    }
+
+    let mut open: Vec<(u32, u32)> = edge_uses
+        .into_values()
+        .filter(|&(uses, _)| uses == 1)
+        .map(|(_, edge)| edge)
+        .filter(|&(a, b)| {
+            on_border(mesh.positions[a as usize], dims)
+                && on_border(mesh.positions[b as usize], dims)
+        })
+        .collect();
+    open.sort_unstable();
+
+    let mut lowered: HashMap<u32, u32> = HashMap::new();
+    let mut lower = |mesh: &mut MeshData, vertex: u32| {
+        *lowered.entry(vertex).or_insert_with(|| {
+            let i = vertex as usize;
+            let normal = mesh.normals[i];
+            mesh.positions.push(mesh.positions[i] - normal * depth);
+            mesh.normals.push(normal);
+            for values in mesh.attributes.values_mut() {
+                values.duplicate(i);
+            }
+            mesh.positions.len() as u32 - 1
+        })
+    };
+    for (a, b) in open {
+        let (lower_a, lower_b) = (lower(mesh, a), lower(mesh, b));
+        mesh.indices
+            .extend_from_slice(&[b, a, lower_a, b, lower_a, lower_b]);
+    }
+}
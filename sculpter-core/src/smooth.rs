@@ -0,0 +1,90 @@
+//! Laplacian and Taubin smoothing, ironing out the voxel-grid ripples of raw
+//! surface nets on organic shapes.
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use crate::mesh::MeshData;
+
+/// How a mesh is smoothed. Each iteration moves every vertex `lambda` of the way
+/// to the average of its neighbours, then `mu` of the way, which being negative
+/// pushes it back out so the mesh doesn't shrink (Taubin smoothing).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Smoothing {
+    pub iterations: u32,
+    /// Share of the way to the neighbours' average moved per step, 0 to 1
+    pub lambda: f32,
+    /// Shrink compensation, slightly larger than `-lambda`. 0 gives plain
+    /// Laplacian smoothing, which shrinks the mesh.
+    pub mu: f32,
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Self {
+            iterations: 4,
+            lambda: 0.5,
+            mu: -0.53,
+        }
+    }
+}
+
+/// Smooth the positions of `mesh` in place. Vertices on open edges, such as the
+/// volume's borders, stay pinned so neighbouring volumes still line up. Normals
+/// are left alone.
+pub fn smooth(mesh: &mut MeshData, smoothing: Smoothing) {
+    let neighbours = vertex_neighbours(mesh);
+    for _ in 0..smoothing.iterations {
+        step(&mut mesh.positions, &neighbours, smoothing.lambda);
+        if smoothing.mu != 0.0 {
+            step(&mut mesh.positions, &neighbours, smoothing.mu);
+        }
+    }
+}
+
+/// Move every free vertex `factor` of the way to its neighbours' average.
+fn step(positions: &mut [Vec3], neighbours: &[Option<Vec<u32>>], factor: f32) {
+    let moved: Vec<Vec3> = positions
+        .iter()
+        .zip(neighbours)
+        .map(|(&position, neighbours)| match neighbours {
+            Some(neighbours) if !neighbours.is_empty() => {
+                let average = neighbours
+                    .iter()
+                    .map(|&i| positions[i as usize])
+                    .sum::<Vec3>()
+                    / neighbours.len() as f32;
+                position + (average - position) * factor
+            }
+            _ => position,
+        })
+        .collect();
+    positions.copy_from_slice(&moved);
+}
+
+/// The vertices sharing an edge with each vertex, or `None` for vertices on an
+/// open edge, which stay pinned.
+fn vertex_neighbours(mesh: &MeshData) -> Vec<Option<Vec<u32>>> {
+    // Edges not shared by exactly two triangles are open
+    let mut edges = HashMap::new();
+    for triangle in mesh.indices.chunks_exact(3) {
+        for i in 0..3 {
+            let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+            *edges.entry((a.min(b), a.max(b))).or_insert(0_u32) += 1;
+        }
+    }
+
+    let mut neighbours = vec![Some(Vec::new()); mesh.positions.len()];
+    for (&(a, b), &uses) in &edges {
+        for (vertex, other) in [(a, b), (b, a)] {
+            let slot = &mut neighbours[vertex as usize];
+            if uses != 2 {
+                *slot = None;
+            } else if let Some(list) = slot {
+                list.push(other);
+            }
+        }
+    }
+    neighbours
+}
@@ -0,0 +1,490 @@
+//! Sparse volumes: densities stored only in the 8³ leaves that hold samples
+//! other than a background value, like the leaf level of an OpenVDB tree, so
+//! fields that are mostly empty space take little memory and disk space.
+//!
+//! The file format, `.svol`, is little endian:
+//!
+//! - magic `SVOL`, format version (`u32`, 1), background (`f32`) and number of
+//!   leaves (`u32`)
+//! - per leaf: its coordinate (`i32` × 3, the first sample divided by 8), the
+//!   active mask (`u64` × 8, bit `i` of word `w` for voxel `64 * w + i`, x
+//!   fastest then y then z) and a value (`f32`) per active voxel, in order
+//!
+//! Voxels that aren't active read as the background.
+//!
+//! [`SparseVolume::read_nanovdb`] also reads a subset of NanoVDB (`.nvdb`)
+//! files, as written by `nanovdb::io::writeGrid`: the first grid of an
+//! uncompressed file, which must be a float grid of format version 32. Its
+//! leaves and background are read, while tiles of the internal nodes and the
+//! grid's transform are ignored, so constant regions stored as tiles read as
+//! the background and samples stay in index space.
+
+use std::{collections::HashMap, fmt, io};
+
+use glam::{IVec3, UVec3};
+
+use crate::coords::CoordinateSystem;
+
+/// Voxels along each axis of a leaf.
+pub const LEAF_SIZE: i32 = 8;
+const LEAF_VOXELS: usize = (LEAF_SIZE * LEAF_SIZE * LEAF_SIZE) as usize;
+const MAGIC: &[u8; 4] = b"SVOL";
+const VERSION: u32 = 1;
+
+/// Start of the magic numbers of NanoVDB files and grids, followed by a digit.
+const NANOVDB_MAGIC: &[u8; 7] = b"NanoVDB";
+/// Major NanoVDB format version whose layout is read below.
+const NANOVDB_MAJOR_VERSION: u32 = 32;
+const NANOVDB_GRID_TYPE_FLOAT: u32 = 1;
+const NANOVDB_FILE_HEADER_SIZE: usize = 16;
+const NANOVDB_FILE_METADATA_SIZE: usize = 176;
+const NANOVDB_GRID_DATA_SIZE: usize = 672;
+/// A float leaf: origin, bounding box, flags, value mask, four statistics and
+/// then the 512 values, 32-byte aligned.
+const NANOVDB_LEAF_SIZE: usize = 96 + LEAF_VOXELS * 4;
+
+/// A sparse, unbounded grid of densities.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseVolume {
+    /// Density of every voxel that isn't active
+    pub background: f32,
+    leaves: HashMap<IVec3, Leaf>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Leaf {
+    mask: [u64; 8],
+    values: Box<[f32; LEAF_VOXELS]>,
+}
+
+impl Leaf {
+    fn is_active(&self, index: usize) -> bool {
+        self.mask[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
+/// Why a sparse volume couldn't be read.
+#[derive(Debug)]
+pub enum SparseVolumeError {
+    Io(io::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for SparseVolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read sparse volume: {error}"),
+            Self::Invalid(reason) => write!(f, "invalid sparse volume: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SparseVolumeError {}
+
+impl From<io::Error> for SparseVolumeError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Leaf holding `sample`, and the voxel's index within it.
+fn locate(sample: IVec3) -> (IVec3, usize) {
+    let leaf = sample.div_euclid(IVec3::splat(LEAF_SIZE));
+    let local = sample.rem_euclid(IVec3::splat(LEAF_SIZE));
+    let index = (local.z * LEAF_SIZE + local.y) * LEAF_SIZE + local.x;
+    (leaf, index as usize)
+}
+
+impl SparseVolume {
+    pub fn new(background: f32) -> Self {
+        Self {
+            background,
+            leaves: HashMap::new(),
+        }
+    }
+
+    /// The densities of a `dims` grid, x fastest, placed with its first sample
+    /// at `origin`. Samples equal to `background` stay inactive.
+    pub fn from_dense(densities: &[f32], dims: UVec3, origin: IVec3, background: f32) -> Self {
+        let mut volume = Self::new(background);
+        volume.insert_dense(densities, dims, origin);
+        volume
+    }
+
+    /// Density at `sample`.
+    pub fn get(&self, sample: IVec3) -> f32 {
+        let (leaf, index) = locate(sample);
+        match self.leaves.get(&leaf) {
+            Some(leaf) if leaf.is_active(index) => leaf.values[index],
+            _ => self.background,
+        }
+    }
+
+    /// Activate `sample` with the given density.
+    pub fn set(&mut self, sample: IVec3, value: f32) {
+        let (leaf, index) = locate(sample);
+        let background = self.background;
+        let leaf = self.leaves.entry(leaf).or_insert_with(|| Leaf {
+            mask: [0; 8],
+            values: Box::new([background; LEAF_VOXELS]),
+        });
+        leaf.mask[index / 64] |= 1 << (index % 64);
+        leaf.values[index] = value;
+    }
+
+    /// Write the densities of a `dims` grid with its first sample at `origin`,
+    /// activating the samples that differ from the background. Samples equal to
+    /// it keep their previous value.
+    pub fn insert_dense(&mut self, densities: &[f32], dims: UVec3, origin: IVec3) {
+        let mut index = 0;
+        for z in 0..dims.z {
+            for y in 0..dims.y {
+                for x in 0..dims.x {
+                    let value = densities[index];
+                    index += 1;
+                    if value != self.background {
+                        self.set(origin + UVec3::new(x, y, z).as_ivec3(), value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Densities of a `dims` grid with its first sample at `origin`, x fastest.
+    pub fn to_dense(&self, origin: IVec3, dims: UVec3) -> Vec<f32> {
+        let mut densities = Vec::with_capacity(dims.element_product() as usize);
+        for z in 0..dims.z {
+            for y in 0..dims.y {
+                for x in 0..dims.x {
+                    densities.push(self.get(origin + UVec3::new(x, y, z).as_ivec3()));
+                }
+            }
+        }
+        densities
+    }
+
+    /// Every active voxel and its density, in no particular order.
+    pub fn active(&self) -> impl Iterator<Item = (IVec3, f32)> + '_ {
+        self.leaves.iter().flat_map(|(coord, leaf)| {
+            (0..LEAF_VOXELS)
+                .filter(|&index| leaf.is_active(index))
+                .map(move |index| {
+                    let index = index as i32;
+                    let local = IVec3::new(
+                        index % LEAF_SIZE,
+                        index / LEAF_SIZE % LEAF_SIZE,
+                        index / (LEAF_SIZE * LEAF_SIZE),
+                    );
+                    (coord * LEAF_SIZE + local, leaf.values[index as usize])
+                })
+        })
+    }
+
+    /// The volume converted from `coordinate_system` to Bevy's axes.
+    pub fn to_bevy(&self, coordinate_system: CoordinateSystem) -> Self {
+        self.remapped(|sample| coordinate_system.sample_to_bevy(sample))
+    }
+
+    /// The volume converted from Bevy's axes to `coordinate_system`.
+    pub fn from_bevy(&self, coordinate_system: CoordinateSystem) -> Self {
+        self.remapped(|sample| coordinate_system.sample_from_bevy(sample))
+    }
+
+    fn remapped(&self, map: impl Fn(IVec3) -> IVec3) -> Self {
+        let mut volume = Self::new(self.background);
+        for (sample, value) in self.active() {
+            volume.set(map(sample), value);
+        }
+        volume
+    }
+
+    /// Number of active voxels.
+    pub fn active_count(&self) -> usize {
+        self.leaves
+            .values()
+            .flat_map(|leaf| leaf.mask)
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// First and last sample of the leaves holding active voxels, or `None`
+    /// if there are none.
+    pub fn bounds(&self) -> Option<(IVec3, IVec3)> {
+        let mut leaves = self.leaves.keys();
+        let first = *leaves.next()?;
+        let (min, max) = leaves.fold((first, first), |(min, max), &leaf| {
+            (min.min(leaf), max.max(leaf))
+        });
+        Some((min * LEAF_SIZE, (max + 1) * LEAF_SIZE - 1))
+    }
+
+    /// Write the volume as an `.svol` file, leaves sorted so equal volumes give
+    /// equal files.
+    pub fn write(&self, mut writer: impl io::Write) -> io::Result<()> {
+        let mut leaves: Vec<_> = self.leaves.iter().collect();
+        leaves.sort_by_key(|(coord, _)| (coord.z, coord.y, coord.x));
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.background.to_le_bytes())?;
+        writer.write_all(&(leaves.len() as u32).to_le_bytes())?;
+        for (coord, leaf) in leaves {
+            for component in coord.to_array() {
+                writer.write_all(&component.to_le_bytes())?;
+            }
+            for word in leaf.mask {
+                writer.write_all(&word.to_le_bytes())?;
+            }
+            for (index, value) in leaf.values.iter().enumerate() {
+                if leaf.is_active(index) {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read an `.svol` file.
+    pub fn read(bytes: &[u8]) -> Result<Self, SparseVolumeError> {
+        let mut input = bytes;
+        if take(&mut input, 4)? != MAGIC {
+            return Err(SparseVolumeError::Invalid("not a sparse volume"));
+        }
+        if read_u32(&mut input)? != VERSION {
+            return Err(SparseVolumeError::Invalid("unsupported version"));
+        }
+        let mut volume = Self::new(f32::from_bits(read_u32(&mut input)?));
+        let leaf_count = read_u32(&mut input)?;
+        for _ in 0..leaf_count {
+            let mut component = || read_u32(&mut input).map(|value| value as i32);
+            let coord = IVec3::new(component()?, component()?, component()?);
+            let mut leaf = Leaf {
+                mask: [0; 8],
+                values: Box::new([volume.background; LEAF_VOXELS]),
+            };
+            for word in &mut leaf.mask {
+                *word = u64::from_le_bytes(take(&mut input, 8)?.try_into().unwrap());
+            }
+            for index in 0..LEAF_VOXELS {
+                if leaf.is_active(index) {
+                    leaf.values[index] = f32::from_bits(read_u32(&mut input)?);
+                }
+            }
+            if volume.leaves.insert(coord, leaf).is_some() {
+                return Err(SparseVolumeError::Invalid("duplicate leaf"));
+            }
+        }
+        Ok(volume)
+    }
+
+    /// Read the first grid of a NanoVDB file, within the subset described in the
+    /// module documentation.
+    pub fn read_nanovdb(bytes: &[u8]) -> Result<Self, SparseVolumeError> {
+        // File header: magic, version, grid count and codec
+        if bytes.get(..NANOVDB_MAGIC.len()) != Some(NANOVDB_MAGIC) {
+            return Err(SparseVolumeError::Invalid("not a NanoVDB file"));
+        }
+        if u16_at(bytes, 12)? == 0 {
+            return Err(SparseVolumeError::Invalid("no grids"));
+        }
+        if u16_at(bytes, 14)? != 0 {
+            return Err(SparseVolumeError::Invalid(
+                "compressed grids are not supported",
+            ));
+        }
+
+        // The first grid's file metadata and name come before the grid itself
+        let metadata = NANOVDB_FILE_HEADER_SIZE;
+        let name_size = u32_at(bytes, metadata + 136)? as usize;
+        let grid = metadata + NANOVDB_FILE_METADATA_SIZE + name_size;
+        if bytes.get(grid..grid + NANOVDB_MAGIC.len()) != Some(NANOVDB_MAGIC) {
+            return Err(SparseVolumeError::Invalid("no grid after the file header"));
+        }
+        if u32_at(bytes, grid + 16)? >> 21 != NANOVDB_MAJOR_VERSION {
+            return Err(SparseVolumeError::Invalid("unsupported NanoVDB version"));
+        }
+        if u32_at(bytes, grid + 636)? != NANOVDB_GRID_TYPE_FLOAT {
+            return Err(SparseVolumeError::Invalid("not a float grid"));
+        }
+
+        // Tree: byte offsets of the leaf and root nodes from it, and the leaf count
+        let tree = grid + NANOVDB_GRID_DATA_SIZE;
+        let node_offset = |level: usize| {
+            let offset = i64::from_le_bytes(bytes_at(bytes, tree + level * 8)?);
+            usize::try_from(offset)
+                .ok()
+                .and_then(|offset| tree.checked_add(offset))
+                .ok_or(SparseVolumeError::Invalid("invalid node offset"))
+        };
+        let leaves = node_offset(0)?;
+        let root = node_offset(3)?;
+        let leaf_count = u32_at(bytes, tree + 32)? as usize;
+
+        // The root's bounding box and table size come before its background
+        let mut volume = Self::new(f32::from_bits(u32_at(bytes, root + 28)?));
+        for leaf in 0..leaf_count {
+            let leaf = leaves + leaf * NANOVDB_LEAF_SIZE;
+            let min = IVec3::new(
+                u32_at(bytes, leaf)? as i32,
+                u32_at(bytes, leaf + 4)? as i32,
+                u32_at(bytes, leaf + 8)? as i32,
+            );
+            // The leaf's bounding box minimum, rounded down to its origin
+            let origin = min & IVec3::splat(!(LEAF_SIZE - 1));
+            for word in 0..8 {
+                let mask = u64::from_le_bytes(bytes_at(bytes, leaf + 16 + word * 8)?);
+                for bit in (0..64).filter(|bit| mask & (1 << bit) != 0) {
+                    // NanoVDB leaves are z fastest
+                    let index = word * 64 + bit;
+                    let local =
+                        IVec3::new(index as i32 >> 6, (index as i32 >> 3) & 7, index as i32 & 7);
+                    let value = f32::from_bits(u32_at(bytes, leaf + 96 + index * 4)?);
+                    volume.set(origin + local, value);
+                }
+            }
+        }
+        Ok(volume)
+    }
+}
+
+/// `N` bytes at `offset`.
+fn bytes_at<const N: usize>(bytes: &[u8], offset: usize) -> Result<[u8; N], SparseVolumeError> {
+    offset
+        .checked_add(N)
+        .and_then(|end| bytes.get(offset..end))
+        .map(|bytes| bytes.try_into().unwrap())
+        .ok_or(SparseVolumeError::Invalid("unexpected end of file"))
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, SparseVolumeError> {
+    Ok(u16::from_le_bytes(bytes_at(bytes, offset)?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, SparseVolumeError> {
+    Ok(u32::from_le_bytes(bytes_at(bytes, offset)?))
+}
+
+/// Split `count` bytes off the front of `input`.
+fn take<'a>(input: &mut &'a [u8], count: usize) -> Result<&'a [u8], SparseVolumeError> {
+    if input.len() < count {
+        return Err(SparseVolumeError::Invalid("unexpected end of file"));
+    }
+    let (taken, rest) = input.split_at(count);
+    *input = rest;
+    Ok(taken)
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, SparseVolumeError> {
+    let bytes = take(input, 4)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svol_round_trip() {
+        let dims = UVec3::new(11, 9, 10);
+        let densities: Vec<f32> = (0..dims.element_product())
+            .map(|i| if i % 7 == 0 { -(i as f32) * 0.5 } else { 1.0 })
+            .collect();
+        let volume = SparseVolume::from_dense(&densities, dims, IVec3::new(-5, 3, -12), 1.0);
+
+        let mut file = Vec::new();
+        volume.write(&mut file).unwrap();
+        let read = SparseVolume::read(&file).unwrap();
+
+        assert_eq!(read, volume);
+        assert_eq!(read.to_dense(IVec3::new(-5, 3, -12), dims), densities);
+    }
+
+    #[test]
+    fn svol_rejects_truncated_files() {
+        let mut volume = SparseVolume::new(0.0);
+        volume.set(IVec3::new(3, -9, 20), 2.0);
+        let mut file = Vec::new();
+        volume.write(&mut file).unwrap();
+
+        assert!(SparseVolume::read(&file[..file.len() - 1]).is_err());
+        assert!(SparseVolume::read(b"NOPE").is_err());
+    }
+
+    /// A single-grid NanoVDB file laid out as `read_nanovdb` expects, with
+    /// one leaf holding the given voxels.
+    fn nanovdb_file(background: f32, leaf_min: IVec3, voxels: &[(IVec3, f32)]) -> Vec<u8> {
+        let name = b"density\0";
+        let grid = NANOVDB_FILE_HEADER_SIZE + NANOVDB_FILE_METADATA_SIZE + name.len();
+        let tree = grid + NANOVDB_GRID_DATA_SIZE;
+        let leaf = tree + 64;
+        let root = leaf + NANOVDB_LEAF_SIZE;
+        let mut bytes = vec![0; root + 32];
+        let mut put = |offset: usize, value: &[u8]| {
+            bytes[offset..offset + value.len()].copy_from_slice(value);
+        };
+
+        put(0, b"NanoVDB0");
+        put(12, &1u16.to_le_bytes());
+        put(
+            NANOVDB_FILE_HEADER_SIZE + 136,
+            &(name.len() as u32).to_le_bytes(),
+        );
+        put(grid - name.len(), name);
+        put(grid, b"NanoVDB0");
+        put(grid + 16, &(NANOVDB_MAJOR_VERSION << 21).to_le_bytes());
+        put(grid + 636, &NANOVDB_GRID_TYPE_FLOAT.to_le_bytes());
+        put(tree, &((leaf - tree) as i64).to_le_bytes());
+        put(tree + 24, &((root - tree) as i64).to_le_bytes());
+        put(tree + 32, &1u32.to_le_bytes());
+        put(root + 28, &background.to_bits().to_le_bytes());
+        for (axis, component) in leaf_min.to_array().into_iter().enumerate() {
+            put(leaf + axis * 4, &component.to_le_bytes());
+        }
+        let mut mask = [0u64; 8];
+        for &(local, value) in voxels {
+            // z fastest
+            let index = (local.x * 64 + local.y * 8 + local.z) as usize;
+            mask[index / 64] |= 1 << (index % 64);
+            put(leaf + 96 + index * 4, &value.to_bits().to_le_bytes());
+        }
+        for (word, bits) in mask.into_iter().enumerate() {
+            put(leaf + 16 + word * 8, &bits.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn nanovdb_reads_leaf_voxels() {
+        let voxels = [
+            (IVec3::new(1, 2, 3), 0.25),
+            (IVec3::new(7, 0, 0), -1.5),
+            (IVec3::new(0, 7, 7), 4.0),
+        ];
+        // The bounding box minimum needn't be the leaf's origin
+        let file = nanovdb_file(3.0, IVec3::new(-15, 18, 33), &voxels);
+        let volume = SparseVolume::read_nanovdb(&file).unwrap();
+
+        let origin = IVec3::new(-16, 16, 32);
+        assert_eq!(volume.background, 3.0);
+        assert_eq!(volume.active_count(), voxels.len());
+        for (local, value) in voxels {
+            assert_eq!(volume.get(origin + local), value);
+        }
+        assert_eq!(volume.get(origin + IVec3::new(1, 2, 4)), 3.0);
+    }
+
+    #[test]
+    fn nanovdb_rejects_unsupported_files() {
+        let mut compressed = nanovdb_file(0.0, IVec3::ZERO, &[]);
+        compressed[14] = 1;
+        assert!(SparseVolume::read_nanovdb(&compressed).is_err());
+
+        let mut not_float = nanovdb_file(0.0, IVec3::ZERO, &[]);
+        let grid_type = NANOVDB_FILE_HEADER_SIZE + NANOVDB_FILE_METADATA_SIZE + 8 + 636;
+        not_float[grid_type] = 2;
+        assert!(SparseVolume::read_nanovdb(&not_float).is_err());
+
+        let file = nanovdb_file(0.0, IVec3::ZERO, &[(IVec3::ONE, 1.0)]);
+        assert!(SparseVolume::read_nanovdb(&file[..file.len() - 8]).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Surface nets: one vertex per surface cell, joined by quads.
+
+use glam::{UVec3, Vec3, uvec3, vec3};
+
+use crate::{
+    grid::{density_count, index},
+    mesh::FaceBuffers,
+};
+
+/// Where surface nets places the single vertex of each surface cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum VertexPlacement {
+    /// Average of the iso crossings on the cell edges. Smooth, the classic choice.
+    #[default]
+    CrossingAverage,
+    /// Centre of the cell. Blocky, but stable under small density changes.
+    CellCentroid,
+    /// Crossing average projected onto the iso surface along the density gradient.
+    /// Most faithful to the field, at the cost of extra samples.
+    GradientProjected,
+}
+
+/// Optional pass that pulls each vertex onto the iso-surface after placement by
+/// repeatedly stepping along the density gradient. Vertices never leave their
+/// cell, so the mesh topology is unchanged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VertexRelaxation {
+    /// Number of gradient steps, 0 disables the pass
+    pub iterations: u32,
+    /// Fraction of each full step to take, lower values converge more gently
+    pub strength: f32,
+}
+
+impl Default for VertexRelaxation {
+    fn default() -> Self {
+        Self {
+            iterations: 0,
+            strength: 1.0,
+        }
+    }
+}
+
+// Must match the corner layout in generate_vertices.wgsl
+const CORNERS: [UVec3; 8] = [
+    uvec3(0, 0, 0),
+    uvec3(1, 0, 0),
+    uvec3(1, 1, 0),
+    uvec3(0, 1, 0),
+    uvec3(0, 0, 1),
+    uvec3(1, 0, 1),
+    uvec3(1, 1, 1),
+    uvec3(0, 1, 1),
+];
+
+// Must match the edge order in generate_vertices.wgsl, the summation order affects rounding
+const EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 0),
+    (4, 5),
+    (5, 6),
+    (6, 7),
+    (7, 4),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// Trilinear interpolation of the grid at `p`, in samples and clamped to the
+/// grid, as `sample_trilinear` in generate_vertices.wgsl. Needs at least two
+/// samples along each axis.
+pub fn sample_trilinear(densities: &[f32], dims: UVec3, p: Vec3) -> f32 {
+    let q = p.clamp(Vec3::ZERO, (dims - 1).as_vec3());
+    let base = q.floor().as_uvec3().min(dims - 2);
+    let f = q - base.as_vec3();
+    let at = |offset: UVec3| {
+        let p = base + offset;
+        densities[index(dims, p.x, p.y, p.z) as usize]
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let c00 = lerp(at(uvec3(0, 0, 0)), at(uvec3(1, 0, 0)), f.x);
+    let c10 = lerp(at(uvec3(0, 1, 0)), at(uvec3(1, 1, 0)), f.x);
+    let c01 = lerp(at(uvec3(0, 0, 1)), at(uvec3(1, 0, 1)), f.x);
+    let c11 = lerp(at(uvec3(0, 1, 1)), at(uvec3(1, 1, 1)), f.x);
+    lerp(lerp(c00, c10, f.y), lerp(c01, c11, f.y), f.z)
+}
+
+/// Central differences of the interpolated field at `p`, in density per sample,
+/// as `density_gradient` in generate_vertices.wgsl.
+pub fn density_gradient(densities: &[f32], dims: UVec3, p: Vec3) -> Vec3 {
+    let h = 0.5;
+    let sample = |p: Vec3| sample_trilinear(densities, dims, p);
+    vec3(
+        sample(p + Vec3::X * h) - sample(p - Vec3::X * h),
+        sample(p + Vec3::Y * h) - sample(p - Vec3::Y * h),
+        sample(p + Vec3::Z * h) - sample(p - Vec3::Z * h),
+    ) / (2.0 * h)
+}
+
+/// One Newton step towards the isosurface, as `project_to_surface` in generate_vertices.wgsl.
+fn project_to_surface(densities: &[f32], dims: UVec3, p: Vec3, cell: UVec3, strength: f32) -> Vec3 {
+    let gradient = density_gradient(densities, dims, p);
+    let length_squared = gradient.length_squared();
+    if length_squared < 1e-12 {
+        return p;
+    }
+    let projected = p - strength * sample_trilinear(densities, dims, p) * gradient / length_squared;
+    projected.clamp(cell.as_vec3(), cell.as_vec3() + 1.0)
+}
+
+/// Runs the six GPU stages on the CPU, in the same cell order, so both backends
+/// produce identical vertex and face buffers.
+pub fn surface_nets(
+    densities: &[f32],
+    dims: UVec3,
+    placement: VertexPlacement,
+    relaxation: VertexRelaxation,
+) -> FaceBuffers {
+    dual_mesh(densities, dims, |cell, crossings| {
+        let average = crossings.iter().sum::<Vec3>() / crossings.len() as f32;
+        let mut vertex_pos = match placement {
+            VertexPlacement::CrossingAverage => average,
+            VertexPlacement::CellCentroid => cell.as_vec3() + 0.5,
+            VertexPlacement::GradientProjected => {
+                project_to_surface(densities, dims, average, cell, 1.0)
+            }
+        };
+        for _ in 0..relaxation.iterations {
+            vertex_pos = project_to_surface(densities, dims, vertex_pos, cell, relaxation.strength);
+        }
+        vertex_pos
+    })
+}
+
+/// Stages shared by the surface nets topology: one vertex per cell with an edge
+/// crossing, placed by `place` from the crossings (in edge order), joined by
+/// quads across every crossed edge.
+pub(crate) fn dual_mesh(
+    densities: &[f32],
+    dims: UVec3,
+    mut place: impl FnMut(UVec3, &[Vec3]) -> Vec3,
+) -> FaceBuffers {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    if dims.min_element() < 2 || densities.len() < density_count(dims) as usize {
+        return FaceBuffers { vertices, faces };
+    }
+
+    // Stages 1-3: generate vertices and compact them in cell index order
+    let mut vertex_indices = vec![None; density_count(dims) as usize];
+    for z in 0..dims.z - 1 {
+        for y in 0..dims.y - 1 {
+            for x in 0..dims.x - 1 {
+                let cell = uvec3(x, y, z);
+                let mut crossings = [Vec3::ZERO; 12];
+                let mut crossing_count = 0;
+                for (c0, c1) in EDGES {
+                    let p0 = cell + CORNERS[c0];
+                    let p1 = cell + CORNERS[c1];
+                    let v0 = densities[index(dims, p0.x, p0.y, p0.z) as usize];
+                    let v1 = densities[index(dims, p1.x, p1.y, p1.z) as usize];
+                    if v0 * v1 < 0.0 {
+                        let t = v0 / (v0 - v1);
+                        crossings[crossing_count] =
+                            p0.as_vec3() + t * (p1.as_vec3() - p0.as_vec3());
+                        crossing_count += 1;
+                    }
+                }
+                if crossing_count > 0 {
+                    let vertex_pos = place(cell, &crossings[..crossing_count]);
+                    vertex_indices[index(dims, x, y, z) as usize] =
+                        Some((vertices.len() / 3) as u32);
+                    vertices.extend_from_slice(&[vertex_pos.x, vertex_pos.y, vertex_pos.z]);
+                }
+            }
+        }
+    }
+
+    // Stages 4-6: generate faces and compact them in cell index order
+    let vertex_at = |x: u32, y: u32, z: u32| vertex_indices[index(dims, x, y, z) as usize];
+    for z in 0..dims.z - 1 {
+        for y in 0..dims.y - 1 {
+            for x in 0..dims.x - 1 {
+                let Some(v0) = vertex_at(x, y, z) else {
+                    continue;
+                };
+                // Same three planes, in the same order, as generate_faces.wgsl
+                let mut quads = [None; 3];
+                if x + 1 < dims.x - 1 && y + 1 < dims.y - 1 {
+                    quads[0] = Some([(x + 1, y, z), (x + 1, y + 1, z), (x, y + 1, z)]);
+                }
+                if x + 1 < dims.x - 1 && z + 1 < dims.z - 1 {
+                    quads[1] = Some([(x + 1, y, z), (x + 1, y, z + 1), (x, y, z + 1)]);
+                }
+                if y + 1 < dims.y - 1 && z + 1 < dims.z - 1 {
+                    quads[2] = Some([(x, y + 1, z), (x, y + 1, z + 1), (x, y, z + 1)]);
+                }
+                for [a, b, c] in quads.into_iter().flatten() {
+                    if let (Some(v1), Some(v2), Some(v3)) = (
+                        vertex_at(a.0, a.1, a.2),
+                        vertex_at(b.0, b.1, b.2),
+                        vertex_at(c.0, c.1, c.2),
+                    ) {
+                        faces.extend_from_slice(&[v0, v1, v2, v3]);
+                    }
+                }
+            }
+        }
+    }
+
+    FaceBuffers { vertices, faces }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        mesh::MeshData,
+        orientation::{gradient_normals, orient_to_gradient},
+    };
+
+    /// A `dims` field of a sphere of `radius` samples in the middle of the grid,
+    /// negative inside.
+    pub(crate) fn sphere(dims: UVec3, radius: f32) -> Vec<f32> {
+        let centre = (dims - 1).as_vec3() / 2.0;
+        (0..density_count(dims))
+            .map(|i| {
+                let p = uvec3(i % dims.x, i / dims.x % dims.y, i / (dims.x * dims.y));
+                p.as_vec3().distance(centre) - radius
+            })
+            .collect()
+    }
+
+    /// Mesher output wound out of the solid, as the plugin builds it.
+    pub(crate) fn oriented(faces: &FaceBuffers, densities: &[f32], dims: UVec3) -> MeshData {
+        let mut mesh = faces.to_mesh_data();
+        let outward = gradient_normals(&mesh.positions, densities, dims);
+        orient_to_gradient(&mut mesh, &outward);
+        mesh
+    }
+
+    /// Triangle edges without a neighbour running the other way, none on a
+    /// closed, consistently wound surface.
+    pub(crate) fn boundary_edges(mesh: &MeshData) -> usize {
+        let edges: HashSet<(u32, u32)> = mesh
+            .indices
+            .chunks_exact(3)
+            .flat_map(|t| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+            .collect();
+        edges
+            .iter()
+            .filter(|&&(a, b)| !edges.contains(&(b, a)))
+            .count()
+    }
+
+    fn smooth(densities: &[f32], dims: UVec3) -> FaceBuffers {
+        surface_nets(
+            densities,
+            dims,
+            VertexPlacement::default(),
+            VertexRelaxation::default(),
+        )
+    }
+
+    #[test]
+    fn sphere_is_closed() {
+        let dims = UVec3::splat(16);
+        let densities = sphere(dims, 5.0);
+        let mesh = oriented(&smooth(&densities, dims), &densities, dims);
+        assert!(!mesh.indices.is_empty());
+        assert_eq!(boundary_edges(&mesh), 0);
+        // Vertices lie near the surface
+        let centre = (dims - 1).as_vec3() / 2.0;
+        for position in &mesh.positions {
+            assert!((position.distance(centre) - 5.0).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn uniform_fields_have_no_faces() {
+        let dims = UVec3::splat(8);
+        let count = density_count(dims) as usize;
+        for density in [1.0, -1.0] {
+            assert_eq!(smooth(&vec![density; count], dims), FaceBuffers::default());
+        }
+    }
+
+    #[test]
+    fn placement_keeps_the_topology() {
+        let dims = UVec3::splat(12);
+        let densities = sphere(dims, 4.0);
+        let relaxation = VertexRelaxation {
+            iterations: 4,
+            strength: 1.0,
+        };
+        let faces = smooth(&densities, dims).faces;
+        for placement in [
+            VertexPlacement::CellCentroid,
+            VertexPlacement::GradientProjected,
+        ] {
+            let moved = surface_nets(&densities, dims, placement, relaxation);
+            assert_eq!(moved.faces, faces);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Transition cells closing the cracks between a volume and a neighbour meshed
+//! with marching cubes at half its resolution.
+//!
+//! Where the two volumes meet, each one's surface ends on the shared plane along
+//! the contour of its own samples, and the contours differ. On every face marked
+//! as bordering a coarser neighbour, the finer volume fills the gap between its
+//! contour and the coarse one with polygons lying in the plane. Like transvoxel
+//! transition cells with no thickness, they only need the finer volume's samples,
+//! since the coarse samples are every other one of them.
+
+use glam::{IVec3, UVec3, Vec3};
+
+use crate::{grid::index, mesh::FaceBuffers};
+
+/// Number of faces of a volume. Bit `d` of a transition mask is face `d`, in the
+/// order +X, -X, +Y, -Y, +Z, -Z.
+pub const FACE_COUNT: u32 = 6;
+
+/// A crossing on one of the edges of a coarse face, as a node of the contour graph.
+#[derive(Clone, Copy)]
+struct Node {
+    position: Vec3,
+    crossed: bool,
+}
+
+/// A link between two crossings, with a point on the inside of the fine contour
+/// for links along it.
+#[derive(Clone, Copy)]
+struct Link {
+    nodes: [usize; 2],
+    fine_inside: Option<Vec3>,
+}
+
+/// Crossing on the edge from `a` to `b`, as in marching cubes.
+fn crossing(a: Vec3, va: f32, b: Vec3, vb: f32) -> Node {
+    let crossed = (va < 0.0) != (vb < 0.0);
+    let t = if crossed { va / (va - vb) } else { 0.0 };
+    Node {
+        position: a + t * (b - a),
+        crossed,
+    }
+}
+
+/// Join the crossings on the four edges of a square face (edge k runs from
+/// corner k to corner k + 1) the way marching cubes does: in a pair, or around
+/// each inside corner when the face is ambiguous.
+fn link_square(
+    corners: [(Vec3, f32); 4],
+    edges: [usize; 4],
+    nodes: &[Node],
+    fine: bool,
+    links: &mut Vec<Link>,
+) {
+    let inside = |c: usize| corners[c].1 < 0.0;
+    let crossed: Vec<usize> = (0..4).filter(|&k| nodes[edges[k]].crossed).collect();
+    let fine_inside = |c: usize| fine.then_some(corners[c].0);
+    match crossed.len() {
+        2 => {
+            let corner = (0..4).find(|&c| inside(c)).unwrap_or(0);
+            links.push(Link {
+                nodes: [edges[crossed[0]], edges[crossed[1]]],
+                fine_inside: fine_inside(corner),
+            });
+        }
+        4 => {
+            // Edges k - 1 and k meet at corner k
+            for c in (0..4).filter(|&c| inside(c)) {
+                links.push(Link {
+                    nodes: [edges[(c + 3) % 4], edges[c]],
+                    fine_inside: fine_inside(c),
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Polygons filling the gap between the fine and coarse contours on the faces in
+/// `mask` (bit `d` for face `d`, see [`FACE_COUNT`]), as triangles repeating their
+/// last index. They face away from the volume where it is solid and the coarse
+/// neighbour isn't, and into it elsewhere, so the joined meshes are closed.
+///
+/// The neighbour's samples must be every other sample of this volume's face,
+/// starting at its corner. On a face with an even sample count the last row
+/// has no coarse counterpart and isn't stitched.
+pub fn transition_faces(densities: &[f32], dims: UVec3, mask: u8) -> FaceBuffers {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    if dims.min_element() < 3 || densities.len() < crate::grid::density_count(dims) as usize {
+        return FaceBuffers { vertices, faces };
+    }
+
+    for d in (0..FACE_COUNT).filter(|d| mask >> d & 1 == 1) {
+        let axis = (d / 2) as usize;
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let outward = IVec3::AXES[axis] * if d & 1 == 0 { 1 } else { -1 };
+        let layer = if d & 1 == 0 { dims[axis] - 1 } else { 0 };
+        let point = |i: u32, j: u32| {
+            let mut p = UVec3::ZERO;
+            p[axis] = layer;
+            p[u] = i;
+            p[v] = j;
+            (p.as_vec3(), densities[index(dims, p.x, p.y, p.z) as usize])
+        };
+
+        for j0 in (0..dims[v] - 2).step_by(2) {
+            for i0 in (0..dims[u] - 2).step_by(2) {
+                let s = |i: u32, j: u32| point(i0 + i, j0 + j);
+                stitch_square(&s, outward.as_vec3(), &mut vertices, &mut faces);
+            }
+        }
+    }
+
+    FaceBuffers { vertices, faces }
+}
+
+/// Stitch one coarse face, whose 3x3 fine samples are given by `s`.
+fn stitch_square(
+    s: &impl Fn(u32, u32) -> (Vec3, f32),
+    outward: Vec3,
+    vertices: &mut Vec<f32>,
+    faces: &mut Vec<u32>,
+) {
+    let edge = |a: (u32, u32), b: (u32, u32)| {
+        let ((pa, va), (pb, vb)) = (s(a.0, a.1), s(b.0, b.1));
+        crossing(pa, va, pb, vb)
+    };
+
+    // Fine edges along u (6), then along v (6), then the 4 coarse edges
+    let mut nodes = Vec::with_capacity(16);
+    for j in 0..3 {
+        for i in 0..2 {
+            nodes.push(edge((i, j), (i + 1, j)));
+        }
+    }
+    for j in 0..2 {
+        for i in 0..3 {
+            nodes.push(edge((i, j), (i, j + 1)));
+        }
+    }
+    let along_u = |i: u32, j: u32| (j * 2 + i) as usize;
+    let along_v = |i: u32, j: u32| (6 + j * 3 + i) as usize;
+    let coarse = [
+        edge((0, 0), (2, 0)),
+        edge((2, 0), (2, 2)),
+        edge((0, 2), (2, 2)),
+        edge((0, 0), (0, 2)),
+    ];
+    nodes.extend(coarse);
+    if !nodes.iter().any(|node| node.crossed) {
+        return;
+    }
+
+    let mut links = Vec::new();
+    for sj in 0..2 {
+        for si in 0..2 {
+            let corners = [s(si, sj), s(si + 1, sj), s(si + 1, sj + 1), s(si, sj + 1)];
+            let edges = [
+                along_u(si, sj),
+                along_v(si + 1, sj),
+                along_u(si, sj + 1),
+                along_v(si, sj),
+            ];
+            link_square(corners, edges, &nodes, true, &mut links);
+        }
+    }
+    let corners = [s(0, 0), s(2, 0), s(2, 2), s(0, 2)];
+    link_square(corners, [12, 13, 14, 15], &nodes, false, &mut links);
+
+    // Along each coarse edge, join its crossing to the fine one, or the two fine
+    // crossings of a bump the coarse edge misses
+    let halves = [
+        (along_u(0, 0), along_u(1, 0)),
+        (along_v(2, 0), along_v(2, 1)),
+        (along_u(0, 2), along_u(1, 2)),
+        (along_v(0, 0), along_v(0, 1)),
+    ];
+    for (k, (a, b)) in halves.into_iter().enumerate() {
+        let linked = if nodes[12 + k].crossed {
+            [12 + k, if nodes[a].crossed { a } else { b }]
+        } else if nodes[a].crossed && nodes[b].crossed {
+            [a, b]
+        } else {
+            continue;
+        };
+        links.push(Link {
+            nodes: linked,
+            fine_inside: None,
+        });
+    }
+
+    // Every crossing now has two links, walk them into loops
+    let mut used = vec![false; links.len()];
+    for start in 0..links.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let mut polygon = vec![links[start].nodes[0]];
+        let mut walked = vec![(links[start], links[start].nodes[0])];
+        let mut current = links[start].nodes[1];
+        while current != polygon[0] {
+            let Some(next) =
+                (0..links.len()).find(|&l| !used[l] && links[l].nodes.contains(&current))
+            else {
+                break;
+            };
+            used[next] = true;
+            polygon.push(current);
+            walked.push((links[next], current));
+            let [a, b] = links[next].nodes;
+            current = if a == current { b } else { a };
+        }
+        if polygon.len() < 3 {
+            continue;
+        }
+
+        // Keep the inside of the fine contour on the left, seen from outside
+        let flip = walked.iter().find_map(|&(link, from)| {
+            let inside = link.fine_inside?;
+            let to = if link.nodes[0] == from {
+                link.nodes[1]
+            } else {
+                link.nodes[0]
+            };
+            let (a, b) = (nodes[from].position, nodes[to].position);
+            let side = outward.cross(b - a).dot(inside - a);
+            (side != 0.0).then_some(side < 0.0)
+        });
+        if flip == Some(true) {
+            polygon.reverse();
+        }
+
+        let first = (vertices.len() / 3) as u32;
+        for &node in &polygon {
+            vertices.extend_from_slice(&nodes[node].position.to_array());
+        }
+        for k in 1..polygon.len() as u32 - 1 {
+            faces.extend_from_slice(&[first, first + k, first + k + 1, first + k + 1]);
+        }
+    }
+}
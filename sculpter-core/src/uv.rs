@@ -0,0 +1,90 @@
+//! Texture coordinates for generated meshes.
+
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3};
+
+use crate::mesh::{MeshData, VertexAttributeData};
+
+/// Name of the `Float32x2` vertex attribute holding the first UV channel.
+pub const UV_0_ATTRIBUTE: &str = "Vertex_Uv";
+
+/// Box faces in atlas order, as the axis and sign their normal points along.
+const BOX_FACES: [(usize, f32); 6] = [
+    (0, 1.0),
+    (0, -1.0),
+    (1, 1.0),
+    (1, -1.0),
+    (2, 1.0),
+    (2, -1.0),
+];
+
+/// Project `positions` onto the plane facing `direction`, one UV unit per
+/// `tile_size` along it.
+pub fn planar_uvs(positions: &[Vec3], direction: Vec3, tile_size: f32) -> Vec<Vec2> {
+    let (u, v) = direction.normalize_or(Vec3::Y).any_orthonormal_pair();
+    positions
+        .iter()
+        .map(|position| Vec2::new(position.dot(u), position.dot(v)) / tile_size)
+        .collect()
+}
+
+/// Unwrap a mesh lying in the box from the origin to `size` onto a 3x2 atlas,
+/// one tile per box face in the order +X, -X, +Y on the top row and -Y, +Z, -Z
+/// below. Each triangle goes to the face its normal points at most, vertices
+/// shared by triangles on different faces are duplicated so tiles don't bleed.
+pub fn box_atlas_unwrap(mesh: &mut MeshData, size: Vec3) {
+    let mut faces: Vec<Option<usize>> = vec![None; mesh.positions.len()];
+    let mut copies: HashMap<(u32, usize), u32> = HashMap::new();
+    for triangle in 0..mesh.indices.len() / 3 {
+        let corners = &mesh.indices[triangle * 3..triangle * 3 + 3];
+        let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[corners[i] as usize]);
+        let face = box_face((b - a).cross(c - a));
+
+        for corner in triangle * 3..triangle * 3 + 3 {
+            let vertex = mesh.indices[corner];
+            match faces[vertex as usize] {
+                Some(existing) if existing != face => {
+                    let copy = *copies.entry((vertex, face)).or_insert_with(|| {
+                        faces.push(Some(face));
+                        mesh.duplicate_vertex(vertex)
+                    });
+                    mesh.indices[corner] = copy;
+                }
+                Some(_) => {}
+                None => faces[vertex as usize] = Some(face),
+            }
+        }
+    }
+
+    let uvs = mesh
+        .positions
+        .iter()
+        .zip(&faces)
+        .map(|(&position, face)| atlas_uv(position / size, face.unwrap_or(0)))
+        .collect();
+    mesh.attributes
+        .insert(UV_0_ATTRIBUTE, VertexAttributeData::Float32x2(uvs));
+}
+
+/// Index into `BOX_FACES` of the face `normal` points at most.
+pub(crate) fn box_face(normal: Vec3) -> usize {
+    let axis = normal.abs().max_position();
+    let negative = normal[axis] < 0.0;
+    axis * 2 + negative as usize
+}
+
+/// UV in the atlas of a point in the unit box, projected onto box face `face`.
+fn atlas_uv(p: Vec3, face: usize) -> Vec2 {
+    let (axis, sign) = BOX_FACES[face];
+    // The two other axes, flipped on negative faces so textures aren't mirrored
+    let (s, t) = ((axis + 1) % 3, (axis + 2) % 3);
+    let local = Vec2::new(p[s], p[t]).clamp(Vec2::ZERO, Vec2::ONE);
+    let local = if sign < 0.0 {
+        Vec2::new(1.0 - local.x, local.y)
+    } else {
+        local
+    };
+    let tile = Vec2::new((face % 3) as f32, (face / 3) as f32);
+    (tile + local) / Vec2::new(3.0, 2.0)
+}
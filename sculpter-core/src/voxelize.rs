@@ -0,0 +1,143 @@
+//! Voxelization of closed triangle meshes into signed distance fields.
+
+use std::ops::Range;
+
+use glam::{UVec3, Vec2, Vec3, Vec3Swizzles};
+
+use crate::grid::density_count;
+
+// Rays are nudged off the sample rows so they don't graze the edges and
+// vertices of grid-aligned meshes, which would count a crossing twice
+const RAY_OFFSET: Vec2 = Vec2::new(1.3e-4, 0.7e-4);
+
+/// Signed distance from every sample to the closed mesh `triangles`, in grid
+/// space (sample `(x, y, z)` at that position), negative inside. Distances are
+/// exact within `band` voxels of the surface and clamped to `band` beyond, which
+/// is all a mesher needs and keeps the cost proportional to the surface area.
+pub fn voxelize(triangles: &[[Vec3; 3]], dims: UVec3, band: f32) -> Vec<f32> {
+    voxelize_slab(triangles, dims, 0..dims.z, band)
+}
+
+/// The values of [`voxelize`] for the slices `slab` along z only, so slabs can
+/// be voxelized in parallel and concatenated.
+pub fn voxelize_slab(
+    triangles: &[[Vec3; 3]],
+    dims: UVec3,
+    slab: Range<u32>,
+    band: f32,
+) -> Vec<f32> {
+    let slab = slab.start.min(dims.z)..slab.end.min(dims.z);
+    let slab_dims = UVec3::new(dims.x, dims.y, slab.len() as u32);
+    let index =
+        |x: u32, y: u32, z: u32| ((z - slab.start) * dims.y * dims.x + y * dims.x + x) as usize;
+    let mut distances = vec![band; density_count(slab_dims) as usize];
+    // Surface crossings of the ray along +x through each row
+    let mut crossings = vec![Vec::new(); (slab_dims.y * slab_dims.z) as usize];
+    let last = dims.as_vec3() - 1.0;
+    let slab_min = Vec3::new(0.0, 0.0, slab.start as f32);
+    let slab_max = Vec3::new(last.x, last.y, slab.end as f32 - 1.0);
+
+    for triangle in triangles {
+        let min = triangle[0].min(triangle[1]).min(triangle[2]);
+        let max = triangle[0].max(triangle[1]).max(triangle[2]);
+
+        // Exact distances to the samples within the band around the triangle
+        let low = (min - band).ceil().max(slab_min);
+        let high = (max + band).floor().min(slab_max);
+        if low.cmple(high).all() {
+            let (low, high) = (low.as_uvec3(), high.as_uvec3());
+            for z in low.z..=high.z {
+                for y in low.y..=high.y {
+                    for x in low.x..=high.x {
+                        let point = UVec3::new(x, y, z).as_vec3();
+                        let distance = point.distance(closest_point(point, triangle));
+                        let value = &mut distances[index(x, y, z)];
+                        *value = value.min(distance);
+                    }
+                }
+            }
+        }
+
+        // Crossings of the rows whose ray passes through the triangle
+        let low = min.yz().ceil().max(slab_min.yz());
+        let high = max.yz().floor().min(slab_max.yz());
+        if low.cmple(high).all() {
+            let (low, high) = (low.as_uvec2(), high.as_uvec2());
+            for z in low.y..=high.y {
+                for y in low.x..=high.x {
+                    let ray = Vec2::new(y as f32, z as f32) + RAY_OFFSET;
+                    if let Some(x) = ray_crossing(ray, triangle) {
+                        crossings[((z - slab.start) * dims.y + y) as usize].push(x);
+                    }
+                }
+            }
+        }
+    }
+
+    // Inside where an odd number of crossings lies before the sample
+    for z in slab.clone() {
+        for y in 0..dims.y {
+            let row = &mut crossings[((z - slab.start) * dims.y + y) as usize];
+            row.sort_by(f32::total_cmp);
+            let mut passed = 0;
+            for x in 0..dims.x {
+                while passed < row.len() && row[passed] < x as f32 {
+                    passed += 1;
+                }
+                if passed % 2 == 1 {
+                    distances[index(x, y, z)] *= -1.0;
+                }
+            }
+        }
+    }
+    distances
+}
+
+/// Where the ray along +x through `(y, z)` crosses the triangle, if it does.
+fn ray_crossing(ray: Vec2, [a, b, c]: &[Vec3; 3]) -> Option<f32> {
+    let (a2, b2, c2) = (a.yz(), b.yz(), c.yz());
+    let area = (b2 - a2).perp_dot(c2 - a2);
+    if area.abs() < f32::EPSILON {
+        // Parallel to the ray
+        return None;
+    }
+    let u = (b2 - ray).perp_dot(c2 - ray) / area;
+    let v = (c2 - ray).perp_dot(a2 - ray) / area;
+    let w = 1.0 - u - v;
+    (u >= 0.0 && v >= 0.0 && w >= 0.0).then_some(u * a.x + v * b.x + w * c.x)
+}
+
+/// Point of the triangle nearest to `point`, by the Voronoi region it lies in.
+fn closest_point(point: Vec3, [a, b, c]: &[Vec3; 3]) -> Vec3 {
+    let (a, b, c) = (*a, *b, *c);
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = point - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = point - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    // Inside the face
+    let denominator = 1.0 / (va + vb + vc);
+    a + ab * (vb * denominator) + ac * (vc * denominator)
+}
@@ -0,0 +1,27 @@
+//! Extraction of the walkable part of a surface, for navigation meshes.
+
+use glam::Vec3;
+
+use crate::{cleanup::compact_vertices, mesh::MeshData};
+
+/// The triangles of `mesh` facing `up` and no steeper than `max_slope` radians
+/// from level, with the vertices they use. Facing is taken from the winding, so
+/// the back faces of a double sided mesh are left out.
+pub fn walkable_triangles(mesh: &MeshData, up: Vec3, max_slope: f32) -> MeshData {
+    let up = up.normalize_or_zero();
+    let min_cos = max_slope.cos();
+    let indices = mesh
+        .indices
+        .chunks_exact(3)
+        .filter(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[triangle[i] as usize]);
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            normal != Vec3::ZERO && normal.dot(up) >= min_cos
+        })
+        .flatten()
+        .copied()
+        .collect();
+    let mut walkable = mesh.clone();
+    compact_vertices(&mut walkable, indices);
+    walkable
+}
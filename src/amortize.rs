@@ -0,0 +1,133 @@
+use bevy::{
+    diagnostic::FrameCount,
+    prelude::*,
+    render::{
+        MainWorld, extract_component::ExtractComponent, render_resource::PipelineCache,
+        sync_world::MainEntity,
+    },
+};
+
+use crate::{
+    bind_group::{GeneratedDensity, SurfaceNetsBindGroups},
+    gpu_density::DensityGeneration,
+    pipeline::SurfaceNetsPipelines,
+};
+
+/// Number of compute stages in one surface nets generation.
+pub(crate) const STAGE_COUNT: u32 = 7;
+
+/// Spreads one generation of a volume's compute stages over several frames.
+///
+/// Inserted alongside GPU work for volumes whose `SculptSettings::stages_per_frame`
+/// is set. A new `start_frame` restarts the stages from the first one.
+#[derive(Component, ExtractComponent, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StageSchedule {
+    /// At most this many stages are dispatched per frame
+    pub stages_per_frame: u32,
+    /// Main world frame in which the generation was requested
+    pub start_frame: u32,
+}
+
+impl StageSchedule {
+    pub(crate) fn new(stages_per_frame: u32, frame: &FrameCount) -> Self {
+        Self {
+            stages_per_frame: stages_per_frame.clamp(1, STAGE_COUNT),
+            start_frame: frame.0,
+        }
+    }
+}
+
+/// `start_frame` of the last `StageSchedule` whose stages have all been dispatched,
+/// reported back from the render world. Amortized volumes are read back once it
+/// matches their schedule.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StagesDispatched(pub u32);
+
+/// Render world progress through a `StageSchedule`. Amortized volumes without one
+/// are not dispatched.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct StageCursor {
+    start_frame: u32,
+    /// Stages dispatched in earlier frames
+    completed: u32,
+    stages_per_frame: u32,
+}
+
+impl StageCursor {
+    /// Whether `stage` (1-based, in dispatch order) runs this frame.
+    pub fn runs(&self, stage: u32) -> bool {
+        stage > self.completed && stage <= self.completed + self.stages_per_frame
+    }
+
+    /// Whether the last stage was dispatched in the frame the cursor last advanced.
+    fn finished(&self) -> bool {
+        self.completed + self.stages_per_frame >= STAGE_COUNT
+    }
+}
+
+/// Start or advance the stage cursor of amortized volumes, once per frame before dispatch.
+pub fn advance_stage_cursors(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &StageSchedule,
+            Option<&mut StageCursor>,
+            Option<&DensityGeneration>,
+            Option<&GeneratedDensity>,
+        ),
+        With<SurfaceNetsBindGroups>,
+    >,
+    pipeline_cache: Res<PipelineCache>,
+    pipelines: Res<SurfaceNetsPipelines>,
+) {
+    // Don't start cursors until every stage can be dispatched, so none are skipped
+    let ready = pipelines
+        .all()
+        .all(|id| pipeline_cache.get_compute_pipeline(id).is_some());
+    if !ready {
+        return;
+    }
+
+    for (entity, schedule, cursor, generation, generated) in &mut query {
+        // The node skips the stages of volumes whose densities are still being generated
+        if generation
+            .is_some_and(|generation| generated.is_none_or(|generated| generated.0 != *generation))
+        {
+            continue;
+        }
+        match cursor {
+            Some(mut cursor) if cursor.start_frame == schedule.start_frame => {
+                cursor.completed = (cursor.completed + cursor.stages_per_frame).min(STAGE_COUNT);
+            }
+            // New generation, start from the first stage this frame
+            _ => {
+                commands.entity(entity).insert(StageCursor {
+                    start_frame: schedule.start_frame,
+                    completed: 0,
+                    stages_per_frame: schedule.stages_per_frame,
+                });
+            }
+        }
+    }
+}
+
+/// Report the schedules whose last stage went out last frame to the main world, so
+/// their volumes aren't read back before the meshing finished.
+pub fn extract_dispatched_stages(
+    mut main_world: ResMut<MainWorld>,
+    cursors: Query<(&MainEntity, &StageCursor)>,
+) {
+    for (main_entity, cursor) in &cursors {
+        if !cursor.finished() {
+            continue;
+        }
+        let dispatched = StagesDispatched(cursor.start_frame);
+        let Ok(mut entity) = main_world.get_entity_mut(main_entity.id()) else {
+            continue;
+        };
+        if entity.get::<StagesDispatched>() != Some(&dispatched) {
+            entity.insert(dispatched);
+        }
+    }
+}
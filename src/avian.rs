@@ -0,0 +1,86 @@
+use avian3d::{
+    parry::shape::SharedShape,
+    prelude::{Collider, TrimeshFlags},
+};
+use bevy::{
+    ecs::entity::EntityHashSet,
+    prelude::*,
+    tasks::{Task, block_on, futures_lite::future},
+};
+
+use crate::collision::{CollisionMesh, ConvexDecomposition, spawn_convex_decomposition};
+
+/// `ConvexDecomposition` of a volume's avian `Collider` running on the
+/// `AsyncComputeTaskPool`.
+#[derive(Component)]
+pub struct AvianDecompositionTask(Task<Option<SharedShape>>);
+
+/// Give volumes whose `CollisionMesh` or `ConvexDecomposition` changed a
+/// trimesh `Collider` of it, or take it away when the mesh is empty. Volumes
+/// with a `ConvexDecomposition` start decomposing instead, dropping a stale
+/// decomposition still running.
+pub fn update_avian_colliders(
+    mut commands: Commands,
+    meshes: Query<(Entity, &CollisionMesh, Option<&ConvexDecomposition>)>,
+    changed: Query<Entity, Or<(Changed<CollisionMesh>, Changed<ConvexDecomposition>)>>,
+    // Back to a trimesh
+    mut removed: RemovedComponents<ConvexDecomposition>,
+) {
+    let entities: EntityHashSet = changed.iter().chain(removed.read()).collect();
+    for (entity, mesh, decomposition) in meshes.iter_many(entities) {
+        let mut entity_commands = commands.entity(entity);
+        if let Some(decomposition) = decomposition {
+            let task = spawn_convex_decomposition(mesh, *decomposition);
+            entity_commands.try_insert(AvianDecompositionTask(task));
+            continue;
+        }
+        entity_commands.try_remove::<AvianDecompositionTask>();
+
+        let indices: Vec<_> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+        match Collider::try_trimesh_with_config(
+            mesh.positions.clone(),
+            indices,
+            TrimeshFlags::MERGE_DUPLICATE_VERTICES | TrimeshFlags::DELETE_DEGENERATE_TRIANGLES,
+        ) {
+            Ok(collider) => {
+                entity_commands.try_insert(collider);
+            }
+            Err(error) => {
+                if !mesh.indices.is_empty() {
+                    warn!("Could not build a collider for {entity}: {error}");
+                }
+                entity_commands.try_remove::<Collider>();
+            }
+        }
+    }
+}
+
+/// Give volumes whose decomposition finished a compound `Collider` of its
+/// convex hulls, or take it away when there are none.
+pub fn poll_avian_decompositions(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut AvianDecompositionTask, &CollisionMesh)>,
+) {
+    for (entity, mut task, mesh) in &mut tasks {
+        let Some(shape) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_remove::<AvianDecompositionTask>();
+        match shape {
+            Some(shape) => {
+                entity_commands.try_insert(Collider::from(shape));
+            }
+            None => {
+                if !mesh.indices.is_empty() {
+                    warn!("Could not build a collider for {entity}: no convex hulls were found");
+                }
+                entity_commands.try_remove::<Collider>();
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+#[cfg(feature = "gpu")]
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{
+            BindGroup, BindGroupLayout, BindingResource, Buffer, CachedComputePipelineId,
+            ComputePipelineDescriptor, PipelineCache,
+        },
+        renderer::RenderDevice,
+    },
+    shader::ShaderDefVal,
+};
+
+use sculpter_core::triangulate_quad;
+
+#[cfg(feature = "gpu")]
+use crate::{
+    DensityFieldSize,
+    dirty_region::{DensityFieldDirtyRegion, MeshingRegion},
+};
+use crate::{
+    blocky::Blocky, dual_contouring::DualContouring, heightfield::Heightfield,
+    marching_cubes::MarchingCubes, marching_tetrahedra::MarchingTetrahedra,
+    settings::MeshingAlgorithm, surface_nets::SurfaceNets,
+};
+
+#[cfg(feature = "gpu")]
+pub(crate) const WORKGROUP_SIZE: u32 = 8;
+
+/// An isosurface extractor plugged into the GPU pipeline.
+///
+/// A backend supplies the two 3D stages, generate vertices (1) and generate faces
+/// (4), writing per-slot vertices and quads with validity flags. The prefix sum
+/// and compaction stages between and after them are shared by every backend.
+/// Only the slot layout and triangulation exist without the `gpu` feature.
+///
+/// To add one, implement this trait, add a `MeshingAlgorithm` variant and return
+/// the backend from `MeshingAlgorithm::backend`.
+pub trait MeshingBackend: Sync {
+    /// Vertex slots per grid point in the GPU buffers.
+    #[cfg(feature = "gpu")]
+    fn vertices_per_point(&self) -> u32;
+
+    /// Face slots per grid point in the GPU buffers.
+    #[cfg(feature = "gpu")]
+    fn faces_per_point(&self) -> u32;
+
+    /// Create the bind group layouts and queue the stage 1 and 4 pipelines,
+    /// once the render device exists.
+    #[cfg(feature = "gpu")]
+    fn init_kernels(&self, init: &BackendInit) -> BackendKernels;
+
+    /// Bind groups for stages 1 and 4, against the layouts from `init_kernels`.
+    #[cfg(feature = "gpu")]
+    fn bind_groups(
+        &self,
+        kernels: &BackendKernels,
+        render_device: &RenderDevice,
+        bindings: &BackendBindings,
+    ) -> [BindGroup; 2];
+
+    /// Cells to regenerate after the densities in `dirty` changed.
+    #[cfg(feature = "gpu")]
+    fn meshing_region(
+        &self,
+        dirty: DensityFieldDirtyRegion,
+        dimensions: DensityFieldSize,
+    ) -> MeshingRegion {
+        MeshingRegion::from_dirty(dirty, dimensions)
+    }
+
+    /// Workgroup counts of stages 1 and 4 for a dispatch region of `size` cells.
+    #[cfg(feature = "gpu")]
+    fn workgroups(&self, size: UVec3) -> [(u32, u32, u32); 2] {
+        let cells = workgroups_3d(size);
+        [cells, cells]
+    }
+
+    /// Append the triangles of one read back face (4 vertex indices) to `indices`.
+    /// By default a face is a quad, or a triangle when it repeats its last index.
+    fn triangulate(&self, face: &[u32], indices: &mut Vec<u32>) {
+        triangulate_quad(face, indices);
+    }
+}
+
+impl MeshingAlgorithm {
+    /// Every algorithm, so each backend's kernels can be created up front.
+    #[cfg(feature = "gpu")]
+    pub(crate) const ALL: [Self; 6] = [
+        Self::SurfaceNets,
+        Self::MarchingTetrahedra,
+        Self::MarchingCubes,
+        Self::DualContouring,
+        Self::Blocky,
+        Self::Heightfield,
+    ];
+
+    pub(crate) fn backend(&self) -> &'static dyn MeshingBackend {
+        match self {
+            Self::SurfaceNets => &SurfaceNets,
+            Self::MarchingTetrahedra => &MarchingTetrahedra,
+            Self::MarchingCubes => &MarchingCubes,
+            Self::DualContouring => &DualContouring,
+            Self::Blocky => &Blocky,
+            Self::Heightfield => &Heightfield,
+        }
+    }
+
+    /// Samples neighbouring chunks must share for their meshes to meet. Dual
+    /// methods place vertices in cells, so a face across the seam needs the cell
+    /// on each side; blocky meshes close every chunk on its own.
+    pub(crate) fn chunk_overlap(&self) -> u32 {
+        match self {
+            Self::SurfaceNets | Self::DualContouring => 2,
+            Self::MarchingCubes | Self::MarchingTetrahedra | Self::Heightfield => 1,
+            Self::Blocky => 0,
+        }
+    }
+}
+
+/// Render resources a backend creates its kernels from.
+#[cfg(feature = "gpu")]
+pub struct BackendInit<'a> {
+    pub asset_server: &'a AssetServer,
+    pub pipeline_cache: &'a PipelineCache,
+    pub render_device: &'a RenderDevice,
+}
+
+#[cfg(feature = "gpu")]
+impl BackendInit<'_> {
+    /// Queue a compute pipeline with a single bind group.
+    pub fn queue_pipeline(
+        &self,
+        label: &'static str,
+        layout: &BindGroupLayout,
+        shader: &'static str,
+        entry_point: &'static str,
+        shader_defs: Vec<ShaderDefVal>,
+    ) -> CachedComputePipelineId {
+        self.pipeline_cache
+            .queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(label.into()),
+                layout: vec![layout.clone()],
+                shader: self.asset_server.load(shader),
+                shader_defs,
+                entry_point: Some(entry_point.into()),
+                ..default()
+            })
+    }
+}
+
+/// A backend's stage 1 and 4 pipelines and the layouts its bind groups use.
+#[cfg(feature = "gpu")]
+pub struct BackendKernels {
+    /// Stage 1 and 4 pipelines reading the density buffer
+    pub buffer: [CachedComputePipelineId; 2],
+    /// Stage 1 and 4 pipelines sampling a density texture
+    pub texture: [CachedComputePipelineId; 2],
+    /// Layouts in whatever order the backend's `bind_groups` expects
+    pub layouts: Vec<BindGroupLayout>,
+    /// Static buffers the backend binds itself, such as lookup tables
+    pub buffers: Vec<Buffer>,
+}
+
+#[cfg(feature = "gpu")]
+impl BackendKernels {
+    pub fn pipelines(&self, texture: bool) -> [CachedComputePipelineId; 2] {
+        if texture { self.texture } else { self.buffer }
+    }
+}
+
+/// Everything a backend may bind for one surface.
+#[cfg(feature = "gpu")]
+pub struct BackendBindings<'a> {
+    /// Density storage buffer, or texture view when `density_sampler` is set
+    pub density: BindingResource<'a>,
+    pub density_sampler: Option<BindingResource<'a>>,
+    pub vertices: BindingResource<'a>,
+    pub vertex_valid: BindingResource<'a>,
+    pub vertex_indices: BindingResource<'a>,
+    pub faces: BindingResource<'a>,
+    pub face_valid: BindingResource<'a>,
+    pub dimensions: BindingResource<'a>,
+    pub density_decode: BindingResource<'a>,
+    pub region: BindingResource<'a>,
+    pub params: BindingResource<'a>,
+}
+
+/// Workgroups covering `size` invocations with the 3D kernels' workgroup size.
+#[cfg(feature = "gpu")]
+pub fn workgroups_3d(size: UVec3) -> (u32, u32, u32) {
+    (
+        size.x.div_ceil(WORKGROUP_SIZE),
+        size.y.div_ceil(WORKGROUP_SIZE),
+        size.z.div_ceil(WORKGROUP_SIZE),
+    )
+}
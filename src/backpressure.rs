@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+#[cfg(feature = "gpu")]
+use crate::{buffers::SurfaceNetsBuffers, dirty_region::Remesh, watchdog::MeshingFailed};
+
+/// What to do with volumes that can't be admitted to the GPU this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BackpressurePolicy {
+    /// Leave them queued until GPU work drains.
+    #[default]
+    Defer,
+    /// Mesh them on the CPU backend instead.
+    ShiftToCpu,
+}
+
+/// Limits on outstanding GPU meshing work, used to keep frame time stable while
+/// many volumes are being generated.
+#[derive(Resource, Clone, Debug)]
+pub struct GpuBackpressure {
+    /// Maximum number of volumes waiting on GPU meshing and readback at once
+    pub max_in_flight: usize,
+    /// When the average dispatch-to-readback latency exceeds this, only one
+    /// volume is admitted at a time until it recovers
+    pub max_readback_latency: Duration,
+    pub policy: BackpressurePolicy,
+}
+
+impl Default for GpuBackpressure {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 8,
+            max_readback_latency: Duration::from_millis(250),
+            policy: BackpressurePolicy::Defer,
+        }
+    }
+}
+
+/// Live measurements of GPU meshing work, updated every frame.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct GpuMeshingLoad {
+    /// Volumes with GPU buffers that haven't received a mesh yet
+    pub in_flight: usize,
+    /// Volumes waiting for admission
+    pub queued: usize,
+    /// Exponential moving average of dispatch-to-mesh latency
+    pub average_latency: Duration,
+    /// Whether new work was throttled this frame
+    pub saturated: bool,
+    // Admissions left this frame
+    #[cfg(feature = "gpu")]
+    pub(crate) budget: usize,
+}
+
+impl GpuMeshingLoad {
+    // Weight of the newest sample in the latency average
+    const LATENCY_SMOOTHING: f32 = 0.2;
+
+    pub(crate) fn record_latency(&mut self, latency: Duration) {
+        self.average_latency = if self.average_latency.is_zero() {
+            latency
+        } else {
+            self.average_latency.mul_f32(1.0 - Self::LATENCY_SMOOTHING)
+                + latency.mul_f32(Self::LATENCY_SMOOTHING)
+        };
+    }
+
+    /// How many new volumes may start GPU meshing this frame.
+    #[cfg(feature = "gpu")]
+    pub(crate) fn admission_budget(&self, limits: &GpuBackpressure) -> usize {
+        let limit = if self.average_latency > limits.max_readback_latency {
+            1
+        } else {
+            limits.max_in_flight
+        };
+        limit.saturating_sub(self.in_flight)
+    }
+
+    /// Try to start GPU work for one volume. Critical work is always admitted.
+    #[cfg(feature = "gpu")]
+    pub(crate) fn admit(&mut self, critical: bool) -> bool {
+        if self.budget > 0 {
+            self.budget -= 1;
+            true
+        } else {
+            self.saturated = true;
+            critical
+        }
+    }
+}
+
+/// Marks a remesh as gameplay-critical (e.g. the ground under the player was
+/// destroyed). It is admitted ahead of routine work, ignoring backpressure limits,
+/// and is removed once the new mesh is built.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct CriticalRemesh;
+
+/// Orders work so critical volumes are admitted first.
+#[cfg(feature = "gpu")]
+pub(crate) fn critical_first<T>(mut work: Vec<(bool, T)>) -> impl Iterator<Item = (bool, T)> {
+    work.sort_by_key(|(critical, _)| !critical);
+    work.into_iter()
+}
+
+/// Measure outstanding GPU work and reset this frame's admission budget.
+#[cfg(feature = "gpu")]
+pub fn update_gpu_meshing_load(
+    in_flight_query: Query<
+        (),
+        (
+            With<SurfaceNetsBuffers>,
+            Or<(Without<Mesh3d>, With<Remesh>)>,
+            Without<MeshingFailed>,
+        ),
+    >,
+    limits: Res<GpuBackpressure>,
+    mut load: ResMut<GpuMeshingLoad>,
+) {
+    load.in_flight = in_flight_query.iter().count();
+    load.queued = 0;
+    load.saturated = false;
+    load.budget = load.admission_budget(&limits);
+}
+
+/// Time (since startup) at which a volume's GPU buffers were created.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GpuGenerationStarted(pub Duration);
+
+/// Marks a volume that was moved to the CPU backend because the GPU was saturated.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct CpuFallback;
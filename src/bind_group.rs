@@ -2,13 +2,26 @@ use bevy::{
     prelude::*,
     render::{
         render_asset::RenderAssets,
-        render_resource::{BindGroup, BindGroupEntries, BindGroupLayout, UniformBuffer},
+        render_resource::{
+            BindGroup, BindGroupEntries, BindGroupLayout, BufferDescriptor, BufferInitDescriptor,
+            BufferUsages, CachedComputePipelineId, IntoBinding, PipelineCache, StorageBuffer,
+            UniformBuffer,
+        },
         renderer::{RenderDevice, RenderQueue},
         storage::GpuShaderStorageBuffer,
+        texture::GpuImage,
     },
 };
 
-use crate::buffers::SurfaceNetsBuffers;
+use crate::{
+    backend::{BackendBindings, workgroups_3d},
+    brush::PendingBrushStrokes,
+    buffers::SurfaceNetsBuffers,
+    csg::PendingCsgEdits,
+    dirty_region::MeshingRegion,
+    gpu_density::DensityGeneration,
+    pipeline::{DensityKernelPipelines, SurfaceNetsPipelines},
+};
 
 #[derive(Component)]
 pub struct SurfaceNetsBindGroups {
@@ -18,34 +31,77 @@ pub struct SurfaceNetsBindGroups {
     pub generate_faces: BindGroup,
     pub prefix_sum_faces: BindGroup,
     pub compact_faces: BindGroup,
+    pub vertex_normals: BindGroup,
+    // Cells the 3D stages are dispatched over, rewritten when the region changes
+    pub region: UniformBuffer<MeshingRegion>,
 }
 
 // Store bind group layouts as a resource
 #[derive(Resource)]
 pub struct SurfaceNetsBindGroupLayouts {
-    pub generate_vertices: BindGroupLayout,
+    // Layouts of the shared stages, backends own the layouts of stages 1 and 4
     pub prefix_sum: BindGroupLayout,
     pub compact_vertices: BindGroupLayout,
-    pub generate_faces: BindGroupLayout,
     pub compact_faces: BindGroupLayout,
+    pub vertex_normals: BindGroupLayout,
+    pub vertex_normals_texture: BindGroupLayout,
+    pub generate_density: BindGroupLayout,
+    pub generate_density_heightmap: BindGroupLayout,
+    pub generate_density_sdf: BindGroupLayout,
+    pub csg: BindGroupLayout,
+    pub brush: BindGroupLayout,
 }
 
+/// Bind group and kernel of a density generation to dispatch this frame.
+#[derive(Component)]
+pub struct DensityGenerationBindGroup(pub BindGroup, pub CachedComputePipelineId);
+
+/// Bind groups and workgroup counts of the CSG edits to dispatch this frame.
+#[derive(Component)]
+pub struct CsgEditBindGroups(pub Vec<(BindGroup, (u32, u32, u32))>);
+
+/// Bind group of each brush application to dispatch this frame, with the
+/// workgroup counts of its copy of the densities around it, if it reads them,
+/// and its kernel and workgroup counts.
+#[derive(Component)]
+pub struct BrushStrokeBindGroups(pub Vec<BrushStrokeDispatch>);
+
+pub struct BrushStrokeDispatch {
+    pub bind_group: BindGroup,
+    pub snapshot: Option<(u32, u32, u32)>,
+    pub pipeline: CachedComputePipelineId,
+    pub workgroups: (u32, u32, u32),
+}
+
+/// Generation the density buffer was last filled by.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct GeneratedDensity(pub DensityGeneration);
+
 pub fn prepare_bind_groups(
     mut commands: Commands,
     layouts: Res<SurfaceNetsBindGroupLayouts>,
+    pipelines: Res<SurfaceNetsPipelines>,
     entities_needing_bind_groups: Query<
-        (Entity, &SurfaceNetsBuffers),
+        (Entity, &SurfaceNetsBuffers, &MeshingRegion),
         Without<SurfaceNetsBindGroups>,
     >,
     gpu_buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
 ) {
-    for (entity, buffers) in &entities_needing_bind_groups {
+    for (entity, buffers, region) in &entities_needing_bind_groups {
         // Get GPU buffers - skip if any are not ready
         let Some(density_field) = gpu_buffers.get(&buffers.density_field) else {
             continue;
         };
+        let density_texture = match &buffers.density_texture {
+            Some(texture) => match gpu_images.get(texture) {
+                Some(image) => Some(image),
+                None => continue,
+            },
+            None => None,
+        };
         let Some(vertices) = gpu_buffers.get(&buffers.vertices) else {
             continue;
         };
@@ -76,21 +132,76 @@ pub fn prepare_bind_groups(
         let Some(compacted_faces) = gpu_buffers.get(&buffers.compacted_faces) else {
             continue;
         };
+        let Some(compacted_normals) = gpu_buffers.get(&buffers.compacted_normals) else {
+            continue;
+        };
+        let Some(compacted_occlusion) = gpu_buffers.get(&buffers.compacted_occlusion) else {
+            continue;
+        };
+        let Some(material_field) = gpu_buffers.get(&buffers.material_field) else {
+            continue;
+        };
+        let Some(compacted_material_ids) = gpu_buffers.get(&buffers.compacted_material_ids) else {
+            continue;
+        };
+        let Some(compacted_material_weights) = gpu_buffers.get(&buffers.compacted_material_weights)
+        else {
+            continue;
+        };
 
         // Create uniform buffer for dimensions
         let mut dimensions_uniform = UniformBuffer::from(buffers.dimensions.0);
         dimensions_uniform.write_buffer(&render_device, &render_queue);
 
-        // Bind Group 1: Generate Vertices
-        let generate_vertices_bg = render_device.create_bind_group(
-            Some("generate_vertices_bind_group"),
-            &layouts.generate_vertices,
-            &BindGroupEntries::sequential((
-                density_field.buffer.as_entire_buffer_binding(),
-                vertices.buffer.as_entire_buffer_binding(),
-                vertex_valid.buffer.as_entire_buffer_binding(),
-                dimensions_uniform.binding().unwrap(),
-            )),
+        // Create uniform buffer describing the density encoding
+        let mut density_decode_uniform = UniformBuffer::from(buffers.density_decode);
+        density_decode_uniform.write_buffer(&render_device, &render_queue);
+
+        // Create uniform buffer for the dispatch region
+        let mut region_uniform = UniformBuffer::from(*region);
+        region_uniform.write_buffer(&render_device, &render_queue);
+
+        // Create uniform buffer for the per-volume meshing options
+        let mut params_uniform = UniformBuffer::from(buffers.params);
+        params_uniform.write_buffer(&render_device, &render_queue);
+
+        // Bind Groups 1 and 4: Generate Vertices and Generate Faces, from the volume's backend
+        let (density, density_sampler) = match density_texture {
+            Some(density_texture) => (
+                density_texture.texture_view.into_binding(),
+                Some(pipelines.density_sampler.into_binding()),
+            ),
+            None => (
+                density_field
+                    .buffer
+                    .as_entire_buffer_binding()
+                    .into_binding(),
+                None,
+            ),
+        };
+        let bindings = BackendBindings {
+            density,
+            density_sampler,
+            vertices: vertices.buffer.as_entire_buffer_binding().into_binding(),
+            vertex_valid: vertex_valid
+                .buffer
+                .as_entire_buffer_binding()
+                .into_binding(),
+            vertex_indices: vertex_indices
+                .buffer
+                .as_entire_buffer_binding()
+                .into_binding(),
+            faces: faces.buffer.as_entire_buffer_binding().into_binding(),
+            face_valid: face_valid.buffer.as_entire_buffer_binding().into_binding(),
+            dimensions: dimensions_uniform.binding().unwrap(),
+            density_decode: density_decode_uniform.binding().unwrap(),
+            region: region_uniform.binding().unwrap(),
+            params: params_uniform.binding().unwrap(),
+        };
+        let [generate_vertices_bg, generate_faces_bg] = buffers.algorithm.backend().bind_groups(
+            &pipelines.backends[&buffers.algorithm],
+            &render_device,
+            &bindings,
         );
 
         // Bind Group 2: Prefix Sum (vertices)
@@ -116,19 +227,6 @@ pub fn prepare_bind_groups(
             )),
         );
 
-        // Bind Group 4: Generate Faces
-        let generate_faces_bg = render_device.create_bind_group(
-            Some("generate_faces_bind_group"),
-            &layouts.generate_faces,
-            &BindGroupEntries::sequential((
-                vertex_valid.buffer.as_entire_buffer_binding(),
-                vertex_indices.buffer.as_entire_buffer_binding(),
-                faces.buffer.as_entire_buffer_binding(),
-                face_valid.buffer.as_entire_buffer_binding(),
-                dimensions_uniform.binding().unwrap(),
-            )),
-        );
-
         // Bind Group 5: Prefix Sum (faces)
         let prefix_sum_faces_bg = render_device.create_bind_group(
             Some("prefix_sum_faces_bind_group"),
@@ -149,9 +247,46 @@ pub fn prepare_bind_groups(
                 face_valid.buffer.as_entire_buffer_binding(),
                 face_indices.buffer.as_entire_buffer_binding(),
                 compacted_faces.buffer.as_entire_buffer_binding(),
+                vertex_indices.buffer.as_entire_buffer_binding(),
             )),
         );
 
+        // Bind Group 7: Vertex Normals (from the density buffer or texture)
+        let vertex_normals_bg = match &bindings.density_sampler {
+            Some(density_sampler) => render_device.create_bind_group(
+                Some("vertex_normals_texture_bind_group"),
+                &layouts.vertex_normals_texture,
+                &BindGroupEntries::sequential((
+                    bindings.density.clone(),
+                    compacted_vertices.buffer.as_entire_buffer_binding(),
+                    vertex_count.buffer.as_entire_buffer_binding(),
+                    compacted_normals.buffer.as_entire_buffer_binding(),
+                    bindings.dimensions.clone(),
+                    bindings.density_decode.clone(),
+                    bindings.params.clone(),
+                    compacted_occlusion.buffer.as_entire_buffer_binding(),
+                    density_sampler.clone(),
+                )),
+            ),
+            None => render_device.create_bind_group(
+                Some("vertex_normals_bind_group"),
+                &layouts.vertex_normals,
+                &BindGroupEntries::sequential((
+                    bindings.density.clone(),
+                    compacted_vertices.buffer.as_entire_buffer_binding(),
+                    vertex_count.buffer.as_entire_buffer_binding(),
+                    compacted_normals.buffer.as_entire_buffer_binding(),
+                    bindings.dimensions.clone(),
+                    bindings.density_decode.clone(),
+                    bindings.params.clone(),
+                    compacted_occlusion.buffer.as_entire_buffer_binding(),
+                    material_field.buffer.as_entire_buffer_binding(),
+                    compacted_material_ids.buffer.as_entire_buffer_binding(),
+                    compacted_material_weights.buffer.as_entire_buffer_binding(),
+                )),
+            ),
+        };
+
         // Add bind groups component to this entity
         commands.entity(entity).insert(SurfaceNetsBindGroups {
             generate_vertices: generate_vertices_bg,
@@ -160,6 +295,274 @@ pub fn prepare_bind_groups(
             generate_faces: generate_faces_bg,
             prefix_sum_faces: prefix_sum_faces_bg,
             compact_faces: compact_faces_bg,
+            vertex_normals: vertex_normals_bg,
+            region: region_uniform,
         });
     }
 }
+
+/// Rewrite the dispatch region uniform of volumes whose region changed.
+pub fn write_meshing_regions(
+    mut query: Query<(&MeshingRegion, &mut SurfaceNetsBindGroups)>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for (region, mut bind_groups) in &mut query {
+        if bind_groups.region.get() != region {
+            bind_groups.region.set(*region);
+            // Same size, so this writes into the already bound buffer
+            bind_groups
+                .region
+                .write_buffer(&render_device, &render_queue);
+        }
+    }
+}
+
+/// Bind the density buffers of volumes whose densities are out of date, once
+/// their generation kernel is ready, and unbind those generated last frame.
+pub fn prepare_density_generation_bind_groups(
+    mut commands: Commands,
+    layouts: Res<SurfaceNetsBindGroupLayouts>,
+    pipelines: Res<SurfaceNetsPipelines>,
+    mut kernel_pipelines: ResMut<DensityKernelPipelines>,
+    pipeline_cache: Res<PipelineCache>,
+    query: Query<(
+        Entity,
+        &SurfaceNetsBuffers,
+        &DensityGeneration,
+        Option<&GeneratedDensity>,
+        Has<DensityGenerationBindGroup>,
+    )>,
+    gpu_buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for (entity, buffers, generation, generated, bound) in &query {
+        if generated.is_some_and(|generated| generated.0 == *generation) {
+            if bound {
+                commands
+                    .entity(entity)
+                    .remove::<DensityGenerationBindGroup>();
+            }
+            continue;
+        }
+        let pipeline = match &generation.kernel {
+            Some(kernel) => kernel_pipelines.get_or_queue(kernel, &layouts, &pipeline_cache),
+            None => pipelines.generate_density(generation),
+        };
+        if pipeline_cache.get_compute_pipeline(pipeline).is_none() {
+            continue;
+        }
+        let Some(density_field) = gpu_buffers.get(&buffers.density_field) else {
+            continue;
+        };
+
+        let mut params_uniform = UniformBuffer::from(generation.params);
+        params_uniform.write_buffer(&render_device, &render_queue);
+
+        let bind_group = match (&generation.heightmap, &generation.sdf) {
+            // Heightmaps must be loaded first
+            (Some(heightmap), _) => {
+                let Some(heightmap) = gpu_images.get(heightmap) else {
+                    continue;
+                };
+                render_device.create_bind_group(
+                    Some("generate_density_heightmap_bind_group"),
+                    &layouts.generate_density_heightmap,
+                    &BindGroupEntries::sequential((
+                        density_field.buffer.as_entire_buffer_binding(),
+                        params_uniform.binding().unwrap(),
+                        &heightmap.texture_view,
+                        &pipelines.density_sampler,
+                    )),
+                )
+            }
+            (None, Some(parts)) => {
+                let mut parts = StorageBuffer::from(parts.clone());
+                parts.write_buffer(&render_device, &render_queue);
+                render_device.create_bind_group(
+                    Some("generate_density_sdf_bind_group"),
+                    &layouts.generate_density_sdf,
+                    &BindGroupEntries::sequential((
+                        density_field.buffer.as_entire_buffer_binding(),
+                        params_uniform.binding().unwrap(),
+                        parts.binding().unwrap(),
+                    )),
+                )
+            }
+            (None, None) => render_device.create_bind_group(
+                Some("generate_density_bind_group"),
+                &layouts.generate_density,
+                &BindGroupEntries::sequential((
+                    density_field.buffer.as_entire_buffer_binding(),
+                    params_uniform.binding().unwrap(),
+                )),
+            ),
+        };
+        // The node dispatches it this frame
+        commands.entity(entity).insert((
+            DensityGenerationBindGroup(bind_group, pipeline),
+            GeneratedDensity(generation.clone()),
+        ));
+    }
+}
+
+/// Bind the density buffers of volumes with CSG edits this frame to their
+/// parameters and sources, and unbind those edited last frame.
+pub fn prepare_csg_bind_groups(
+    mut commands: Commands,
+    layouts: Res<SurfaceNetsBindGroupLayouts>,
+    pipelines: Res<SurfaceNetsPipelines>,
+    pipeline_cache: Res<PipelineCache>,
+    query: Query<(
+        Entity,
+        &SurfaceNetsBuffers,
+        Option<&PendingCsgEdits>,
+        Has<CsgEditBindGroups>,
+    )>,
+    gpu_buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for (entity, buffers, edits, bound) in &query {
+        if bound {
+            commands.entity(entity).remove::<CsgEditBindGroups>();
+        }
+        let Some(edits) = edits else {
+            continue;
+        };
+        if pipeline_cache
+            .get_compute_pipeline(pipelines.csg_pipeline)
+            .is_none()
+        {
+            continue;
+        }
+        let Some(density_field) = gpu_buffers.get(&buffers.density_field) else {
+            continue;
+        };
+
+        let bind_groups = edits
+            .0
+            .iter()
+            .map(|edit| {
+                let mut params_uniform = UniformBuffer::from(edit.params);
+                params_uniform.write_buffer(&render_device, &render_queue);
+                // Primitives read no source, but the binding must hold something
+                let source: &[f32] = edit.source.as_deref().map_or(&[0.0], |field| field);
+                let source = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                    label: Some("csg_source_field"),
+                    contents: bytemuck::cast_slice(source),
+                    usage: BufferUsages::STORAGE,
+                });
+                let mut parts = StorageBuffer::from(edit.parts.clone());
+                parts.write_buffer(&render_device, &render_queue);
+                let bind_group = render_device.create_bind_group(
+                    Some("csg_bind_group"),
+                    &layouts.csg,
+                    &BindGroupEntries::sequential((
+                        density_field.buffer.as_entire_buffer_binding(),
+                        params_uniform.binding().unwrap(),
+                        source.as_entire_buffer_binding(),
+                        parts.binding().unwrap(),
+                    )),
+                );
+                (bind_group, workgroups_3d(edit.params.region_size))
+            })
+            .collect();
+        // The node dispatches them this frame
+        commands
+            .entity(entity)
+            .insert(CsgEditBindGroups(bind_groups));
+    }
+}
+
+/// Bind the density buffers of volumes sculpted this frame to each brush
+/// application, a copy of the densities around it and its falloff curve, and
+/// unbind those sculpted last frame.
+pub fn prepare_brush_bind_groups(
+    mut commands: Commands,
+    layouts: Res<SurfaceNetsBindGroupLayouts>,
+    pipelines: Res<SurfaceNetsPipelines>,
+    pipeline_cache: Res<PipelineCache>,
+    query: Query<(
+        Entity,
+        &SurfaceNetsBuffers,
+        Option<&PendingBrushStrokes>,
+        Has<BrushStrokeBindGroups>,
+    )>,
+    gpu_buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for (entity, buffers, strokes, bound) in &query {
+        if bound {
+            commands.entity(entity).remove::<BrushStrokeBindGroups>();
+        }
+        let Some(strokes) = strokes else {
+            continue;
+        };
+        if [
+            pipelines.brush_snapshot_pipeline,
+            pipelines.brush_pipeline,
+            pipelines.brush_smooth_pipeline,
+            pipelines.brush_flatten_pipeline,
+        ]
+        .into_iter()
+        .any(|pipeline| pipeline_cache.get_compute_pipeline(pipeline).is_none())
+        {
+            continue;
+        }
+        let Some(density_field) = gpu_buffers.get(&buffers.density_field) else {
+            continue;
+        };
+
+        let dispatches = strokes
+            .0
+            .iter()
+            .map(|stroke| {
+                let params = &stroke.params;
+                let mut params_uniform = UniformBuffer::from(*params);
+                params_uniform.write_buffer(&render_device, &render_queue);
+                // Brushes not reading neighbours copy nothing, but the binding must hold something
+                let snapshot_size = match params.reads_neighbours() {
+                    true => params.snapshot_size.element_product() as u64,
+                    false => 1,
+                };
+                let snapshot = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("brush_snapshot"),
+                    size: snapshot_size * size_of::<f32>() as u64,
+                    usage: BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                });
+                let curve = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                    label: Some("brush_curve"),
+                    contents: bytemuck::cast_slice(&stroke.curve),
+                    usage: BufferUsages::STORAGE,
+                });
+                let bind_group = render_device.create_bind_group(
+                    Some("brush_bind_group"),
+                    &layouts.brush,
+                    &BindGroupEntries::sequential((
+                        density_field.buffer.as_entire_buffer_binding(),
+                        params_uniform.binding().unwrap(),
+                        snapshot.as_entire_buffer_binding(),
+                        curve.as_entire_buffer_binding(),
+                    )),
+                );
+                BrushStrokeDispatch {
+                    bind_group,
+                    snapshot: params
+                        .reads_neighbours()
+                        .then(|| workgroups_3d(params.snapshot_size)),
+                    pipeline: pipelines.brush(params),
+                    workgroups: workgroups_3d(params.region_size),
+                }
+            })
+            .collect();
+        // The node dispatches them this frame
+        commands
+            .entity(entity)
+            .insert(BrushStrokeBindGroups(dispatches));
+    }
+}
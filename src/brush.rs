@@ -0,0 +1,870 @@
+use std::sync::Arc;
+
+use bevy::{
+    diagnostic::FrameCount,
+    math::Affine3A,
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent,
+        gpu_readback::{Readback, ReadbackComplete},
+        render_resource::ShaderType,
+    },
+};
+use sculpter_core::{grid::index, transform_bounds};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize,
+    amortize::StageSchedule,
+    buffers::SurfaceNetsBuffers,
+    csg::CsgOperation,
+    dirty_region::{DensityEdited, DensityFieldDirtyRegion, Remesh},
+    material::MaterialField,
+    mesh::Meshed,
+    quantize::DENSITY_FORMAT_F32,
+    sdf::{GpuSdfPart, SdfPrimitive},
+    settings::SculptSettings,
+    units::VoxelSpacing,
+};
+
+// Must match `MODE_*` in sculpt_brush.wgsl
+const MODE_ADD: u32 = 0;
+const MODE_SUBTRACT: u32 = 1;
+const MODE_SMOOTH: u32 = 2;
+const MODE_FLATTEN: u32 = 3;
+
+// Must match `FALLOFF_*` in sculpt_brush.wgsl
+const FALLOFF_LINEAR: u32 = 0;
+const FALLOFF_SMOOTHSTEP: u32 = 1;
+const FALLOFF_CURVE: u32 = 2;
+
+// Must match `BLUR_*` in sculpt_brush.wgsl
+const BLUR_BOX: u32 = 0;
+const BLUR_GAUSSIAN: u32 = 1;
+
+// Must match `PLANE_*` in sculpt_brush.wgsl
+const PLANE_FIXED: u32 = 0;
+const PLANE_SURFACE: u32 = 1;
+
+/// Most samples a smoothing brush averages on each side of each sample, as the
+/// kernel reads every sample within them.
+pub const MAX_BLUR_RADIUS: u32 = 4;
+
+/// Least spacing between interpolated applications, relative to the brush's
+/// radius.
+pub const MIN_BRUSH_SPACING: f32 = 0.05;
+
+/// Most applications a brush is spread into along one segment, however far it
+/// moved, so a long or fast stroke can't flood the frame with dispatches.
+pub const MAX_BRUSH_APPLICATIONS: u32 = 256;
+
+/// The volume a `SculptBrush` reaches, `radius` from its centre.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum BrushShape {
+    #[default]
+    Sphere,
+    Cube,
+    /// Along the brush's Y axis
+    Cylinder,
+}
+
+impl BrushShape {
+    pub(crate) fn primitive(self, radius: f32) -> SdfPrimitive {
+        match self {
+            Self::Sphere => SdfPrimitive::Sphere { radius },
+            Self::Cube => SdfPrimitive::Cuboid {
+                half_extents: Vec3::splat(radius),
+            },
+            Self::Cylinder => SdfPrimitive::Cylinder {
+                half_height: radius,
+                radius,
+            },
+        }
+    }
+}
+
+/// What a `SculptBrush` does to the densities it reaches, by up to its
+/// strength at its centre and less towards its edge.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum BrushMode {
+    /// Lower the densities, growing the solid
+    #[default]
+    Add,
+    /// Raise the densities, carving the solid away
+    Subtract,
+    /// Blend the densities towards their blurred neighbourhood, rounding off
+    /// ridges and filling in pits
+    Smooth(BlurKernel),
+    /// Blend the densities towards the distance to a plane, levelling the
+    /// surface onto it
+    Flatten(FlattenPlane),
+    /// Set the id of the volume's `MaterialField` on the solid samples the
+    /// brush reaches, wherever its falloff is above 0, leaving the densities
+    /// alone. Painted on the CPU, so only volumes with a `DensityField` can be
+    /// painted.
+    Paint(u32),
+}
+
+impl BrushMode {
+    fn gpu_mode(self) -> u32 {
+        match self {
+            Self::Add => MODE_ADD,
+            Self::Subtract => MODE_SUBTRACT,
+            Self::Smooth(_) => MODE_SMOOTH,
+            Self::Flatten(_) => MODE_FLATTEN,
+            Self::Paint(_) => unreachable!("materials are painted on the CPU"),
+        }
+    }
+
+    /// Samples beyond the brush the kernel reads around each sample it changes.
+    pub(crate) fn reach(self) -> u32 {
+        match self {
+            Self::Add | Self::Subtract | Self::Paint(_) => 0,
+            Self::Smooth(kernel) => kernel.radius(),
+            // The slope at the brush's centre
+            Self::Flatten(_) => 1,
+        }
+    }
+}
+
+/// The weights a smoothing brush averages a sample's neighbourhood with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlurKernel {
+    /// Unweighted mean of the samples within `radius` on each axis
+    Box { radius: u32 },
+    /// Mean weighted by a Gaussian of the distance, `sigma` samples wide,
+    /// reaching three times as far
+    Gaussian { sigma: f32 },
+}
+
+impl Default for BlurKernel {
+    fn default() -> Self {
+        Self::Box { radius: 1 }
+    }
+}
+
+impl BlurKernel {
+    /// Samples averaged on each side, at most `MAX_BLUR_RADIUS`.
+    pub fn radius(self) -> u32 {
+        let radius = match self {
+            Self::Box { radius } => radius,
+            Self::Gaussian { sigma } => (3.0 * sigma).ceil() as u32,
+        };
+        radius.clamp(1, MAX_BLUR_RADIUS)
+    }
+}
+
+/// The plane a flattening brush levels the surface onto.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum FlattenPlane {
+    /// Through the brush's centre, across its Y axis
+    #[default]
+    Brush,
+    /// Tangent to the surface at the brush's centre, read from the densities
+    /// around it, so strokes level bumps without tilting the surface. Falls
+    /// back to the brush's plane away from any surface.
+    Surface,
+    /// Through `point` across `normal`, in world space
+    Fixed { point: Vec3, normal: Dir3 },
+}
+
+/// How a `SculptBrush` weakens from its full strength at its centre to nothing
+/// at its edge.
+#[derive(Clone, Debug, Default)]
+pub enum BrushFalloff {
+    #[default]
+    Linear,
+    /// Flat near the centre and the edge, for soft edged strokes
+    Smoothstep,
+    /// Weights evenly spaced from the centre to the edge, interpolated
+    /// linearly. Without any, the falloff is linear.
+    Curve(Arc<[f32]>),
+}
+
+impl BrushFalloff {
+    fn gpu_falloff(&self) -> (u32, Arc<[f32]>) {
+        match self {
+            Self::Curve(weights) if !weights.is_empty() => (FALLOFF_CURVE, weights.clone()),
+            // Storage bindings can't be empty, the others bind an unused weight
+            Self::Smoothstep => (FALLOFF_SMOOTHSTEP, Arc::new([0.0])),
+            _ => (FALLOFF_LINEAR, Arc::new([0.0])),
+        }
+    }
+
+    /// Weight `t` of the way from the centre to the edge, as the brush kernel
+    /// computes it.
+    pub(crate) fn weight(&self, t: f32) -> f32 {
+        match self {
+            Self::Curve(weights) if !weights.is_empty() => {
+                let last = weights.len() - 1;
+                let x = t * last as f32;
+                let i = (x as usize).min(last);
+                let next = (i + 1).min(last);
+                weights[i].lerp(weights[next], x.fract())
+            }
+            Self::Smoothstep => {
+                let t = t.clamp(0.0, 1.0);
+                1.0 - t * t * (3.0 - 2.0 * t)
+            }
+            _ => 1.0 - t,
+        }
+    }
+}
+
+/// Trigger on a meshed volume to sculpt its densities where the brush reaches
+/// and remesh it. The brush is applied to the volume's density buffer by a
+/// compute pass, and the sculpted region is then read back into its
+/// `DensityField`, if it has one, a few frames later. The write-back bypasses
+/// change detection and sends a `DensityEdited` instead.
+///
+/// Volumes with quantized or texture densities can't be sculpted. Painting
+/// materials edits the volume's `MaterialField` instead, see `BrushMode::Paint`.
+#[derive(EntityEvent, Clone, Debug)]
+pub struct SculptBrush {
+    pub entity: Entity,
+    pub shape: BrushShape,
+    /// Reach from the brush's centre, in metres
+    pub radius: f32,
+    /// Density change at the brush's centre for adding and subtracting, or
+    /// the blend towards the target for smoothing and flattening, where 1 and
+    /// above replace the densities. Paints wherever it is above 0.
+    pub strength: f32,
+    pub mode: BrushMode,
+    pub falloff: BrushFalloff,
+    /// Placement of the brush in world space
+    pub transform: Transform,
+}
+
+impl SculptBrush {
+    /// The brush spread along the segment from `from` to its position, in
+    /// applications evenly spaced at most `spacing` times its radius apart, the
+    /// last at its position. Its strength is shared between them, so a stroke
+    /// moved quickly is continuous rather than a trail of dots.
+    ///
+    /// `spacing` is at least `MIN_BRUSH_SPACING`, and there are at most
+    /// `MAX_BRUSH_APPLICATIONS`, spaced further apart on longer segments.
+    pub fn interpolated(&self, from: Vec3, spacing: f32) -> impl Iterator<Item = Self> + '_ {
+        let segment = self.transform.translation - from;
+        let step = (spacing.max(MIN_BRUSH_SPACING) * self.radius).max(f32::EPSILON);
+        let count = (segment.length() / step)
+            .ceil()
+            .clamp(1.0, MAX_BRUSH_APPLICATIONS as f32) as u32;
+        (1..=count).map(move |i| {
+            let mut brush = self.clone();
+            brush.transform.translation = from + segment * (i as f32 / count as f32);
+            brush.strength /= count as f32;
+            brush
+        })
+    }
+}
+
+/// Applies its brush every frame while it is held, at its strength per second,
+/// building up over `ramp` seconds from being pressed, spread along the path
+/// the brush took since last frame. Insert it when the button is pressed, move
+/// `brush.transform` with the cursor and remove it when the button is
+/// released.
+#[derive(Component, Clone, Debug)]
+pub struct HeldBrush {
+    pub brush: SculptBrush,
+    /// Seconds from being pressed to full strength, 0 for full strength at once
+    pub ramp: f32,
+    /// Most distance between applications along the path, relative to the
+    /// brush's radius, at least `MIN_BRUSH_SPACING`
+    pub spacing: f32,
+    /// Seconds it has been held
+    pub held: f32,
+    /// Where the brush was applied last frame
+    pub last_position: Option<Vec3>,
+}
+
+impl HeldBrush {
+    pub fn new(brush: SculptBrush) -> Self {
+        Self {
+            brush,
+            ramp: 0.0,
+            spacing: 0.25,
+            held: 0.0,
+            last_position: None,
+        }
+    }
+
+    pub fn with_ramp(mut self, ramp: f32) -> Self {
+        self.ramp = ramp;
+        self
+    }
+
+    pub fn with_spacing(mut self, spacing: f32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+}
+
+/// Uniform of the brush kernel.
+#[derive(ShaderType, Clone, Copy, Debug, Default, PartialEq)]
+pub struct BrushParams {
+    /// Grid space to the brush's space
+    pub inverse: Mat4,
+    /// Plane flattened towards in grid space, its normal and offset
+    pub plane: Vec4,
+    pub size: Vec4,
+    /// Centre of the brush in grid space
+    pub center: Vec3,
+    /// Samples the brush kernel is dispatched over
+    pub region_min: UVec3,
+    pub region_size: UVec3,
+    /// Samples copied for neighbourhood reads, the region and its surroundings
+    pub snapshot_min: UVec3,
+    pub snapshot_size: UVec3,
+    pub dimensions: UVec3,
+    pub shape: u32,
+    pub mode: u32,
+    pub falloff: u32,
+    /// Weights of a curve falloff
+    pub curve_length: u32,
+    pub blur: u32,
+    /// Samples averaged on each side when smoothing
+    pub blur_radius: u32,
+    pub plane_source: u32,
+    /// Width of a Gaussian blur, in samples
+    pub sigma: f32,
+    pub radius: f32,
+    pub strength: f32,
+}
+
+impl BrushParams {
+    /// Whether the kernel reads a copy of the densities around the region.
+    pub fn reads_neighbours(&self) -> bool {
+        self.mode == MODE_SMOOTH || self.mode == MODE_FLATTEN
+    }
+
+    pub fn smooths(&self) -> bool {
+        self.mode == MODE_SMOOTH
+    }
+
+    pub fn flattens(&self) -> bool {
+        self.mode == MODE_FLATTEN
+    }
+}
+
+/// A brush application for the brush kernel.
+#[derive(Clone, Debug)]
+pub struct GpuBrushStroke {
+    pub params: BrushParams,
+    /// Weights of the falloff curve, uploaded along with the stroke
+    pub curve: Arc<[f32]>,
+}
+
+/// Brush applications waiting for their volume to be meshed.
+#[derive(Component, Clone, Debug, Default)]
+pub struct QueuedBrushStrokes(Vec<(GpuBrushStroke, DensityFieldDirtyRegion)>);
+
+impl QueuedBrushStrokes {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Brush applications the render world applies to the volume's density buffer
+/// this frame, after generating it and compositing into it and before meshing.
+#[derive(Component, ExtractComponent, Clone, Debug, Default)]
+pub struct PendingBrushStrokes(pub Vec<GpuBrushStroke>);
+
+/// Sculpted densities of a volume being read back from its density buffer into
+/// its `DensityField`, on a child of the volume.
+#[derive(Component, Clone, Debug)]
+pub struct BrushWriteBack {
+    pub region: DensityFieldDirtyRegion,
+    /// First sample of the span read back
+    start: usize,
+    /// The field's samples over the span when the strokes were flushed, so
+    /// samples edited on the CPU meanwhile keep their edit
+    before: Vec<f32>,
+}
+
+/// On a volume whose sculpted densities are being read back. Its next strokes
+/// and dirty region wait for the write-back, so the field and buffer agree on
+/// what each of them started from.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct WritingBackBrushStrokes;
+
+/// Mirror and rotational symmetry of a volume, so every `SculptBrush` applied
+/// to it is also applied mirrored and rotated copies of itself. Planes and the
+/// rotation axis pass through `center`, in the volume's local space.
+///
+/// Copies landing on the centre of one already applied are dropped, so a brush
+/// on a symmetry plane or axis isn't applied twice.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SculptSymmetry {
+    /// Mirror across the plane perpendicular to each set axis
+    pub mirror: BVec3,
+    /// Copies rotated evenly around the Y axis, 0 or 1 for none
+    pub radial: u32,
+    /// The middle of the volume by default
+    pub center: Option<Vec3>,
+}
+
+impl SculptSymmetry {
+    pub fn mirrored(mirror: BVec3) -> Self {
+        Self {
+            mirror,
+            ..default()
+        }
+    }
+
+    pub fn radial(count: u32) -> Self {
+        Self {
+            radial: count,
+            ..default()
+        }
+    }
+
+    pub fn with_mirror(mut self, mirror: BVec3) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    pub fn with_radial(mut self, count: u32) -> Self {
+        self.radial = count;
+        self
+    }
+
+    pub fn with_center(mut self, center: Vec3) -> Self {
+        self.center = Some(center);
+        self
+    }
+
+    /// Every copy in local space, the identity first, about `center`.
+    pub fn transforms(&self, center: Vec3) -> Vec<Affine3A> {
+        let mirrors = (0..8u32).filter_map(|axes| {
+            let flip = BVec3::new(axes & 1 != 0, axes & 2 != 0, axes & 4 != 0);
+            (flip & !self.mirror == BVec3::FALSE)
+                .then(|| Affine3A::from_scale(Vec3::select(flip, Vec3::NEG_ONE, Vec3::ONE)))
+        });
+        let count = self.radial.max(1);
+        let rotations: Vec<_> = (0..count)
+            .map(|i| Affine3A::from_rotation_y(i as f32 * std::f32::consts::TAU / count as f32))
+            .collect();
+        let to_center = Affine3A::from_translation(center);
+        let from_center = Affine3A::from_translation(-center);
+        mirrors
+            .flat_map(|mirror| {
+                rotations
+                    .iter()
+                    .map(move |&rotation| to_center * rotation * mirror * from_center)
+            })
+            .collect()
+    }
+}
+
+/// The brush's space, and world space for planes, to grid space for each of
+/// the brush's symmetric copies on the volume.
+fn symmetric_transforms(
+    brush: &SculptBrush,
+    symmetry: Option<&SculptSymmetry>,
+    global_transform: &GlobalTransform,
+    scale: Vec3,
+    dimensions: UVec3,
+) -> Vec<(Affine3A, Affine3A)> {
+    // Grid sample (x, y, z) sits at (x, y, z) times the spacing in local space
+    let grid_from_local = Affine3A::from_scale(scale.recip());
+    let local_from_world = global_transform.affine().inverse();
+    let brush_affine = brush.transform.compute_affine();
+    let Some(symmetry) = symmetry else {
+        let grid_from_world = grid_from_local * local_from_world;
+        return vec![(grid_from_world * brush_affine, grid_from_world)];
+    };
+
+    let center = symmetry
+        .center
+        .unwrap_or(scale * dimensions.saturating_sub(UVec3::ONE).as_vec3() / 2.0);
+    let mut copies: Vec<(Affine3A, Affine3A)> = Vec::new();
+    for mirror in symmetry.transforms(center) {
+        let grid_from_world = grid_from_local * mirror * local_from_world;
+        let transform = grid_from_world * brush_affine;
+        let landed = copies
+            .iter()
+            .any(|(copy, _)| copy.translation.distance_squared(transform.translation) < 1e-6);
+        if !landed {
+            copies.push((transform, grid_from_world));
+        }
+    }
+    copies
+}
+
+/// Queue the `SculptBrush` triggered on a volume, and its symmetric copies,
+/// for its density buffer, or paint its materials.
+pub fn apply_sculpt_brush(
+    brush: On<SculptBrush>,
+    mut commands: Commands,
+    volumes: Query<(&SurfaceNetsBuffers, &GlobalTransform, Option<&VoxelSpacing>)>,
+    mut materials: Query<(
+        &mut MaterialField,
+        &DensityField,
+        &GlobalTransform,
+        Option<&VoxelSpacing>,
+    )>,
+    symmetries: Query<&SculptSymmetry>,
+    mesh_size: Res<DensityFieldMeshSize>,
+    field_size: Res<DensityFieldSize>,
+) {
+    let entity = brush.entity;
+    let symmetry = symmetries.get(entity).ok();
+    if let BrushMode::Paint(material) = brush.mode {
+        let Ok((mut materials, density_field, global_transform, spacing)) =
+            materials.get_mut(entity)
+        else {
+            warn!("Can't paint {entity}: it has no DensityField and MaterialField");
+            return;
+        };
+        let dimensions = field_size.0;
+        if materials.len() != density_field.len() {
+            warn!("Can't paint {entity}: its MaterialField doesn't match its DensityField");
+            return;
+        }
+        let scale = VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions);
+        for (transform, _) in
+            symmetric_transforms(&brush, symmetry, global_transform, scale, dimensions)
+        {
+            paint_materials(
+                &brush,
+                material,
+                transform,
+                dimensions,
+                materials.reborrow(),
+                density_field,
+            );
+        }
+        return;
+    }
+
+    let Ok((buffers, global_transform, spacing)) = volumes.get(entity) else {
+        warn!("Can't sculpt {entity}: it has no density buffer yet");
+        return;
+    };
+    if buffers.density_texture.is_some() || buffers.density_decode.format != DENSITY_FORMAT_F32 {
+        warn!("Can't sculpt {entity}: its densities are quantized or a texture");
+        return;
+    }
+
+    let dimensions = buffers.dimensions.0;
+    let scale = VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions);
+    let strokes: Vec<_> =
+        symmetric_transforms(&brush, symmetry, global_transform, scale, dimensions)
+            .into_iter()
+            .filter_map(|(transform, grid_from_world)| {
+                gpu_stroke(&brush, transform, grid_from_world, dimensions)
+            })
+            .collect();
+    if strokes.is_empty() {
+        return;
+    }
+    commands
+        .entity(entity)
+        .entry::<QueuedBrushStrokes>()
+        .or_default()
+        .and_modify(move |mut queued| queued.0.extend(strokes));
+}
+
+/// The application of `brush`, placed in grid space by `transform`, for the
+/// brush kernel, along with the region it covers, or `None` if it misses the
+/// volume. Fixed planes are placed by `grid_from_world`.
+fn gpu_stroke(
+    brush: &SculptBrush,
+    transform: Affine3A,
+    grid_from_world: Affine3A,
+    dimensions: UVec3,
+) -> Option<(GpuBrushStroke, DensityFieldDirtyRegion)> {
+    let (min, max) = transform_bounds(
+        Vec3::splat(-brush.radius),
+        Vec3::splat(brush.radius),
+        transform,
+    );
+    let min = min.floor().max(Vec3::ZERO).as_uvec3();
+    let max = (max.ceil() + 1.0).min(dimensions.as_vec3()).as_uvec3();
+    if min.cmpge(max).any() {
+        return None;
+    }
+    // Smoothing and flattening read samples beyond the region
+    let reach = brush.mode.reach();
+    let snapshot_min = min.saturating_sub(UVec3::splat(reach));
+    let snapshot_max = (max + reach).min(dimensions);
+
+    let center = Vec3::from(transform.translation);
+    let (blur, blur_radius, sigma) = match brush.mode {
+        BrushMode::Smooth(kernel @ BlurKernel::Gaussian { sigma }) => {
+            (BLUR_GAUSSIAN, kernel.radius(), sigma.max(f32::EPSILON))
+        }
+        BrushMode::Smooth(kernel) => (BLUR_BOX, kernel.radius(), 0.0),
+        _ => (BLUR_BOX, 0, 0.0),
+    };
+    let (plane, plane_source) = reference_plane(brush.mode, transform, grid_from_world);
+    let part = GpuSdfPart::new(
+        brush.shape.primitive(brush.radius),
+        Affine3A::IDENTITY,
+        CsgOperation::Union,
+    );
+    let (falloff, curve) = brush.falloff.gpu_falloff();
+    let params = BrushParams {
+        inverse: Mat4::from(transform.inverse()),
+        plane,
+        size: part.size,
+        center,
+        region_min: min,
+        region_size: max - min,
+        snapshot_min,
+        snapshot_size: snapshot_max - snapshot_min,
+        dimensions,
+        shape: part.shape,
+        mode: brush.mode.gpu_mode(),
+        falloff,
+        curve_length: curve.len() as u32,
+        blur,
+        blur_radius,
+        plane_source,
+        sigma,
+        radius: brush.radius,
+        strength: brush.strength,
+    };
+    Some((
+        GpuBrushStroke { params, curve },
+        DensityFieldDirtyRegion::new(min, max),
+    ))
+}
+
+/// Set `material` on the solid samples `brush`, placed in grid space by
+/// `transform`, reaches with a weight above 0, marking the field changed only
+/// if a sample changed so untouched volumes aren't remeshed.
+fn paint_materials(
+    brush: &SculptBrush,
+    material: u32,
+    transform: Affine3A,
+    dimensions: UVec3,
+    mut materials: Mut<MaterialField>,
+    density_field: &DensityField,
+) {
+    if brush.strength <= 0.0 {
+        return;
+    }
+    let (min, max) = transform_bounds(
+        Vec3::splat(-brush.radius),
+        Vec3::splat(brush.radius),
+        transform,
+    );
+    let min = min.floor().max(Vec3::ZERO).as_uvec3();
+    let max = (max.ceil() + 1.0).min(dimensions.as_vec3()).as_uvec3();
+    let primitive = brush.shape.primitive(brush.radius);
+    let inverse = transform.inverse();
+
+    let mut painted = false;
+    for z in min.z..max.z {
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let i = index(dimensions, x, y, z) as usize;
+                if density_field[i] >= 0.0 || materials[i] == material {
+                    continue;
+                }
+                let p = inverse.transform_point3(vec3(x as f32, y as f32, z as f32));
+                let distance = primitive.distance(p);
+                if distance >= 0.0 {
+                    continue;
+                }
+                let t = (1.0 + distance / brush.radius).clamp(0.0, 1.0);
+                if brush.falloff.weight(t) > 0.0 {
+                    materials.bypass_change_detection()[i] = material;
+                    painted = true;
+                }
+            }
+        }
+    }
+    if painted {
+        materials.set_changed();
+    }
+}
+
+/// The plane a flattening brush placed in grid space by `transform` levels
+/// onto, as a normal and offset in grid space, and where the kernel takes it
+/// from.
+pub(crate) fn reference_plane(
+    mode: BrushMode,
+    transform: Affine3A,
+    grid_from_world: Affine3A,
+) -> (Vec4, u32) {
+    let center = Vec3::from(transform.translation);
+    match mode {
+        BrushMode::Flatten(FlattenPlane::Fixed { point, normal }) => (
+            grid_plane(
+                grid_from_world,
+                grid_from_world.transform_point3(point),
+                *normal,
+            ),
+            PLANE_FIXED,
+        ),
+        BrushMode::Flatten(FlattenPlane::Surface) => {
+            (grid_plane(transform, center, Vec3::Y), PLANE_SURFACE)
+        }
+        _ => (grid_plane(transform, center, Vec3::Y), PLANE_FIXED),
+    }
+}
+
+/// The plane through the grid space `point` across `normal`, which
+/// `transform` takes into grid space, as a normal and offset.
+fn grid_plane(transform: Affine3A, point: Vec3, normal: Vec3) -> Vec4 {
+    // Normals transform with the inverse transpose
+    let normal = (Mat3::from(transform.matrix3.inverse()).transpose() * normal).normalize_or_zero();
+    normal.extend(-normal.dot(point))
+}
+
+/// Hand the queued brush applications of meshed volumes to the render world,
+/// remeshing the region they cover and reading it back into the volume's
+/// `DensityField`, and drop last frame's.
+pub fn flush_brush_strokes(
+    mut commands: Commands,
+    previous: Query<Entity, With<PendingBrushStrokes>>,
+    previous_write_backs: Query<Entity, (With<BrushWriteBack>, With<Readback>)>,
+    mut volumes: Query<
+        (
+            Entity,
+            &mut QueuedBrushStrokes,
+            &SurfaceNetsBuffers,
+            Option<&DensityField>,
+            Option<&SculptSettings>,
+        ),
+        (
+            With<Meshed>,
+            Without<Remesh>,
+            Without<DensityFieldDirtyRegion>,
+            Without<WritingBackBrushStrokes>,
+        ),
+    >,
+    frame: Res<FrameCount>,
+) {
+    // Strokes are extracted once, and read back in the frame they are applied
+    for entity in &previous {
+        commands.entity(entity).remove::<PendingBrushStrokes>();
+    }
+    for entity in &previous_write_backs {
+        commands.entity(entity).remove::<Readback>();
+    }
+
+    for (entity, mut queued, buffers, density_field, settings) in &mut volumes {
+        let queued = std::mem::take(&mut queued.0);
+        let Some(region) = queued
+            .iter()
+            .map(|(_, region)| *region)
+            .reduce(|a, b| a.union(&b))
+        else {
+            continue;
+        };
+        let strokes = queued.into_iter().map(|(stroke, _)| stroke).collect();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert((
+            PendingBrushStrokes(strokes),
+            buffers
+                .algorithm
+                .backend()
+                .meshing_region(region, buffers.dimensions),
+            Remesh,
+        ));
+        if let Some(stages_per_frame) = settings.and_then(|settings| settings.stages_per_frame) {
+            entity_commands.insert(StageSchedule::new(stages_per_frame, &frame));
+        }
+
+        // GpuDensity volumes have no field to write back to
+        let Some(density_field) = density_field
+            .filter(|field| field.len() == buffers.dimensions.density_count() as usize)
+        else {
+            continue;
+        };
+        let Some(region) = region.clamped(buffers.dimensions) else {
+            continue;
+        };
+        // Rows of the region are strided, read back the span covering them all
+        let start = buffers
+            .dimensions
+            .index(region.min.x, region.min.y, region.min.z) as usize;
+        let end = buffers
+            .dimensions
+            .index(region.max.x - 1, region.max.y - 1, region.max.z - 1) as usize
+            + 1;
+        entity_commands.insert(WritingBackBrushStrokes);
+        commands
+            .spawn((
+                Readback::buffer_range(
+                    buffers.density_field.clone(),
+                    (start * size_of::<f32>()) as u64,
+                    ((end - start) * size_of::<f32>()) as u64,
+                ),
+                BrushWriteBack {
+                    region,
+                    start,
+                    before: density_field[start..end].to_vec(),
+                },
+                ChildOf(entity),
+            ))
+            .observe(write_back_brush_strokes);
+    }
+}
+
+/// Copy the densities read back for a `BrushWriteBack` into its volume's
+/// `DensityField`, except where the field was edited since, and report them.
+fn write_back_brush_strokes(
+    event: On<ReadbackComplete>,
+    mut commands: Commands,
+    write_backs: Query<(&BrushWriteBack, &ChildOf)>,
+    mut fields: Query<&mut DensityField>,
+    mut edited: MessageWriter<DensityEdited>,
+) {
+    commands.entity(event.entity).try_despawn();
+    // The volume was despawned meanwhile
+    let Ok((write_back, &ChildOf(volume))) = write_backs.get(event.entity) else {
+        return;
+    };
+    commands
+        .entity(volume)
+        .try_remove::<WritingBackBrushStrokes>();
+    let Ok(mut density_field) = fields.get_mut(volume) else {
+        return;
+    };
+
+    let densities: Vec<f32> = event.to_shader_type();
+    let end = write_back.start + write_back.before.len();
+    let Some(samples) = density_field
+        .bypass_change_detection()
+        .get_mut(write_back.start..end)
+    else {
+        return;
+    };
+    for ((sample, &before), &density) in samples.iter_mut().zip(&write_back.before).zip(&densities)
+    {
+        if *sample == before {
+            *sample = density;
+        }
+    }
+    edited.write(DensityEdited {
+        entity: volume,
+        region: write_back.region,
+    });
+}
+
+/// Apply each `HeldBrush` for the time since last frame, along the path it
+/// took.
+pub fn apply_held_brushes(
+    mut commands: Commands,
+    mut held_brushes: Query<&mut HeldBrush>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+    for mut held in &mut held_brushes {
+        held.held += delta;
+        let ramp = match held.ramp > 0.0 {
+            true => (held.held / held.ramp).min(1.0),
+            false => 1.0,
+        };
+        let mut brush = held.brush.clone();
+        brush.strength *= ramp * delta;
+        let from = held.last_position.unwrap_or(brush.transform.translation);
+        for brush in brush.interpolated(from, held.spacing) {
+            commands.trigger(brush);
+        }
+        held.last_position = Some(brush.transform.translation);
+    }
+}
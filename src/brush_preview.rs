@@ -0,0 +1,250 @@
+use bevy::{ecs::entity::EntityHashSet, math::Affine3A, prelude::*};
+use sculpter_core::{grid::index, transform_bounds};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize,
+    brush::{BlurKernel, BrushMode, BrushShape, SculptBrush, reference_plane},
+    dirty_region::DensityEdited,
+    mesh_data::ToMesh,
+    meshing::surface_nets_cpu,
+    settings::SculptSettings,
+    units::VoxelSpacing,
+};
+
+/// Draw a brush's reach with gizmos, e.g. under the cursor before a stroke,
+/// without applying it.
+///
+/// With `ghost`, the surface the brush would leave on its volume is also shown
+/// as a translucent child of the volume, remeshed on the CPU from every
+/// `ghost`th sample around the brush only. Only volumes with a `DensityField`
+/// get a ghost, and flattening onto the surface is previewed as flattening
+/// onto the brush's plane.
+#[derive(Component, Clone, Debug)]
+pub struct BrushPreview {
+    pub brush: SculptBrush,
+    pub color: Color,
+    /// Samples between those the ghost is meshed from, or `None` for no ghost
+    pub ghost: Option<u32>,
+}
+
+impl BrushPreview {
+    pub fn new(brush: SculptBrush) -> Self {
+        Self {
+            brush,
+            color: Color::srgb(0.3, 0.7, 1.0),
+            ghost: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_ghost(mut self, resolution: u32) -> Self {
+        self.ghost = Some(resolution.max(1));
+        self
+    }
+}
+
+/// Child of a volume showing the ghost surface of the `BrushPreview` on this
+/// entity.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BrushGhost(pub Entity);
+
+/// Outline the reach of every `BrushPreview`.
+pub fn draw_brush_previews(mut gizmos: Gizmos, previews: Query<&BrushPreview>) {
+    for preview in &previews {
+        let brush = &preview.brush;
+        let transform = brush.transform;
+        let isometry = Isometry3d::new(transform.translation, transform.rotation);
+        let radius = brush.radius * transform.scale.max_element();
+        match brush.shape {
+            BrushShape::Sphere => {
+                gizmos.primitive_3d(&Sphere::new(radius), isometry, preview.color);
+            }
+            BrushShape::Cube => {
+                gizmos.primitive_3d(&Cuboid::from_length(2.0 * radius), isometry, preview.color);
+            }
+            BrushShape::Cylinder => {
+                gizmos.primitive_3d(
+                    &Cylinder::new(radius, 2.0 * radius),
+                    isometry,
+                    preview.color,
+                );
+            }
+        }
+    }
+}
+
+/// Remesh the ghosts of changed `BrushPreview`s, or of previews whose volume
+/// was edited or sculpted, and despawn the ghosts of those gone or without one.
+pub fn update_brush_ghosts(
+    mut commands: Commands,
+    previews: Query<(Entity, Ref<BrushPreview>)>,
+    volumes: Query<(Ref<DensityField>, &GlobalTransform, Option<&VoxelSpacing>)>,
+    ghosts: Query<(Entity, &BrushGhost, &Mesh3d, &ChildOf)>,
+    mut sculpted: MessageReader<DensityEdited>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    dimensions: Res<DensityFieldSize>,
+    mesh_size: Res<DensityFieldMeshSize>,
+) {
+    let sculpted: EntityHashSet = sculpted.read().map(|sculpted| sculpted.entity).collect();
+    for (ghost, &BrushGhost(preview), _, &ChildOf(volume)) in &ghosts {
+        let wanted = previews
+            .get(preview)
+            .is_ok_and(|(_, preview)| preview.ghost.is_some() && preview.brush.entity == volume);
+        if !wanted {
+            commands.entity(ghost).try_despawn();
+        }
+    }
+
+    for (entity, preview) in &previews {
+        let Some(resolution) = preview.ghost else {
+            continue;
+        };
+        let volume = preview.brush.entity;
+        let Ok((density_field, global_transform, spacing)) = volumes.get(volume) else {
+            continue;
+        };
+        let existing = ghosts
+            .iter()
+            .find(|(_, ghost, _, child_of)| ghost.0 == entity && child_of.parent() == volume);
+        let edited = density_field.is_changed() || sculpted.contains(&volume);
+        if existing.is_some() && !preview.is_changed() && !edited {
+            continue;
+        }
+
+        let dims = dimensions.0;
+        let scale = VoxelSpacing::volume_scale(spacing, *mesh_size, dims);
+        let grid_from_world =
+            Affine3A::from_scale(scale.recip()) * global_transform.affine().inverse();
+        let Some((mesh, min)) = ghost_surface(
+            &preview.brush,
+            grid_from_world,
+            &density_field,
+            dims,
+            resolution,
+        ) else {
+            if let Some((ghost, ..)) = existing {
+                commands.entity(ghost).try_despawn();
+            }
+            continue;
+        };
+        let transform = Transform::from_translation(min.as_vec3() * scale)
+            .with_scale(scale * resolution as f32);
+        match existing {
+            Some((ghost, _, Mesh3d(handle), _)) => {
+                let _ = meshes.insert(handle, mesh);
+                commands.entity(ghost).try_insert(transform);
+            }
+            None => {
+                commands.spawn((
+                    BrushGhost(entity),
+                    Mesh3d(meshes.add(mesh)),
+                    MeshMaterial3d(materials.add(StandardMaterial {
+                        base_color: preview.color.with_alpha(0.35),
+                        alpha_mode: AlphaMode::Blend,
+                        ..default()
+                    })),
+                    transform,
+                    ChildOf(volume),
+                ));
+            }
+        }
+    }
+}
+
+/// The surface `brush` would leave around it, meshed from every `resolution`th
+/// sample in the grid space of the box it starts at, and the box's first
+/// sample, or `None` if the brush misses the field.
+fn ghost_surface(
+    brush: &SculptBrush,
+    grid_from_world: Affine3A,
+    density_field: &DensityField,
+    dims: UVec3,
+    resolution: u32,
+) -> Option<(Mesh, UVec3)> {
+    let transform = grid_from_world * brush.transform.compute_affine();
+    let (min, max) = transform_bounds(
+        Vec3::splat(-brush.radius),
+        Vec3::splat(brush.radius),
+        transform,
+    );
+    // Room for the neighbours smoothing reads, and to show where the surface
+    // meets the untouched field
+    let margin = brush.mode.reach().max(1) as f32;
+    let min = (min.floor() - margin).max(Vec3::ZERO).as_uvec3();
+    let max = (max.ceil() + 1.0 + margin).min(dims.as_vec3()).as_uvec3();
+    let before = density_field.copy_region(dims, min, max)?;
+    let size = before.dimensions;
+
+    let primitive = brush.shape.primitive(brush.radius);
+    let inverse = transform.inverse();
+    let (plane, _) = reference_plane(brush.mode, transform, grid_from_world);
+    let mut densities = before.densities.0.clone();
+    for z in 0..size.z {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let local = uvec3(x, y, z);
+                let sample = (min + local).as_vec3();
+                let distance = primitive.distance(inverse.transform_point3(sample));
+                if distance >= 0.0 {
+                    continue;
+                }
+                let t = (1.0 + distance / brush.radius).clamp(0.0, 1.0);
+                let amount = brush.strength * brush.falloff.weight(t);
+                let i = index(size, x, y, z) as usize;
+                let density = densities[i];
+                densities[i] = match brush.mode {
+                    BrushMode::Add => density - amount,
+                    BrushMode::Subtract => density + amount,
+                    BrushMode::Smooth(kernel) => density.lerp(
+                        blurred(&before.densities, size, local, kernel),
+                        amount.clamp(0.0, 1.0),
+                    ),
+                    BrushMode::Flatten(_) => {
+                        let target = plane.truncate().dot(sample) + plane.w;
+                        density.lerp(target, amount.clamp(0.0, 1.0))
+                    }
+                    BrushMode::Paint(_) => density,
+                };
+            }
+        }
+    }
+
+    let (densities, low) = sculpter_core::grid::downsample(&densities, size, resolution);
+    if low.cmplt(UVec3::splat(2)).any() {
+        return None;
+    }
+    let mesh_data = surface_nets_cpu(&densities, low, &SculptSettings::default());
+    Some((mesh_data.to_mesh(), min))
+}
+
+/// The densities around `sample` of a `size` box averaged by `kernel`, as the
+/// smoothing brush kernel does.
+fn blurred(densities: &[f32], size: UVec3, sample: UVec3, kernel: BlurKernel) -> f32 {
+    let r = kernel.radius() as i32;
+    let (mut total, mut weights) = (0.0, 0.0);
+    for z in -r..=r {
+        for y in -r..=r {
+            for x in -r..=r {
+                let offset = ivec3(x, y, z);
+                let p = (sample.as_ivec3() + offset)
+                    .clamp(IVec3::ZERO, size.as_ivec3() - 1)
+                    .as_uvec3();
+                let weight = match kernel {
+                    BlurKernel::Gaussian { sigma } => {
+                        let sigma = sigma.max(f32::EPSILON);
+                        (-offset.as_vec3().length_squared() / (2.0 * sigma * sigma)).exp()
+                    }
+                    BlurKernel::Box { .. } => 1.0,
+                };
+                total += weight * densities[index(size, p.x, p.y, p.z) as usize];
+                weights += weight;
+            }
+        }
+    }
+    total / weights
+}
@@ -1,16 +1,43 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::diagnostic::FrameCount;
 use bevy::prelude::*;
+use bevy::render::extract_component::ExtractComponent;
 use bevy::render::render_resource::*;
 use bevy::render::storage::ShaderStorageBuffer;
 
-use crate::{DensityField, DensityFieldSize};
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize, DensityTexture,
+    amortize::StageSchedule,
+    backpressure::{
+        BackpressurePolicy, CpuFallback, CriticalRemesh, GpuBackpressure, GpuGenerationStarted,
+        GpuMeshingLoad, critical_first,
+    },
+    cpu::ComputeShaderSupport,
+    dirty_region::MeshingRegion,
+    gpu_density::{DensityGeneration, DensityKernel, GpuDensity},
+    material::MaterialField,
+    quantize::{DensityDecode, DensityQuantization},
+    settings::{MeshingAlgorithm, SculptSettings, SurfaceNetsParams},
+    units::VoxelSpacing,
+};
 
 // Component that holds GPU buffers during generation (one per generating entity)
-#[derive(Component)]
+#[derive(Component, ExtractComponent, Clone)]
 pub struct SurfaceNetsBuffers {
     // Stage 0: Inputs
     pub density_field: Handle<ShaderStorageBuffer>,
+    //Material id per sample, laid out like `density_field`, or a single id without a `MaterialField`
+    pub material_field: Handle<ShaderStorageBuffer>,
     //Dimensions of the Input
     pub dimensions: DensityFieldSize,
+    //How the density buffer is encoded (f32 or quantized bytes)
+    pub density_decode: DensityDecode,
+    //3D texture sampled instead of `density_field` when set
+    pub density_texture: Option<Handle<Image>>,
+    //Meshing options from the volume's `SculptSettings`
+    pub params: SurfaceNetsParams,
+    //Extraction algorithm, decides the kernels and the slot counts below
+    pub algorithm: MeshingAlgorithm,
     //pub dimensions: Handle<ShaderStorageBuffer>,
 
     // Stage 1: Generate Vertices
@@ -30,32 +57,175 @@ pub struct SurfaceNetsBuffers {
     pub face_indices: Handle<ShaderStorageBuffer>,
     pub face_count: Handle<ShaderStorageBuffer>,
     pub compacted_faces: Handle<ShaderStorageBuffer>,
+
+    // Stage 7: Vertex Normals
+    pub compacted_normals: Handle<ShaderStorageBuffer>,
+    pub compacted_occlusion: Handle<ShaderStorageBuffer>,
+    pub compacted_material_ids: Handle<ShaderStorageBuffer>,
+    pub compacted_material_weights: Handle<ShaderStorageBuffer>,
 }
 
 impl SurfaceNetsBuffers {
     pub fn new(
-        density_field: &DensityField,
+        density_field: &[f32],
+        material_field: Option<&[u32]>,
+        dimensions: &DensityFieldSize,
+        quantization: Option<&DensityQuantization>,
+        settings: &SculptSettings,
+        algorithm: MeshingAlgorithm,
+        buffers: &mut Assets<ShaderStorageBuffer>,
+    ) -> Self {
+        // Create density field buffer, packing quantized fields 4 bytes per u32
+        let density_buffer = match quantization {
+            Some(quantization) => ShaderStorageBuffer::new(
+                &quantization.encode(density_field),
+                RenderAssetUsages::default(),
+            ),
+            None => ShaderStorageBuffer::from(density_field.to_vec()),
+        };
+        let decode = quantization.map(DensityDecode::from).unwrap_or_default();
+        Self::with_density(
+            density_buffer,
+            material_field,
+            None,
+            decode,
+            settings,
+            algorithm,
+            dimensions,
+            buffers,
+        )
+    }
+
+    /// Buffers for a field sampled from a 3D texture rather than uploaded from the CPU.
+    pub fn from_texture(
+        texture: Handle<Image>,
+        dimensions: &DensityFieldSize,
+        settings: &SculptSettings,
+        algorithm: MeshingAlgorithm,
+        buffers: &mut Assets<ShaderStorageBuffer>,
+    ) -> Self {
+        // The storage binding is unused on the texture path, keep it minimal
+        let density_buffer = ShaderStorageBuffer::from(vec![0u32; 1]);
+        Self::with_density(
+            density_buffer,
+            None,
+            Some(texture),
+            DensityDecode::default(),
+            settings,
+            algorithm,
+            dimensions,
+            buffers,
+        )
+    }
+
+    /// Buffers for a field generated on the GPU, never uploaded from the CPU.
+    pub fn generated(
+        dimensions: &DensityFieldSize,
+        settings: &SculptSettings,
+        algorithm: MeshingAlgorithm,
+        buffers: &mut Assets<ShaderStorageBuffer>,
+    ) -> Self {
+        let density_buffer = ShaderStorageBuffer::with_size(
+            dimensions.density_count() as usize * size_of::<f32>(),
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        Self::with_density(
+            density_buffer,
+            None,
+            None,
+            DensityDecode::default(),
+            settings,
+            algorithm,
+            dimensions,
+            buffers,
+        )
+    }
+
+    fn with_density(
+        mut density_buffer: ShaderStorageBuffer,
+        material_field: Option<&[u32]>,
+        density_texture: Option<Handle<Image>>,
+        density_decode: DensityDecode,
+        settings: &SculptSettings,
+        algorithm: MeshingAlgorithm,
         dimensions: &DensityFieldSize,
-        buffers: &mut ResMut<Assets<ShaderStorageBuffer>>,
+        buffers: &mut Assets<ShaderStorageBuffer>,
     ) -> Self {
-        let cell_count = dimensions.cell_count();
-        let max_faces = cell_count * 3;
+        // Sculpted regions are read back into the volume's field
+        density_buffer.buffer_description.usage |=
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+
+        // Volumes without materials bind a single id
+        let mut material_buffer = ShaderStorageBuffer::from(
+            material_field.map_or_else(|| vec![0u32; 1], <[u32]>::to_vec),
+        );
+        material_buffer.buffer_description.usage |= BufferUsages::STORAGE | BufferUsages::COPY_DST;
 
-        // Create density field buffer
-        let mut density_buffer = ShaderStorageBuffer::from(density_field.0.clone());
-        density_buffer.buffer_description.usage |= BufferUsages::STORAGE | BufferUsages::COPY_DST;
+        Self::with_inputs(
+            buffers.add(density_buffer),
+            buffers.add(material_buffer),
+            density_texture,
+            density_decode,
+            SurfaceNetsParams {
+                materials: material_field.is_some() as u32,
+                source_dimensions: dimensions.0,
+                ..SurfaceNetsParams::from(settings)
+            },
+            algorithm,
+            dimensions,
+            buffers,
+        )
+    }
+
+    /// Buffers meshing this volume's field every `factor` samples, for a coarser
+    /// level of detail. The inputs are shared, so the level sees every density
+    /// written to the volume, whether uploaded, generated or sculpted.
+    pub fn lod_level(&self, factor: u32, buffers: &mut Assets<ShaderStorageBuffer>) -> Self {
+        let factor = factor.max(1);
+        // As `grid::downsample` does on the CPU
+        let dimensions =
+            DensityFieldSize(self.dimensions.0.saturating_sub(UVec3::ONE) / factor + UVec3::ONE);
+        Self::with_inputs(
+            self.density_field.clone(),
+            self.material_field.clone(),
+            self.density_texture.clone(),
+            self.density_decode,
+            SurfaceNetsParams {
+                stride: self.params.stride * factor,
+                ..self.params
+            },
+            self.algorithm,
+            &dimensions,
+            buffers,
+        )
+    }
+
+    fn with_inputs(
+        density_field: Handle<ShaderStorageBuffer>,
+        material_field: Handle<ShaderStorageBuffer>,
+        density_texture: Option<Handle<Image>>,
+        density_decode: DensityDecode,
+        params: SurfaceNetsParams,
+        algorithm: MeshingAlgorithm,
+        dimensions: &DensityFieldSize,
+        buffers: &mut Assets<ShaderStorageBuffer>,
+    ) -> Self {
+        // Slots are indexed by grid point, so size by points rather than cells
+        let vertex_slots = dimensions.density_count() * algorithm.backend().vertices_per_point();
+        let max_faces = dimensions.density_count() * algorithm.backend().faces_per_point();
 
         // Stage 1 buffers: Generate Vertices
         let mut vertices_buffer =
-            ShaderStorageBuffer::from(vec![0.0f32; (cell_count * 3) as usize]);
+            ShaderStorageBuffer::from(vec![0.0f32; (vertex_slots * 3) as usize]);
         vertices_buffer.buffer_description.usage |= BufferUsages::STORAGE | BufferUsages::COPY_SRC;
 
-        let mut vertex_valid_buffer = ShaderStorageBuffer::from(vec![0u32; cell_count as usize]);
+        let mut vertex_valid_buffer = ShaderStorageBuffer::from(vec![0u32; vertex_slots as usize]);
         vertex_valid_buffer.buffer_description.usage |=
             BufferUsages::STORAGE | BufferUsages::COPY_SRC;
 
         // Stage 2 buffers: Prefix Sum (vertices)
-        let mut vertex_indices_buffer = ShaderStorageBuffer::from(vec![0u32; cell_count as usize]);
+        let mut vertex_indices_buffer =
+            ShaderStorageBuffer::from(vec![0u32; vertex_slots as usize]);
         vertex_indices_buffer.buffer_description.usage |=
             BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
 
@@ -65,7 +235,7 @@ impl SurfaceNetsBuffers {
 
         // Stage 3 buffers: Compact Vertices
         let mut compacted_vertices_buffer =
-            ShaderStorageBuffer::from(vec![0.0f32; (cell_count * 3) as usize]);
+            ShaderStorageBuffer::from(vec![0.0f32; (vertex_slots * 3) as usize]);
         compacted_vertices_buffer.buffer_description.usage |=
             BufferUsages::STORAGE | BufferUsages::COPY_SRC;
 
@@ -92,8 +262,39 @@ impl SurfaceNetsBuffers {
         compacted_faces_buffer.buffer_description.usage |=
             BufferUsages::STORAGE | BufferUsages::COPY_SRC;
 
+        // Stage 7 buffers: Vertex Normals
+        let mut compacted_normals_buffer =
+            ShaderStorageBuffer::from(vec![0.0f32; (vertex_slots * 3) as usize]);
+        compacted_normals_buffer.buffer_description.usage |=
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC;
+
+        // Ambient occlusion is only written when enabled, keep the buffer minimal otherwise
+        let occlusion_slots = match params.occlusion_rays {
+            0 => 1,
+            _ => vertex_slots as usize,
+        };
+        let mut compacted_occlusion_buffer =
+            ShaderStorageBuffer::from(vec![0.0f32; occlusion_slots]);
+        compacted_occlusion_buffer.buffer_description.usage |=
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC;
+
+        // Likewise for materials, written for volumes with a material field
+        let material_slots = match params.materials {
+            0 => 1,
+            _ => vertex_slots as usize,
+        };
+        let mut compacted_material_ids_buffer =
+            ShaderStorageBuffer::from(vec![0u32; material_slots]);
+        compacted_material_ids_buffer.buffer_description.usage |=
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC;
+        let mut compacted_material_weights_buffer =
+            ShaderStorageBuffer::from(vec![0.0f32; material_slots * 4]);
+        compacted_material_weights_buffer.buffer_description.usage |=
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC;
+
         SurfaceNetsBuffers {
-            density_field: buffers.add(density_buffer),
+            density_field,
+            material_field,
             vertices: buffers.add(vertices_buffer),
             vertex_valid: buffers.add(vertex_valid_buffer),
             vertex_indices: buffers.add(vertex_indices_buffer),
@@ -104,25 +305,174 @@ impl SurfaceNetsBuffers {
             face_indices: buffers.add(face_indices_buffer),
             face_count: buffers.add(face_count_buffer),
             compacted_faces: buffers.add(compacted_faces_buffer),
+            compacted_normals: buffers.add(compacted_normals_buffer),
+            compacted_occlusion: buffers.add(compacted_occlusion_buffer),
+            compacted_material_ids: buffers.add(compacted_material_ids_buffer),
+            compacted_material_weights: buffers.add(compacted_material_weights_buffer),
             dimensions: *dimensions,
+            density_decode,
+            density_texture,
+            params,
+            algorithm,
         }
     }
+
+    /// Length of the per-slot vertex arrays.
+    pub fn vertex_slots(&self) -> u32 {
+        self.dimensions.density_count() * self.algorithm.backend().vertices_per_point()
+    }
+
+    /// Length of the per-slot face arrays.
+    pub fn face_slots(&self) -> u32 {
+        self.dimensions.density_count() * self.algorithm.backend().faces_per_point()
+    }
 }
 
-/// Prepare Buffers (per entResMut<Assets<ShaderStorageBuffer>>ity)
+/// Prepare Buffers (per entity), admitting only as many volumes as the GPU backpressure limits allow
 pub fn prepare_surface_nets_buffers(
     mut commands: Commands,
-    // Query entities that have DensityField but no Mesh3d
+    // Query entities that have DensityField but no GPU buffers or Mesh3d yet
     needs_mesh_query: Query<
-        (Entity, &DensityField),
-        Or<(Without<SurfaceNetsBuffers>, Without<Mesh3d>)>,
+        (
+            Entity,
+            Option<&DensityField>,
+            Option<&MaterialField>,
+            Option<&DensityTexture>,
+            Option<&DensityQuantization>,
+            Option<&SculptSettings>,
+            Option<&MeshingAlgorithm>,
+            Has<CriticalRemesh>,
+            Option<&GpuDensity>,
+            Option<&DensityKernel>,
+            Option<&VoxelSpacing>,
+        ),
+        (
+            Or<(With<DensityField>, With<DensityTexture>, With<GpuDensity>)>,
+            Without<SurfaceNetsBuffers>,
+            Without<Mesh3d>,
+            Without<CpuFallback>,
+        ),
     >,
     dimensions: Res<DensityFieldSize>,
+    mesh_size: Res<DensityFieldMeshSize>,
+    limits: Res<GpuBackpressure>,
+    mut load: ResMut<GpuMeshingLoad>,
+    compute_support: Res<ComputeShaderSupport>,
+    time: Res<Time<Real>>,
+    frame: Res<FrameCount>,
+    images: Res<Assets<Image>>,
     mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
 ) {
-    for (entity, density_field) in needs_mesh_query.iter() {
+    let work = needs_mesh_query.iter().map(|item| (item.7, item)).collect();
+    for (
+        critical,
+        (
+            entity,
+            density_field,
+            material_field,
+            density_texture,
+            quantization,
+            settings,
+            algorithm,
+            _,
+            gpu_density,
+            kernel,
+            spacing,
+        ),
+    ) in critical_first(work)
+    {
+        // Texture volumes take their dimensions from the image, and must wait for it to load
+        let texture_dimensions = match density_texture {
+            Some(texture) => match images.get(&texture.0) {
+                Some(image) => {
+                    let size = image.texture_descriptor.size;
+                    Some(DensityFieldSize(uvec3(
+                        size.width,
+                        size.height,
+                        size.depth_or_array_layers,
+                    )))
+                }
+                None => continue,
+            },
+            None => None,
+        };
+        // Generated volumes wait for their kernel to compile
+        let generation = match gpu_density {
+            Some(gpu_density) => {
+                match DensityGeneration::new(gpu_density, kernel, spacing, *mesh_size, *dimensions)
+                {
+                    Some(generation) => Some(generation),
+                    None => continue,
+                }
+            }
+            None => None,
+        };
+
+        // Volumes on the CPU backend are meshed by `mesh_cpu_backend_fields`
+        if density_field.is_some()
+            && settings.is_some_and(|settings| {
+                settings.meshes_on_cpu(algorithm.copied().unwrap_or_default())
+            })
+        {
+            continue;
+        }
+        // Without compute shaders only CPU-side fields can be meshed, by the same system
+        if !compute_support.0 {
+            continue;
+        }
+
+        if !load.admit(critical) {
+            match limits.policy {
+                // Only CPU-side fields can be meshed on the CPU
+                BackpressurePolicy::ShiftToCpu if density_field.is_some() => {
+                    commands.entity(entity).try_insert(CpuFallback);
+                }
+                _ => load.queued += 1,
+            }
+            continue;
+        }
+
         // Create GPU buffers to start generation
-        let buffers = SurfaceNetsBuffers::new(density_field, &dimensions, &mut buffers);
-        commands.entity(entity).insert(buffers);
+        let settings = settings.cloned().unwrap_or_default();
+        let algorithm = algorithm.copied().unwrap_or_default();
+        let buffers = match (density_texture, texture_dimensions) {
+            (Some(texture), Some(texture_dimensions)) => SurfaceNetsBuffers::from_texture(
+                texture.0.clone(),
+                &texture_dimensions,
+                &settings,
+                algorithm,
+                &mut buffers,
+            ),
+            _ if gpu_density.is_some() => {
+                SurfaceNetsBuffers::generated(&dimensions, &settings, algorithm, &mut buffers)
+            }
+            _ => {
+                let Some(density_field) = density_field else {
+                    continue;
+                };
+                // Mismatched material fields are ignored, as when assigning vertex materials
+                let material_field = material_field
+                    .filter(|materials| materials.len() == density_field.len())
+                    .map(|materials| materials.as_slice());
+                SurfaceNetsBuffers::new(
+                    density_field,
+                    material_field,
+                    &dimensions,
+                    quantization,
+                    &settings,
+                    algorithm,
+                    &mut buffers,
+                )
+            }
+        };
+        let region = MeshingRegion::full(buffers.dimensions);
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert((buffers, region, GpuGenerationStarted(time.elapsed())));
+        if let Some(generation) = generation {
+            entity_commands.try_insert(generation);
+        }
+        if let Some(stages_per_frame) = settings.stages_per_frame {
+            entity_commands.try_insert(StageSchedule::new(stages_per_frame, &frame));
+        }
     }
 }
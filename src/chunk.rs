@@ -0,0 +1,235 @@
+use bevy::{
+    ecs::entity::{EntityHashMap, EntityHashSet},
+    platform::collections::HashMap,
+    prelude::*,
+};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize,
+    dirty_region::{DensityEdited, DensityFieldDirtyRegion},
+    mesh::Meshed,
+    settings::MeshingAlgorithm,
+    units::VoxelSpacing,
+};
+
+/// Place the volume in the `ChunkMap` grid at this coordinate. Its `Transform`
+/// is set from the coordinate, and the samples it shares with neighbouring
+/// chunks are kept equal, so their meshes meet without cracks. Respawn a chunk
+/// to move it.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Deref)]
+#[require(Transform)]
+pub struct Chunk(pub IVec3);
+
+/// The grid of `Chunk` volumes, each a `DensityFieldSize` field overlapping the
+/// next one along every axis by an apron of shared samples. Edits to a chunk's
+/// `DensityField`, flagged with a `DensityFieldDirtyRegion`, are copied into the
+/// aprons of its neighbours, which remesh too.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ChunkMap {
+    /// Algorithm every chunk is meshed with, which decides the apron width.
+    /// Set it before spawning chunks.
+    pub algorithm: MeshingAlgorithm,
+    chunks: HashMap<IVec3, Entity>,
+}
+
+impl ChunkMap {
+    /// Chunk at `coord`, if one is spawned.
+    pub fn get(&self, coord: IVec3) -> Option<Entity> {
+        self.chunks.get(&coord).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, Entity)> + '_ {
+        self.chunks.iter().map(|(&coord, &entity)| (coord, entity))
+    }
+
+    /// Samples each chunk shares with the next one along every axis.
+    pub fn overlap(&self) -> u32 {
+        self.algorithm.chunk_overlap()
+    }
+
+    /// Samples from the start of one chunk to the start of the next.
+    pub fn stride(&self, dimensions: DensityFieldSize) -> UVec3 {
+        dimensions
+            .saturating_sub(UVec3::splat(self.overlap()))
+            .max(UVec3::ONE)
+    }
+
+    /// Map-wide sample the first sample of chunk `coord` lies at.
+    pub fn sample_origin(&self, coord: IVec3, dimensions: DensityFieldSize) -> IVec3 {
+        coord * self.stride(dimensions).as_ivec3()
+    }
+
+    /// Chunk holding the map-wide `sample`. Apron samples also lie in the
+    /// chunks before it.
+    pub fn chunk_at(&self, sample: IVec3, dimensions: DensityFieldSize) -> IVec3 {
+        sample.div_euclid(self.stride(dimensions).as_ivec3())
+    }
+}
+
+/// Register new chunks, placing them and filling their aprons from the
+/// neighbours already there, then copy the dirty regions of edited chunks, and
+/// the regions of `DensityEdited` ones, into the neighbours sharing them. Only samples that differ are written, so the
+/// copies don't bounce back. Chunks given new densities count as new, and
+/// remesh if they were meshed.
+pub fn sync_chunks(
+    mut commands: Commands,
+    mut map: ResMut<ChunkMap>,
+    mut queries: ParamSet<(
+        Query<
+            (
+                Entity,
+                &Chunk,
+                &mut Transform,
+                Option<&VoxelSpacing>,
+                Has<Meshed>,
+            ),
+            Or<(Added<Chunk>, Added<DensityField>)>,
+        >,
+        ChunkFields,
+    )>,
+    edited: Query<(Entity, &Chunk, &DensityFieldDirtyRegion), Changed<DensityFieldDirtyRegion>>,
+    chunks: Query<&Chunk>,
+    mut sculpted: MessageReader<DensityEdited>,
+    mut removed: RemovedComponents<Chunk>,
+    dimensions: Res<DensityFieldSize>,
+    mesh_size: Res<DensityFieldMeshSize>,
+) {
+    let removed: EntityHashSet = removed.read().collect();
+    if !removed.is_empty() {
+        map.chunks.retain(|_, entity| !removed.contains(entity));
+    }
+
+    let stride = map.stride(*dimensions);
+    let mut dirty = EntityHashMap::default();
+    let mut new_chunks = Vec::new();
+    for (entity, &Chunk(coord), mut transform, spacing, meshed) in &mut queries.p0() {
+        if let Some(previous) = map.chunks.insert(coord, entity)
+            && previous != entity
+        {
+            warn!("Chunk {entity} replaces {previous} at {coord}");
+        }
+        let scale = VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions.0);
+        transform.translation = (coord * stride.as_ivec3()).as_vec3() * scale;
+        commands.entity(entity).try_insert(map.algorithm);
+        if meshed {
+            dirty.insert(
+                entity,
+                DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0),
+            );
+        }
+        new_chunks.push((entity, coord));
+    }
+
+    let mut fields = queries.p1();
+
+    let full = (IVec3::MIN, IVec3::MAX);
+    for (entity, coord) in new_chunks {
+        for (neighbour, neighbour_coord) in neighbours(&map, coord) {
+            let from = (neighbour, map.sample_origin(neighbour_coord, *dimensions));
+            let to = (entity, map.sample_origin(coord, *dimensions));
+            copy_shared(&mut fields, from, to, full, *dimensions, &mut dirty);
+        }
+    }
+
+    let sculpted = sculpted.read().filter_map(|sculpted| {
+        let chunk = chunks.get(sculpted.entity).ok()?;
+        Some((sculpted.entity, chunk, &sculpted.region))
+    });
+    for (entity, &Chunk(coord), region) in edited.iter().chain(sculpted) {
+        let Some(region) = region.clamped(*dimensions) else {
+            continue;
+        };
+        let origin = map.sample_origin(coord, *dimensions);
+        let region = (
+            origin + region.min.as_ivec3(),
+            origin + region.max.as_ivec3(),
+        );
+        for (neighbour, neighbour_coord) in neighbours(&map, coord) {
+            let to = (neighbour, map.sample_origin(neighbour_coord, *dimensions));
+            copy_shared(
+                &mut fields,
+                (entity, origin),
+                to,
+                region,
+                *dimensions,
+                &mut dirty,
+            );
+        }
+    }
+
+    for (entity, region) in dirty {
+        let Ok((_, existing, true)) = fields.get(entity) else {
+            continue;
+        };
+        let region = existing.map_or(region, |existing| existing.union(&region));
+        commands.entity(entity).try_insert(region);
+    }
+}
+
+type ChunkFields<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut DensityField,
+        Option<&'static DensityFieldDirtyRegion>,
+        Has<Meshed>,
+    ),
+    With<Chunk>,
+>;
+
+/// The chunks around `coord`, with their coordinates.
+fn neighbours(map: &ChunkMap, coord: IVec3) -> impl Iterator<Item = (Entity, IVec3)> + '_ {
+    (-1..=1)
+        .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| ivec3(x, y, z))))
+        .filter(|offset| *offset != IVec3::ZERO)
+        .filter_map(move |offset| Some((map.get(coord + offset)?, coord + offset)))
+}
+
+/// Copy the samples of chunk `from` within the map-wide box `region` that chunk
+/// `to` shares, each given with its sample origin, and grow `to`'s entry in
+/// `dirty` by the ones that changed.
+fn copy_shared(
+    fields: &mut ChunkFields,
+    (from, from_origin): (Entity, IVec3),
+    (to, to_origin): (Entity, IVec3),
+    (min, max): (IVec3, IVec3),
+    dimensions: DensityFieldSize,
+    dirty: &mut EntityHashMap<DensityFieldDirtyRegion>,
+) {
+    let size = dimensions.as_ivec3();
+    let min = min.max(from_origin).max(to_origin);
+    let max = max.min(from_origin + size).min(to_origin + size);
+    if min.cmpge(max).any() {
+        return;
+    }
+    let Ok([(source, ..), (mut target, ..)]) = fields.get_many_mut([from, to]) else {
+        return;
+    };
+    let local_index = |sample: IVec3, origin: IVec3| {
+        let local = (sample - origin).as_uvec3();
+        dimensions.index(local.x, local.y, local.z) as usize
+    };
+
+    let mut changed: Option<DensityFieldDirtyRegion> = None;
+    for z in min.z..max.z {
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let sample = ivec3(x, y, z);
+                let density = source[local_index(sample, from_origin)];
+                let index = local_index(sample, to_origin);
+                if target[index] == density {
+                    continue;
+                }
+                target[index] = density;
+                let voxel = DensityFieldDirtyRegion::voxel((sample - to_origin).as_uvec3());
+                changed = Some(changed.map_or(voxel, |changed| changed.union(&voxel)));
+            }
+        }
+    }
+    if let Some(changed) = changed {
+        dirty
+            .entry(to)
+            .and_modify(|region: &mut DensityFieldDirtyRegion| *region = region.union(&changed))
+            .or_insert(changed);
+    }
+}
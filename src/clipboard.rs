@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use sculpter_core::grid::index;
+
+use crate::{
+    DensityField,
+    csg::{CompositeDensity, CsgOperation, CsgSource},
+};
+
+/// An axis of a field's grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GridAxis {
+    X,
+    Y,
+    Z,
+}
+
+/// Densities copied out of a box of a `DensityField`, to paste elsewhere, in
+/// the same volume or another, as a `CsgSource::Clipboard`.
+///
+/// Pasting composites the copy like any other source, so a smooth operation
+/// blends it into the densities around it instead of leaving a seam. Outside
+/// its box the copy is empty.
+#[derive(Clone, Debug)]
+pub struct FieldClipboard {
+    /// Samples along each axis, at least 2
+    pub dimensions: UVec3,
+    pub densities: Arc<DensityField>,
+}
+
+impl DensityField {
+    /// Copy the samples from `min` (inclusive) to `max` (exclusive) of this
+    /// `dimensions` field, clamped to it, or `None` if fewer than 2 samples
+    /// along an axis are left.
+    pub fn copy_region(&self, dimensions: UVec3, min: UVec3, max: UVec3) -> Option<FieldClipboard> {
+        let max = max.min(dimensions);
+        let size = max.saturating_sub(min);
+        if size.cmplt(UVec3::splat(2)).any() {
+            return None;
+        }
+        let mut densities = Vec::with_capacity(size.element_product() as usize);
+        for z in min.z..max.z {
+            for y in min.y..max.y {
+                let start = index(dimensions, min.x, y, z) as usize;
+                densities.extend_from_slice(&self[start..start + size.x as usize]);
+            }
+        }
+        Some(FieldClipboard {
+            dimensions: size,
+            densities: Arc::new(DensityField(densities)),
+        })
+    }
+}
+
+impl FieldClipboard {
+    /// The copy turned `quarter_turns` times 90° anticlockwise around `axis`,
+    /// looking down it, resampled exactly.
+    pub fn rotated(&self, axis: GridAxis, quarter_turns: u32) -> Self {
+        let mut rotated = self.clone();
+        for _ in 0..quarter_turns % 4 {
+            let d = rotated.dimensions;
+            rotated = match axis {
+                GridAxis::X => rotated.remapped(d.xzy(), |p| uvec3(p.x, d.z - 1 - p.z, p.y)),
+                GridAxis::Y => rotated.remapped(d.zyx(), |p| uvec3(p.z, p.y, d.x - 1 - p.x)),
+                GridAxis::Z => rotated.remapped(d.yxz(), |p| uvec3(d.y - 1 - p.y, p.x, p.z)),
+            };
+        }
+        rotated
+    }
+
+    /// The copy flipped along `axis`.
+    pub fn mirrored(&self, axis: GridAxis) -> Self {
+        let d = self.dimensions;
+        self.remapped(d, |p| match axis {
+            GridAxis::X => uvec3(d.x - 1 - p.x, p.y, p.z),
+            GridAxis::Y => uvec3(p.x, d.y - 1 - p.y, p.z),
+            GridAxis::Z => uvec3(p.x, p.y, d.z - 1 - p.z),
+        })
+    }
+
+    /// Move every sample to where `to` takes it, in a `dimensions` copy.
+    fn remapped(&self, dimensions: UVec3, to: impl Fn(UVec3) -> UVec3) -> Self {
+        let from = self.dimensions;
+        let mut densities = vec![0.0; self.densities.len()];
+        for z in 0..from.z {
+            for y in 0..from.y {
+                for x in 0..from.x {
+                    let p = to(uvec3(x, y, z));
+                    densities[index(dimensions, p.x, p.y, p.z) as usize] =
+                        self.densities[index(from, x, y, z) as usize];
+                }
+            }
+        }
+        Self {
+            dimensions,
+            densities: Arc::new(DensityField(densities)),
+        }
+    }
+
+    /// The composite pasting the copy into `entity` with its first sample at
+    /// `min`.
+    pub fn paste(
+        self: &Arc<Self>,
+        entity: Entity,
+        min: UVec3,
+        operation: CsgOperation,
+    ) -> CompositeDensity {
+        CompositeDensity {
+            entity,
+            source: CsgSource::Clipboard(self.clone()),
+            transform: Transform::from_translation(min.as_vec3()),
+            operation,
+        }
+    }
+}
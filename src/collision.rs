@@ -0,0 +1,182 @@
+#[cfg(feature = "avian")]
+use avian3d::parry::{
+    shape::SharedShape,
+    transformation::vhacd::{VHACD, VHACDParameters},
+};
+#[cfg(any(feature = "avian", feature = "rapier"))]
+use bevy::tasks::AsyncComputeTaskPool;
+use bevy::{
+    ecs::entity::EntityHashSet,
+    prelude::*,
+    tasks::{Task, block_on, futures_lite::future},
+};
+// Both backends use the same parry, so their shapes are interchangeable
+#[cfg(all(feature = "rapier", not(feature = "avian")))]
+use bevy_rapier3d::parry::{
+    shape::SharedShape,
+    transformation::vhacd::{VHACD, VHACDParameters},
+};
+
+#[cfg(feature = "gpu")]
+use crate::buffers::SurfaceNetsBuffers;
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize, cpu::spawn_downsampled_mesh,
+    mesh::GeneratedMesh, mesh_data::MeshData, settings::MeshingAlgorithm, units::VoxelSpacing,
+};
+
+/// Also build a `CollisionMesh` whenever this volume is meshed, from its
+/// `DensityField` downsampled `downsample` times along each axis, so physics
+/// colliders don't pay for the full resolution surface.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GenerateCollisionMesh {
+    pub downsample: u32,
+}
+
+impl Default for GenerateCollisionMesh {
+    fn default() -> Self {
+        Self { downsample: 2 }
+    }
+}
+
+/// Give this volume a compound collider of convex hulls decomposed from its
+/// `CollisionMesh` with V-HACD, instead of a trimesh, so it can be a dynamic
+/// rigid body such as destruction debris. Decomposing takes a while, so it
+/// runs on the `AsyncComputeTaskPool` and the collider is replaced once it's
+/// done; keep it to small fragments all the same.
+#[cfg(any(feature = "avian", feature = "rapier"))]
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct ConvexDecomposition {
+    /// Concavity left in each hull, relative to the mesh's size. Lower values
+    /// fit closer with more hulls.
+    pub concavity: f32,
+    /// Voxels along the mesh's longest side when voxelized for decomposing
+    pub resolution: u32,
+    pub max_convex_hulls: u32,
+}
+
+#[cfg(any(feature = "avian", feature = "rapier"))]
+impl Default for ConvexDecomposition {
+    fn default() -> Self {
+        Self {
+            concavity: 0.01,
+            resolution: 64,
+            max_convex_hulls: 32,
+        }
+    }
+}
+
+/// Start decomposing `mesh` into the convex hulls `decomposition` asks for on
+/// the `AsyncComputeTaskPool`. The task gives their compound, or `None` when
+/// there are none, as `Collider::convex_decomposition` would panic then.
+#[cfg(any(feature = "avian", feature = "rapier"))]
+pub(crate) fn spawn_convex_decomposition(
+    mesh: &MeshData,
+    decomposition: ConvexDecomposition,
+) -> Task<Option<SharedShape>> {
+    let points: Vec<_> = mesh
+        .positions
+        .iter()
+        .map(|&position| position.into())
+        .collect();
+    let indices: Vec<_> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect();
+    AsyncComputeTaskPool::get().spawn(async move {
+        if indices.is_empty() {
+            return None;
+        }
+        let parameters = VHACDParameters {
+            concavity: decomposition.concavity,
+            resolution: decomposition.resolution,
+            max_convex_hulls: decomposition.max_convex_hulls,
+            ..default()
+        };
+        let parts: Vec<_> = VHACD::decompose(&parameters, &points, &indices, true)
+            .compute_exact_convex_hulls(&points, &indices)
+            .into_iter()
+            .filter_map(|(vertices, indices)| SharedShape::convex_mesh(vertices, &indices))
+            .map(|convex| (default(), convex))
+            .collect();
+        (!parts.is_empty()).then(|| SharedShape::compound(parts))
+    })
+}
+
+/// Geometry the volume's physics colliders are built from, scaled like its
+/// mesh: the low resolution mesh asked for by `GenerateCollisionMesh`, or else,
+/// with the `avian` or `rapier` feature, a copy of the volume's own mesh.
+#[derive(Component, Clone, Debug, Default, Deref)]
+pub struct CollisionMesh(pub MeshData);
+
+/// Collision mesh meshing running on the `AsyncComputeTaskPool`.
+#[derive(Component)]
+pub struct CollisionMeshTask(Task<MeshData>);
+
+/// Start meshing the collision meshes of freshly meshed volumes, from their
+/// field at the size their GPU buffers were made for. A volume remeshed while
+/// its task runs starts over, dropping the stale task. Collision meshes of
+/// volumes that dropped `GenerateCollisionMesh` are removed.
+pub fn build_collision_meshes(
+    mut commands: Commands,
+    mesh_size: Res<DensityFieldMeshSize>,
+    dimensions: Res<DensityFieldSize>,
+    query: Query<(
+        Entity,
+        &DensityField,
+        &GenerateCollisionMesh,
+        Option<&MeshingAlgorithm>,
+        Option<&VoxelSpacing>,
+    )>,
+    #[cfg(feature = "gpu")] buffers: Query<&SurfaceNetsBuffers>,
+    changed: Query<Entity, Changed<GenerateCollisionMesh>>,
+    // `build_mesh_from_readback` takes the `GeneratedMesh` of every volume it meshes
+    mut built: RemovedComponents<GeneratedMesh>,
+    mut removed: RemovedComponents<GenerateCollisionMesh>,
+) {
+    for entity in removed.read() {
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_remove::<(CollisionMesh, CollisionMeshTask)>();
+        // Remeshed for colliders of the volume's own mesh
+        #[cfg(any(feature = "avian", feature = "rapier"))]
+        entity_commands.try_insert(crate::dirty_region::Remesh);
+    }
+
+    let entities: EntityHashSet = built.read().chain(&changed).collect();
+    for (entity, density_field, collision, algorithm, spacing) in query.iter_many(entities) {
+        #[cfg(feature = "gpu")]
+        let dimensions = buffers
+            .get(entity)
+            .map_or(*dimensions, |buffers| buffers.dimensions);
+        #[cfg(not(feature = "gpu"))]
+        let dimensions = *dimensions;
+        if density_field.len() != dimensions.density_count() as usize {
+            continue;
+        }
+
+        let task = spawn_downsampled_mesh(
+            density_field,
+            dimensions,
+            collision.downsample,
+            algorithm.copied().unwrap_or_default(),
+            VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions.0),
+        );
+        commands.entity(entity).try_insert(CollisionMeshTask(task));
+    }
+}
+
+/// Attach finished collision meshes.
+pub fn poll_collision_mesh_tasks(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut CollisionMeshTask)>,
+) {
+    for (entity, mut task) in &mut tasks {
+        let Some(mesh_data) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        commands
+            .entity(entity)
+            .try_remove::<CollisionMeshTask>()
+            .try_insert(CollisionMesh(mesh_data));
+    }
+}
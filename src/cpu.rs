@@ -0,0 +1,186 @@
+#[cfg(feature = "gpu")]
+use bevy::render::{render_resource::DownlevelFlags, renderer::RenderAdapter};
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+};
+
+#[cfg(feature = "gpu")]
+use crate::readback::ReadbackBuffers;
+use crate::{
+    DensityField, DensityFieldSize,
+    backpressure::CpuFallback,
+    dirty_region::Remesh,
+    mesh::{GeneratedMesh, Meshed, add_skirts, apply_gradient_normals},
+    mesh_data::{MeshData, VertexAttributeData},
+    quantize::DensityQuantization,
+    settings::{MeshingAlgorithm, SculptSettings},
+};
+
+/// Run the CPU mesher for `algorithm`, triangulate its faces facing outward with
+/// gradient normals, bake ambient occlusion if asked for and add skirts.
+pub(crate) fn mesh_cpu(
+    densities: &[f32],
+    dimensions: DensityFieldSize,
+    algorithm: MeshingAlgorithm,
+    settings: &SculptSettings,
+) -> MeshData {
+    let dims = dimensions.0;
+    let output = match algorithm {
+        MeshingAlgorithm::SurfaceNets => sculpter_core::surface_nets(
+            densities,
+            dims,
+            settings.vertex_placement,
+            settings.relaxation,
+        ),
+        MeshingAlgorithm::MarchingTetrahedra => sculpter_core::marching_tetrahedra(densities, dims),
+        MeshingAlgorithm::MarchingCubes => sculpter_core::marching_cubes(densities, dims),
+        MeshingAlgorithm::DualContouring => match settings.adaptive_error {
+            Some(error) => sculpter_core::adaptive_dual_contouring(densities, dims, error),
+            None => sculpter_core::dual_contouring(densities, dims),
+        },
+        MeshingAlgorithm::Blocky => sculpter_core::blocky(densities, dims),
+        MeshingAlgorithm::Heightfield => sculpter_core::heightfield(densities, dims),
+    };
+    let backend = algorithm.backend();
+    let mut mesh = MeshData::from_faces(&output.vertices, &output.faces, |face, indices| {
+        backend.triangulate(face, indices)
+    });
+    let gradients = sculpter_core::gradient_normals(&mesh.positions, densities, dims);
+    apply_gradient_normals(&mut mesh, &gradients, algorithm);
+    if let Some(occlusion) = settings.ambient_occlusion {
+        let occlusion =
+            sculpter_core::vertex_ambient_occlusion(&mesh.positions, densities, dims, occlusion);
+        mesh.attributes.insert(
+            sculpter_core::AMBIENT_OCCLUSION_ATTRIBUTE,
+            VertexAttributeData::Float32(occlusion),
+        );
+    }
+    add_skirts(&mut mesh, dims, algorithm, settings);
+    mesh
+}
+
+/// Whether the render device can run the compute pipeline. Without it (WebGL2,
+/// some software renderers, or no renderer at all) every volume with a
+/// `DensityField` is meshed on the CPU. Detected at startup.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ComputeShaderSupport(pub bool);
+
+/// Detect compute shader support once the renderer has been created.
+#[cfg(feature = "gpu")]
+pub fn detect_compute_shader_support(
+    mut commands: Commands,
+    render_adapter: Option<Res<RenderAdapter>>,
+) {
+    let supported = render_adapter.is_some_and(|adapter| {
+        adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::COMPUTE_SHADERS)
+    });
+    if !supported {
+        info!("Compute shaders are unavailable, volumes will be meshed on the CPU");
+    }
+    commands.insert_resource(ComputeShaderSupport(supported));
+}
+
+/// Mesh `densities` downsampled `factor` times along each axis with default
+/// settings on the `AsyncComputeTaskPool`, for low resolution stand-ins. Only
+/// the downsampled copy goes to the task. Positions are in the full field's
+/// grid units times `scale`.
+pub(crate) fn spawn_downsampled_mesh(
+    densities: &[f32],
+    dimensions: DensityFieldSize,
+    factor: u32,
+    algorithm: MeshingAlgorithm,
+    scale: Vec3,
+) -> Task<MeshData> {
+    let factor = factor.max(1);
+    let (densities, low) = sculpter_core::grid::downsample(densities, dimensions.0, factor);
+    AsyncComputeTaskPool::get().spawn(async move {
+        let mut mesh = mesh_cpu(
+            &densities,
+            DensityFieldSize(low),
+            algorithm,
+            &SculptSettings::default(),
+        );
+        mesh.scale(scale * factor as f32);
+        mesh
+    })
+}
+
+/// CPU meshing running on the `AsyncComputeTaskPool`.
+#[derive(Component)]
+pub struct CpuMeshingTask(Task<MeshData>);
+
+/// Start CPU meshing for volumes that opted into the CPU backend, were shifted
+/// there by GPU backpressure, or can't use compute shaders at all. A volume
+/// edited while its task runs starts over, dropping the stale task.
+pub fn mesh_cpu_backend_fields(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            Ref<DensityField>,
+            Option<&SculptSettings>,
+            Option<&MeshingAlgorithm>,
+            Option<&DensityQuantization>,
+            Has<CpuFallback>,
+            Has<CpuMeshingTask>,
+        ),
+        Or<(Without<Meshed>, With<Remesh>)>,
+    >,
+    #[cfg(feature = "gpu")] reading_back: Query<(), With<ReadbackBuffers>>,
+    dimensions: Res<DensityFieldSize>,
+    compute_support: Res<ComputeShaderSupport>,
+) {
+    for (entity, density_field, settings, algorithm, quantization, fallback, running) in &query {
+        #[cfg(feature = "gpu")]
+        if reading_back.contains(entity) {
+            continue;
+        }
+        let wants_cpu = settings
+            .is_some_and(|settings| settings.meshes_on_cpu(algorithm.copied().unwrap_or_default()));
+        if !wants_cpu && !fallback && compute_support.0 {
+            continue;
+        }
+        if running && !density_field.is_changed() {
+            continue;
+        }
+
+        let settings = settings.cloned().unwrap_or_default();
+        let algorithm = algorithm.copied().unwrap_or_default();
+        let dimensions = *dimensions;
+
+        // Round-trip through the quantizer so the CPU sees what the GPU would decode
+        let densities: Vec<f32> = match quantization {
+            Some(q) => density_field
+                .iter()
+                .map(|&d| q.dequantize(q.quantize(d)))
+                .collect(),
+            None => density_field.0.clone(),
+        };
+
+        let task = AsyncComputeTaskPool::get()
+            .spawn(async move { mesh_cpu(&densities, dimensions, algorithm, &settings) });
+        commands.entity(entity).try_insert(CpuMeshingTask(task));
+    }
+}
+
+/// Hand finished CPU meshes over as `GeneratedMesh`, the same output the GPU
+/// path produces, so mesh building is shared.
+pub fn poll_cpu_meshing_tasks(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut CpuMeshingTask)>,
+) {
+    for (entity, mut task) in &mut tasks {
+        let Some(mesh) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        commands
+            .entity(entity)
+            .try_insert(GeneratedMesh(mesh))
+            .try_remove::<(CpuMeshingTask, CpuFallback)>();
+    }
+}
@@ -0,0 +1,285 @@
+pub use sculpter_core::CsgOperation;
+
+use std::sync::Arc;
+
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::{
+    diagnostic::FrameCount,
+    math::Affine3A,
+    render::{extract_component::ExtractComponent, render_resource::ShaderType},
+};
+use sculpter_core::CsgShape;
+
+use crate::{
+    DensityField, DensityFieldSize,
+    clipboard::FieldClipboard,
+    dirty_region::DensityFieldDirtyRegion,
+    sdf::{SdfPrimitive, SdfShape},
+};
+#[cfg(feature = "gpu")]
+use crate::{
+    amortize::StageSchedule,
+    buffers::SurfaceNetsBuffers,
+    dirty_region::Remesh,
+    gpu_density::GpuDensity,
+    mesh::Meshed,
+    sdf::{GpuSdfPart, gpu_operation},
+    settings::SculptSettings,
+};
+
+// Must match `SOURCE_*` in csg.wgsl
+#[cfg(feature = "gpu")]
+const SOURCE_FIELD: u32 = 0;
+#[cfg(feature = "gpu")]
+const SOURCE_SHAPE: u32 = 1;
+
+/// What is composited into a volume's densities.
+#[derive(Clone, Debug)]
+pub enum CsgSource {
+    Primitive(SdfPrimitive),
+    /// Primitives blended together, placed in the source's space
+    Shape(SdfShape),
+    /// Densities of another field of the `DensityFieldSize`, e.g. a prefab
+    /// stamped into terrain
+    Field(Arc<DensityField>),
+    /// Densities copied out of a box of a field
+    Clipboard(Arc<FieldClipboard>),
+}
+
+impl CsgSource {
+    fn shape(&self, dimensions: UVec3) -> CsgShape<'_> {
+        match self {
+            Self::Primitive(primitive) => CsgShape::Primitive(*primitive),
+            Self::Shape(shape) => CsgShape::Sdf(shape),
+            Self::Field(field) => CsgShape::Field {
+                densities: field,
+                dims: dimensions,
+            },
+            Self::Clipboard(clipboard) => CsgShape::Field {
+                densities: &clipboard.densities,
+                dims: clipboard.dimensions,
+            },
+        }
+    }
+}
+
+impl DensityField {
+    /// Composite `source`, placed in this field's grid space by `transform`,
+    /// with `operation`, on the calling thread. Sizes and blend radii are in
+    /// samples, as in `sample`.
+    ///
+    /// Returns the region to mark dirty for the volume to be remeshed, or
+    /// `None` if the source missed the field.
+    pub fn composite(
+        &mut self,
+        dimensions: UVec3,
+        source: &CsgSource,
+        transform: Transform,
+        operation: CsgOperation,
+    ) -> Option<DensityFieldDirtyRegion> {
+        let (min, max) = sculpter_core::composite(
+            &mut self.0,
+            dimensions,
+            &source.shape(dimensions),
+            transform.compute_affine(),
+            operation,
+        )?;
+        Some(DensityFieldDirtyRegion::new(min, max))
+    }
+}
+
+/// Trigger on a volume to composite a shape into its densities and remesh it.
+/// `DensityField`s are edited on the CPU; `GpuDensity` volumes, which have no
+/// field, get a compute pass on their density buffer once they are meshed,
+/// until their densities are regenerated.
+#[derive(EntityEvent, Clone, Debug)]
+pub struct CompositeDensity {
+    pub entity: Entity,
+    pub source: CsgSource,
+    /// Placement of the source in the volume's grid space
+    pub transform: Transform,
+    pub operation: CsgOperation,
+}
+
+/// Uniform of the CSG kernel.
+#[cfg(feature = "gpu")]
+#[derive(ShaderType, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CsgParams {
+    /// Grid space to the source's space
+    pub inverse: Mat4,
+    /// Samples the kernel is dispatched over
+    pub region_min: UVec3,
+    pub region_size: UVec3,
+    pub dimensions: UVec3,
+    /// Samples of a field source along each axis
+    pub source_dimensions: UVec3,
+    pub source: u32,
+    /// Parts of a shape source
+    pub part_count: u32,
+    pub operation: u32,
+    /// Blend radius, 0 for the sharp operations
+    pub radius: f32,
+    /// Source distances to samples
+    pub scale: f32,
+}
+
+/// A composite for the CSG kernel.
+#[cfg(feature = "gpu")]
+#[derive(Clone, Debug)]
+pub struct GpuCsgEdit {
+    pub params: CsgParams,
+    /// Densities of a field source, uploaded along with the edit
+    pub source: Option<Arc<DensityField>>,
+    /// Parts of a primitive or shape source
+    pub parts: Vec<GpuSdfPart>,
+}
+
+/// Composites waiting for their `GpuDensity` volume to be meshed.
+#[cfg(feature = "gpu")]
+#[derive(Component, Clone, Debug, Default)]
+pub struct QueuedCsgEdits(Vec<(GpuCsgEdit, DensityFieldDirtyRegion)>);
+
+/// Composites the render world applies to the volume's density buffer this
+/// frame, after generating it and before meshing.
+#[cfg(feature = "gpu")]
+#[derive(Component, ExtractComponent, Clone, Debug, Default)]
+pub struct PendingCsgEdits(pub Vec<GpuCsgEdit>);
+
+/// Composite into the `DensityField` of the volume `CompositeDensity` was
+/// triggered on, or queue the composite for its GPU densities.
+pub fn composite_density(
+    composite: On<CompositeDensity>,
+    mut commands: Commands,
+    dimensions: Res<DensityFieldSize>,
+    mut fields: Query<&mut DensityField>,
+    #[cfg(feature = "gpu")] gpu_densities: Query<(), With<GpuDensity>>,
+) {
+    let entity = composite.entity;
+    if let Ok(mut density_field) = fields.get_mut(entity) {
+        let Some(region) = density_field.composite(
+            dimensions.0,
+            &composite.source,
+            composite.transform,
+            composite.operation,
+        ) else {
+            return;
+        };
+        commands
+            .entity(entity)
+            .entry::<DensityFieldDirtyRegion>()
+            .and_modify(move |mut dirty| *dirty = dirty.union(&region))
+            .or_insert(region);
+        return;
+    }
+
+    #[cfg(feature = "gpu")]
+    if gpu_densities.contains(entity) {
+        let shape = composite.source.shape(dimensions.0);
+        let transform = composite.transform.compute_affine();
+        let operation = composite.operation;
+        let Some((min, max)) =
+            sculpter_core::csg_region(dimensions.0, &shape, transform, operation)
+        else {
+            return;
+        };
+        let (source, parts) = match &composite.source {
+            CsgSource::Primitive(primitive) => (
+                None,
+                vec![GpuSdfPart::new(
+                    *primitive,
+                    Affine3A::IDENTITY,
+                    CsgOperation::Union,
+                )],
+            ),
+            CsgSource::Shape(shape) => (None, GpuSdfPart::from_shape(shape)),
+            // Storage bindings can't be empty, the field binds a placeholder part
+            CsgSource::Field(field) => (Some(field.clone()), vec![GpuSdfPart::default()]),
+            CsgSource::Clipboard(clipboard) => (
+                Some(clipboard.densities.clone()),
+                vec![GpuSdfPart::default()],
+            ),
+        };
+        let params = CsgParams {
+            inverse: Mat4::from(transform.inverse()),
+            region_min: min,
+            region_size: max - min,
+            dimensions: dimensions.0,
+            source_dimensions: match &composite.source {
+                CsgSource::Clipboard(clipboard) => clipboard.dimensions,
+                _ => dimensions.0,
+            },
+            source: match composite.source {
+                CsgSource::Field(_) | CsgSource::Clipboard(_) => SOURCE_FIELD,
+                _ => SOURCE_SHAPE,
+            },
+            part_count: parts.len() as u32,
+            operation: gpu_operation(operation),
+            radius: operation.radius(),
+            scale: sculpter_core::csg_scale(transform),
+        };
+        let edit = (
+            GpuCsgEdit {
+                params,
+                source,
+                parts,
+            },
+            DensityFieldDirtyRegion::new(min, max),
+        );
+        commands
+            .entity(entity)
+            .entry::<QueuedCsgEdits>()
+            .or_default()
+            .and_modify(move |mut queued| queued.0.push(edit));
+        return;
+    }
+
+    warn!("Can't composite into {entity}: it has neither a DensityField nor a GpuDensity");
+}
+
+/// Hand the queued composites of meshed `GpuDensity` volumes to the render
+/// world, remeshing the region they cover, and drop last frame's.
+#[cfg(feature = "gpu")]
+pub fn flush_csg_edits(
+    mut commands: Commands,
+    previous: Query<Entity, With<PendingCsgEdits>>,
+    mut volumes: Query<
+        (
+            Entity,
+            &mut QueuedCsgEdits,
+            &SurfaceNetsBuffers,
+            Option<&SculptSettings>,
+        ),
+        (With<Meshed>, Without<Remesh>),
+    >,
+    frame: Res<FrameCount>,
+) {
+    // Edits are extracted once
+    for entity in &previous {
+        commands.entity(entity).remove::<PendingCsgEdits>();
+    }
+
+    for (entity, mut queued, buffers, settings) in &mut volumes {
+        let queued = std::mem::take(&mut queued.0);
+        let Some(region) = queued
+            .iter()
+            .map(|(_, region)| *region)
+            .reduce(|a, b| a.union(&b))
+        else {
+            continue;
+        };
+        let edits = queued.into_iter().map(|(edit, _)| edit).collect();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert((
+            PendingCsgEdits(edits),
+            buffers
+                .algorithm
+                .backend()
+                .meshing_region(region, buffers.dimensions),
+            Remesh,
+        ));
+        if let Some(stages_per_frame) = settings.and_then(|settings| settings.stages_per_frame) {
+            entity_commands.insert(StageSchedule::new(stages_per_frame, &frame));
+        }
+    }
+}
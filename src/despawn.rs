@@ -0,0 +1,53 @@
+use bevy::{prelude::*, render::gpu_readback::Readback, render::storage::ShaderStorageBuffer};
+
+use crate::{buffers::SurfaceNetsBuffers, lod_chain::LodLevel};
+
+/// Tear down a volume despawned while it is being generated.
+///
+/// Its pending readbacks are despawned so their observers never look for the
+/// missing volume, and its storage buffers are freed now instead of whenever the
+/// last handle clone drops. The render world copy goes away with entity sync,
+/// which takes it out of the compute node's queue, and `GpuMeshingLoad` is
+/// recounted next frame, freeing its in-flight slot. A `LodLevel` leaves the
+/// inputs it shares with its volume alone.
+pub fn release_despawned_volume(
+    despawn: On<Despawn, SurfaceNetsBuffers>,
+    mut commands: Commands,
+    volumes: Query<(&SurfaceNetsBuffers, Option<&Children>, Has<LodLevel>)>,
+    readbacks: Query<(), With<Readback>>,
+    mut storage_buffers: ResMut<Assets<ShaderStorageBuffer>>,
+) {
+    let Ok((buffers, children, lod_level)) = volumes.get(despawn.entity) else {
+        return;
+    };
+
+    for child in children.into_iter().flatten() {
+        if readbacks.contains(*child) {
+            commands.entity(*child).try_despawn();
+        }
+    }
+
+    if !lod_level {
+        storage_buffers.remove(&buffers.density_field);
+        storage_buffers.remove(&buffers.material_field);
+    }
+    // The density texture belongs to the user, only the buffers are ours
+    for handle in [
+        &buffers.vertices,
+        &buffers.vertex_valid,
+        &buffers.vertex_indices,
+        &buffers.vertex_count,
+        &buffers.compacted_vertices,
+        &buffers.faces,
+        &buffers.face_valid,
+        &buffers.face_indices,
+        &buffers.face_count,
+        &buffers.compacted_faces,
+        &buffers.compacted_normals,
+        &buffers.compacted_occlusion,
+        &buffers.compacted_material_ids,
+        &buffers.compacted_material_weights,
+    ] {
+        storage_buffers.remove(handle);
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::DensityFieldSize;
+
+/// A triangle edge used by exactly one triangle, i.e. the rim of a hole.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundaryEdge {
+    /// Vertex indices of the edge
+    pub vertices: [u32; 2],
+    /// Cells containing each endpoint
+    pub cells: [UVec3; 2],
+}
+
+/// Cell containing a grid-space surface nets vertex.
+fn vertex_cell(position: Vec3, dimensions: DensityFieldSize) -> UVec3 {
+    let max_cell = dimensions.saturating_sub(UVec3::splat(2));
+    position.floor().max(Vec3::ZERO).as_uvec3().min(max_cell)
+}
+
+fn on_volume_border(cell: UVec3, dimensions: DensityFieldSize) -> bool {
+    let max_cell = dimensions.saturating_sub(UVec3::splat(2));
+    cell.cmpeq(UVec3::ZERO).any() || cell.cmpeq(max_cell).any()
+}
+
+/// Find boundary edges that don't lie on the border of the volume. A closed
+/// surface has none of these, so each one points at a meshing bug.
+///
+/// `grid_positions` are vertex positions in grid (voxel) space.
+pub fn find_interior_boundary_edges(
+    grid_positions: &[Vec3],
+    triangle_indices: &[u32],
+    dimensions: DensityFieldSize,
+) -> Vec<BoundaryEdge> {
+    let mut edge_uses: HashMap<(u32, u32), u32> = HashMap::new();
+    for triangle in triangle_indices.chunks_exact(3) {
+        for (a, b) in [
+            (triangle[0], triangle[1]),
+            (triangle[1], triangle[2]),
+            (triangle[2], triangle[0]),
+        ] {
+            *edge_uses.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+
+    let mut edges: Vec<BoundaryEdge> = edge_uses
+        .into_iter()
+        .filter(|&(_, uses)| uses == 1)
+        .filter_map(|((a, b), _)| {
+            let cells = [
+                vertex_cell(*grid_positions.get(a as usize)?, dimensions),
+                vertex_cell(*grid_positions.get(b as usize)?, dimensions),
+            ];
+            let on_border = cells.iter().all(|&cell| on_volume_border(cell, dimensions));
+            (!on_border).then_some(BoundaryEdge {
+                vertices: [a, b],
+                cells,
+            })
+        })
+        .collect();
+    edges.sort_by_key(|edge| edge.vertices);
+    edges
+}
+
+/// Log interior boundary edges of a freshly built mesh.
+pub(crate) fn report_mesh_holes(
+    entity: Entity,
+    grid_positions: &[Vec3],
+    triangle_indices: &[u32],
+    dimensions: DensityFieldSize,
+) {
+    let edges = find_interior_boundary_edges(grid_positions, triangle_indices, dimensions);
+    if edges.is_empty() {
+        return;
+    }
+
+    warn!(
+        "Mesh for {entity} has {} boundary edges inside the volume (holes)",
+        edges.len()
+    );
+    for edge in &edges {
+        warn!(
+            "  hole edge between vertices {:?} in cells {} and {}",
+            edge.vertices, edge.cells[0], edge.cells[1]
+        );
+    }
+}
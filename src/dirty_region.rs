@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::{
+    diagnostic::FrameCount,
+    render::{
+        extract_component::ExtractComponent, render_asset::RenderAssets,
+        render_resource::ShaderType, renderer::RenderQueue, storage::GpuShaderStorageBuffer,
+    },
+};
+
+#[cfg(feature = "gpu")]
+use crate::{
+    DensityField,
+    amortize::StageSchedule,
+    backpressure::{CriticalRemesh, GpuMeshingLoad, critical_first},
+    brush::WritingBackBrushStrokes,
+    buffers::SurfaceNetsBuffers,
+    material::MaterialField,
+    quantize::DensityQuantization,
+    settings::SculptSettings,
+};
+use crate::{DensityFieldSize, mesh::Meshed};
+
+/// Voxel-space box (`min` inclusive, `max` exclusive) of densities that were
+/// modified on the CPU since the field was last uploaded.
+///
+/// Insert this after editing a meshed `DensityField` and only the touched rows of
+/// the GPU density buffer are rewritten before the field is remeshed.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DensityFieldDirtyRegion {
+    pub min: UVec3,
+    pub max: UVec3,
+}
+
+impl DensityFieldDirtyRegion {
+    pub fn new(min: UVec3, max: UVec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Region covering a single voxel.
+    pub fn voxel(pos: UVec3) -> Self {
+        Self::new(pos, pos + UVec3::ONE)
+    }
+
+    /// Smallest region covering both `self` and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Clamp to the field bounds, returning `None` if nothing is left.
+    pub fn clamped(&self, dimensions: DensityFieldSize) -> Option<Self> {
+        let max = self.max.min(dimensions.0);
+        let min = self.min.min(max);
+        (min.cmplt(max).all()).then_some(Self::new(min, max))
+    }
+}
+
+/// Cell-space box (`min` inclusive, `max` exclusive) the vertex and face
+/// generation stages are dispatched over.
+///
+/// Cells outside the region keep their per-cell vertices and faces from earlier
+/// dispatches, and the (cheap, 1D) compaction stages then stitch everything into
+/// a complete mesh, so an edit only re-runs the 3D stages where it changed the field.
+#[cfg(feature = "gpu")]
+#[derive(Component, ExtractComponent, ShaderType, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MeshingRegion {
+    pub min: UVec3,
+    pub max: UVec3,
+}
+
+#[cfg(feature = "gpu")]
+impl MeshingRegion {
+    /// Every cell of the field.
+    pub fn full(dimensions: DensityFieldSize) -> Self {
+        Self {
+            min: UVec3::ZERO,
+            max: dimensions.saturating_sub(UVec3::ONE),
+        }
+    }
+
+    /// Cells that must be regenerated after the densities in `dirty` changed.
+    ///
+    /// Cells within one of the dirty voxels get new vertices, and faces are owned
+    /// by the cell at their minimum corner, so the region reaches two cells below.
+    pub fn from_dirty(dirty: DensityFieldDirtyRegion, dimensions: DensityFieldSize) -> Self {
+        let full = Self::full(dimensions);
+        Self {
+            min: dirty.min.saturating_sub(UVec3::splat(2)).min(full.max),
+            max: dirty.max.min(full.max),
+        }
+    }
+
+    pub fn size(&self) -> UVec3 {
+        self.max.saturating_sub(self.min)
+    }
+}
+
+/// Densities of a volume's `DensityField` changed outside of a
+/// `DensityFieldDirtyRegion`, without marking the field changed, such as brush
+/// strokes read back from its density buffer. The buffer already holds them,
+/// so the field isn't uploaded or remeshed.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct DensityEdited {
+    pub entity: Entity,
+    pub region: DensityFieldDirtyRegion,
+}
+
+/// Request that a volume be meshed again. Its current mesh stays visible until
+/// the new one is ready.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Remesh;
+
+/// A byte range of the density or material buffer to overwrite.
+#[cfg(feature = "gpu")]
+#[derive(Clone, Debug)]
+pub struct DensityWrite {
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// Density and material buffer writes for this frame, consumed by the render world.
+#[cfg(feature = "gpu")]
+#[derive(Component, ExtractComponent, Clone, Debug, Default)]
+pub struct PendingDensityWrites {
+    pub densities: Vec<DensityWrite>,
+    /// Empty for volumes without a `MaterialField`
+    pub materials: Vec<DensityWrite>,
+}
+
+/// Sample ranges of the rows covering `region`, merging rows that are contiguous in memory.
+#[cfg(feature = "gpu")]
+fn dirty_rows(
+    dimensions: DensityFieldSize,
+    region: DensityFieldDirtyRegion,
+) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for z in region.min.z..region.max.z {
+        for y in region.min.y..region.max.y {
+            let start = dimensions.index(region.min.x, y, z) as usize;
+            let end = dimensions.index(region.max.x - 1, y, z) as usize + 1;
+            match ranges.last_mut() {
+                Some(last) if last.1 >= start => last.1 = last.1.max(end),
+                _ => ranges.push((start, end)),
+            }
+        }
+    }
+    ranges
+}
+
+/// Density buffer writes covering the sample ranges `rows`.
+#[cfg(feature = "gpu")]
+fn density_writes(
+    density_field: &DensityField,
+    quantization: Option<&DensityQuantization>,
+    rows: &[(usize, usize)],
+) -> Vec<DensityWrite> {
+    rows.iter()
+        .map(|&(start, end)| match quantization {
+            // Buffer writes must be 4-byte aligned, widen to whole packed words
+            Some(quantization) => {
+                let start = start & !3;
+                let end = end
+                    .next_multiple_of(4)
+                    .min(density_field.len().next_multiple_of(4));
+                let mut bytes =
+                    quantization.encode(&density_field[start..end.min(density_field.len())]);
+                bytes.resize(end - start, 0);
+                DensityWrite {
+                    offset: start as u64,
+                    bytes,
+                }
+            }
+            None => DensityWrite {
+                offset: (start * size_of::<f32>()) as u64,
+                bytes: bytemuck::cast_slice(&density_field[start..end]).to_vec(),
+            },
+        })
+        .collect()
+}
+
+/// Material buffer writes covering the sample ranges `rows`.
+#[cfg(feature = "gpu")]
+fn material_writes(material_field: &MaterialField, rows: &[(usize, usize)]) -> Vec<DensityWrite> {
+    rows.iter()
+        .map(|&(start, end)| DensityWrite {
+            offset: (start * size_of::<u32>()) as u64,
+            bytes: bytemuck::cast_slice(&material_field[start..end]).to_vec(),
+        })
+        .collect()
+}
+
+/// Turn dirty regions on meshed volumes into partial density and material
+/// uploads plus a remesh.
+///
+/// GPU remeshes go through backpressure admission (critical ones first); regions
+/// that can't be admitted stay dirty until a later frame, as do those of
+/// volumes whose brush strokes are still being read back.
+#[cfg(feature = "gpu")]
+pub fn upload_dirty_regions(
+    mut commands: Commands,
+    previous_writes: Query<Entity, With<PendingDensityWrites>>,
+    dirty_query: Query<
+        (
+            Entity,
+            &DensityField,
+            Option<&MaterialField>,
+            &DensityFieldDirtyRegion,
+            Option<&SurfaceNetsBuffers>,
+            Option<&DensityQuantization>,
+            Option<&SculptSettings>,
+            Has<CriticalRemesh>,
+        ),
+        (
+            With<Meshed>,
+            Without<Remesh>,
+            Without<WritingBackBrushStrokes>,
+        ),
+    >,
+    mut load: ResMut<GpuMeshingLoad>,
+    frame: Res<FrameCount>,
+) {
+    // Writes are extracted once, drop last frame's
+    for entity in &previous_writes {
+        commands.entity(entity).remove::<PendingDensityWrites>();
+    }
+
+    let work = dirty_query.iter().map(|item| (item.7, item)).collect();
+    for (
+        critical,
+        (entity, density_field, material_field, region, buffers, quantization, settings, _),
+    ) in critical_first(work)
+    {
+        if buffers.is_some() && !load.admit(critical) {
+            load.queued += 1;
+            continue;
+        }
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands
+            .remove::<DensityFieldDirtyRegion>()
+            .insert(Remesh);
+
+        // CPU backend volumes have no GPU copy and simply remesh
+        let Some(buffers) = buffers else {
+            continue;
+        };
+        let Some(region) = region.clamped(buffers.dimensions) else {
+            continue;
+        };
+        let rows = dirty_rows(buffers.dimensions, region);
+        // Mismatched material fields were never uploaded
+        let materials = material_field
+            .filter(|materials| materials.len() == density_field.len())
+            .map_or_else(Vec::new, |materials| material_writes(materials, &rows));
+        entity_commands.insert((
+            PendingDensityWrites {
+                densities: density_writes(density_field, quantization, &rows),
+                materials,
+            },
+            buffers
+                .algorithm
+                .backend()
+                .meshing_region(region, buffers.dimensions),
+        ));
+        if let Some(stages_per_frame) = settings.and_then(|settings| settings.stages_per_frame) {
+            entity_commands.insert(StageSchedule::new(stages_per_frame, &frame));
+        }
+    }
+}
+
+/// Without the GPU pipeline a dirty region just remeshes the volume.
+#[cfg(not(feature = "gpu"))]
+pub fn upload_dirty_regions(
+    mut commands: Commands,
+    dirty_query: Query<Entity, (With<DensityFieldDirtyRegion>, With<Meshed>, Without<Remesh>)>,
+) {
+    for entity in &dirty_query {
+        commands
+            .entity(entity)
+            .try_remove::<DensityFieldDirtyRegion>()
+            .try_insert(Remesh);
+    }
+}
+
+/// Apply pending partial writes to the GPU density and material buffers before
+/// the meshing dispatch.
+#[cfg(feature = "gpu")]
+pub fn write_pending_density_regions(
+    query: Query<(&SurfaceNetsBuffers, &PendingDensityWrites)>,
+    gpu_buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    render_queue: Res<RenderQueue>,
+) {
+    for (buffers, writes) in &query {
+        for (handle, writes) in [
+            (&buffers.density_field, &writes.densities),
+            (&buffers.material_field, &writes.materials),
+        ] {
+            let Some(buffer) = gpu_buffers.get(handle) else {
+                continue;
+            };
+            for write in writes {
+                render_queue.write_buffer(&buffer.buffer, write.offset, &write.bytes);
+            }
+        }
+    }
+}
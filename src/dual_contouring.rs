@@ -0,0 +1,53 @@
+#[cfg(feature = "gpu")]
+use bevy::render::{render_resource::BindGroup, renderer::RenderDevice};
+
+use crate::backend::MeshingBackend;
+#[cfg(feature = "gpu")]
+use crate::{
+    backend::{BackendBindings, BackendInit, BackendKernels},
+    surface_nets::SurfaceNets,
+};
+
+#[cfg(feature = "gpu")]
+const DUAL_CONTOURING_SHADER: &str = "shaders/dual_contouring.wgsl";
+
+/// Surface nets topology with each vertex placed by minimising the quadric error
+/// of the cell's edge crossings and their normals (Hermite data), which keeps
+/// sharp edges and corners.
+pub struct DualContouring;
+
+impl MeshingBackend for DualContouring {
+    #[cfg(feature = "gpu")]
+    fn vertices_per_point(&self) -> u32 {
+        SurfaceNets.vertices_per_point()
+    }
+
+    #[cfg(feature = "gpu")]
+    fn faces_per_point(&self) -> u32 {
+        SurfaceNets.faces_per_point()
+    }
+
+    #[cfg(feature = "gpu")]
+    fn init_kernels(&self, init: &BackendInit) -> BackendKernels {
+        SurfaceNets::kernels_with_vertex_stage(
+            init,
+            [
+                "dual_contouring_vertices_pipeline",
+                "dual_contouring_vertices_texture_pipeline",
+            ],
+            DUAL_CONTOURING_SHADER,
+            "generate_dc_vertices",
+        )
+    }
+
+    #[cfg(feature = "gpu")]
+    fn bind_groups(
+        &self,
+        kernels: &BackendKernels,
+        render_device: &RenderDevice,
+        bindings: &BackendBindings,
+    ) -> [BindGroup; 2] {
+        // Bound exactly like surface nets
+        SurfaceNets.bind_groups(kernels, render_device, bindings)
+    }
+}
@@ -0,0 +1,60 @@
+pub use sculpter_core::{CoordinateSystem, Handedness, UpAxis, write_obj, write_stl};
+
+use bevy::prelude::*;
+
+use crate::{
+    mesh_data::MeshData,
+    meshing::mesh_density_cpu,
+    settings::{MeshingAlgorithm, SculptSettings},
+    units::{LengthUnit, VoxelSpacing},
+};
+
+/// Density of the samples closing a field's boundary: one voxel outside the
+/// surface, for fields holding distances in voxels.
+const OUTSIDE: f32 = 1.0;
+
+/// Mesh a density field on the calling thread for export with `write_obj` or
+/// `write_stl`, in millimetres under `spacing` as slicers expect. The mesh stays
+/// in Bevy's axes, the writers convert it to the file's `CoordinateSystem`.
+///
+/// With `close_boundary`, surfaces cut off by the field's faces are closed
+/// within a sample of them, so a sculpt touching the edge still prints as a
+/// solid. Skirts are left out and faces always point out of the solid, while
+/// `settings`' degenerate filter, smoothing and simplification apply as for the
+/// volume's own mesh.
+pub fn printable_mesh(
+    densities: &[f32],
+    dimensions: UVec3,
+    algorithm: MeshingAlgorithm,
+    settings: &SculptSettings,
+    spacing: VoxelSpacing,
+    close_boundary: bool,
+) -> MeshData {
+    let settings = SculptSettings {
+        skirt_depth: 0.0,
+        ..settings.clone()
+    };
+    let mut mesh = if close_boundary {
+        let (padded, padded_dimensions) =
+            sculpter_core::pad_densities(densities, dimensions, OUTSIDE);
+        let mut mesh = mesh_density_cpu(&padded, padded_dimensions, algorithm, &settings);
+        for position in &mut mesh.positions {
+            *position -= Vec3::ONE;
+        }
+        mesh
+    } else {
+        mesh_density_cpu(densities, dimensions, algorithm, &settings)
+    };
+
+    if let Some(filter) = settings.degenerate_filter {
+        sculpter_core::remove_degenerate_triangles(&mut mesh, filter);
+    }
+    if let Some(smoothing) = settings.smoothing {
+        sculpter_core::smooth(&mut mesh, smoothing);
+    }
+    if let Some(simplification) = settings.simplification {
+        sculpter_core::simplify(&mut mesh, simplification);
+    }
+    mesh.scale(spacing.to_unit(LengthUnit::Millimeters).spacing);
+    mesh
+}
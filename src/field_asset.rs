@@ -0,0 +1,158 @@
+use std::{fmt, io};
+
+use bevy::{
+    asset::{AssetEvent, AssetLoader, LoadContext, io::Reader},
+    platform::collections::HashSet,
+    prelude::*,
+};
+
+use crate::{DensityField, DensityFieldSize, dirty_region::DensityFieldDirtyRegion, mesh::Meshed};
+
+const MAGIC: &[u8; 4] = b"DFLD";
+const VERSION: u32 = 1;
+
+/// The volume's `DensityField`, copied from a `DensityField` asset once it has
+/// loaded and again whenever it changes, so one field can be shared between
+/// volumes and hot-reloaded from the asset folder.
+#[derive(Component, Clone, Debug, Deref)]
+pub struct DensityFieldHandle(pub Handle<DensityField>);
+
+/// Loads `.dfield` files as `DensityField`s.
+///
+/// The format is little endian: magic `DFLD`, format version (`u32`, 1), the
+/// field's dimensions (`u32` × 3) and a density (`f32`) per sample, x fastest.
+#[derive(Default)]
+pub struct DensityFieldLoader;
+
+/// Why a `.dfield` file couldn't be loaded.
+#[derive(Debug)]
+pub enum DensityFieldError {
+    Io(io::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for DensityFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read .dfield file: {error}"),
+            Self::Invalid(reason) => write!(f, "invalid .dfield file: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for DensityFieldError {}
+
+impl From<io::Error> for DensityFieldError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl AssetLoader for DensityFieldLoader {
+    type Asset = DensityField;
+    type Settings = ();
+    type Error = DensityFieldError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<DensityField, DensityFieldError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let (_, field) = DensityField::read_dfield(&bytes)?;
+        Ok(field)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dfield"]
+    }
+}
+
+impl DensityField {
+    /// Write the field as a `.dfield` file of the given dimensions.
+    pub fn write_dfield(&self, dimensions: UVec3, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        for component in dimensions.to_array() {
+            writer.write_all(&component.to_le_bytes())?;
+        }
+        for density in &self.0 {
+            writer.write_all(&density.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read a `.dfield` file, with the dimensions it was written with.
+    pub fn read_dfield(bytes: &[u8]) -> Result<(UVec3, Self), DensityFieldError> {
+        let header = |range: std::ops::Range<usize>| {
+            bytes
+                .get(range)
+                .ok_or(DensityFieldError::Invalid("unexpected end of file"))
+        };
+        let read_u32 = |offset: usize| {
+            header(offset..offset + 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        if header(0..4)? != MAGIC {
+            return Err(DensityFieldError::Invalid("not a density field"));
+        }
+        if read_u32(4)? != VERSION {
+            return Err(DensityFieldError::Invalid("unsupported version"));
+        }
+        let dimensions = uvec3(read_u32(8)?, read_u32(12)?, read_u32(16)?);
+        let densities = &bytes[20..];
+        if densities.len() != dimensions.element_product() as usize * 4 {
+            return Err(DensityFieldError::Invalid(
+                "density count doesn't match the dimensions",
+            ));
+        }
+        let densities = densities
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        Ok((dimensions, Self(densities)))
+    }
+}
+
+/// Copy loaded and changed `DensityField` assets into the volumes referencing
+/// them, remeshing volumes that were already meshed.
+pub fn sync_density_field_handles(
+    mut commands: Commands,
+    mut field_events: MessageReader<AssetEvent<DensityField>>,
+    handles: Query<(Entity, Ref<DensityFieldHandle>, Has<Meshed>)>,
+    fields: Res<Assets<DensityField>>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let changed_fields: HashSet<AssetId<DensityField>> = field_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, handle, meshed) in &handles {
+        if !handle.is_changed() && !changed_fields.contains(&handle.id()) {
+            continue;
+        }
+        // Copied once it has loaded
+        let Some(field) = fields.get(&handle.0) else {
+            continue;
+        };
+        if field.len() != dimensions.density_count() as usize {
+            warn!(
+                "Density field asset of {entity} has {} samples, but DensityFieldSize needs {}",
+                field.len(),
+                dimensions.density_count()
+            );
+            continue;
+        }
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert(field.clone());
+        if meshed {
+            entity_commands.try_insert(DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0));
+        }
+    }
+}
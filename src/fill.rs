@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+
+use crate::{
+    DensityField, DensityFieldSize, dirty_region::DensityFieldDirtyRegion,
+    islands::sample_position, material::MaterialField,
+};
+
+/// Trigger on a volume to flood fill from a sample through the samples sharing
+/// faces with it, for quick level editing. A fill that would reach more than
+/// `max_samples` changes nothing.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct BucketFill {
+    pub entity: Entity,
+    /// Sample the fill starts from
+    pub start: UVec3,
+    pub mode: BucketFillMode,
+    pub max_samples: u32,
+}
+
+/// What a `BucketFill` fills.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BucketFillMode {
+    /// Give the solid of the start sample's material connected to it this
+    /// material id in the volume's `MaterialField`
+    Repaint(u32),
+    /// Fill the air connected to the start sample with solid, if the solid
+    /// encloses it within the field
+    Pocket,
+}
+
+impl BucketFill {
+    pub fn repaint(entity: Entity, start: UVec3, material: u32) -> Self {
+        Self {
+            entity,
+            start,
+            mode: BucketFillMode::Repaint(material),
+            max_samples: 1 << 20,
+        }
+    }
+
+    pub fn pocket(entity: Entity, start: UVec3) -> Self {
+        Self {
+            entity,
+            start,
+            mode: BucketFillMode::Pocket,
+            max_samples: 1 << 20,
+        }
+    }
+
+    pub fn with_max_samples(mut self, max_samples: u32) -> Self {
+        self.max_samples = max_samples;
+        self
+    }
+}
+
+/// Flood fill the volume `BucketFill` was triggered on, remeshing it.
+pub fn bucket_fill(
+    fill: On<BucketFill>,
+    mut commands: Commands,
+    mut volumes: Query<(&mut DensityField, Option<&mut MaterialField>)>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let entity = fill.entity;
+    let Ok((mut density_field, materials)) = volumes.get_mut(entity) else {
+        warn!("Can't fill {entity}: it has no DensityField");
+        return;
+    };
+    if fill.start.cmpge(dimensions.0).any() {
+        return;
+    }
+    let max_samples = fill.max_samples as usize;
+    match fill.mode {
+        BucketFillMode::Repaint(material) => {
+            let Some(mut materials) =
+                materials.filter(|materials| materials.len() == density_field.len())
+            else {
+                warn!("Can't repaint {entity}: its MaterialField doesn't match its DensityField");
+                return;
+            };
+            let start = dimensions.index(fill.start.x, fill.start.y, fill.start.z) as usize;
+            let replaced = materials[start];
+            if replaced == material {
+                return;
+            }
+            let Some(reached) =
+                sculpter_core::flood_fill(dimensions.0, fill.start, max_samples, |i| {
+                    density_field[i] <= 0.0 && materials[i] == replaced
+                })
+            else {
+                warn!("Can't repaint {entity}: the fill reaches over {max_samples} samples");
+                return;
+            };
+            for &i in &reached.samples {
+                materials[i as usize] = material;
+            }
+        }
+        BucketFillMode::Pocket => {
+            let Some(reached) =
+                sculpter_core::flood_fill(dimensions.0, fill.start, max_samples, |i| {
+                    density_field[i] > 0.0
+                })
+            else {
+                warn!("Can't fill the pocket of {entity}: it reaches over {max_samples} samples");
+                return;
+            };
+            if reached.touches_boundary {
+                warn!("Can't fill the pocket of {entity}: it isn't enclosed");
+                return;
+            }
+            let Some(region) = reached
+                .samples
+                .iter()
+                .map(|&i| DensityFieldDirtyRegion::voxel(sample_position(i, dimensions.0)))
+                .reduce(|a, b| a.union(&b))
+            else {
+                return;
+            };
+            // Mirrored to inside the solid, keeping the densities smooth
+            for &i in &reached.samples {
+                density_field[i as usize] = -density_field[i as usize];
+            }
+            commands
+                .entity(entity)
+                .entry::<DensityFieldDirtyRegion>()
+                .and_modify(move |mut dirty| *dirty = dirty.union(&region))
+                .or_insert(region);
+        }
+    }
+}
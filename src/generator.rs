@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize, streaming::ChunkRequested,
+    units::VoxelSpacing,
+};
+
+/// Procedural densities for the whole `ChunkMap`, filling every streamed chunk
+/// once installed as the `ChunkGenerator`.
+pub trait WorldGenerator: Send + Sync + 'static {
+    /// Density at `position`, in the map's space.
+    fn density(&self, position: Vec3) -> f32;
+
+    /// Densities of a `dimensions` grid starting at `origin`, `spacing` apart,
+    /// x fastest then y then z. Override to generate a chunk at once, e.g.
+    /// vectorized or in a compute pass.
+    fn densities(&self, origin: Vec3, spacing: Vec3, dimensions: UVec3) -> Vec<f32> {
+        let mut densities = Vec::with_capacity(dimensions.element_product() as usize);
+        for z in 0..dimensions.z {
+            for y in 0..dimensions.y {
+                for x in 0..dimensions.x {
+                    densities.push(self.density(origin + uvec3(x, y, z).as_vec3() * spacing));
+                }
+            }
+        }
+        densities
+    }
+}
+
+impl<F: Fn(Vec3) -> f32 + Send + Sync + 'static> WorldGenerator for F {
+    fn density(&self, position: Vec3) -> f32 {
+        self(position)
+    }
+}
+
+/// The `WorldGenerator` requested chunks are filled with, in the background.
+/// `ChunkRequested` observers then needn't insert a `DensityField` themselves.
+#[derive(Resource, Clone)]
+pub struct ChunkGenerator(pub Arc<dyn WorldGenerator>);
+
+impl ChunkGenerator {
+    pub fn new(generator: impl WorldGenerator) -> Self {
+        Self(Arc::new(generator))
+    }
+}
+
+/// Chunk generation running on the `AsyncComputeTaskPool`.
+#[derive(Component)]
+pub struct ChunkGenerationTask(Task<Vec<f32>>);
+
+/// Start generating a requested chunk's densities, dropping the task of the
+/// coordinate it was requested for before.
+pub fn generate_requested_chunk(
+    requested: On<ChunkRequested>,
+    mut commands: Commands,
+    generator: Option<Res<ChunkGenerator>>,
+    dimensions: Res<DensityFieldSize>,
+    mesh_size: Res<DensityFieldMeshSize>,
+) {
+    let Some(generator) = generator else {
+        return;
+    };
+    let generator = generator.0.clone();
+    let dimensions = dimensions.0;
+    let spacing = VoxelSpacing::volume_scale(None, *mesh_size, dimensions);
+    let origin = requested.origin.as_vec3() * spacing;
+    let task = AsyncComputeTaskPool::get()
+        .spawn(async move { generator.densities(origin, spacing, dimensions) });
+    commands
+        .entity(requested.entity)
+        .try_insert(ChunkGenerationTask(task));
+}
+
+/// Hand finished densities to their chunks, which meshes them.
+pub fn poll_chunk_generation(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut ChunkGenerationTask)>,
+) {
+    for (entity, mut task) in &mut tasks {
+        let Some(densities) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        commands
+            .entity(entity)
+            .try_insert(DensityField(densities))
+            .try_remove::<ChunkGenerationTask>();
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+use crate::mesh_data::MeshData;
+
+/// Raw geometry of a meshed volume, scaled like its `Mesh3d` would be and
+/// replaced on every remesh. On a build without the `gpu` feature it is the only
+/// output, and marks the volume as meshed.
+#[derive(Component, Clone, Debug, Default, Deref)]
+pub struct VolumeGeometry(pub MeshData);
+
+/// Also build `ColliderGeometry` whenever this volume is meshed.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct GenerateCollider;
+
+/// Also build `QuadMesh` whenever this volume is meshed.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct GenerateQuadMesh;
+
+/// The volume's geometry with the quads the mesher produced kept whole, for
+/// export to DCC tools or subdivision surfaces. Scaled like `VolumeGeometry`,
+/// whose triangles it covers exactly.
+#[derive(Component, Clone, Debug, Default)]
+pub struct QuadMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    /// Counter-clockwise quads
+    pub quads: Vec<[u32; 4]>,
+    /// Faces that were never quads, such as most marching cubes output
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl From<&MeshData> for QuadMesh {
+    fn from(mesh: &MeshData) -> Self {
+        let (quads, triangles) =
+            sculpter_core::quads_from_triangles(&mesh.positions, &mesh.indices);
+        Self {
+            positions: mesh.positions.clone(),
+            normals: mesh.normals.clone(),
+            quads,
+            triangles,
+        }
+    }
+}
+
+/// Triangle mesh collider input, laid out the way physics engines take trimesh
+/// colliders (e.g. `Collider::trimesh` in avian and rapier).
+#[derive(Component, Clone, Debug, Default)]
+pub struct ColliderGeometry {
+    pub vertices: Vec<Vec3>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl From<&MeshData> for ColliderGeometry {
+    fn from(mesh: &MeshData) -> Self {
+        Self {
+            vertices: mesh.positions.clone(),
+            indices: mesh
+                .indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+        }
+    }
+}
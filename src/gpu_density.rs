@@ -0,0 +1,341 @@
+use bevy::{
+    diagnostic::FrameCount,
+    prelude::*,
+    render::{extract_component::ExtractComponent, render_resource::ShaderType},
+    shader::Shader,
+};
+
+#[cfg(feature = "sdf_graph")]
+use crate::sdf_graph::SdfGraph;
+use crate::{
+    DensityFieldMeshSize, DensityFieldSize,
+    amortize::StageSchedule,
+    buffers::SurfaceNetsBuffers,
+    dirty_region::{MeshingRegion, Remesh},
+    mesh::Meshed,
+    sdf::{GpuSdfPart, SdfShape},
+    settings::SculptSettings,
+    units::VoxelSpacing,
+};
+
+// Must match `NOISE_*` in generate_density.wgsl
+const NOISE_FBM: u32 = 0;
+const NOISE_RIDGED: u32 = 1;
+
+// Must match `NOISE_BASIS_*` in noise.wgsl
+const NOISE_BASIS_PERLIN: u32 = 0;
+const NOISE_BASIS_SIMPLEX: u32 = 1;
+const NOISE_BASIS_WORLEY: u32 = 2;
+
+/// Fill the volume's density buffer on the GPU instead of uploading a
+/// `DensityField`, so procedural terrain never allocates its field on the CPU.
+/// Changing it regenerates and remeshes the volume.
+///
+/// Needs compute shaders. Features reading the `DensityField`, such as the CPU
+/// backend, level of detail chains and chunk aprons, skip such volumes.
+#[derive(Component, Clone, Debug, Default)]
+pub struct GpuDensity {
+    pub function: DensityFunction,
+    /// Position of the first sample, the others lying a voxel apart from it
+    pub origin: Vec3,
+}
+
+/// What a `GpuDensity` volume's densities are computed from.
+#[derive(Clone, Debug)]
+pub enum DensityFunction {
+    Noise(NoiseStack),
+    /// Height above the heightmap stretched over the volume's x and z extent,
+    /// its red channel times `vertical_scale`
+    Heightmap {
+        image: Handle<Image>,
+        vertical_scale: f32,
+    },
+    /// The `density(position: vec3<f32>) -> f32` function of the loaded shader
+    /// with `#define_import_path sculpter::custom_density`, which can import
+    /// the built-in noise from `sculpter::noise`
+    Custom,
+    /// Signed distance from each sample's position to the shape, for authored
+    /// base shapes
+    Sdf(SdfShape),
+    /// The compiled graph, once it has loaded
+    #[cfg(feature = "sdf_graph")]
+    Graph(Handle<SdfGraph>),
+}
+
+impl Default for DensityFunction {
+    fn default() -> Self {
+        Self::Noise(default())
+    }
+}
+
+/// How a noise octave is shaped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum NoiseKind {
+    /// Plain gradient noise, rolling hills
+    #[default]
+    Fbm,
+    /// Folded noise, sharp crests like mountain ranges
+    Ridged,
+}
+
+/// The noise each octave samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum NoiseBasis {
+    /// Gradient noise on a cubic lattice
+    #[default]
+    Perlin,
+    /// Gradient noise on a tetrahedral lattice, without Perlin's axis-aligned
+    /// artifacts
+    Simplex,
+    /// Distance to the nearest of randomly scattered points, cells and pockets
+    Worley,
+}
+
+/// Octaves of 3D noise. With a `ground`, densities are the height above it
+/// raised and lowered by up to `amplitude`, for terrain; without, they are the
+/// noise itself scaled by `amplitude`, solid where it is positive.
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseStack {
+    pub basis: NoiseBasis,
+    pub kind: NoiseKind,
+    pub seed: u32,
+    pub octaves: u32,
+    /// Frequency of the first octave, per unit of distance
+    pub frequency: f32,
+    /// Frequency multiplier from one octave to the next
+    pub lacunarity: f32,
+    /// Weight multiplier from one octave to the next
+    pub gain: f32,
+    pub amplitude: f32,
+    /// Distance the lookups are displaced by another noise stack, for twisted
+    /// overhangs. 0 disables the domain warp.
+    pub warp: f32,
+    pub ground: Option<f32>,
+}
+
+impl Default for NoiseStack {
+    fn default() -> Self {
+        Self {
+            basis: NoiseBasis::Perlin,
+            kind: NoiseKind::Fbm,
+            seed: 0,
+            octaves: 5,
+            frequency: 0.1,
+            lacunarity: 2.0,
+            gain: 0.5,
+            amplitude: 4.0,
+            warp: 0.0,
+            ground: Some(5.0),
+        }
+    }
+}
+
+/// Uniform of the density generation kernel.
+#[derive(ShaderType, Clone, Copy, Debug, Default, PartialEq)]
+pub struct DensityGenerationParams {
+    pub origin: Vec3,
+    pub spacing: Vec3,
+    pub dimensions: UVec3,
+    pub noise: u32,
+    pub basis: u32,
+    pub terrain: u32,
+    pub seed: u32,
+    pub octaves: u32,
+    pub frequency: f32,
+    pub lacunarity: f32,
+    pub gain: f32,
+    pub amplitude: f32,
+    pub warp: f32,
+    pub ground: f32,
+    pub vertical_scale: f32,
+    pub sdf_parts: u32,
+}
+
+impl DensityGenerationParams {
+    fn new(density: &GpuDensity, spacing: Vec3, dimensions: DensityFieldSize) -> Self {
+        let noise = match &density.function {
+            DensityFunction::Noise(noise) => *noise,
+            _ => default(),
+        };
+        let vertical_scale = match &density.function {
+            DensityFunction::Heightmap { vertical_scale, .. } => *vertical_scale,
+            _ => 0.0,
+        };
+        let sdf_parts = match &density.function {
+            DensityFunction::Sdf(shape) => shape.parts.len() as u32,
+            _ => 0,
+        };
+        Self {
+            origin: density.origin,
+            spacing,
+            dimensions: dimensions.0,
+            noise: match noise.kind {
+                NoiseKind::Fbm => NOISE_FBM,
+                NoiseKind::Ridged => NOISE_RIDGED,
+            },
+            basis: match noise.basis {
+                NoiseBasis::Perlin => NOISE_BASIS_PERLIN,
+                NoiseBasis::Simplex => NOISE_BASIS_SIMPLEX,
+                NoiseBasis::Worley => NOISE_BASIS_WORLEY,
+            },
+            terrain: noise.ground.is_some() as u32,
+            seed: noise.seed,
+            octaves: noise.octaves,
+            frequency: noise.frequency,
+            lacunarity: noise.lacunarity,
+            gain: noise.gain,
+            amplitude: noise.amplitude,
+            warp: noise.warp,
+            ground: noise.ground.unwrap_or_default(),
+            vertical_scale,
+            sdf_parts,
+        }
+    }
+}
+
+/// A compiled kernel filling a `GpuDensity` volume's buffer in place of the
+/// built-in one, binding the density buffer and parameters of
+/// `sculpter::density_generation`. Kept up to date by whatever compiled it;
+/// bumping `revision` regenerates the volume after the shader is replaced.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct DensityKernel {
+    pub shader: Handle<Shader>,
+    pub revision: u32,
+}
+
+/// Densities a `GpuDensity` volume's buffer should hold. The render world
+/// generates them again whenever they change.
+#[derive(Component, ExtractComponent, Clone, Debug, PartialEq)]
+pub struct DensityGeneration {
+    pub params: DensityGenerationParams,
+    /// Run the user's `density` function rather than the noise stack
+    pub custom: bool,
+    /// Heightmap sampled rather than the noise stack
+    pub heightmap: Option<Handle<Image>>,
+    /// Parts of the shape evaluated rather than the noise stack
+    pub sdf: Option<Vec<GpuSdfPart>>,
+    /// Compiled kernel run rather than the built-in one
+    pub kernel: Option<DensityKernel>,
+}
+
+impl DensityGeneration {
+    /// `None` while the volume waits for its kernel to compile.
+    pub(crate) fn new(
+        density: &GpuDensity,
+        kernel: Option<&DensityKernel>,
+        spacing: Option<&VoxelSpacing>,
+        mesh_size: DensityFieldMeshSize,
+        dimensions: DensityFieldSize,
+    ) -> Option<Self> {
+        #[cfg(feature = "sdf_graph")]
+        if matches!(density.function, DensityFunction::Graph(_)) && kernel.is_none() {
+            return None;
+        }
+        let spacing = VoxelSpacing::volume_scale(spacing, mesh_size, dimensions.0);
+        Some(Self {
+            params: DensityGenerationParams::new(density, spacing, dimensions),
+            custom: matches!(density.function, DensityFunction::Custom),
+            heightmap: match &density.function {
+                DensityFunction::Heightmap { image, .. } => Some(image.clone()),
+                _ => None,
+            },
+            sdf: match &density.function {
+                DensityFunction::Sdf(shape) => Some(GpuSdfPart::from_shape(shape)),
+                _ => None,
+            },
+            kernel: kernel.cloned(),
+        })
+    }
+}
+
+/// Regenerate and remesh volumes whose `GpuDensity` or kernel changed after
+/// their buffers were created. Volumes without buffers yet are generated along
+/// with them.
+pub fn queue_density_generation(
+    mut commands: Commands,
+    changed: Query<
+        (
+            Entity,
+            &GpuDensity,
+            Option<&DensityKernel>,
+            &SurfaceNetsBuffers,
+            Option<&VoxelSpacing>,
+            Option<&SculptSettings>,
+            Has<Meshed>,
+        ),
+        Or<(Changed<GpuDensity>, Changed<DensityKernel>)>,
+    >,
+    mesh_size: Res<DensityFieldMeshSize>,
+    frame: Res<FrameCount>,
+) {
+    for (entity, density, kernel, buffers, spacing, settings, meshed) in &changed {
+        let Some(generation) =
+            DensityGeneration::new(density, kernel, spacing, *mesh_size, buffers.dimensions)
+        else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert((generation, MeshingRegion::full(buffers.dimensions)));
+        if meshed {
+            entity_commands.try_insert(Remesh);
+        }
+        if let Some(stages_per_frame) = settings.and_then(|settings| settings.stages_per_frame) {
+            entity_commands.try_insert(StageSchedule::new(stages_per_frame, &frame));
+        }
+    }
+}
+
+/// Fill the volume with fractal noise on the GPU before meshing, solid where
+/// the noise is positive. Shorthand for a `GpuDensity` running the matching
+/// `NoiseStack`; use that directly for ridges, domain warp or terrain.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct NoiseDensitySource {
+    pub basis: NoiseBasis,
+    pub seed: u32,
+    pub octaves: u32,
+    /// Frequency of the first octave, per unit of distance
+    pub frequency: f32,
+    /// Frequency multiplier from one octave to the next
+    pub lacunarity: f32,
+}
+
+impl Default for NoiseDensitySource {
+    fn default() -> Self {
+        Self {
+            basis: NoiseBasis::Perlin,
+            seed: 0,
+            octaves: 5,
+            frequency: 0.1,
+            lacunarity: 2.0,
+        }
+    }
+}
+
+impl From<NoiseDensitySource> for NoiseStack {
+    fn from(source: NoiseDensitySource) -> Self {
+        Self {
+            basis: source.basis,
+            seed: source.seed,
+            octaves: source.octaves,
+            frequency: source.frequency,
+            lacunarity: source.lacunarity,
+            amplitude: 1.0,
+            ground: None,
+            ..default()
+        }
+    }
+}
+
+/// Turn changed `NoiseDensitySource`s into the `GpuDensity` generating them,
+/// keeping its origin.
+pub fn sync_noise_density_sources(
+    mut commands: Commands,
+    sources: Query<(Entity, &NoiseDensitySource, Option<&GpuDensity>), Changed<NoiseDensitySource>>,
+) {
+    for (entity, source, density) in &sources {
+        commands.entity(entity).try_insert(GpuDensity {
+            function: DensityFunction::Noise((*source).into()),
+            origin: density.map_or(Vec3::ZERO, |density| density.origin),
+        });
+    }
+}
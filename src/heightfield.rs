@@ -0,0 +1,194 @@
+#[cfg(feature = "gpu")]
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::render::{
+    render_resource::{binding_types::*, *},
+    renderer::RenderDevice,
+};
+
+use crate::backend::MeshingBackend;
+#[cfg(feature = "gpu")]
+use crate::{
+    DensityFieldSize,
+    backend::{BackendBindings, BackendInit, BackendKernels, WORKGROUP_SIZE},
+    dirty_region::{DensityFieldDirtyRegion, MeshingRegion},
+    quantize::DensityDecode,
+    settings::SurfaceNetsParams,
+};
+
+#[cfg(feature = "gpu")]
+const HEIGHTFIELD_SHADER: &str = "shaders/heightfield.wgsl";
+
+// Indices into `BackendKernels::layouts`
+#[cfg(feature = "gpu")]
+const BUFFER_LAYOUT: usize = 0;
+#[cfg(feature = "gpu")]
+const TEXTURE_LAYOUT: usize = 1;
+
+/// One vertex per column at its surface height, found by binary search, joined
+/// into a regular grid of quads.
+pub struct Heightfield;
+
+impl MeshingBackend for Heightfield {
+    #[cfg(feature = "gpu")]
+    fn vertices_per_point(&self) -> u32 {
+        // Only the points at y = 0 are used, one per column
+        1
+    }
+
+    #[cfg(feature = "gpu")]
+    fn faces_per_point(&self) -> u32 {
+        1
+    }
+
+    #[cfg(feature = "gpu")]
+    fn meshing_region(
+        &self,
+        dirty: DensityFieldDirtyRegion,
+        dimensions: DensityFieldSize,
+    ) -> MeshingRegion {
+        // An edit anywhere in a column can move its surface
+        let mut region = MeshingRegion::from_dirty(dirty, dimensions);
+        region.min.y = 0;
+        region.max.y = MeshingRegion::full(dimensions).max.y;
+        region
+    }
+
+    #[cfg(feature = "gpu")]
+    fn init_kernels(&self, init: &BackendInit) -> BackendKernels {
+        // Shared by the vertex and face stages
+        let layout = init.render_device.create_bind_group_layout(
+            "HeightfieldLayout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    storage_buffer_read_only::<Vec<u32>>(false), // density_field (f32 bits or packed bytes)
+                    storage_buffer::<Vec<f32>>(false),           // vertices (output)
+                    storage_buffer::<Vec<u32>>(false),           // vertex_valid (output)
+                    uniform_buffer::<UVec3>(false),              // dimensions
+                    uniform_buffer::<DensityDecode>(false),      // density_decode
+                    uniform_buffer::<MeshingRegion>(false),      // region
+                    uniform_buffer::<SurfaceNetsParams>(false),  // params
+                    storage_buffer::<Vec<u32>>(false),           // faces (output)
+                    storage_buffer::<Vec<u32>>(false),           // face_valid (output)
+                ),
+            ),
+        );
+
+        // Same, from a 3D density texture
+        let texture_layout = init.render_device.create_bind_group_layout(
+            "HeightfieldTextureLayout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    texture_3d(TextureSampleType::Float { filterable: true }), // density_texture
+                    storage_buffer::<Vec<f32>>(false),                         // vertices (output)
+                    storage_buffer::<Vec<u32>>(false), // vertex_valid (output)
+                    uniform_buffer::<UVec3>(false),    // dimensions
+                    uniform_buffer::<DensityDecode>(false), // density_decode
+                    uniform_buffer::<MeshingRegion>(false), // region
+                    uniform_buffer::<SurfaceNetsParams>(false), // params
+                    storage_buffer::<Vec<u32>>(false), // faces (output)
+                    storage_buffer::<Vec<u32>>(false), // face_valid (output)
+                    sampler(SamplerBindingType::Filtering), // density_sampler
+                ),
+            ),
+        );
+
+        // One pipeline per entry point and density input
+        let texture_def = || vec!["DENSITY_TEXTURE".into()];
+        BackendKernels {
+            buffer: [
+                init.queue_pipeline(
+                    "heightfield_vertices_pipeline",
+                    &layout,
+                    HEIGHTFIELD_SHADER,
+                    "generate_heightfield_vertices",
+                    vec![],
+                ),
+                init.queue_pipeline(
+                    "heightfield_faces_pipeline",
+                    &layout,
+                    HEIGHTFIELD_SHADER,
+                    "generate_heightfield_faces",
+                    vec![],
+                ),
+            ],
+            texture: [
+                init.queue_pipeline(
+                    "heightfield_vertices_texture_pipeline",
+                    &texture_layout,
+                    HEIGHTFIELD_SHADER,
+                    "generate_heightfield_vertices",
+                    texture_def(),
+                ),
+                init.queue_pipeline(
+                    "heightfield_faces_texture_pipeline",
+                    &texture_layout,
+                    HEIGHTFIELD_SHADER,
+                    "generate_heightfield_faces",
+                    texture_def(),
+                ),
+            ],
+            layouts: vec![layout, texture_layout],
+            buffers: vec![],
+        }
+    }
+
+    #[cfg(feature = "gpu")]
+    fn bind_groups(
+        &self,
+        kernels: &BackendKernels,
+        render_device: &RenderDevice,
+        bindings: &BackendBindings,
+    ) -> [BindGroup; 2] {
+        // Both stages run from one bind group
+        let bind_group = match &bindings.density_sampler {
+            Some(density_sampler) => render_device.create_bind_group(
+                Some("heightfield_texture_bind_group"),
+                &kernels.layouts[TEXTURE_LAYOUT],
+                &BindGroupEntries::sequential((
+                    bindings.density.clone(),
+                    bindings.vertices.clone(),
+                    bindings.vertex_valid.clone(),
+                    bindings.dimensions.clone(),
+                    bindings.density_decode.clone(),
+                    bindings.region.clone(),
+                    bindings.params.clone(),
+                    bindings.faces.clone(),
+                    bindings.face_valid.clone(),
+                    density_sampler.clone(),
+                )),
+            ),
+            None => render_device.create_bind_group(
+                Some("heightfield_bind_group"),
+                &kernels.layouts[BUFFER_LAYOUT],
+                &BindGroupEntries::sequential((
+                    bindings.density.clone(),
+                    bindings.vertices.clone(),
+                    bindings.vertex_valid.clone(),
+                    bindings.dimensions.clone(),
+                    bindings.density_decode.clone(),
+                    bindings.region.clone(),
+                    bindings.params.clone(),
+                    bindings.faces.clone(),
+                    bindings.face_valid.clone(),
+                )),
+            ),
+        };
+        [bind_group.clone(), bind_group]
+    }
+
+    #[cfg(feature = "gpu")]
+    fn workgroups(&self, size: UVec3) -> [(u32, u32, u32); 2] {
+        // Columns and column cells only, vertices on one more column per axis
+        let columns = |size: UVec3| {
+            (
+                size.x.div_ceil(WORKGROUP_SIZE),
+                1,
+                size.z.div_ceil(WORKGROUP_SIZE),
+            )
+        };
+        [columns(size + 1), columns(size)]
+    }
+}
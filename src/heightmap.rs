@@ -0,0 +1,193 @@
+use bevy::{asset::AssetEvent, platform::collections::HashSet, prelude::*};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize,
+    dirty_region::DensityFieldDirtyRegion,
+    gpu_density::{DensityFunction, GpuDensity},
+    material::MaterialField,
+    mesh::Meshed,
+    settings::SculptBackend,
+    units::VoxelSpacing,
+};
+
+/// Terrain from a heightmap image stretched over the volume's x and z extent:
+/// samples are solid below the image's red channel times `vertical_scale`.
+///
+/// Load the image as linear rather than sRGB (`is_srgb: false`), or the
+/// heights are curved by the sRGB transfer function.
+#[derive(Component, Clone, Debug)]
+pub struct HeightmapDensitySource {
+    pub image: Handle<Image>,
+    /// Height of a full intensity texel, above the volume's first sample
+    pub vertical_scale: f32,
+    /// Material layers from the surface down, written to the volume's
+    /// `MaterialField`. Only the CPU conversion fills them in.
+    pub layers: Vec<HeightmapLayer>,
+    /// Where the densities are computed: `Cpu` fills a `DensityField` once the
+    /// image has loaded and again whenever it changes, `Gpu` a `GpuDensity`
+    /// pass without any field on the CPU
+    pub backend: SculptBackend,
+}
+
+impl HeightmapDensitySource {
+    pub fn new(image: Handle<Image>, vertical_scale: f32) -> Self {
+        Self {
+            image,
+            vertical_scale,
+            layers: Vec::new(),
+            backend: SculptBackend::Cpu,
+        }
+    }
+}
+
+/// A layer of material under the heightmap's surface.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeightmapLayer {
+    pub material: u32,
+    /// Depth of the layer, the last one reaching all the way down
+    pub thickness: f32,
+}
+
+/// Convert heightmaps whose source or image changed into their volume's
+/// densities, remeshing volumes that were already meshed.
+pub fn sync_heightmap_sources(
+    mut commands: Commands,
+    mut image_events: MessageReader<AssetEvent<Image>>,
+    sources: Query<(
+        Entity,
+        Ref<HeightmapDensitySource>,
+        Option<&GpuDensity>,
+        Option<&VoxelSpacing>,
+        Has<Meshed>,
+    )>,
+    images: Res<Assets<Image>>,
+    dimensions: Res<DensityFieldSize>,
+    mesh_size: Res<DensityFieldMeshSize>,
+) {
+    let changed_images: HashSet<AssetId<Image>> = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, source, density, spacing, meshed) in &sources {
+        if source.backend == SculptBackend::Gpu {
+            // The render world waits for the image itself
+            if source.is_changed() {
+                commands.entity(entity).try_insert(GpuDensity {
+                    function: DensityFunction::Heightmap {
+                        image: source.image.clone(),
+                        vertical_scale: source.vertical_scale,
+                    },
+                    origin: density.map_or(Vec3::ZERO, |density| density.origin),
+                });
+            }
+            continue;
+        }
+
+        if !source.is_changed() && !changed_images.contains(&source.image.id()) {
+            continue;
+        }
+        // Converted once it has loaded
+        let Some(image) = images.get(&source.image) else {
+            continue;
+        };
+        let spacing = VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions.0);
+        let heights = column_heights(image, dimensions.0, source.vertical_scale);
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert(DensityField(heightmap_densities(
+            &heights,
+            dimensions.0,
+            spacing.y,
+        )));
+        if !source.layers.is_empty() {
+            entity_commands.try_insert(MaterialField(heightmap_materials(
+                &heights,
+                dimensions.0,
+                spacing.y,
+                &source.layers,
+            )));
+        }
+        if meshed {
+            entity_commands.try_insert(DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0));
+        }
+    }
+}
+
+/// Surface height of each sample column, x fastest then z, sampled bilinearly
+/// with the first and last columns on the centers of the edge texels, as the
+/// GPU pass does.
+fn column_heights(image: &Image, dimensions: UVec3, vertical_scale: f32) -> Vec<f32> {
+    let size = image.size().as_vec2();
+    let texel = |x: u32, y: u32| {
+        image
+            .get_color_at(x, y)
+            .map_or(0.0, |color| color.to_linear().red)
+    };
+    let last = dimensions
+        .xz()
+        .saturating_sub(UVec2::ONE)
+        .max(UVec2::ONE)
+        .as_vec2();
+
+    let mut heights = Vec::with_capacity((dimensions.x * dimensions.z) as usize);
+    for z in 0..dimensions.z {
+        for x in 0..dimensions.x {
+            let position = uvec2(x, z).as_vec2() / last * (size - 1.0);
+            let low = position.floor().as_uvec2();
+            let high = position.ceil().as_uvec2();
+            let t = position.fract();
+            let near = texel(low.x, low.y).lerp(texel(high.x, low.y), t.x);
+            let far = texel(low.x, high.y).lerp(texel(high.x, high.y), t.x);
+            heights.push(near.lerp(far, t.y) * vertical_scale);
+        }
+    }
+    heights
+}
+
+/// Height of every sample above its column's surface, negative below it.
+fn heightmap_densities(heights: &[f32], dimensions: UVec3, spacing: f32) -> Vec<f32> {
+    let mut densities = Vec::with_capacity(dimensions.element_product() as usize);
+    for z in 0..dimensions.z {
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                let height = heights[(z * dimensions.x + x) as usize];
+                densities.push(y as f32 * spacing - height);
+            }
+        }
+    }
+    densities
+}
+
+/// Material of the layer every sample lies in, by its depth below the surface.
+fn heightmap_materials(
+    heights: &[f32],
+    dimensions: UVec3,
+    spacing: f32,
+    layers: &[HeightmapLayer],
+) -> Vec<u32> {
+    let layer_at = |depth: f32| {
+        let mut bottom = 0.0;
+        for layer in layers {
+            bottom += layer.thickness;
+            if depth < bottom {
+                return layer.material;
+            }
+        }
+        layers[layers.len() - 1].material
+    };
+
+    let mut materials = Vec::with_capacity(dimensions.element_product() as usize);
+    for z in 0..dimensions.z {
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                let height = heights[(z * dimensions.x + x) as usize];
+                materials.push(layer_at(height - y as f32 * spacing));
+            }
+        }
+    }
+    materials
+}
@@ -0,0 +1,545 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use sculpter_core::grid::index;
+
+#[cfg(feature = "gpu")]
+use crate::brush::{QueuedBrushStrokes, WritingBackBrushStrokes};
+use crate::{
+    DensityField, DensityFieldSize,
+    dirty_region::{DensityEdited, DensityFieldDirtyRegion},
+    material::MaterialField,
+};
+
+/// Undo and redo of the edits to a volume's `DensityField` and
+/// `MaterialField`.
+///
+/// Each frame the fields change in is recorded as one edit: the box around the
+/// samples that changed, XORed with their previous values and run length
+/// encoded, so an edit costs about as much as the samples it changed. Edits
+/// are found by comparing against a copy of the fields taken when the history
+/// is added, which doesn't count towards `max_bytes`. Brush strokes are
+/// recorded once they are read back into the field, and undoing or redoing
+/// waits for strokes still on their way, then uploads the restored samples to
+/// the volume's density buffer.
+#[derive(Component, Clone, Debug)]
+pub struct EditHistory {
+    /// Most bytes of edits kept, the oldest dropped first
+    pub max_bytes: usize,
+    /// Most edits kept, the oldest dropped first
+    pub max_edits: usize,
+    undo: VecDeque<FieldEdit>,
+    redo: Vec<FieldEdit>,
+    bytes: usize,
+    densities: Vec<f32>,
+    materials: Option<Vec<u32>>,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024 * 1024,
+            max_edits: 100,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            bytes: 0,
+            densities: Vec::new(),
+            materials: None,
+        }
+    }
+}
+
+/// The samples an edit changed, within `min` (inclusive) and `max`
+/// (exclusive).
+#[derive(Clone, Debug)]
+struct FieldEdit {
+    min: UVec3,
+    max: UVec3,
+    densities: Vec<u32>,
+    materials: Option<Vec<u32>>,
+}
+
+impl FieldEdit {
+    fn bytes(&self) -> usize {
+        let words = self.densities.len() + self.materials.as_ref().map_or(0, Vec::len);
+        size_of::<Self>() + words * size_of::<u32>()
+    }
+}
+
+impl EditHistory {
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_max_edits(mut self, max_edits: usize) -> Self {
+        self.max_edits = max_edits;
+        self
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Bytes taken by the recorded edits.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Forget every edit, keeping the fields as they are.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.bytes = 0;
+    }
+
+    /// Take back the latest edit of the fields, or the latest redone one.
+    ///
+    /// Returns the region to mark dirty for the volume to be remeshed, or
+    /// `None` if there is nothing to undo.
+    pub fn undo(
+        &mut self,
+        dimensions: UVec3,
+        density_field: &mut DensityField,
+        materials: Option<&mut MaterialField>,
+    ) -> Option<DensityFieldDirtyRegion> {
+        let edit = self.undo.pop_back()?;
+        self.bytes -= edit.bytes();
+        let region = self.apply(&edit, dimensions, density_field, materials);
+        self.redo.push(edit);
+        Some(region)
+    }
+
+    /// Make the latest undone edit again.
+    ///
+    /// Returns the region to mark dirty for the volume to be remeshed, or
+    /// `None` if there is nothing to redo.
+    pub fn redo(
+        &mut self,
+        dimensions: UVec3,
+        density_field: &mut DensityField,
+        materials: Option<&mut MaterialField>,
+    ) -> Option<DensityFieldDirtyRegion> {
+        let edit = self.redo.pop()?;
+        let region = self.apply(&edit, dimensions, density_field, materials);
+        self.push(edit);
+        Some(region)
+    }
+
+    /// Flip the samples of `edit` in the fields and in the copy of them, which
+    /// undoes it the first time and redoes it the next.
+    fn apply(
+        &mut self,
+        edit: &FieldEdit,
+        dimensions: UVec3,
+        density_field: &mut DensityField,
+        materials: Option<&mut MaterialField>,
+    ) -> DensityFieldDirtyRegion {
+        let samples = || region_indices(dimensions, edit.min, edit.max);
+        apply_diff(&edit.densities, samples(), |i, word| {
+            density_field[i] = f32::from_bits(density_field[i].to_bits() ^ word);
+            self.densities[i] = density_field[i];
+        });
+        if let (Some(diff), Some(materials), Some(copy)) =
+            (&edit.materials, materials, &mut self.materials)
+        {
+            apply_diff(diff, samples(), |i, word| {
+                materials[i] ^= word;
+                copy[i] = materials[i];
+            });
+        }
+        DensityFieldDirtyRegion::new(edit.min, edit.max)
+    }
+
+    fn push(&mut self, edit: FieldEdit) {
+        self.bytes += edit.bytes();
+        self.undo.push_back(edit);
+        while self.bytes > self.max_bytes || self.undo.len() > self.max_edits {
+            let Some(oldest) = self.undo.pop_front() else {
+                break;
+            };
+            self.bytes -= oldest.bytes();
+        }
+    }
+
+    /// Record the samples that changed since the copy of the fields, within
+    /// `search`, as an edit, and bring the copy up to date.
+    fn record(
+        &mut self,
+        dimensions: UVec3,
+        search: DensityFieldDirtyRegion,
+        density_field: &DensityField,
+        materials: Option<&MaterialField>,
+    ) {
+        // A field replaced by one of another size starts a new history
+        let materials = materials.filter(|materials| materials.len() == density_field.len());
+        if self.densities.len() != density_field.len()
+            || self.materials.is_some() != materials.is_some()
+        {
+            self.clear();
+            self.densities.clone_from(density_field);
+            self.materials = materials.map(|materials| materials.0.clone());
+            return;
+        }
+
+        let mut changed: Option<DensityFieldDirtyRegion> = None;
+        for z in search.min.z..search.max.z {
+            for y in search.min.y..search.max.y {
+                for x in search.min.x..search.max.x {
+                    let i = index(dimensions, x, y, z) as usize;
+                    let differs = density_field[i].to_bits() != self.densities[i].to_bits()
+                        || materials
+                            .zip(self.materials.as_ref())
+                            .is_some_and(|(materials, copy)| materials[i] != copy[i]);
+                    if differs {
+                        let voxel = DensityFieldDirtyRegion::voxel(uvec3(x, y, z));
+                        changed = Some(changed.map_or(voxel, |region| region.union(&voxel)));
+                    }
+                }
+            }
+        }
+        let Some(region) = changed else {
+            return;
+        };
+
+        let samples = || region_indices(dimensions, region.min, region.max);
+        let densities = encode_diff(
+            samples().map(|i| density_field[i].to_bits() ^ self.densities[i].to_bits()),
+        );
+        let materials = materials
+            .zip(self.materials.as_mut())
+            .map(|(materials, copy)| {
+                let diff = encode_diff(samples().map(|i| materials[i] ^ copy[i]));
+                samples().for_each(|i| copy[i] = materials[i]);
+                diff
+            });
+        samples().for_each(|i| self.densities[i] = density_field[i]);
+
+        self.redo.clear();
+        self.push(FieldEdit {
+            min: region.min,
+            max: region.max,
+            densities,
+            materials,
+        });
+    }
+}
+
+/// Indices of the samples from `min` (inclusive) to `max` (exclusive), x
+/// fastest.
+fn region_indices(dimensions: UVec3, min: UVec3, max: UVec3) -> impl Iterator<Item = usize> {
+    (min.z..max.z).flat_map(move |z| {
+        (min.y..max.y)
+            .flat_map(move |y| (min.x..max.x).map(move |x| index(dimensions, x, y, z) as usize))
+    })
+}
+
+/// XORed words as alternating runs of zeros and of changed words, each run of
+/// zeros a length and each run of changed words a length followed by them.
+fn encode_diff(words: impl Iterator<Item = u32>) -> Vec<u32> {
+    let mut encoded = Vec::new();
+    let mut zeros = 0;
+    let mut changed = Vec::new();
+    for word in words {
+        if word != 0 {
+            changed.push(word);
+            continue;
+        }
+        if !changed.is_empty() {
+            encoded.extend([zeros, changed.len() as u32]);
+            encoded.append(&mut changed);
+            zeros = 0;
+        }
+        zeros += 1;
+    }
+    if !changed.is_empty() {
+        encoded.extend([zeros, changed.len() as u32]);
+        encoded.append(&mut changed);
+    }
+    encoded
+}
+
+/// Call `apply` with the sample index and word of every changed word of
+/// `encoded`, read along `samples`.
+fn apply_diff(
+    encoded: &[u32],
+    mut samples: impl Iterator<Item = usize>,
+    mut apply: impl FnMut(usize, u32),
+) {
+    let mut rest = encoded;
+    while let [zeros, count, tail @ ..] = rest {
+        if *zeros > 0 {
+            samples.nth(*zeros as usize - 1);
+        }
+        let (words, tail) = tail.split_at((*count as usize).min(tail.len()));
+        for (&word, i) in words.iter().zip(&mut samples) {
+            apply(i, word);
+        }
+        rest = tail;
+    }
+}
+
+/// Trigger on a volume with an `EditHistory` to undo its latest edit and
+/// remesh it.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct UndoEdit {
+    pub entity: Entity,
+}
+
+/// Trigger on a volume with an `EditHistory` to redo its latest undone edit
+/// and remesh it.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct RedoEdit {
+    pub entity: Entity,
+}
+
+/// Undo or redo waiting for a volume's brush strokes to reach its field.
+#[cfg(feature = "gpu")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HistoryStep {
+    Undo,
+    Redo,
+}
+
+/// Undos and redos of a volume in the order they were triggered, applied once
+/// its brush strokes are read back.
+#[cfg(feature = "gpu")]
+#[derive(Component, Clone, Debug, Default)]
+pub struct DeferredHistorySteps(Vec<HistoryStep>);
+
+/// Record the edits made to volumes with an `EditHistory` this frame, looking
+/// for changed densities within their dirty region or the region brush
+/// strokes were read back into, and for changed materials anywhere.
+pub fn record_edit_history(
+    mut histories: Query<(
+        &mut EditHistory,
+        Ref<DensityField>,
+        Option<Ref<MaterialField>>,
+        Option<&DensityFieldDirtyRegion>,
+    )>,
+    mut sculpted: MessageReader<DensityEdited>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let full = DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0);
+    for (mut history, density_field, materials, dirty) in &mut histories {
+        let repainted = materials.as_ref().is_some_and(Ref::is_changed);
+        if !history.is_added() && !density_field.is_changed() && !repainted {
+            continue;
+        }
+        let search = match dirty {
+            Some(dirty) if !repainted => dirty.clamped(*dimensions).unwrap_or(full),
+            _ => full,
+        };
+        history.record(dimensions.0, search, &density_field, materials.as_deref());
+    }
+
+    for sculpted in sculpted.read() {
+        let Ok((mut history, density_field, materials, _)) = histories.get_mut(sculpted.entity)
+        else {
+            continue;
+        };
+        let search = sculpted.region.clamped(*dimensions).unwrap_or(full);
+        history.record(dimensions.0, search, &density_field, materials.as_deref());
+    }
+}
+
+type HistoryVolume = (
+    &'static mut EditHistory,
+    &'static mut DensityField,
+    Option<&'static mut MaterialField>,
+);
+
+/// Whether a volume has brush strokes not yet read back into its field, or
+/// steps already waiting for them.
+#[cfg(feature = "gpu")]
+type SculptingVolume = (
+    Has<WritingBackBrushStrokes>,
+    Option<&'static QueuedBrushStrokes>,
+    Has<DeferredHistorySteps>,
+);
+
+/// Queue `step` on the volume if its brush strokes haven't all reached its
+/// field, as undoing them now would restore samples the strokes then
+/// overwrite, or upload stale samples over them. Returns whether it did.
+#[cfg(feature = "gpu")]
+fn defer_step(
+    commands: &mut Commands,
+    entity: Entity,
+    step: HistoryStep,
+    sculpting: &Query<SculptingVolume>,
+) -> bool {
+    let Ok((writing_back, queued, deferred)) = sculpting.get(entity) else {
+        return false;
+    };
+    if !writing_back && queued.is_none_or(QueuedBrushStrokes::is_empty) && !deferred {
+        return false;
+    }
+    commands
+        .entity(entity)
+        .entry::<DeferredHistorySteps>()
+        .or_default()
+        .and_modify(move |mut deferred| deferred.0.push(step));
+    true
+}
+
+/// Undo the latest edit of the volume `UndoEdit` was triggered on.
+pub fn undo_edit(
+    undo: On<UndoEdit>,
+    mut commands: Commands,
+    mut volumes: Query<HistoryVolume>,
+    #[cfg(feature = "gpu")] sculpting: Query<SculptingVolume>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let entity = undo.entity;
+    #[cfg(feature = "gpu")]
+    if defer_step(&mut commands, entity, HistoryStep::Undo, &sculpting) {
+        return;
+    }
+    let Ok((mut history, mut density_field, materials)) = volumes.get_mut(entity) else {
+        warn!("Can't undo edits of {entity}: it has no EditHistory and DensityField");
+        return;
+    };
+    // Edits not recorded yet, such as strokes read back this frame, come last
+    let full = DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0);
+    history.record(dimensions.0, full, &density_field, materials.as_deref());
+    let Some(region) = history.undo(
+        dimensions.0,
+        &mut density_field,
+        materials.map(Mut::into_inner),
+    ) else {
+        return;
+    };
+    mark_dirty(&mut commands, entity, region);
+}
+
+/// Redo the latest undone edit of the volume `RedoEdit` was triggered on.
+pub fn redo_edit(
+    redo: On<RedoEdit>,
+    mut commands: Commands,
+    mut volumes: Query<HistoryVolume>,
+    #[cfg(feature = "gpu")] sculpting: Query<SculptingVolume>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let entity = redo.entity;
+    #[cfg(feature = "gpu")]
+    if defer_step(&mut commands, entity, HistoryStep::Redo, &sculpting) {
+        return;
+    }
+    let Ok((mut history, mut density_field, materials)) = volumes.get_mut(entity) else {
+        warn!("Can't redo edits of {entity}: it has no EditHistory and DensityField");
+        return;
+    };
+    // An edit not recorded yet replaces what could be redone
+    let full = DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0);
+    history.record(dimensions.0, full, &density_field, materials.as_deref());
+    let Some(region) = history.redo(
+        dimensions.0,
+        &mut density_field,
+        materials.map(Mut::into_inner),
+    ) else {
+        return;
+    };
+    mark_dirty(&mut commands, entity, region);
+}
+
+/// Apply the undos and redos deferred on volumes whose brush strokes have all
+/// been read back, in order.
+#[cfg(feature = "gpu")]
+pub fn apply_deferred_history_steps(
+    mut commands: Commands,
+    volumes: Query<
+        (Entity, &DeferredHistorySteps, Option<&QueuedBrushStrokes>),
+        Without<WritingBackBrushStrokes>,
+    >,
+) {
+    for (entity, deferred, queued) in &volumes {
+        if queued.is_some_and(|queued| !queued.is_empty()) {
+            continue;
+        }
+        commands.entity(entity).remove::<DeferredHistorySteps>();
+        for &step in &deferred.0 {
+            match step {
+                HistoryStep::Undo => commands.trigger(UndoEdit { entity }),
+                HistoryStep::Redo => commands.trigger(RedoEdit { entity }),
+            }
+        }
+    }
+}
+
+fn mark_dirty(commands: &mut Commands, entity: Entity, region: DensityFieldDirtyRegion) {
+    commands
+        .entity(entity)
+        .entry::<DensityFieldDirtyRegion>()
+        .and_modify(move |mut dirty| *dirty = dirty.union(&region))
+        .or_insert(region);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(words: &[u32]) {
+        let encoded = encode_diff(words.iter().copied());
+        let mut decoded = vec![0; words.len()];
+        apply_diff(&encoded, 0..words.len(), |i, word| decoded[i] = word);
+        assert_eq!(decoded, words);
+    }
+
+    #[test]
+    fn diff_round_trip() {
+        round_trip(&[]);
+        round_trip(&[0, 0, 0]);
+        round_trip(&[5, 6]);
+        round_trip(&[0, 0, 1, 2, 0, 3, 0, 0, 0, 4, 5, 6]);
+        round_trip(&[7, 0, 0, 8, 0]);
+    }
+
+    #[test]
+    fn diff_skips_unchanged_words() {
+        // Runs of zeros cost one word, whatever their length
+        let mut words = vec![0; 1000];
+        words[500] = 9;
+        assert_eq!(encode_diff(words.iter().copied()), [500, 1, 9]);
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_fields() {
+        let dimensions = UVec3::splat(4);
+        let count = dimensions.element_product() as usize;
+        let full = DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions);
+        let original = DensityField((0..count).map(|i| i as f32 - 30.0).collect());
+        let original_materials = MaterialField(vec![1; count]);
+
+        let mut history = EditHistory::default();
+        let mut field = original.clone();
+        let mut materials = original_materials.clone();
+        history.record(dimensions, full, &field, Some(&materials));
+        assert!(!history.can_undo());
+
+        field[index(dimensions, 1, 2, 3) as usize] = 0.5;
+        field[index(dimensions, 2, 2, 3) as usize] = -0.0;
+        materials[index(dimensions, 3, 0, 1) as usize] = 4;
+        history.record(dimensions, full, &field, Some(&materials));
+        let (edited, edited_materials) = (field.clone(), materials.clone());
+
+        let region = history
+            .undo(dimensions, &mut field, Some(&mut materials))
+            .unwrap();
+        assert_eq!(region.min, uvec3(1, 0, 1));
+        assert_eq!(region.max, uvec3(4, 3, 4));
+        assert_eq!(field.0, original.0);
+        assert_eq!(materials.0, original_materials.0);
+        assert!(history.can_redo());
+
+        history.redo(dimensions, &mut field, Some(&mut materials));
+        // Compared by bits, so -0.0 must come back as -0.0
+        let bits = |field: &DensityField| field.iter().map(|d| d.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&field), bits(&edited));
+        assert_eq!(materials.0, edited_materials.0);
+        assert!(!history.can_redo());
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::{asset::AssetEvent, platform::collections::HashSet, prelude::*};
+use sculpter_core::{CoordinateSystem, grid};
+
+use crate::{
+    DensityField, DensityFieldSize, dirty_region::DensityFieldDirtyRegion, mesh::Meshed,
+    units::VoxelSpacing,
+};
+
+/// A volume from a stack of grayscale slices, such as CT or MRI scans: slice
+/// `i` is the stack's `z = i` plane, with the image's top row at the top, and
+/// the stack is turned from its `coordinate_system` into Bevy's axes to fill
+/// the field from its minimum corner. Samples are solid where the red channel
+/// lies above the `window`'s level. Stacks larger than `DensityFieldSize` are
+/// cut off, and the field is filled once every slice has loaded.
+///
+/// Load the slices as linear rather than sRGB (`is_srgb: false`), or the
+/// intensities are curved by the sRGB transfer function.
+#[derive(Component, Clone, Debug)]
+pub struct ImageStackDensitySource {
+    pub slices: Vec<Handle<Image>>,
+    /// Distance between pixels along x and y, and between slices along z,
+    /// converted like the stack and inserted as the volume's `VoxelSpacing`.
+    /// `None` keeps the volume's own.
+    pub spacing: Option<VoxelSpacing>,
+    pub window: IntensityWindow,
+    /// Axes of the stack, Y-up by default so slices stack along Bevy's z. Axial
+    /// scans in patient coordinates are usually `CoordinateSystem::Z_UP_RIGHT_HANDED`.
+    pub coordinate_system: CoordinateSystem,
+}
+
+impl ImageStackDensitySource {
+    pub fn new(slices: Vec<Handle<Image>>) -> Self {
+        Self {
+            slices,
+            spacing: None,
+            window: IntensityWindow::default(),
+            coordinate_system: CoordinateSystem::Y_UP_RIGHT_HANDED,
+        }
+    }
+}
+
+/// Range of intensities the surface is extracted from, as in medical viewers:
+/// the surface lies at `level`, and intensities more than half the `width`
+/// away from it are clamped, so noise and outliers outside the window don't
+/// bend the surface. Intensities are in [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntensityWindow {
+    pub level: f32,
+    pub width: f32,
+}
+
+impl Default for IntensityWindow {
+    fn default() -> Self {
+        Self {
+            level: 0.5,
+            width: 1.0,
+        }
+    }
+}
+
+impl IntensityWindow {
+    /// Density of a sample of the given intensity, in [-0.5, 0.5] and negative
+    /// above the level.
+    pub fn density(&self, intensity: f32) -> f32 {
+        let width = self.width.max(f32::EPSILON);
+        ((self.level - intensity) / width).clamp(-0.5, 0.5)
+    }
+}
+
+/// Convert image stacks whose source or slices changed into their volume's
+/// densities, remeshing volumes that were already meshed.
+pub fn sync_image_stacks(
+    mut commands: Commands,
+    mut image_events: MessageReader<AssetEvent<Image>>,
+    sources: Query<(Entity, Ref<ImageStackDensitySource>, Has<Meshed>)>,
+    images: Res<Assets<Image>>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let changed_images: HashSet<AssetId<Image>> = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, source, meshed) in &sources {
+        let changed = source.is_changed()
+            || source
+                .slices
+                .iter()
+                .any(|slice| changed_images.contains(&slice.id()));
+        if !changed {
+            continue;
+        }
+        // Converted once every slice has loaded
+        let Some(slices) = source
+            .slices
+            .iter()
+            .map(|slice| images.get(slice))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert(DensityField(image_stack_densities(
+            &slices,
+            dimensions.0,
+            source.window,
+            source.coordinate_system,
+        )));
+        if let Some(spacing) = source.spacing {
+            entity_commands.try_insert(VoxelSpacing {
+                spacing: source.coordinate_system.to_bevy(spacing.spacing).abs(),
+                ..spacing
+            });
+        }
+        if meshed {
+            entity_commands.try_insert(DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0));
+        }
+    }
+}
+
+/// Windowed density of every sample, empty where no slice or pixel covers it.
+fn image_stack_densities(
+    slices: &[&Image],
+    dimensions: UVec3,
+    window: IntensityWindow,
+    coordinate_system: CoordinateSystem,
+) -> Vec<f32> {
+    let empty = window.density(0.0);
+    let size = slices
+        .iter()
+        .fold(UVec2::ZERO, |size, image| size.max(image.size()));
+    let stack_dims = size.extend(slices.len() as u32);
+    let mut densities = vec![empty; stack_dims.element_product() as usize];
+    for (z, image) in slices.iter().enumerate() {
+        let size = image.size();
+        for row in 0..size.y {
+            // Image rows run downwards
+            let y = size.y - 1 - row;
+            for x in 0..size.x {
+                let intensity = image
+                    .get_color_at(x, row)
+                    .map_or(0.0, |color| color.to_linear().red);
+                let index = grid::index(stack_dims, x, y, z as u32);
+                densities[index as usize] = window.density(intensity);
+            }
+        }
+    }
+    let (densities, stack_dims) = coordinate_system.volume_to_bevy(&densities, stack_dims);
+    grid::resize(&densities, stack_dims, dimensions, empty)
+}
@@ -0,0 +1,212 @@
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+
+use crate::{
+    DensityField, DensityFieldSize,
+    dirty_region::{DensityEdited, DensityFieldDirtyRegion},
+    material::MaterialField,
+    settings::{MeshingAlgorithm, SculptSettings},
+    units::VoxelSpacing,
+};
+
+/// Density cut out samples are set to: one voxel outside the surface.
+const OUTSIDE: f32 = 1.0;
+
+/// Look for parts of this volume's solid left floating whenever it is edited,
+/// triggering `FloatingIslands` on it when there are any.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct DetectFloatingIslands {
+    pub anchor: IslandAnchor,
+}
+
+/// What holds a volume's solid in place, every other part of it floating.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IslandAnchor {
+    /// Its largest connected part, for free standing sculpts
+    #[default]
+    Largest,
+    /// Whatever reaches a face of the field, for chunks held by their neighbours
+    Boundary,
+}
+
+/// A connected part of a volume's solid that nothing anchors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatingIsland {
+    /// Its solid samples
+    pub samples: Vec<UVec3>,
+    /// Region covering the samples, to mark dirty after removing them
+    pub region: DensityFieldDirtyRegion,
+}
+
+/// Triggered on an edited volume with a `DetectFloatingIslands` whose solid has
+/// floating parts, so they can be deleted or turned into physics debris.
+#[derive(EntityEvent, Clone, Debug)]
+pub struct FloatingIslands {
+    pub entity: Entity,
+    pub islands: Vec<FloatingIsland>,
+}
+
+/// Move the floating islands of this volume into volumes of their own as
+/// `FloatingIslands` reports them, so the top of a pillar carved through falls
+/// off. With the `avian` or `rapier` feature they become dynamic rigid bodies
+/// with a `ConvexDecomposition` collider.
+///
+/// The pieces share the volume's placement, meshing and materials, and split
+/// again when carved.
+#[derive(Component, Clone, Copy, Debug, Default)]
+#[require(DetectFloatingIslands)]
+pub struct SplitFloatingIslands;
+
+/// Triggered on each volume `SplitFloatingIslands` spawns, e.g. to give it
+/// gameplay components.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct IslandSplitOff {
+    pub entity: Entity,
+    /// The volume it was cut out of
+    pub from: Entity,
+}
+
+/// Label the solid of volumes edited since the last frame, or that just got a
+/// `DetectFloatingIslands`, and report what floats.
+pub fn detect_floating_islands(
+    mut commands: Commands,
+    dimensions: Res<DensityFieldSize>,
+    volumes: Query<(
+        Entity,
+        &DensityField,
+        Ref<DetectFloatingIslands>,
+        Option<Ref<DensityFieldDirtyRegion>>,
+    )>,
+    mut sculpted: MessageReader<DensityEdited>,
+) {
+    let sculpted: EntityHashSet = sculpted.read().map(|sculpted| sculpted.entity).collect();
+    for (entity, density_field, detect, dirty) in &volumes {
+        let edited = dirty.is_some_and(|dirty| dirty.is_changed()) || sculpted.contains(&entity);
+        if !edited && !detect.is_changed() {
+            continue;
+        }
+        let components = sculpter_core::solid_components(density_field, dimensions.0);
+        let anchored: Vec<bool> = match detect.anchor {
+            IslandAnchor::Largest => {
+                let largest = (0..components.sizes.len()).max_by_key(|&i| components.sizes[i]);
+                (0..components.sizes.len())
+                    .map(|i| Some(i) == largest)
+                    .collect()
+            }
+            IslandAnchor::Boundary => components.touches_boundary.clone(),
+        };
+        if anchored.iter().all(|&anchored| anchored) {
+            continue;
+        }
+
+        let mut samples: Vec<Vec<UVec3>> = vec![Vec::new(); anchored.len()];
+        for (i, &label) in components.labels.iter().enumerate() {
+            if label != sculpter_core::NO_COMPONENT && !anchored[label as usize] {
+                samples[label as usize].push(sample_position(i as u32, dimensions.0));
+            }
+        }
+        let islands = samples
+            .into_iter()
+            .filter(|samples| !samples.is_empty())
+            .map(|samples| {
+                let (min, max) = samples
+                    .iter()
+                    .fold((UVec3::MAX, UVec3::ZERO), |(min, max), &sample| {
+                        (min.min(sample), max.max(sample))
+                    });
+                FloatingIsland {
+                    samples,
+                    region: DensityFieldDirtyRegion::new(min, max + 1),
+                }
+            })
+            .collect();
+        commands.trigger(FloatingIslands { entity, islands });
+    }
+}
+
+/// Cut the floating islands of a volume with `SplitFloatingIslands` out of its
+/// field into new volumes, and remesh what is left.
+pub fn split_floating_islands(
+    islands: On<FloatingIslands>,
+    mut commands: Commands,
+    dimensions: Res<DensityFieldSize>,
+    mut volumes: Query<
+        (
+            &mut DensityField,
+            Option<&DensityFieldDirtyRegion>,
+            Option<&GlobalTransform>,
+            Option<&MeshingAlgorithm>,
+            Option<&SculptSettings>,
+            Option<&VoxelSpacing>,
+            Option<&MaterialField>,
+        ),
+        With<SplitFloatingIslands>,
+    >,
+    #[cfg(feature = "gpu")] materials: Query<&MeshMaterial3d<StandardMaterial>>,
+) {
+    let entity = islands.entity;
+    let Ok((mut density_field, dirty, transform, algorithm, settings, spacing, material_field)) =
+        volumes.get_mut(entity)
+    else {
+        return;
+    };
+
+    let mut region = dirty.copied();
+    for island in &islands.islands {
+        // The empty space around the island is kept, the rest of the solid dropped
+        let mut densities: Vec<f32> = density_field
+            .iter()
+            .map(|&density| if density > 0.0 { density } else { OUTSIDE })
+            .collect();
+        for sample in &island.samples {
+            let i = sculpter_core::grid::index(dimensions.0, sample.x, sample.y, sample.z) as usize;
+            densities[i] = density_field[i];
+            density_field[i] = OUTSIDE;
+        }
+
+        let mut piece = commands.spawn((
+            DensityField(densities),
+            transform.map_or_else(Transform::default, GlobalTransform::compute_transform),
+            SplitFloatingIslands,
+        ));
+        if let Some(algorithm) = algorithm {
+            piece.insert(*algorithm);
+        }
+        if let Some(settings) = settings {
+            piece.insert(settings.clone());
+        }
+        if let Some(spacing) = spacing {
+            piece.insert(*spacing);
+        }
+        if let Some(material_field) = material_field {
+            piece.insert(material_field.clone());
+        }
+        #[cfg(feature = "gpu")]
+        if let Ok(material) = materials.get(entity) {
+            piece.insert(material.clone());
+        }
+        #[cfg(any(feature = "avian", feature = "rapier"))]
+        piece.insert(crate::collision::ConvexDecomposition::default());
+        #[cfg(feature = "avian")]
+        piece.insert(avian3d::prelude::RigidBody::Dynamic);
+        #[cfg(feature = "rapier")]
+        piece.insert(bevy_rapier3d::prelude::RigidBody::Dynamic);
+
+        let piece = piece.id();
+        commands.trigger(IslandSplitOff {
+            entity: piece,
+            from: entity,
+        });
+        region = Some(region.map_or(island.region, |region| region.union(&island.region)));
+    }
+    if let Some(region) = region {
+        commands.entity(entity).try_insert(region);
+    }
+}
+
+pub(crate) fn sample_position(index: u32, dimensions: UVec3) -> UVec3 {
+    UVec3::new(
+        index % dimensions.x,
+        index / dimensions.x % dimensions.y,
+        index / (dimensions.x * dimensions.y),
+    )
+}
@@ -0,0 +1,98 @@
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+
+use crate::{
+    DensityField, DensityFieldSize,
+    dirty_region::{DensityEdited, DensityFieldDirtyRegion},
+    mesh::Meshed,
+    settings::{MeshingAlgorithm, SculptSettings},
+    units::VoxelSpacing,
+};
+
+/// One extra surface of an `IsoSurfaceSet`.
+#[derive(Clone, Debug, Default)]
+pub struct IsoSurface {
+    /// Density the surface is extracted at, instead of 0
+    pub iso: f32,
+    /// Material of the surface's mesh, `StandardMaterial::default()` when `None`
+    #[cfg(feature = "gpu")]
+    pub material: Option<Handle<StandardMaterial>>,
+}
+
+/// Extra surfaces extracted from a volume's `DensityField`, besides the zero
+/// surface the volume itself gets, e.g. a water table above the rock.
+///
+/// Each surface is meshed as a child volume with an `IsoSurfaceMesh`, in the same
+/// generation as the volume and with its algorithm, settings and spacing.
+/// Editing the field remeshes them all, changing the set replaces the children.
+#[derive(Component, Clone, Debug, Default, Deref, DerefMut)]
+pub struct IsoSurfaceSet(pub Vec<IsoSurface>);
+
+/// Child volume meshing one surface of its parent's `IsoSurfaceSet`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct IsoSurfaceMesh {
+    pub iso: f32,
+}
+
+/// The parent's densities shifted so the surface at `iso` becomes the zero one.
+fn shifted(density_field: &DensityField, iso: f32) -> DensityField {
+    DensityField(density_field.iter().map(|&d| d - iso).collect())
+}
+
+/// Keep the child volumes of each `IsoSurfaceSet` in step with the set and with
+/// the parent's densities.
+pub fn sync_iso_surfaces(
+    mut commands: Commands,
+    volumes: Query<(
+        Entity,
+        Ref<IsoSurfaceSet>,
+        Ref<DensityField>,
+        Option<&Children>,
+        Option<&MeshingAlgorithm>,
+        Option<&SculptSettings>,
+        Option<&VoxelSpacing>,
+    )>,
+    surfaces: Query<(&IsoSurfaceMesh, Has<Meshed>)>,
+    mut sculpted: MessageReader<DensityEdited>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let sculpted: EntityHashSet = sculpted.read().map(|sculpted| sculpted.entity).collect();
+    for (entity, set, density_field, children, algorithm, settings, spacing) in &volumes {
+        let existing = children
+            .into_iter()
+            .flatten()
+            .filter_map(|&child| Some((child, surfaces.get(child).ok()?)));
+
+        if set.is_changed() {
+            for (child, _) in existing {
+                commands.entity(child).try_despawn();
+            }
+            for surface in set.iter() {
+                let mut child = commands.spawn((
+                    IsoSurfaceMesh { iso: surface.iso },
+                    shifted(&density_field, surface.iso),
+                    algorithm.copied().unwrap_or_default(),
+                    Transform::default(),
+                    ChildOf(entity),
+                ));
+                if let Some(settings) = settings {
+                    child.insert(settings.clone());
+                }
+                if let Some(spacing) = spacing {
+                    child.insert(*spacing);
+                }
+                #[cfg(feature = "gpu")]
+                if let Some(material) = &surface.material {
+                    child.insert(MeshMaterial3d(material.clone()));
+                }
+            }
+        } else if density_field.is_changed() || sculpted.contains(&entity) {
+            for (child, (surface, meshed)) in existing {
+                let mut child = commands.entity(child);
+                child.try_insert(shifted(&density_field, surface.iso));
+                if meshed {
+                    child.try_insert(DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0));
+                }
+            }
+        }
+    }
+}
@@ -1,28 +1,340 @@
-use bevy::{
-    prelude::*,
-    render::{
-        Render, RenderApp, RenderStartup, RenderSystems,
-        extract_component::{ExtractComponent, ExtractComponentPlugin},
-        extract_resource::{ExtractResource, ExtractResourcePlugin},
-        render_graph::{RenderGraph, RenderLabel},
-    },
+#[cfg(not(any(feature = "gpu", feature = "cpu")))]
+compile_error!("enable the `gpu` feature, the `cpu` feature, or both");
+
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::render::{
+    ExtractSchedule, Render, RenderApp, RenderStartup, RenderSystems,
+    extract_component::{ExtractComponent, ExtractComponentPlugin},
+    extract_resource::{ExtractResource, ExtractResourcePlugin},
+    render_graph::{RenderGraph, RenderLabel},
 };
+use sculpter_core::grid;
 
+#[cfg(feature = "avian")]
+use crate::avian::{poll_avian_decompositions, update_avian_colliders};
+#[cfg(feature = "meshlet")]
+use crate::meshlet::{poll_meshlet_tasks, remesh_changed_meshlets, start_meshlet_tasks};
+#[cfg(feature = "persistence")]
+use crate::persistence::{
+    ChunkSaves, autosave_chunks, poll_chunk_io, save_chunks_on_exit, save_unloaded_chunk,
+    start_chunk_io, track_unsaved_edits,
+};
+#[cfg(feature = "picking")]
+use crate::picking::update_sculpt_cursor;
+#[cfg(feature = "rapier")]
+use crate::rapier::{poll_rapier_decompositions, update_rapier_colliders};
+#[cfg(feature = "sdf_graph")]
+use crate::sdf_graph::sync_sdf_graphs;
+#[cfg(feature = "sparse_volume")]
+use crate::sparse_volume::sync_sparse_volume_sources;
+#[cfg(feature = "vox")]
+use crate::vox::sync_vox_models;
+#[cfg(feature = "gpu")]
+use crate::{
+    amortize::{StageSchedule, advance_stage_cursors, extract_dispatched_stages},
+    backpressure::update_gpu_meshing_load,
+    bind_group::prepare_density_generation_bind_groups,
+    bind_group::{
+        prepare_bind_groups, prepare_brush_bind_groups, prepare_csg_bind_groups,
+        write_meshing_regions,
+    },
+    brush::{PendingBrushStrokes, apply_held_brushes, apply_sculpt_brush, flush_brush_strokes},
+    brush_preview::{draw_brush_previews, update_brush_ghosts},
+    buffers::{SurfaceNetsBuffers, prepare_surface_nets_buffers},
+    cpu::detect_compute_shader_support,
+    csg::{PendingCsgEdits, flush_csg_edits},
+    despawn::release_despawned_volume,
+    dirty_region::{MeshingRegion, PendingDensityWrites, write_pending_density_regions},
+    field_asset::sync_density_field_handles,
+    gpu_density::{DensityGeneration, queue_density_generation, sync_noise_density_sources},
+    heightmap::sync_heightmap_sources,
+    history::apply_deferred_history_steps,
+    image_stack::sync_image_stacks,
+    lod_chain::{build_lod_chains, collect_lod_levels, poll_lod_chain_tasks, switch_lods},
+    material::remesh_changed_palettes,
+    node::SurfaceNetsNode,
+    pipeline::{DensityKernelPipelines, init_surface_nets_pipelines},
+    readback::{assemble_readback_mesh, setup_readback_for_new_fields},
+    shadow_proxy::{build_shadow_proxies, poll_shadow_proxy_tasks},
+    streaming::reveal_streamed_chunks,
+    voxelize::{poll_voxelization_tasks, stamp_mesh_into_volume, start_voxelization},
+    watchdog::watch_readbacks,
+    wireframe::remesh_changed_wireframes,
+};
 use crate::{
-    bind_group::prepare_bind_groups, buffers::prepare_surface_nets_buffers,
-    mesh::build_mesh_from_readback, node::SurfaceNetsNode, pipeline::init_surface_nets_pipelines,
-    readback::setup_readback_for_new_fields,
+    chunk::sync_chunks,
+    collision::{build_collision_meshes, poll_collision_mesh_tasks},
+    cpu::{mesh_cpu_backend_fields, poll_cpu_meshing_tasks},
+    csg::composite_density,
+    dirty_region::upload_dirty_regions,
+    fill::bucket_fill,
+    generator::{generate_requested_chunk, poll_chunk_generation},
+    history::{record_edit_history, redo_edit, undo_edit},
+    islands::{detect_floating_islands, split_floating_islands},
+    iso_surface::sync_iso_surfaces,
+    mass::update_mass_properties,
+    material::{assign_vertex_materials, remesh_changed_materials},
+    mesh::{build_mesh_from_readback, remesh_changed_winding},
+    navmesh::remesh_changed_walkable_surfaces,
+    scatter::remesh_changed_surface_points,
+    streaming::stream_chunks,
+    transition::{remesh_changed_transitions, stitch_lod_transitions},
+    voxel_grid::sync_voxel_grids,
+};
+
+/// The Bevy-independent meshing math, also usable as its own crate.
+pub use sculpter_core;
+
+#[cfg(feature = "avian")]
+pub use crate::avian::AvianDecompositionTask;
+#[cfg(any(feature = "avian", feature = "rapier"))]
+pub use crate::collision::ConvexDecomposition;
+#[cfg(feature = "mesh_diagnostics")]
+pub use crate::diagnostics::{BoundaryEdge, find_interior_boundary_edges};
+#[cfg(feature = "cpu")]
+pub use crate::geometry::{
+    ColliderGeometry, GenerateCollider, GenerateQuadMesh, QuadMesh, VolumeGeometry,
+};
+#[cfg(feature = "lightmap_uvs")]
+pub use crate::lightmap::LightmapUvs;
+#[cfg(feature = "meshlet")]
+pub use crate::meshlet::{GenerateMeshlets, MeshletTask};
+#[cfg(feature = "persistence")]
+pub use crate::persistence::{
+    ChunkAutosave, ChunkIoFailed, ChunkLoadError, ChunkLoadTask, ChunkLoaded, ChunkSaveTask,
+    ChunkSaved, LoadChunk, SaveChunk, SavedChunk, UnsavedEdits, load_chunk, save_chunk,
+};
+#[cfg(feature = "picking")]
+pub use crate::picking::{CursorHit, SculptCursor, SculptCursorCamera};
+#[cfg(feature = "rapier")]
+pub use crate::rapier::{ColliderMesh, RapierColliderSettings, RapierDecompositionTask};
+#[cfg(feature = "sdf_graph")]
+pub use crate::sdf_graph::{SdfGraph, SdfGraphError, SdfGraphLoader, SdfNode};
+#[cfg(feature = "sparse_volume")]
+pub use crate::sparse_volume::{
+    SparseVolumeAsset, SparseVolumeLoader, SparseVolumeLoaderSettings, SparseVolumeSource,
+    sparse_volume_from_chunks,
+};
+#[cfg(feature = "vox")]
+pub use crate::vox::{VoxDensitySource, VoxError, VoxLoader, VoxLoaderSettings, VoxModel};
+pub use crate::{
+    backpressure::{
+        BackpressurePolicy, CpuFallback, CriticalRemesh, GpuBackpressure, GpuMeshingLoad,
+    },
+    chunk::{Chunk, ChunkMap},
+    clipboard::{FieldClipboard, GridAxis},
+    collision::{CollisionMesh, CollisionMeshTask, GenerateCollisionMesh},
+    cpu::ComputeShaderSupport,
+    csg::{CompositeDensity, CsgOperation, CsgSource},
+    dirty_region::{DensityEdited, DensityFieldDirtyRegion, Remesh},
+    export::{CoordinateSystem, Handedness, UpAxis, printable_mesh, write_obj, write_stl},
+    fill::{BucketFill, BucketFillMode},
+    generator::{ChunkGenerationTask, ChunkGenerator, WorldGenerator},
+    history::{EditHistory, RedoEdit, UndoEdit},
+    islands::{
+        DetectFloatingIslands, FloatingIsland, FloatingIslands, IslandAnchor, IslandSplitOff,
+        SplitFloatingIslands,
+    },
+    iso_surface::{IsoSurface, IsoSurfaceMesh, IsoSurfaceSet},
+    mass::{FieldMassProperties, GenerateMassProperties},
+    material::MaterialField,
+    mesh::InvertWinding,
+    mesh_data::{MeshData, VertexAttributeData},
+    meshing::{mesh_density_cpu, surface_nets_cpu},
+    navmesh::{GenerateWalkableSurface, WalkableSurface},
+    quantize::{DensityQuantization, QuantizedFormat},
+    sampling::RayHit,
+    scatter::{
+        GenerateSurfacePoints, ScatterDistribution, SurfacePoint, SurfacePoints, scatter_points,
+    },
+    sdf::{SdfPart, SdfPrimitive, SdfShape},
+    settings::{
+        AmbientOcclusion, DegenerateFilter, MeshingAlgorithm, SculptBackend, SculptSettings,
+        Simplification, Smoothing, SurfaceSides, UvGeneration, VertexPlacement, VertexRelaxation,
+    },
+    streaming::{ChunkRequested, ChunkStreaming, StreamedChunk, StreamingAnchor},
+    texture_export::DensityTextureFormat,
+    transition::LodTransitions,
+    units::{LengthUnit, VoxelSpacing},
+    voxel_grid::{Voxel, VoxelGrid},
+};
+#[cfg(feature = "gpu")]
+pub use crate::{
+    brush::{
+        BlurKernel, BrushFalloff, BrushMode, BrushShape, BrushWriteBack, FlattenPlane, HeldBrush,
+        MAX_BLUR_RADIUS, MAX_BRUSH_APPLICATIONS, MIN_BRUSH_SPACING, SculptBrush, SculptSymmetry,
+        WritingBackBrushStrokes,
+    },
+    brush_preview::{BrushGhost, BrushPreview},
+    field_asset::{DensityFieldError, DensityFieldHandle, DensityFieldLoader},
+    gpu_density::{
+        DensityFunction, DensityKernel, GpuDensity, NoiseBasis, NoiseDensitySource, NoiseKind,
+        NoiseStack,
+    },
+    heightmap::{HeightmapDensitySource, HeightmapLayer},
+    image_stack::{ImageStackDensitySource, IntensityWindow},
+    lod_chain::{GenerateLodChain, LodChainTask, LodLevel, LodMeshChain, SculptLod},
+    material::{MaterialPalette, MaterialSubmesh, MaterialSubmeshes},
+    mesh_data::{
+        ATTRIBUTE_AMBIENT_OCCLUSION, ATTRIBUTE_MATERIAL_ID, ATTRIBUTE_MATERIAL_WEIGHTS, ToMesh,
+    },
+    meshing::{GpuMeshingJob, gpu_meshing_job},
+    packed::{
+        ATTRIBUTE_PACKED_NORMAL, ATTRIBUTE_PACKED_POSITION, PackedVertexExtension,
+        PackedVertexMaterial,
+    },
+    shadow_proxy::{GenerateShadowProxy, ShadowProxy, ShadowProxyTask},
+    triplanar::{TriplanarExtension, TriplanarMaterial},
+    voxelize::{StampMesh, VoxelizationTask, VoxelizeMesh, stamp_mesh},
+    watchdog::{GenerationFailed, MeshingFailed, ReadbackWatchdog},
+    wireframe::{DebugWireframe, DebugWireframeMesh},
 };
 
+#[cfg(feature = "gpu")]
+mod amortize;
+#[cfg(feature = "avian")]
+mod avian;
+mod backend;
+mod backpressure;
+#[cfg(feature = "gpu")]
 mod bind_group;
+mod blocky;
+#[cfg(feature = "gpu")]
+mod brush;
+#[cfg(feature = "gpu")]
+mod brush_preview;
+#[cfg(feature = "gpu")]
 mod buffers;
+mod chunk;
+mod clipboard;
+mod collision;
+mod cpu;
+mod csg;
+#[cfg(feature = "gpu")]
+mod despawn;
+#[cfg(feature = "mesh_diagnostics")]
+mod diagnostics;
+mod dirty_region;
+mod dual_contouring;
+mod export;
+#[cfg(feature = "gpu")]
+mod field_asset;
+mod fill;
+mod generator;
+#[cfg(feature = "cpu")]
+mod geometry;
+#[cfg(feature = "gpu")]
+mod gpu_density;
+mod heightfield;
+#[cfg(feature = "gpu")]
+mod heightmap;
+mod history;
+#[cfg(feature = "gpu")]
+mod image_stack;
+mod islands;
+mod iso_surface;
+#[cfg(feature = "lightmap_uvs")]
+mod lightmap;
+#[cfg(feature = "gpu")]
+mod lod_chain;
+mod marching_cubes;
+mod marching_tetrahedra;
+mod mass;
+mod material;
 mod mesh;
+mod mesh_data;
+mod meshing;
+#[cfg(feature = "meshlet")]
+mod meshlet;
+mod navmesh;
+#[cfg(feature = "gpu")]
 mod node;
+#[cfg(feature = "gpu")]
+mod packed;
+#[cfg(feature = "persistence")]
+mod persistence;
+#[cfg(feature = "picking")]
+mod picking;
+#[cfg(feature = "gpu")]
 mod pipeline;
+mod quantize;
+#[cfg(feature = "rapier")]
+mod rapier;
+#[cfg(feature = "gpu")]
 mod readback;
+mod sampling;
+mod scatter;
+mod sdf;
+#[cfg(feature = "sdf_graph")]
+mod sdf_graph;
+mod settings;
+#[cfg(feature = "gpu")]
+mod shadow_proxy;
+#[cfg(feature = "sparse_volume")]
+mod sparse_volume;
+mod streaming;
+mod surface_nets;
+mod texture_export;
+mod transition;
+#[cfg(feature = "gpu")]
+mod triplanar;
+mod units;
+#[cfg(feature = "vox")]
+mod vox;
+mod voxel_grid;
+#[cfg(feature = "gpu")]
+mod voxelize;
+#[cfg(feature = "gpu")]
+mod watchdog;
+#[cfg(feature = "gpu")]
+mod wireframe;
 
 pub mod prelude {
-    pub use crate::{DensityField, DensityFieldMeshSize, DensityFieldSize, SculpterPlugin};
+    #[cfg(any(feature = "avian", feature = "rapier"))]
+    pub use crate::ConvexDecomposition;
+    #[cfg(feature = "meshlet")]
+    pub use crate::GenerateMeshlets;
+    #[cfg(feature = "lightmap_uvs")]
+    pub use crate::LightmapUvs;
+    #[cfg(feature = "rapier")]
+    pub use crate::RapierColliderSettings;
+    #[cfg(feature = "picking")]
+    pub use crate::SculptCursor;
+    #[cfg(feature = "sdf_graph")]
+    pub use crate::SdfGraph;
+    #[cfg(feature = "vox")]
+    pub use crate::VoxDensitySource;
+    pub use crate::{
+        AmbientOcclusion, BackpressurePolicy, BucketFill, BucketFillMode, Chunk, ChunkGenerator,
+        ChunkMap, ChunkRequested, ChunkStreaming, CollisionMesh, CompositeDensity,
+        CoordinateSystem, CriticalRemesh, CsgOperation, CsgSource, DegenerateFilter, DensityEdited,
+        DensityField, DensityFieldDirtyRegion, DensityFieldMeshSize, DensityFieldSize,
+        DensityQuantization, DetectFloatingIslands, EditHistory, FieldClipboard,
+        FieldMassProperties, FloatingIslands, GenerateCollisionMesh, GenerateMassProperties,
+        GenerateSurfacePoints, GenerateWalkableSurface, GpuBackpressure, GpuMeshingLoad, GridAxis,
+        InvertWinding, IslandAnchor, IslandSplitOff, IsoSurface, IsoSurfaceSet, LengthUnit,
+        LodTransitions, MaterialField, MeshData, MeshingAlgorithm, QuantizedFormat, RedoEdit,
+        Remesh, ScatterDistribution, SculptBackend, SculptSettings, SculpterPlugin, SdfPrimitive,
+        SdfShape, Simplification, Smoothing, SplitFloatingIslands, StreamingAnchor, SurfacePoints,
+        SurfaceSides, UndoEdit, UvGeneration, VertexPlacement, VertexRelaxation, VoxelGrid,
+        VoxelSpacing, WalkableSurface, WorldGenerator,
+    };
+    #[cfg(feature = "gpu")]
+    pub use crate::{
+        BrushFalloff, BrushMode, BrushPreview, DebugWireframe, DensityFieldHandle, DensityTexture,
+        GenerateLodChain, GenerateShadowProxy, GenerationFailed, GpuDensity,
+        HeightmapDensitySource, ImageStackDensitySource, LodMeshChain, MaterialPalette,
+        MaterialSubmeshes, MeshingFailed, NoiseDensitySource, NoiseStack, PackedVertexMaterial,
+        ReadbackWatchdog, SculptBrush, SculptLod, SculptSymmetry, ShadowProxy, StampMesh, ToMesh,
+        TriplanarExtension, TriplanarMaterial, VoxelizeMesh,
+    };
+    #[cfg(feature = "cpu")]
+    pub use crate::{
+        ColliderGeometry, GenerateCollider, GenerateQuadMesh, QuadMesh, VolumeGeometry,
+    };
+    #[cfg(feature = "sparse_volume")]
+    pub use crate::{SparseVolumeAsset, SparseVolumeSource};
 }
 
 pub struct SculpterPlugin;
@@ -30,58 +342,231 @@ impl Plugin for SculpterPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<DensityFieldSize>()
             .init_resource::<DensityFieldMeshSize>()
-            .add_plugins((
-                ExtractComponentPlugin::<DensityField>::default(),
-                ExtractResourcePlugin::<DensityFieldSize>::default(),
-            ))
+            .init_resource::<GpuBackpressure>()
+            .init_resource::<GpuMeshingLoad>()
+            .init_resource::<ChunkMap>()
+            .init_resource::<ChunkStreaming>()
+            .add_message::<DensityEdited>()
             .add_systems(
                 Update,
                 (
-                    prepare_surface_nets_buffers,
-                    setup_readback_for_new_fields,
+                    (
+                        sync_voxel_grids::<u8>,
+                        sync_voxel_grids::<u16>,
+                        remesh_changed_transitions,
+                        remesh_changed_materials,
+                        remesh_changed_winding,
+                        remesh_changed_walkable_surfaces,
+                        remesh_changed_surface_points,
+                    ),
+                    sync_iso_surfaces,
+                    stream_chunks,
+                    poll_chunk_generation,
+                    sync_chunks,
+                    upload_dirty_regions,
+                    mesh_cpu_backend_fields,
+                    poll_cpu_meshing_tasks,
+                    stitch_lod_transitions,
+                    assign_vertex_materials,
                     build_mesh_from_readback,
+                    (build_collision_meshes, poll_collision_mesh_tasks).chain(),
+                    update_mass_properties,
                 )
                     .chain(),
-            );
+            )
+            .add_systems(
+                Update,
+                (detect_floating_islands, record_edit_history)
+                    .after(sync_chunks)
+                    .before(upload_dirty_regions),
+            )
+            .add_observer(generate_requested_chunk)
+            .add_observer(composite_density)
+            .add_observer(bucket_fill)
+            .add_observer(undo_edit)
+            .add_observer(redo_edit)
+            .add_observer(split_floating_islands);
+        #[cfg(feature = "avian")]
+        app.add_systems(
+            Update,
+            (update_avian_colliders, poll_avian_decompositions)
+                .chain()
+                .after(poll_collision_mesh_tasks),
+        );
+        #[cfg(feature = "rapier")]
+        app.add_systems(
+            Update,
+            (update_rapier_colliders, poll_rapier_decompositions)
+                .chain()
+                .after(poll_collision_mesh_tasks),
+        );
+        #[cfg(feature = "picking")]
+        app.init_resource::<SculptCursor>().add_systems(
+            PostUpdate,
+            update_sculpt_cursor.after(TransformSystems::Propagate),
+        );
+        #[cfg(feature = "persistence")]
+        app.init_resource::<ChunkSaves>()
+            .add_systems(
+                Update,
+                (
+                    (autosave_chunks, start_chunk_io, poll_chunk_io)
+                        .chain()
+                        .before(sync_chunks),
+                    track_unsaved_edits
+                        .after(sync_chunks)
+                        .before(upload_dirty_regions),
+                ),
+            )
+            .add_systems(Last, save_chunks_on_exit)
+            .add_observer(save_unloaded_chunk);
 
-        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
-            error!("Failed to get render app");
-            return;
-        };
+        #[cfg(feature = "gpu")]
+        build_gpu(app);
+        // Headless, everything is meshed on the CPU
+        #[cfg(not(feature = "gpu"))]
+        app.insert_resource(ComputeShaderSupport(false));
+    }
+}
 
-        render_app
-            .add_systems(RenderStartup, init_surface_nets_pipelines)
-            .add_systems(
-                Render,
+/// The compute pipeline, readback and the render world side of the plugin.
+#[cfg(feature = "gpu")]
+fn build_gpu(app: &mut App) {
+    app.init_resource::<ReadbackWatchdog>()
+        .add_plugins((
+            ExtractComponentPlugin::<DensityField>::default(),
+            ExtractComponentPlugin::<SurfaceNetsBuffers>::default(),
+            ExtractComponentPlugin::<PendingDensityWrites>::default(),
+            ExtractComponentPlugin::<MeshingRegion>::default(),
+            ExtractComponentPlugin::<StageSchedule>::default(),
+            ExtractComponentPlugin::<DensityGeneration>::default(),
+            ExtractComponentPlugin::<PendingCsgEdits>::default(),
+            ExtractComponentPlugin::<PendingBrushStrokes>::default(),
+            ExtractResourcePlugin::<DensityFieldSize>::default(),
+            MaterialPlugin::<TriplanarMaterial>::default(),
+            MaterialPlugin::<PackedVertexMaterial>::default(),
+        ))
+        .init_asset::<DensityField>()
+        .register_asset_loader(DensityFieldLoader)
+        .add_systems(Startup, detect_compute_shader_support)
+        .add_systems(
+            Update,
+            (
+                update_gpu_meshing_load.before(upload_dirty_regions),
+                apply_deferred_history_steps
+                    .after(record_edit_history)
+                    .before(upload_dirty_regions),
+                apply_held_brushes.before(flush_brush_strokes),
+                (draw_brush_previews, update_brush_ghosts),
+                remesh_changed_palettes.before(upload_dirty_regions),
+                remesh_changed_wireframes.before(upload_dirty_regions),
+                sync_density_field_handles.before(upload_dirty_regions),
+                sync_heightmap_sources.before(upload_dirty_regions),
+                sync_image_stacks.before(upload_dirty_regions),
+                (start_voxelization, poll_voxelization_tasks)
+                    .chain()
+                    .before(upload_dirty_regions),
                 (
-                    //prepare_surface_nets_buffers.in_set(RenderSystems::PrepareResources),
-                    prepare_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                    sync_noise_density_sources,
+                    queue_density_generation,
+                    flush_csg_edits,
+                    flush_brush_strokes,
+                    prepare_surface_nets_buffers,
+                    setup_readback_for_new_fields,
                 )
-                    .chain(),
-            );
-        let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
-        render_graph.add_node(SurfaceNetsLabel, SurfaceNetsNode::default());
-        render_graph.add_node_edge(SurfaceNetsLabel, bevy::render::graph::CameraDriverLabel);
-    }
+                    .chain()
+                    .after(upload_dirty_regions)
+                    .before(mesh_cpu_backend_fields),
+                (assemble_readback_mesh, collect_lod_levels)
+                    .chain()
+                    .after(poll_cpu_meshing_tasks)
+                    .before(stitch_lod_transitions),
+                watch_readbacks.after(build_mesh_from_readback),
+                (build_shadow_proxies, poll_shadow_proxy_tasks)
+                    .chain()
+                    .after(build_mesh_from_readback),
+                reveal_streamed_chunks.after(build_mesh_from_readback),
+                (build_lod_chains, poll_lod_chain_tasks, switch_lods)
+                    .chain()
+                    .after(build_mesh_from_readback),
+            ),
+        )
+        .add_observer(release_despawned_volume)
+        .add_observer(apply_sculpt_brush)
+        .add_observer(stamp_mesh_into_volume);
+    #[cfg(feature = "meshlet")]
+    app.add_systems(
+        Update,
+        (
+            remesh_changed_meshlets.before(upload_dirty_regions),
+            (start_meshlet_tasks, poll_meshlet_tasks)
+                .chain()
+                .after(build_mesh_from_readback),
+        ),
+    );
+    #[cfg(feature = "sdf_graph")]
+    app.init_asset::<SdfGraph>()
+        .register_asset_loader(SdfGraphLoader)
+        .add_systems(Update, sync_sdf_graphs.before(queue_density_generation));
+    #[cfg(feature = "sparse_volume")]
+    app.init_asset::<SparseVolumeAsset>()
+        .register_asset_loader(SparseVolumeLoader)
+        .add_systems(
+            Update,
+            sync_sparse_volume_sources.before(upload_dirty_regions),
+        );
+    #[cfg(feature = "vox")]
+    app.init_asset::<VoxModel>()
+        .register_asset_loader(VoxLoader)
+        .add_systems(Update, sync_vox_models.before(sync_voxel_grids::<u8>));
+
+    let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+        error!("Failed to get render app");
+        return;
+    };
+
+    render_app
+        .init_resource::<DensityKernelPipelines>()
+        .add_systems(RenderStartup, init_surface_nets_pipelines)
+        .add_systems(ExtractSchedule, extract_dispatched_stages)
+        .add_systems(
+            Render,
+            (
+                //prepare_surface_nets_buffers.in_set(RenderSystems::PrepareResources),
+                write_pending_density_regions.in_set(RenderSystems::PrepareResources),
+                prepare_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                write_meshing_regions.in_set(RenderSystems::PrepareBindGroups),
+                advance_stage_cursors.in_set(RenderSystems::PrepareBindGroups),
+                prepare_density_generation_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                prepare_csg_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                prepare_brush_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+            )
+                .chain(),
+        );
+    let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
+    render_graph.add_node(SurfaceNetsLabel, SurfaceNetsNode);
+    render_graph.add_node_edge(SurfaceNetsLabel, bevy::render::graph::CameraDriverLabel);
 }
 
-#[derive(Resource, ExtractResource, Deref, DerefMut, Clone, Copy, Debug)]
+#[derive(Resource, Deref, DerefMut, Clone, Copy, Debug)]
+#[cfg_attr(feature = "gpu", derive(ExtractResource))]
 pub struct DensityFieldSize(pub UVec3);
 
+#[cfg(feature = "gpu")]
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
 struct SurfaceNetsLabel;
 
 impl DensityFieldSize {
     pub fn density_count(&self) -> u32 {
-        self.x * self.y * self.z
+        grid::density_count(self.0)
     }
 
     pub fn index(&self, x: u32, y: u32, z: u32) -> u32 {
-        z * self.y * self.x + y * self.x + x
+        grid::index(self.0, x, y, z)
     }
 
     pub fn cell_count(&self) -> u32 {
-        (self.x.saturating_sub(1)) * (self.y.saturating_sub(1)) * (self.z.saturating_sub(1))
+        grid::cell_count(self.0)
     }
 }
 
@@ -99,8 +584,19 @@ impl Default for DensityFieldMeshSize {
     }
 }
 
-#[derive(Component, ExtractComponent, Clone, DerefMut, Deref, Debug)]
+/// Densities of a volume, x fastest then y then z. Also an asset, loaded from
+/// `.dfield` files and referenced through a `DensityFieldHandle`.
+#[derive(Component, Clone, DerefMut, Deref, Debug)]
+#[cfg_attr(feature = "gpu", derive(ExtractComponent, Asset, TypePath))]
 pub struct DensityField(pub Vec<f32>);
 
+/// Density supplied as a 3D texture (red channel), e.g. the output of another GPU pass.
+///
+/// Sampled with hardware trilinear filtering, so the image must use a filterable
+/// format such as `R16Float` or `R8Snorm`. The field dimensions come from the image.
+#[cfg(feature = "gpu")]
+#[derive(Component, Clone, Debug)]
+pub struct DensityTexture(pub Handle<Image>);
+
 #[derive(Component, Debug)]
 pub struct MeshGenerationTarget(pub Entity);
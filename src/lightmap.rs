@@ -0,0 +1,16 @@
+use bevy::prelude::*;
+
+/// Unwrap the volume's mesh into non-overlapping lightmap charts, written as
+/// `Mesh::ATTRIBUTE_UV_1` for baking lighting. Slow on large meshes, so only
+/// built with the `lightmap_uvs` feature.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LightmapUvs {
+    /// Width of the lightmap in texels, which sets the gap kept between charts
+    pub resolution: u32,
+}
+
+impl Default for LightmapUvs {
+    fn default() -> Self {
+        Self { resolution: 512 }
+    }
+}
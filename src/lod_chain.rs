@@ -0,0 +1,349 @@
+use bevy::{
+    camera::primitives::Aabb,
+    ecs::entity::{EntityHashMap, EntityHashSet},
+    prelude::*,
+    render::storage::ShaderStorageBuffer,
+    tasks::{Task, block_on},
+};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize,
+    buffers::SurfaceNetsBuffers,
+    cpu::spawn_downsampled_mesh,
+    dirty_region::{MeshingRegion, Remesh},
+    mesh::GeneratedMesh,
+    mesh_data::{MeshData, ToMesh},
+    settings::MeshingAlgorithm,
+    units::VoxelSpacing,
+};
+
+/// Also build a `LodMeshChain` whenever this volume is meshed: one mesh per
+/// entry of `downsample`, each from the volume's field sampled that many voxels
+/// apart. Volumes meshed on the GPU mesh their levels there from the same
+/// density buffer, so generated, texture and sculpted volumes get them too. The
+/// defaults give half and quarter resolution.
+#[derive(Component, Clone, Debug)]
+pub struct GenerateLodChain {
+    pub downsample: Vec<u32>,
+}
+
+impl Default for GenerateLodChain {
+    fn default() -> Self {
+        Self {
+            downsample: vec![2, 4],
+        }
+    }
+}
+
+/// The volume's meshes from full resolution down, all scaled alike: the one
+/// remeshes write into first, then one per `GenerateLodChain::downsample` entry.
+/// `SculptLod` swaps between them, or draw them on children with Bevy's
+/// `VisibilityRange`.
+#[derive(Component, Clone, Debug, Default, Deref)]
+pub struct LodMeshChain(pub Vec<Handle<Mesh>>);
+
+/// Swap the volume's `Mesh3d` along its `LodMeshChain` by distance from the
+/// nearest 3D camera to the volume's bounds center. Not for volumes with
+/// `GenerateMeshlets`, which do their own level of detail.
+#[derive(Component, Clone, Debug)]
+pub struct SculptLod {
+    /// Distance beyond which each level after the first takes over, increasing
+    pub distances: Vec<f32>,
+    /// Share of a distance the camera must move past it before the level
+    /// switches, so volumes at a threshold don't flicker between levels
+    pub hysteresis: f32,
+}
+
+impl Default for SculptLod {
+    fn default() -> Self {
+        Self {
+            distances: vec![64.0, 128.0],
+            hysteresis: 0.1,
+        }
+    }
+}
+
+impl SculptLod {
+    /// Level to draw at `distance` when `current` is drawn.
+    pub fn level(&self, current: usize, distance: f32) -> usize {
+        let mut level = current.min(self.distances.len());
+        while level < self.distances.len()
+            && distance > self.distances[level] * (1.0 + self.hysteresis)
+        {
+            level += 1;
+        }
+        while level > 0 && distance < self.distances[level - 1] * (1.0 - self.hysteresis) {
+            level -= 1;
+        }
+        level
+    }
+}
+
+/// A coarser level of a `GenerateLodChain` volume meshed on the GPU, kept on a
+/// hidden child whose buffers read the volume's own density buffer every
+/// `factor` samples. Its `Mesh3d` is the level's entry in the volume's
+/// `LodMeshChain`.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LodLevel {
+    pub factor: u32,
+}
+
+/// Meshing of the coarser levels of a volume meshed on the CPU, one task per
+/// `GenerateLodChain::downsample` entry, running on the `AsyncComputeTaskPool`.
+#[derive(Component)]
+pub struct LodChainTask(Vec<Task<MeshData>>);
+
+/// Start meshing the coarser levels of freshly meshed volumes. Volumes meshed
+/// on the GPU remesh their `LodLevel` children, spawning them when the chain or
+/// algorithm changed; volumes meshed on the CPU start a `LodChainTask`. Volumes
+/// that dropped `GenerateLodChain` go back to full resolution and lose their
+/// chain.
+pub fn build_lod_chains(
+    mut commands: Commands,
+    mut storage_buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    mesh_size: Res<DensityFieldMeshSize>,
+    dimensions: Res<DensityFieldSize>,
+    query: Query<
+        (
+            Entity,
+            &GenerateLodChain,
+            Option<&SurfaceNetsBuffers>,
+            Option<&DensityField>,
+            Option<&MeshingAlgorithm>,
+            Option<&VoxelSpacing>,
+        ),
+        With<Mesh3d>,
+    >,
+    changed: Query<Entity, Changed<GenerateLodChain>>,
+    chains: Query<&LodMeshChain>,
+    children: Query<&Children>,
+    levels: Query<(Entity, &LodLevel, &SurfaceNetsBuffers)>,
+    // `build_mesh_from_readback` takes the `GeneratedMesh` of every volume it meshes
+    mut built: RemovedComponents<GeneratedMesh>,
+    mut removed: RemovedComponents<GenerateLodChain>,
+) {
+    for entity in removed.read() {
+        let mut entity_commands = commands.entity(entity);
+        if let Ok(chain) = chains.get(entity)
+            && let Some(full) = chain.first()
+        {
+            entity_commands.try_insert(Mesh3d(full.clone()));
+        }
+        entity_commands.try_remove::<(LodMeshChain, LodChainTask)>();
+        for (level, ..) in levels.iter_many(children.get(entity).into_iter().flatten()) {
+            commands.entity(level).try_despawn();
+        }
+    }
+
+    let entities: EntityHashSet = built.read().chain(&changed).collect();
+    for (entity, lods, buffers, density_field, algorithm, spacing) in query.iter_many(entities) {
+        match (buffers, density_field) {
+            (Some(buffers), _) => {
+                let existing: Vec<_> = levels
+                    .iter_many(children.get(entity).into_iter().flatten())
+                    .collect();
+                let current = existing.len() == lods.downsample.len()
+                    && existing.iter().zip(&lods.downsample).all(
+                        |((_, level, level_buffers), &factor)| {
+                            level.factor == factor
+                                && level_buffers.algorithm == buffers.algorithm
+                                && level_buffers.density_field == buffers.density_field
+                        },
+                    );
+                if current {
+                    for (level, _, level_buffers) in existing {
+                        commands
+                            .entity(level)
+                            .try_insert((Remesh, MeshingRegion::full(level_buffers.dimensions)));
+                    }
+                    continue;
+                }
+
+                for (level, ..) in existing {
+                    commands.entity(level).try_despawn();
+                }
+                for &factor in &lods.downsample {
+                    let level = buffers.lod_level(factor, &mut storage_buffers);
+                    commands.spawn((
+                        LodLevel { factor },
+                        MeshingRegion::full(level.dimensions),
+                        level,
+                        buffers.algorithm,
+                        Visibility::Hidden,
+                        ChildOf(entity),
+                    ));
+                }
+            }
+            (None, Some(density_field)) => {
+                if density_field.len() != dimensions.density_count() as usize {
+                    continue;
+                }
+                let algorithm = algorithm.copied().unwrap_or_default();
+                let scale = VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions.0);
+                let tasks = lods
+                    .downsample
+                    .iter()
+                    .map(|&factor| {
+                        spawn_downsampled_mesh(density_field, *dimensions, factor, algorithm, scale)
+                    })
+                    .collect();
+                commands.entity(entity).try_insert(LodChainTask(tasks));
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Scale the meshes read back for `LodLevel`s like their volume's mesh and put
+/// them in its `LodMeshChain`, reusing each level's asset. Runs before
+/// `build_mesh_from_readback`, which would otherwise take them for volumes.
+pub fn collect_lod_levels(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mesh_size: Res<DensityFieldMeshSize>,
+    read_back: Query<
+        (
+            Entity,
+            &GeneratedMesh,
+            &SurfaceNetsBuffers,
+            &ChildOf,
+            Option<&Mesh3d>,
+        ),
+        With<LodLevel>,
+    >,
+    volumes: Query<(
+        &Mesh3d,
+        &GenerateLodChain,
+        Option<&LodMeshChain>,
+        Option<&VoxelSpacing>,
+        &Children,
+    )>,
+    levels: Query<(Entity, &LodLevel, Option<&Mesh3d>)>,
+) {
+    let mut added = EntityHashMap::default();
+    let mut updated = EntityHashSet::default();
+    for (entity, generated, buffers, child_of, existing) in &read_back {
+        let Ok((.., spacing, _)) = volumes.get(child_of.parent()) else {
+            continue;
+        };
+        // Levels are meshed in their own grid units, `stride` voxels of the volume apart
+        let scale =
+            VoxelSpacing::volume_scale(spacing, *mesh_size, buffers.params.source_dimensions);
+        let mut mesh_data = generated.0.clone();
+        mesh_data.scale(scale * buffers.params.stride as f32);
+        let mesh = mesh_data.to_mesh();
+        let mut entity_commands = commands.entity(entity);
+        match existing {
+            Some(Mesh3d(handle)) => {
+                let _ = meshes.insert(handle, mesh);
+            }
+            None => {
+                let handle = meshes.add(mesh);
+                entity_commands.try_insert(Mesh3d(handle.clone()));
+                added.insert(entity, handle);
+            }
+        }
+        // Without a `MeshingRegion` the level is left out of the compute pass
+        entity_commands.try_remove::<(GeneratedMesh, Remesh, MeshingRegion)>();
+        updated.insert(child_of.parent());
+    }
+
+    for entity in updated {
+        let Ok((Mesh3d(active), lods, existing, _, children)) = volumes.get(entity) else {
+            continue;
+        };
+        let full = existing.and_then(|chain| chain.first()).unwrap_or(active);
+        let mut chain = vec![full.clone()];
+        // Levels still waiting for their first mesh end the chain, so its
+        // indices stay the levels'
+        for &factor in &lods.downsample {
+            let Some(handle) = levels
+                .iter_many(children)
+                .find(|(_, level, _)| level.factor == factor)
+                .and_then(|(level, _, mesh)| added.get(&level).or(mesh.map(|mesh| &mesh.0)))
+            else {
+                break;
+            };
+            chain.push(handle.clone());
+        }
+        commands.entity(entity).try_insert(LodMeshChain(chain));
+    }
+}
+
+/// Put the levels of finished `LodChainTask`s in their volume's
+/// `LodMeshChain`, reusing each level's asset.
+pub fn poll_lod_chain_tasks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut tasks: Query<(Entity, &mut LodChainTask, &Mesh3d, Option<&LodMeshChain>)>,
+) {
+    for (entity, mut task, Mesh3d(active), existing) in &mut tasks {
+        if !task.0.iter().all(Task::is_finished) {
+            continue;
+        }
+
+        let full = existing.and_then(|chain| chain.first()).unwrap_or(active);
+        let mut previous = existing.into_iter().flat_map(|chain| chain.iter().skip(1));
+        let mut chain = vec![full.clone()];
+        for level in task.0.drain(..) {
+            let mesh = block_on(level).to_mesh();
+            chain.push(match previous.next() {
+                Some(handle) => {
+                    let _ = meshes.insert(handle, mesh);
+                    handle.clone()
+                }
+                None => meshes.add(mesh),
+            });
+        }
+        commands
+            .entity(entity)
+            .try_remove::<LodChainTask>()
+            .try_insert(LodMeshChain(chain));
+    }
+}
+
+/// Point each `SculptLod` volume's `Mesh3d` at the level of its chain for the
+/// current camera distance. Volumes that dropped `SculptLod` go back to full
+/// resolution.
+pub fn switch_lods(
+    mut volumes: Query<(
+        &SculptLod,
+        &LodMeshChain,
+        &mut Mesh3d,
+        &GlobalTransform,
+        Option<&Aabb>,
+    )>,
+    cameras: Query<&GlobalTransform, With<Camera3d>>,
+    mut removed: RemovedComponents<SculptLod>,
+    mut chains: Query<(&LodMeshChain, &mut Mesh3d), Without<SculptLod>>,
+) {
+    for entity in removed.read() {
+        if let Ok((chain, mut mesh)) = chains.get_mut(entity)
+            && let Some(full) = chain.first()
+            && mesh.0 != *full
+        {
+            mesh.0 = full.clone();
+        }
+    }
+
+    for (lod, chain, mut mesh, transform, aabb) in &mut volumes {
+        let center = transform.transform_point(aabb.map_or(Vec3::ZERO, |aabb| aabb.center.into()));
+        let Some(distance) = cameras
+            .iter()
+            .map(|camera| camera.translation().distance(center))
+            .min_by(f32::total_cmp)
+        else {
+            return;
+        };
+        let current = chain
+            .iter()
+            .position(|handle| *handle == mesh.0)
+            .unwrap_or(0);
+        let level = lod.level(current, distance);
+        if let Some(handle) = chain.get(level).or(chain.last())
+            && *handle != mesh.0
+        {
+            mesh.0 = handle.clone();
+        }
+    }
+}
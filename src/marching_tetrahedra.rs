@@ -0,0 +1,173 @@
+#[cfg(feature = "gpu")]
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::render::{
+    render_resource::{binding_types::*, *},
+    renderer::RenderDevice,
+};
+
+use crate::backend::MeshingBackend;
+#[cfg(feature = "gpu")]
+use crate::{
+    backend::{BackendBindings, BackendInit, BackendKernels, workgroups_3d},
+    dirty_region::MeshingRegion,
+    quantize::DensityDecode,
+    settings::SurfaceNetsParams,
+};
+
+#[cfg(feature = "gpu")]
+const MARCHING_TETRAHEDRA_SHADER: &str = "shaders/marching_tetrahedra.wgsl";
+
+// Indices into `BackendKernels::layouts`
+#[cfg(feature = "gpu")]
+const BUFFER_LAYOUT: usize = 0;
+#[cfg(feature = "gpu")]
+const TEXTURE_LAYOUT: usize = 1;
+
+/// Every cell split into six tetrahedra, one triangle or quad per tetrahedron.
+pub struct MarchingTetrahedra;
+
+impl MeshingBackend for MarchingTetrahedra {
+    #[cfg(feature = "gpu")]
+    fn vertices_per_point(&self) -> u32 {
+        // One per edge leaving the point: 3 axes, 3 face diagonals, 1 body diagonal
+        7
+    }
+
+    #[cfg(feature = "gpu")]
+    fn faces_per_point(&self) -> u32 {
+        // One per tetrahedron
+        6
+    }
+
+    #[cfg(feature = "gpu")]
+    fn init_kernels(&self, init: &BackendInit) -> BackendKernels {
+        // Shared by the vertex and face stages
+        let layout = init.render_device.create_bind_group_layout(
+            "MarchingTetrahedraLayout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    storage_buffer_read_only::<Vec<u32>>(false), // density_field (f32 bits or packed bytes)
+                    storage_buffer::<Vec<f32>>(false),           // vertices (output)
+                    storage_buffer::<Vec<u32>>(false),           // vertex_valid (output)
+                    uniform_buffer::<UVec3>(false),              // dimensions
+                    uniform_buffer::<DensityDecode>(false),      // density_decode
+                    uniform_buffer::<MeshingRegion>(false),      // region
+                    uniform_buffer::<SurfaceNetsParams>(false),  // params
+                    storage_buffer::<Vec<u32>>(false),           // faces (output)
+                    storage_buffer::<Vec<u32>>(false),           // face_valid (output)
+                ),
+            ),
+        );
+
+        // Same, from a 3D density texture
+        let texture_layout = init.render_device.create_bind_group_layout(
+            "MarchingTetrahedraTextureLayout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    texture_3d(TextureSampleType::Float { filterable: true }), // density_texture
+                    storage_buffer::<Vec<f32>>(false),                         // vertices (output)
+                    storage_buffer::<Vec<u32>>(false), // vertex_valid (output)
+                    uniform_buffer::<UVec3>(false),    // dimensions
+                    uniform_buffer::<DensityDecode>(false), // density_decode
+                    uniform_buffer::<MeshingRegion>(false), // region
+                    uniform_buffer::<SurfaceNetsParams>(false), // params
+                    storage_buffer::<Vec<u32>>(false), // faces (output)
+                    storage_buffer::<Vec<u32>>(false), // face_valid (output)
+                    sampler(SamplerBindingType::Filtering), // density_sampler
+                ),
+            ),
+        );
+
+        // One pipeline per entry point and density input
+        let texture_def = || vec!["DENSITY_TEXTURE".into()];
+        BackendKernels {
+            buffer: [
+                init.queue_pipeline(
+                    "tetra_vertices_pipeline",
+                    &layout,
+                    MARCHING_TETRAHEDRA_SHADER,
+                    "generate_tetra_vertices",
+                    vec![],
+                ),
+                init.queue_pipeline(
+                    "tetra_faces_pipeline",
+                    &layout,
+                    MARCHING_TETRAHEDRA_SHADER,
+                    "generate_tetra_faces",
+                    vec![],
+                ),
+            ],
+            texture: [
+                init.queue_pipeline(
+                    "tetra_vertices_texture_pipeline",
+                    &texture_layout,
+                    MARCHING_TETRAHEDRA_SHADER,
+                    "generate_tetra_vertices",
+                    texture_def(),
+                ),
+                init.queue_pipeline(
+                    "tetra_faces_texture_pipeline",
+                    &texture_layout,
+                    MARCHING_TETRAHEDRA_SHADER,
+                    "generate_tetra_faces",
+                    texture_def(),
+                ),
+            ],
+            layouts: vec![layout, texture_layout],
+            buffers: vec![],
+        }
+    }
+
+    #[cfg(feature = "gpu")]
+    fn bind_groups(
+        &self,
+        kernels: &BackendKernels,
+        render_device: &RenderDevice,
+        bindings: &BackendBindings,
+    ) -> [BindGroup; 2] {
+        // Both stages run from one bind group
+        let bind_group = match &bindings.density_sampler {
+            Some(density_sampler) => render_device.create_bind_group(
+                Some("marching_tetrahedra_texture_bind_group"),
+                &kernels.layouts[TEXTURE_LAYOUT],
+                &BindGroupEntries::sequential((
+                    bindings.density.clone(),
+                    bindings.vertices.clone(),
+                    bindings.vertex_valid.clone(),
+                    bindings.dimensions.clone(),
+                    bindings.density_decode.clone(),
+                    bindings.region.clone(),
+                    bindings.params.clone(),
+                    bindings.faces.clone(),
+                    bindings.face_valid.clone(),
+                    density_sampler.clone(),
+                )),
+            ),
+            None => render_device.create_bind_group(
+                Some("marching_tetrahedra_bind_group"),
+                &kernels.layouts[BUFFER_LAYOUT],
+                &BindGroupEntries::sequential((
+                    bindings.density.clone(),
+                    bindings.vertices.clone(),
+                    bindings.vertex_valid.clone(),
+                    bindings.dimensions.clone(),
+                    bindings.density_decode.clone(),
+                    bindings.region.clone(),
+                    bindings.params.clone(),
+                    bindings.faces.clone(),
+                    bindings.face_valid.clone(),
+                )),
+            ),
+        };
+        [bind_group.clone(), bind_group]
+    }
+
+    #[cfg(feature = "gpu")]
+    fn workgroups(&self, size: UVec3) -> [(u32, u32, u32); 2] {
+        // Vertices are generated over grid points, one more than cells on each axis
+        [workgroups_3d(size + 1), workgroups_3d(size)]
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize, dirty_region::DensityEdited,
+    units::VoxelSpacing,
+};
+
+/// Keep a `FieldMassProperties` of this volume up to date with its
+/// `DensityField`, for the solid it encloses made of `density`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GenerateMassProperties {
+    /// Mass per cubic metre
+    pub density: f32,
+}
+
+impl Default for GenerateMassProperties {
+    fn default() -> Self {
+        Self { density: 1.0 }
+    }
+}
+
+/// Mass properties of the volume's solid, integrated over its `DensityField`
+/// samples, which is cheaper and more accurate than over its mesh. In the
+/// volume's local space, in metres, like its mesh.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct FieldMassProperties {
+    pub mass: f32,
+    /// In cubic metres
+    pub volume: f32,
+    pub center_of_mass: Vec3,
+    /// Inertia tensor about the center of mass
+    pub inertia: Mat3,
+}
+
+/// Recompute the mass properties of volumes whose field, spacing or density
+/// changed, or that were sculpted on the GPU. Those of volumes that dropped
+/// `GenerateMassProperties` are removed.
+pub fn update_mass_properties(
+    mut commands: Commands,
+    mesh_size: Res<DensityFieldMeshSize>,
+    dimensions: Res<DensityFieldSize>,
+    query: Query<(
+        Entity,
+        Ref<DensityField>,
+        Ref<GenerateMassProperties>,
+        Option<Ref<VoxelSpacing>>,
+    )>,
+    mut sculpted: MessageReader<DensityEdited>,
+    mut removed: RemovedComponents<GenerateMassProperties>,
+) {
+    for entity in removed.read() {
+        commands.entity(entity).try_remove::<FieldMassProperties>();
+    }
+
+    let sculpted: EntityHashSet = sculpted.read().map(|sculpted| sculpted.entity).collect();
+    for (entity, density_field, generate, spacing) in &query {
+        let changed = density_field.is_changed()
+            || generate.is_changed()
+            || spacing.as_ref().is_some_and(Ref::is_changed);
+        if !changed && !sculpted.contains(&entity) {
+            continue;
+        }
+        let spacing = spacing.as_deref();
+        let properties = sculpter_core::mass_properties(
+            &density_field,
+            dimensions.0,
+            VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions.0),
+        );
+        commands.entity(entity).try_insert(FieldMassProperties {
+            mass: properties.volume * generate.density,
+            volume: properties.volume,
+            center_of_mass: properties.center_of_mass,
+            inertia: properties.inertia * generate.density,
+        });
+    }
+}
@@ -0,0 +1,216 @@
+#[cfg(feature = "gpu")]
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use sculpter_core::{COLOR_ATTRIBUTE, vertex_palette_colors};
+use sculpter_core::{
+    MATERIAL_ID_ATTRIBUTE, MATERIAL_WEIGHTS_ATTRIBUTE, VertexAttributeData,
+    vertex_material_weights, vertex_materials,
+};
+
+#[cfg(feature = "gpu")]
+use crate::dirty_region::Remesh;
+use crate::{
+    DensityField, DensityFieldSize,
+    dirty_region::DensityFieldDirtyRegion,
+    mesh::{GeneratedMesh, Meshed},
+};
+
+/// Material or biome id per density sample, a second channel laid out like the
+/// volume's `DensityField`.
+///
+/// Each vertex of the volume's mesh takes the id of the nearest solid sample of
+/// its cell, as the `Uint32` attribute `ATTRIBUTE_MATERIAL_ID`, and blend weights
+/// of materials 0 to 3 for texture splatting, as `ATTRIBUTE_MATERIAL_WEIGHTS`.
+/// GPU volumes upload it next to their densities. Changing the field uploads it
+/// again and remeshes the volume.
+#[derive(Component, Clone, Debug, Default, Deref, DerefMut)]
+pub struct MaterialField(pub Vec<u32>);
+
+/// Color per material id, written to the mesh of a volume with a
+/// `MaterialField` as `Mesh::ATTRIBUTE_COLOR` so a plain `StandardMaterial` can
+/// color it. Ids past the end of the palette are white.
+#[cfg(feature = "gpu")]
+#[derive(Component, Clone, Debug, Default, Deref, DerefMut)]
+pub struct MaterialPalette(pub Vec<Color>);
+
+/// Draw a volume's mesh as one submesh per material id, for materials that
+/// can't blend between ids.
+///
+/// Each triangle goes to the id most of its vertices have. The volume's own
+/// `Mesh3d` draws the lowest id and every other id gets a child entity, all
+/// using the material mapped to their id or a plain grey one.
+#[cfg(feature = "gpu")]
+#[derive(Component, Clone, Debug, Default, Deref, DerefMut)]
+pub struct MaterialSubmeshes(pub HashMap<u32, Handle<StandardMaterial>>);
+
+/// Material id drawn by a volume split with `MaterialSubmeshes`, or by one of
+/// its submesh children.
+#[cfg(feature = "gpu")]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MaterialSubmesh(pub u32);
+
+/// Upload and remesh volumes whose material channel was edited, through the
+/// whole field's dirty region since the edit could be anywhere.
+pub fn remesh_changed_materials(
+    mut commands: Commands,
+    query: Query<Entity, (Changed<MaterialField>, With<Meshed>)>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    for entity in &query {
+        commands
+            .entity(entity)
+            .try_insert(DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0));
+    }
+}
+
+/// Remesh volumes whose palette was edited, to recolor their vertices.
+#[cfg(feature = "gpu")]
+pub fn remesh_changed_palettes(
+    mut commands: Commands,
+    query: Query<Entity, (Changed<MaterialPalette>, With<MaterialField>, With<Meshed>)>,
+) {
+    for entity in &query {
+        commands.entity(entity).try_insert(Remesh);
+    }
+}
+
+/// Give freshly generated meshes their material ids, once every other stage has
+/// added its vertices. Meshes read back from the GPU already carry ids and
+/// weights computed by the normals kernel, and only get their palette colors.
+pub fn assign_vertex_materials(
+    mut query: Query<(
+        Entity,
+        &mut GeneratedMesh,
+        &MaterialField,
+        Option<&DensityField>,
+    )>,
+    #[cfg(feature = "gpu")] palettes: Query<&MaterialPalette>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    for (_entity, mut generated, materials, density_field) in &mut query {
+        let count = generated.positions.len();
+        let (ids, weights) = match (
+            generated.attributes.get(MATERIAL_ID_ATTRIBUTE),
+            generated.attributes.get(MATERIAL_WEIGHTS_ATTRIBUTE),
+        ) {
+            (
+                Some(VertexAttributeData::Uint32(ids)),
+                Some(VertexAttributeData::Float32x4(weights)),
+            ) if ids.len() == count && weights.len() == count => (ids.clone(), weights.clone()),
+            _ => {
+                let Some(density_field) = density_field else {
+                    continue;
+                };
+                if materials.len() != density_field.len() {
+                    warn_once!("MaterialField length doesn't match the DensityField, ignoring it");
+                    continue;
+                }
+                let positions = &generated.positions;
+                (
+                    vertex_materials(positions, density_field, materials, dimensions.0),
+                    vertex_material_weights(positions, density_field, materials, dimensions.0),
+                )
+            }
+        };
+        #[cfg(feature = "gpu")]
+        if let Ok(palette) = palettes.get(_entity) {
+            let palette: Vec<Vec4> = palette
+                .iter()
+                .map(|color| color.to_linear().to_vec4())
+                .collect();
+            generated.attributes.insert(
+                COLOR_ATTRIBUTE,
+                VertexAttributeData::Float32x4(vertex_palette_colors(&ids, &palette)),
+            );
+        }
+        generated
+            .attributes
+            .insert(MATERIAL_ID_ATTRIBUTE, VertexAttributeData::Uint32(ids));
+        generated.attributes.insert(
+            MATERIAL_WEIGHTS_ATTRIBUTE,
+            VertexAttributeData::Float32x4(weights),
+        );
+    }
+}
+
+/// Split `mesh_data` into the submeshes asked for by `split`, updating or
+/// spawning the volume's submesh children, and return what the volume itself
+/// draws. Without `split` the whole mesh is returned and leftover children are
+/// despawned.
+#[cfg(feature = "gpu")]
+pub(crate) fn build_material_submeshes(
+    commands: &mut Commands,
+    volume: Entity,
+    mesh_data: &crate::mesh_data::MeshData,
+    split: Option<&MaterialSubmeshes>,
+    children: Option<&Children>,
+    submeshes: &Query<(&MaterialSubmesh, &Mesh3d)>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    force_u32_indices: bool,
+) -> (Mesh, Option<Handle<StandardMaterial>>) {
+    use crate::{
+        mesh::{default_material, update_bounds},
+        mesh_data::{MeshData, ToMesh},
+    };
+
+    let to_mesh = |data: &MeshData| data.to_mesh_with_indices(force_u32_indices);
+
+    let mut groups = split.map(|_| sculpter_core::split_by_material(mesh_data));
+    // Ids left without a material share one grey material per remesh
+    let mut grey = None;
+    let mut material_for = |id: u32| {
+        split
+            .and_then(|split| split.get(&id).cloned())
+            .unwrap_or_else(|| {
+                grey.get_or_insert_with(|| materials.add(default_material()))
+                    .clone()
+            })
+    };
+
+    // The volume draws the lowest id
+    let own = groups.as_mut().and_then(|groups| groups.pop_first());
+    let (mesh, material) = match own {
+        Some((id, data)) => {
+            commands.entity(volume).try_insert(MaterialSubmesh(id));
+            (to_mesh(&data), Some(material_for(id)))
+        }
+        None => {
+            commands.entity(volume).try_remove::<MaterialSubmesh>();
+            (to_mesh(mesh_data), split.map(|_| material_for(0)))
+        }
+    };
+    let mut groups = groups.unwrap_or_default();
+
+    // Existing children are updated in place, stale ones despawned
+    for &child in children.into_iter().flatten() {
+        let Ok((&MaterialSubmesh(id), Mesh3d(handle))) = submeshes.get(child) else {
+            continue;
+        };
+        match groups.remove(&id) {
+            Some(data) => {
+                let mesh = to_mesh(&data);
+                let mut child = commands.entity(child);
+                update_bounds(&mut child, &mesh);
+                child.try_insert(MeshMaterial3d(material_for(id)));
+                let _ = meshes.insert(handle, mesh);
+            }
+            None => {
+                commands.entity(child).try_despawn();
+            }
+        }
+    }
+    for (id, data) in groups {
+        let mesh = to_mesh(&data);
+        let mut child = commands.spawn((
+            MeshMaterial3d(material_for(id)),
+            MaterialSubmesh(id),
+            ChildOf(volume),
+        ));
+        update_bounds(&mut child, &mesh);
+        child.insert(Mesh3d(meshes.add(mesh)));
+    }
+
+    (mesh, material)
+}
@@ -1,130 +1,363 @@
-use crate::{DensityFieldMeshSize, DensityFieldSize, readback::ReadbackBuffers};
-use bevy::{asset::RenderAssetUsages, mesh::Indices, prelude::*};
+#[cfg(feature = "cpu")]
+use crate::geometry::{
+    ColliderGeometry, GenerateCollider, GenerateQuadMesh, QuadMesh, VolumeGeometry,
+};
+use crate::{
+    DensityFieldMeshSize, DensityFieldSize,
+    backpressure::{CriticalRemesh, GpuGenerationStarted, GpuMeshingLoad},
+    dirty_region::Remesh,
+    mesh_data::{MeshData, VertexAttributeData},
+    navmesh::{GenerateWalkableSurface, WalkableSurface},
+    scatter::{GenerateSurfacePoints, SurfacePoints, scatter_points},
+    settings::{MeshingAlgorithm, SculptSettings, SurfaceSides, UvGeneration},
+    units::VoxelSpacing,
+};
+#[cfg(feature = "gpu")]
+use crate::{
+    buffers::SurfaceNetsBuffers,
+    lod_chain::LodMeshChain,
+    material::{MaterialSubmesh, MaterialSubmeshes, build_material_submeshes},
+    packed::{PackedVertexMaterial, pack_vertices},
+    triplanar::TriplanarMaterial,
+    watchdog::{ReadbackRetries, ReadbackStarted},
+    wireframe::{DebugWireframe, DebugWireframeMesh, build_debug_wireframe},
+};
+#[cfg(feature = "gpu")]
+use bevy::camera::primitives::{Aabb, MeshAabb};
+use bevy::prelude::*;
 
-pub fn build_mesh_from_readback(
+/// Component whose presence marks a volume as meshed.
+#[cfg(feature = "gpu")]
+pub(crate) type Meshed = Mesh3d;
+#[cfg(not(feature = "gpu"))]
+pub(crate) type Meshed = VolumeGeometry;
+
+/// Mesh produced by the GPU readback or the CPU mesher, in grid units, waiting
+/// to be scaled and attached to its volume.
+#[derive(Component, Clone, Debug, Default, Deref, DerefMut)]
+pub struct GeneratedMesh(pub MeshData);
+
+/// Turn a volume's mesh inside out, facing into the solid, for hollow shells and
+/// surfaces seen from the inside. Meshes otherwise face out of the solid.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct InvertWinding;
+
+/// Remesh volumes that gained or lost `InvertWinding`.
+pub fn remesh_changed_winding(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mesh_size: Res<DensityFieldMeshSize>,
-    dimensions: Res<DensityFieldSize>,
-    query: Query<(Entity, &ReadbackBuffers)>,
+    added: Query<Entity, (Added<InvertWinding>, With<Meshed>)>,
+    mut removed: RemovedComponents<InvertWinding>,
+    meshed: Query<(), With<Meshed>>,
 ) {
-    for (entity, data) in query.iter() {
-        let Some(vertex_count) = data.vertex_count else {
-            continue;
-        };
-        let Some(ref vertices) = data.vertices else {
-            continue;
-        };
-        let Some(face_count) = data.face_count else {
-            continue;
-        };
-        let Some(ref faces) = data.faces else {
-            continue;
-        };
-
-        let scale = **mesh_size / dimensions.as_vec3();
-        let mut world_positions = Vec::with_capacity(vertex_count as usize);
-        for i in 0..vertex_count as usize {
-            let base = i * 3;
-            if base + 2 < vertices.len() {
-                let grid_pos = Vec3::new(vertices[base], vertices[base + 1], vertices[base + 2]);
-                let world_pos = grid_pos * scale; //+ offset
-                world_positions.push([world_pos.x, world_pos.y, world_pos.z]);
-            }
-        }
+    let removed = removed.read().filter(|&entity| meshed.contains(entity));
+    for entity in added.iter().chain(removed) {
+        commands.entity(entity).try_insert(Remesh);
+    }
+}
 
-        let mut triangle_indices = Vec::with_capacity(face_count as usize * 6);
-        for i in 0..face_count as usize {
-            let base = i * 4;
-            if base + 3 < faces.len() {
-                let v0 = faces[base];
-                let v1 = faces[base + 1];
-                let v2 = faces[base + 2];
-                let v3 = faces[base + 3];
-                //triangle 1
-                triangle_indices.push(v0);
-                triangle_indices.push(v1);
-                triangle_indices.push(v2);
-
-                //triangle 2
-                triangle_indices.push(v0);
-                triangle_indices.push(v2);
-                triangle_indices.push(v3);
-            }
+/// Face a freshly generated mesh outward along the density `gradients`, one per
+/// vertex, and take them as normals wherever the field isn't flat. Blocky faces
+/// already face outward and keep their flat normals.
+pub(crate) fn apply_gradient_normals(
+    mesh: &mut MeshData,
+    gradients: &[Vec3],
+    algorithm: MeshingAlgorithm,
+) {
+    if algorithm == MeshingAlgorithm::Blocky || gradients.len() != mesh.positions.len() {
+        return;
+    }
+    sculpter_core::orient_to_gradient(mesh, gradients);
+    for (normal, &gradient) in mesh.normals.iter_mut().zip(gradients) {
+        if gradient != Vec3::ZERO {
+            *normal = gradient;
         }
+    }
+}
 
-        let normals = compute_flat_normals(&world_positions, &triangle_indices);
-
-        let mut mesh = Mesh::new(
-            bevy::mesh::PrimitiveTopology::TriangleList,
-            RenderAssetUsages::default(),
-        );
+/// Hang the skirts asked for by `settings` on a freshly generated mesh.
+pub(crate) fn add_skirts(
+    mesh: &mut MeshData,
+    dimensions: UVec3,
+    algorithm: MeshingAlgorithm,
+    settings: &SculptSettings,
+) {
+    if algorithm != MeshingAlgorithm::Blocky {
+        sculpter_core::add_skirts(mesh, dimensions, settings.skirt_depth);
+    }
+}
 
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, world_positions);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.insert_indices(Indices::U32(triangle_indices));
+/// Texture coordinates asked for by `uv_generation`, for a mesh scaled to mesh
+/// units. Box atlases span the volume's `extent`, planar projections use world
+/// space positions under `transform`.
+fn add_uvs(
+    mesh: &mut MeshData,
+    uv_generation: UvGeneration,
+    extent: Vec3,
+    transform: Option<&GlobalTransform>,
+) {
+    match uv_generation {
+        UvGeneration::Triplanar => {}
+        UvGeneration::Planar {
+            direction,
+            tile_size,
+        } => {
+            let positions: Vec<Vec3> = match transform {
+                Some(transform) => mesh
+                    .positions
+                    .iter()
+                    .map(|&position| transform.transform_point(position))
+                    .collect(),
+                None => mesh.positions.clone(),
+            };
+            let uvs = sculpter_core::planar_uvs(&positions, direction, tile_size);
+            mesh.attributes.insert(
+                sculpter_core::UV_0_ATTRIBUTE,
+                VertexAttributeData::Float32x2(uvs),
+            );
+        }
+        UvGeneration::BoxAtlas => sculpter_core::box_atlas_unwrap(mesh, extent),
+    }
+}
 
-        let mesh_handle = meshes.add(mesh);
-        let material_handle = materials.add(StandardMaterial {
-            base_color: Color::srgb(0.8, 0.8, 0.8),
-            metallic: 0.0,
-            perceptual_roughness: 0.5,
-            ..default()
-        });
+/// Give the entity the tight bounds of `mesh`, for frustum culling. Bevy only
+/// computes an `Aabb` for entities without one, so remeshes would otherwise
+/// keep the first mesh's. Empty meshes lose theirs.
+#[cfg(feature = "gpu")]
+pub(crate) fn update_bounds(entity: &mut EntityCommands, mesh: &Mesh) {
+    match mesh.compute_aabb() {
+        Some(aabb) => entity.try_insert(aabb),
+        None => entity.try_remove::<Aabb>(),
+    };
+}
 
-        commands
-            .entity(entity)
-            .insert((Mesh3d(mesh_handle), MeshMaterial3d(material_handle)))
-            .remove::<ReadbackBuffers>();
+/// Plain grey material given to volumes without one of their own.
+#[cfg(feature = "gpu")]
+pub(crate) fn default_material() -> StandardMaterial {
+    StandardMaterial {
+        base_color: Color::srgb(0.8, 0.8, 0.8),
+        metallic: 0.0,
+        perceptual_roughness: 0.5,
+        ..default()
     }
 }
-fn compute_flat_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
-    let mut normals = vec![[0.0, 0.0, 0.0]; positions.len()];
-    let mut normal_counts = vec![0u32; positions.len()];
-
-    // For each triangle, compute its normal and add to vertices
-    for triangle in indices.chunks_exact(3) {
-        let i0 = triangle[0] as usize;
-        let i1 = triangle[1] as usize;
-        let i2 = triangle[2] as usize;
 
-        if i0 >= positions.len() || i1 >= positions.len() || i2 >= positions.len() {
-            continue;
+pub fn build_mesh_from_readback(
+    mut commands: Commands,
+    #[cfg(feature = "gpu")] mut meshes: ResMut<Assets<Mesh>>,
+    #[cfg(feature = "gpu")] mut materials: ResMut<Assets<StandardMaterial>>,
+    #[cfg(feature = "gpu")] mut packed_materials: ResMut<Assets<PackedVertexMaterial>>,
+    mesh_size: Res<DensityFieldMeshSize>,
+    dimensions: Res<DensityFieldSize>,
+    // Paired to stay within the system parameter limit with every feature on
+    (mut load, time): (ResMut<GpuMeshingLoad>, Res<Time<Real>>),
+    query: Query<(
+        Entity,
+        &GeneratedMesh,
+        Option<&GpuGenerationStarted>,
+        Option<&VoxelSpacing>,
+        Option<&SculptSettings>,
+        Option<&GlobalTransform>,
+        Option<&GenerateWalkableSurface>,
+        Option<&GenerateSurfacePoints>,
+        Has<InvertWinding>,
+    )>,
+    #[cfg(feature = "gpu")] existing: Query<(
+        Option<&Mesh3d>,
+        Option<&MeshMaterial3d<StandardMaterial>>,
+        Has<MeshMaterial3d<TriplanarMaterial>>,
+        Has<MeshMaterial3d<PackedVertexMaterial>>,
+        Option<&LodMeshChain>,
+        Option<&SurfaceNetsBuffers>,
+    )>,
+    #[cfg(feature = "gpu")] split: Query<(
+        Option<&MaterialSubmeshes>,
+        Option<&DebugWireframe>,
+        Option<&Children>,
+    )>,
+    #[cfg(feature = "gpu")] submeshes: Query<(&MaterialSubmesh, &Mesh3d)>,
+    #[cfg(feature = "gpu")] wireframes: Query<&Mesh3d, With<DebugWireframeMesh>>,
+    #[cfg(feature = "cpu")] outputs: Query<(Has<GenerateCollider>, Has<GenerateQuadMesh>)>,
+    #[cfg(feature = "mesh_diagnostics")] algorithms: Query<&MeshingAlgorithm>,
+    #[cfg(feature = "lightmap_uvs")] lightmaps: Query<&crate::lightmap::LightmapUvs>,
+    #[cfg(any(feature = "avian", feature = "rapier"))] collision_meshes: Query<
+        (),
+        With<crate::collision::GenerateCollisionMesh>,
+    >,
+) {
+    for (entity, generated, started, spacing, settings, transform, walkable, scatter, inverted) in
+        query.iter()
+    {
+        if let Some(started) = started {
+            load.record_latency(time.elapsed().saturating_sub(started.0));
         }
 
-        let v0 = Vec3::from(positions[i0]);
-        let v1 = Vec3::from(positions[i1]);
-        let v2 = Vec3::from(positions[i2]);
+        // Texture and generated volumes take their size from their own buffers
+        #[cfg(feature = "gpu")]
+        let dimensions = existing
+            .get(entity)
+            .ok()
+            .and_then(|(.., buffers)| buffers)
+            .map_or(*dimensions, |buffers| buffers.dimensions);
+        #[cfg(not(feature = "gpu"))]
+        let dimensions = *dimensions;
 
-        // Compute face normal using cross product
-        let edge1 = v1 - v0;
-        let edge2 = v2 - v0;
-        let normal = edge1.cross(edge2).normalize_or_zero();
+        // Blocky quads share no vertices, so every edge would look like a hole
+        #[cfg(feature = "mesh_diagnostics")]
+        if algorithms.get(entity).ok() != Some(&MeshingAlgorithm::Blocky) {
+            crate::diagnostics::report_mesh_holes(
+                entity,
+                &generated.positions,
+                &generated.indices,
+                dimensions,
+            );
+        }
 
-        // Add to each vertex of the triangle
-        for &idx in &[i0, i1, i2] {
-            normals[idx][0] += normal.x;
-            normals[idx][1] += normal.y;
-            normals[idx][2] += normal.z;
-            normal_counts[idx] += 1;
+        // Volumes with physical spacing are meshed at real-world scale
+        let scale = VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions.0);
+        let mut mesh_data = generated.0.clone();
+        if let Some(filter) = settings.and_then(|settings| settings.degenerate_filter) {
+            sculpter_core::remove_degenerate_triangles(&mut mesh_data, filter);
+        }
+        if let Some(smoothing) = settings.and_then(|settings| settings.smoothing) {
+            sculpter_core::smooth(&mut mesh_data, smoothing);
+        }
+        if let Some(simplification) = settings.and_then(|settings| settings.simplification) {
+            sculpter_core::simplify(&mut mesh_data, simplification);
+        }
+        let sides = settings.map_or_else(SurfaceSides::default, |settings| settings.sides);
+        if inverted != (sides == SurfaceSides::Inside) {
+            mesh_data.invert_winding();
+        }
+        if let Some(crease_angle) = settings.and_then(|settings| settings.crease_angle) {
+            sculpter_core::split_creases(&mut mesh_data, crease_angle);
+        }
+        mesh_data.scale(scale);
+        if let Some(settings) = settings {
+            let extent = scale * (dimensions.0 - 1).as_vec3();
+            add_uvs(&mut mesh_data, settings.uv_generation, extent, transform);
+        }
+        // After the UVs, so both sides share their texture, but before the
+        // lightmap, so each side gets its own texels
+        if sides == SurfaceSides::Both {
+            mesh_data.make_double_sided();
+        }
+        #[cfg(feature = "lightmap_uvs")]
+        if let Ok(lightmap) = lightmaps.get(entity) {
+            sculpter_core::lightmap_unwrap(&mut mesh_data, lightmap.resolution);
         }
-    }
 
-    // Average the normals
-    for i in 0..normals.len() {
-        if normal_counts[i] > 0 {
-            let count = normal_counts[i] as f32;
-            let normal = Vec3::new(
-                normals[i][0] / count,
-                normals[i][1] / count,
-                normals[i][2] / count,
+        #[cfg(feature = "gpu")]
+        let (mesh, split_material) = {
+            let (split, wireframe, children) = split.get(entity).unwrap_or_default();
+            build_debug_wireframe(
+                &mut commands,
+                entity,
+                &mesh_data,
+                wireframe,
+                children,
+                &wireframes,
+                &mut meshes,
+                &mut materials,
+            );
+            build_material_submeshes(
+                &mut commands,
+                entity,
+                &mesh_data,
+                split,
+                children,
+                &submeshes,
+                &mut meshes,
+                &mut materials,
+                settings.is_some_and(|settings| settings.force_u32_indices),
             )
-            .normalize_or_zero();
+        };
+
+        let mut entity_commands = commands.entity(entity);
 
-            normals[i] = [normal.x, normal.y, normal.z];
+        #[cfg(feature = "gpu")]
+        {
+            let (existing_mesh, existing_material, triplanar, packed, lods, _) =
+                existing.get(entity).unwrap_or_default();
+            // `SculptLod` may have swapped in a coarser level
+            let existing_mesh = lods
+                .and_then(|lods| lods.first())
+                .or(existing_mesh.map(|mesh| &mesh.0));
+            // Only a `PackedVertexMaterial` reads packed vertices
+            let pack = settings.is_some_and(|settings| settings.packed_vertices)
+                && split_material.is_none()
+                && existing_material.is_none()
+                && !triplanar;
+            update_bounds(&mut entity_commands, &mesh);
+            let mut mesh = mesh;
+            if pack {
+                pack_vertices(&mut mesh);
+            }
+
+            // Remeshes replace the existing asset in place and keep their material
+            let mesh_handle = match existing_mesh {
+                Some(handle) => {
+                    let _ = meshes.insert(handle, mesh);
+                    handle.clone()
+                }
+                None => meshes.add(mesh),
+            };
+            entity_commands
+                .try_insert(Mesh3d(mesh_handle))
+                .try_remove::<(ReadbackStarted, ReadbackRetries)>();
+            if packed && !pack {
+                entity_commands.try_remove::<MeshMaterial3d<PackedVertexMaterial>>();
+            }
+            // Volumes without a material of their own get a plain grey one
+            if let Some(material) = split_material {
+                entity_commands.try_insert(MeshMaterial3d(material));
+            } else if pack {
+                if !packed {
+                    entity_commands.try_insert(MeshMaterial3d(packed_materials.add(
+                        PackedVertexMaterial {
+                            base: default_material(),
+                            extension: default(),
+                        },
+                    )));
+                }
+            } else if existing_material.is_none() && !triplanar {
+                entity_commands.try_insert(MeshMaterial3d(materials.add(default_material())));
+            }
+        }
+
+        if let Some(walkable) = walkable {
+            let up = transform.map_or(Vec3::Y, |transform| {
+                transform.rotation().inverse() * Vec3::Y
+            });
+            entity_commands.try_insert(WalkableSurface::new(&mesh_data, up, walkable));
+        }
+        if let Some(scatter) = scatter {
+            entity_commands.try_insert(SurfacePoints(scatter_points(
+                &mesh_data,
+                scatter.count,
+                scatter.distribution,
+                scatter.seed,
+            )));
         }
-    }
 
-    normals
+        // Volumes with a `GenerateCollisionMesh` get a low resolution one instead
+        #[cfg(any(feature = "avian", feature = "rapier"))]
+        if !collision_meshes.contains(entity) {
+            entity_commands.try_insert(crate::collision::CollisionMesh(mesh_data.clone()));
+        }
+
+        #[cfg(feature = "cpu")]
+        {
+            let (wants_collider, wants_quads) = outputs.get(entity).unwrap_or_default();
+            if wants_collider {
+                entity_commands.try_insert(ColliderGeometry::from(&mesh_data));
+            }
+            if wants_quads {
+                entity_commands.try_insert(QuadMesh::from(&mesh_data));
+            }
+            entity_commands.try_insert(VolumeGeometry(mesh_data));
+        }
+
+        entity_commands
+            .try_remove::<(GeneratedMesh, GpuGenerationStarted, Remesh, CriticalRemesh)>();
+    }
 }
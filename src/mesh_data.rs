@@ -0,0 +1,111 @@
+pub use sculpter_core::{MeshData, VertexAttributeData};
+
+#[cfg(feature = "gpu")]
+use bevy::{
+    asset::RenderAssetUsages,
+    mesh::{Indices, MeshVertexAttribute, PrimitiveTopology, VertexAttributeValues, VertexFormat},
+    prelude::*,
+};
+
+/// Per-vertex material ids from a `MaterialField`.
+#[cfg(feature = "gpu")]
+pub const ATTRIBUTE_MATERIAL_ID: MeshVertexAttribute = MeshVertexAttribute::new(
+    sculpter_core::MATERIAL_ID_ATTRIBUTE,
+    0x5c17_0001,
+    VertexFormat::Uint32,
+);
+
+/// Per-vertex splat weights of materials 0 to 3 from a `MaterialField`.
+#[cfg(feature = "gpu")]
+pub const ATTRIBUTE_MATERIAL_WEIGHTS: MeshVertexAttribute = MeshVertexAttribute::new(
+    sculpter_core::MATERIAL_WEIGHTS_ATTRIBUTE,
+    0x5c17_0002,
+    VertexFormat::Float32x4,
+);
+
+/// Per-vertex ambient occlusion baked with `SculptSettings::ambient_occlusion`.
+#[cfg(feature = "gpu")]
+pub const ATTRIBUTE_AMBIENT_OCCLUSION: MeshVertexAttribute = MeshVertexAttribute::new(
+    sculpter_core::AMBIENT_OCCLUSION_ATTRIBUTE,
+    0x5c17_0003,
+    VertexFormat::Float32,
+);
+
+/// Conversion of plain mesh data into a Bevy `Mesh` asset.
+#[cfg(feature = "gpu")]
+pub trait ToMesh {
+    /// Build a triangle list `Mesh` asset, with 16-bit indices when every
+    /// vertex fits.
+    fn to_mesh(&self) -> Mesh {
+        self.to_mesh_with_indices(false)
+    }
+
+    /// Build a triangle list `Mesh` asset, with 32-bit indices if `force_u32`.
+    fn to_mesh_with_indices(&self, force_u32: bool) -> Mesh;
+}
+
+#[cfg(feature = "gpu")]
+impl ToMesh for MeshData {
+    fn to_mesh_with_indices(&self, force_u32: bool) -> Mesh {
+        // Reordered for the GPU's caches, leaving `self` in mesher order
+        #[cfg(feature = "meshopt")]
+        let data = &{
+            let mut data = self.clone();
+            sculpter_core::optimize_vertex_order(&mut data);
+            data
+        };
+        #[cfg(not(feature = "meshopt"))]
+        let data = self;
+
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, data.positions.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, data.normals.clone());
+        for (&name, attribute) in &data.attributes {
+            let (format, values) = match attribute.clone() {
+                VertexAttributeData::Float32(values) => (VertexFormat::Float32, values.into()),
+                VertexAttributeData::Float32x2(values) => (VertexFormat::Float32x2, values.into()),
+                VertexAttributeData::Float32x3(values) => (VertexFormat::Float32x3, values.into()),
+                VertexAttributeData::Float32x4(values) => (VertexFormat::Float32x4, values.into()),
+                VertexAttributeData::Uint32(values) => (VertexFormat::Uint32, values.into()),
+            };
+            let values: VertexAttributeValues = values;
+            mesh.insert_attribute(vertex_attribute(name, format), values);
+        }
+        // Half the index memory for the many small chunks that need no more
+        let indices = if !force_u32 && data.positions.len() <= u16::MAX as usize + 1 {
+            Indices::U16(data.indices.iter().map(|&index| index as u16).collect())
+        } else {
+            Indices::U32(data.indices.clone())
+        };
+        mesh.insert_indices(indices);
+        mesh
+    }
+}
+
+/// Bevy's attribute with this name, or a custom one identified by the name's hash.
+#[cfg(feature = "gpu")]
+fn vertex_attribute(name: &'static str, format: VertexFormat) -> MeshVertexAttribute {
+    let builtin = [
+        Mesh::ATTRIBUTE_UV_0,
+        Mesh::ATTRIBUTE_UV_1,
+        Mesh::ATTRIBUTE_TANGENT,
+        Mesh::ATTRIBUTE_COLOR,
+        ATTRIBUTE_MATERIAL_ID,
+        ATTRIBUTE_MATERIAL_WEIGHTS,
+        ATTRIBUTE_AMBIENT_OCCLUSION,
+    ];
+    builtin
+        .into_iter()
+        .find(|attribute| attribute.name == name)
+        .unwrap_or_else(|| {
+            // FNV-1a, stable across runs so custom shaders can rely on the id
+            let id = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            });
+            MeshVertexAttribute::new(name, id, format)
+        })
+}
@@ -0,0 +1,94 @@
+//! Meshing entry points that work on plain data, for use outside the plugin's
+//! `DensityField` entity flow (custom systems, tools and tests).
+
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::render::storage::ShaderStorageBuffer;
+
+use crate::{
+    DensityFieldSize, cpu,
+    mesh_data::MeshData,
+    settings::{MeshingAlgorithm, SculptSettings},
+};
+#[cfg(feature = "gpu")]
+use crate::{buffers::SurfaceNetsBuffers, dirty_region::MeshingRegion};
+
+/// Mesh a density field with surface nets on the calling thread.
+///
+/// `densities` holds `dimensions.x * dimensions.y * dimensions.z` values, x
+/// fastest, negative inside. Positions are in grid units, one per voxel, and
+/// match what the GPU pipeline produces for the same field and settings.
+pub fn surface_nets_cpu(
+    densities: &[f32],
+    dimensions: UVec3,
+    settings: &SculptSettings,
+) -> MeshData {
+    mesh_density_cpu(
+        densities,
+        dimensions,
+        MeshingAlgorithm::SurfaceNets,
+        settings,
+    )
+}
+
+/// Mesh a density field with any algorithm on the calling thread, see
+/// [`surface_nets_cpu`].
+pub fn mesh_density_cpu(
+    densities: &[f32],
+    dimensions: UVec3,
+    algorithm: MeshingAlgorithm,
+    settings: &SculptSettings,
+) -> MeshData {
+    assert_eq!(
+        densities.len(),
+        DensityFieldSize(dimensions).density_count() as usize,
+        "density count doesn't match the dimensions"
+    );
+    cpu::mesh_cpu(densities, DensityFieldSize(dimensions), algorithm, settings)
+}
+
+/// GPU meshing work for a density field, built without a `DensityField`.
+///
+/// Insert it on any entity and the plugin dispatches it, reads it back and
+/// attaches `Mesh3d` like for a `DensityField` volume. Positions are scaled by
+/// `DensityFieldMeshSize` over `DensityFieldSize`, so give the entity a
+/// `VoxelSpacing` when the job's dimensions differ from `DensityFieldSize`.
+/// An entity that already has a mesh also needs `Remesh` to be meshed again.
+#[cfg(feature = "gpu")]
+#[derive(Bundle)]
+pub struct GpuMeshingJob {
+    buffers: SurfaceNetsBuffers,
+    region: MeshingRegion,
+    algorithm: MeshingAlgorithm,
+}
+
+/// Upload a density field and allocate the buffers to mesh it on the GPU.
+#[cfg(feature = "gpu")]
+pub fn gpu_meshing_job(
+    densities: &[f32],
+    dimensions: UVec3,
+    algorithm: MeshingAlgorithm,
+    settings: &SculptSettings,
+    storage_buffers: &mut Assets<ShaderStorageBuffer>,
+) -> GpuMeshingJob {
+    let dimensions = DensityFieldSize(dimensions);
+    assert_eq!(
+        densities.len(),
+        dimensions.density_count() as usize,
+        "density count doesn't match the dimensions"
+    );
+    let buffers = SurfaceNetsBuffers::new(
+        densities,
+        None,
+        &dimensions,
+        None,
+        settings,
+        algorithm,
+        storage_buffers,
+    );
+    GpuMeshingJob {
+        buffers,
+        region: MeshingRegion::full(dimensions),
+        algorithm,
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+    pbr::experimental::meshlet::{
+        MESHLET_DEFAULT_VERTEX_POSITION_QUANTIZATION_FACTOR, MeshToMeshletMeshConversionError,
+        MeshletMesh, MeshletMesh3d,
+    },
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+};
+
+use crate::{dirty_region::Remesh, mesh::Meshed};
+
+/// Draw the volume through Bevy's virtual geometry renderer: every remesh is
+/// converted into a `MeshletMesh` in the background and attached as
+/// `MeshletMesh3d`, for huge terrains of dense geometry.
+///
+/// The app needs Bevy's `MeshletPlugin`, and the volume a plain
+/// `StandardMaterial` without packed vertices. Its `Mesh3d` stays, for picking
+/// and physics, but only in the main world, so it's no longer drawn. Volumes
+/// split by `MaterialSubmeshes` convert only the part they draw themselves.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GenerateMeshlets {
+    /// Positions snap to 1/2^x of a centimeter. Neighbouring volumes need the
+    /// same factor to meet without cracks.
+    pub quantization_factor: u8,
+}
+
+impl Default for GenerateMeshlets {
+    fn default() -> Self {
+        Self {
+            quantization_factor: MESHLET_DEFAULT_VERTEX_POSITION_QUANTIZATION_FACTOR,
+        }
+    }
+}
+
+/// Meshlet conversion running on the `AsyncComputeTaskPool`.
+#[derive(Component)]
+pub struct MeshletTask(Task<Result<MeshletMesh, MeshToMeshletMeshConversionError>>);
+
+/// Remesh volumes that gained or lost `GenerateMeshlets`, dropping the meshlets
+/// of the latter.
+pub fn remesh_changed_meshlets(
+    mut commands: Commands,
+    added: Query<Entity, (Added<GenerateMeshlets>, With<Meshed>)>,
+    mut removed: RemovedComponents<GenerateMeshlets>,
+    meshed: Query<(), With<Meshed>>,
+) {
+    for entity in added.iter() {
+        commands.entity(entity).try_insert(Remesh);
+    }
+    for entity in removed.read().filter(|&entity| meshed.contains(entity)) {
+        commands
+            .entity(entity)
+            .try_insert(Remesh)
+            .try_remove::<(MeshletMesh3d, MeshletTask)>();
+    }
+}
+
+/// Start converting freshly built meshes of `GenerateMeshlets` volumes, moving
+/// the `Mesh` itself out of the render world. A volume remeshed while its
+/// conversion runs starts over, dropping the stale task.
+pub fn start_meshlet_tasks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query: Query<(Entity, &Mesh3d, &GenerateMeshlets), Changed<Mesh3d>>,
+) {
+    for (entity, Mesh3d(handle), meshlets) in &query {
+        // Meshes already moved out of the render world were converted
+        let fresh = meshes
+            .get(handle)
+            .is_some_and(|mesh| mesh.asset_usage.contains(RenderAssetUsages::RENDER_WORLD));
+        let Some(mut mesh) = fresh.then(|| meshes.remove(handle)).flatten() else {
+            continue;
+        };
+        let input = meshlet_input(&mesh);
+        // A new handle, so the render world drops the mesh it already had
+        mesh.asset_usage = RenderAssetUsages::MAIN_WORLD;
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert(Mesh3d(meshes.add(mesh)));
+
+        let Some(input) = input else {
+            entity_commands.try_remove::<(MeshletMesh3d, MeshletTask)>();
+            continue;
+        };
+        let factor = meshlets.quantization_factor;
+        let task = AsyncComputeTaskPool::get()
+            .spawn(async move { MeshletMesh::from_mesh(&input, factor) });
+        entity_commands.try_insert(MeshletTask(task));
+    }
+}
+
+/// Attach finished meshlet meshes, replacing the previous one in place.
+pub fn poll_meshlet_tasks(
+    mut commands: Commands,
+    mut meshlet_meshes: ResMut<Assets<MeshletMesh>>,
+    mut tasks: Query<(Entity, &mut MeshletTask, Option<&MeshletMesh3d>)>,
+) {
+    for (entity, mut task, existing) in &mut tasks {
+        let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_remove::<MeshletTask>();
+        match result {
+            Ok(meshlet_mesh) => {
+                let handle = match existing {
+                    Some(MeshletMesh3d(handle)) => {
+                        let _ = meshlet_meshes.insert(handle, meshlet_mesh);
+                        handle.clone()
+                    }
+                    None => meshlet_meshes.add(meshlet_mesh),
+                };
+                entity_commands.try_insert(MeshletMesh3d(handle));
+            }
+            Err(error) => warn!("Failed to build meshlets for {entity}: {error}"),
+        }
+    }
+}
+
+/// Copy of `mesh` with exactly the attributes meshlet conversion takes:
+/// positions, normals and UVs, zeroed if the mesh has none. `None` for meshes
+/// without triangles.
+fn meshlet_input(mesh: &Mesh) -> Option<Mesh> {
+    let indices: Vec<u32> = mesh.indices()?.iter().map(|i| i as u32).collect();
+    if indices.is_empty() {
+        return None;
+    }
+    let mut input = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::MAIN_WORLD,
+    );
+    for attribute in [Mesh::ATTRIBUTE_POSITION, Mesh::ATTRIBUTE_NORMAL] {
+        input.insert_attribute(attribute, mesh.attribute(attribute)?.clone());
+    }
+    let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(uvs) => uvs.clone(),
+        None => VertexAttributeValues::Float32x2(vec![[0.0; 2]; mesh.count_vertices()]),
+    };
+    input.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    input.insert_indices(Indices::U32(indices));
+    Some(input)
+}
@@ -0,0 +1,59 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::prelude::*;
+
+use crate::{dirty_region::Remesh, mesh::Meshed, mesh_data::MeshData};
+
+/// Also build a `WalkableSurface` whenever this volume is meshed, so edits to
+/// the terrain reach navigation meshes such as `oxidized_navigation`'s.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GenerateWalkableSurface {
+    /// Steepest walkable slope, in radians from level
+    pub max_slope: f32,
+}
+
+impl Default for GenerateWalkableSurface {
+    fn default() -> Self {
+        Self {
+            max_slope: FRAC_PI_4,
+        }
+    }
+}
+
+/// The triangles of the volume's mesh facing up no steeper than its
+/// `GenerateWalkableSurface` allows, up being world +Y under the volume's
+/// rotation. Scaled like the mesh and laid out as trimesh input.
+#[derive(Component, Clone, Debug, Default)]
+pub struct WalkableSurface {
+    pub vertices: Vec<Vec3>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl WalkableSurface {
+    pub(crate) fn new(mesh: &MeshData, up: Vec3, generate: &GenerateWalkableSurface) -> Self {
+        let walkable = sculpter_core::walkable_triangles(mesh, up, generate.max_slope);
+        Self {
+            vertices: walkable.positions,
+            indices: walkable
+                .indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+        }
+    }
+}
+
+/// Remesh volumes whose `GenerateWalkableSurface` was added or changed. Surfaces
+/// of volumes that dropped it are removed.
+pub fn remesh_changed_walkable_surfaces(
+    mut commands: Commands,
+    changed: Query<Entity, (Changed<GenerateWalkableSurface>, With<Meshed>)>,
+    mut removed: RemovedComponents<GenerateWalkableSurface>,
+) {
+    for entity in removed.read() {
+        commands.entity(entity).try_remove::<WalkableSurface>();
+    }
+    for entity in &changed {
+        commands.entity(entity).try_insert(Remesh);
+    }
+}
@@ -2,17 +2,26 @@ use bevy::{
     prelude::*,
     render::{
         render_graph,
-        render_resource::{ComputePassDescriptor, PipelineCache},
+        render_resource::{
+            BindGroup, CachedComputePipelineId, ComputePass, ComputePassDescriptor, PipelineCache,
+        },
         renderer::RenderContext,
     },
 };
 
 use crate::{
-    bind_group::SurfaceNetsBindGroups, buffers::SurfaceNetsBuffers, pipeline::SurfaceNetsPipelines,
+    amortize::{STAGE_COUNT, StageCursor, StageSchedule},
+    backend::workgroups_3d,
+    bind_group::{
+        BrushStrokeBindGroups, CsgEditBindGroups, DensityGenerationBindGroup, GeneratedDensity,
+        SurfaceNetsBindGroups,
+    },
+    buffers::SurfaceNetsBuffers,
+    dirty_region::MeshingRegion,
+    gpu_density::DensityGeneration,
+    pipeline::SurfaceNetsPipelines,
 };
 
-const WORKGROUP_SIZE: u32 = 8;
-
 #[derive(Default)]
 pub struct SurfaceNetsNode;
 
@@ -23,14 +32,6 @@ impl render_graph::Node for SurfaceNetsNode {
         render_context: &mut RenderContext<'w>,
         world: &'w World,
     ) -> std::result::Result<(), render_graph::NodeRunError> {
-        let pipeline_cache = world.resource::<PipelineCache>();
-        let pipelines = world.resource::<SurfaceNetsPipelines>();
-
-        // Query all entities with both buffers and bind groups ready
-        let mut query = world
-            .try_query::<(&SurfaceNetsBuffers, &SurfaceNetsBindGroups)>()
-            .unwrap();
-
         let mut pass =
             render_context
                 .command_encoder()
@@ -38,85 +39,183 @@ impl render_graph::Node for SurfaceNetsNode {
                     label: Some("surface_nets_compute_pass"),
                     ..default()
                 });
+        dispatch_surface_nets(&mut pass, world);
+        Ok(())
+    }
+}
 
-        // Process each entity
-        for (buffers, bind_groups) in query.iter(world) {
-            // Calculate workgroup counts for this entity's dimensions
-            let dims = buffers.dimensions.0;
-            let workgroup_count_3d = (
-                (dims.x + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
-                (dims.y + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
-                (dims.z + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
-            );
-            let cell_count = buffers.dimensions.cell_count();
-            let workgroup_count_1d = (cell_count + 255) / 256;
-
-            // Stage 1: Generate Vertices
-            if let Some(pipeline) =
-                pipeline_cache.get_compute_pipeline(pipelines.generate_vertices_pipeline)
-            {
-                pass.set_bind_group(0, &bind_groups.generate_vertices, &[]);
-                pass.set_pipeline(pipeline);
-                pass.dispatch_workgroups(
-                    workgroup_count_3d.0,
-                    workgroup_count_3d.1,
-                    workgroup_count_3d.2,
-                );
-            }
+/// One surface's share of the pass: its bind groups plus the kernels and
+/// workgroup counts of each stage.
+struct SurfaceDispatch<'w> {
+    bind_groups: &'w SurfaceNetsBindGroups,
+    // Kernel and workgroup count per stage, in dispatch order
+    stages: [(CachedComputePipelineId, (u32, u32, u32)); STAGE_COUNT as usize],
+    // Which stages run this frame
+    runs: [bool; STAGE_COUNT as usize],
+}
 
-            // Stage 2: Prefix Sum (vertices)
-            if let Some(pipeline) =
-                pipeline_cache.get_compute_pipeline(pipelines.prefix_sum_pipeline)
-            {
-                pass.set_bind_group(0, &bind_groups.prefix_sum_vertices, &[]);
-                pass.set_pipeline(pipeline);
-                pass.dispatch_workgroups(workgroup_count_1d, 1, 1);
-            }
+impl SurfaceDispatch<'_> {
+    fn bind_group(&self, stage: usize) -> &BindGroup {
+        let bind_groups = self.bind_groups;
+        [
+            &bind_groups.generate_vertices,
+            &bind_groups.prefix_sum_vertices,
+            &bind_groups.compact_vertices,
+            &bind_groups.generate_faces,
+            &bind_groups.prefix_sum_faces,
+            &bind_groups.compact_faces,
+            &bind_groups.vertex_normals,
+        ][stage]
+    }
+}
+
+/// Record all seven stages for every surface with buffers and bind groups ready,
+/// after generating the densities of `GpuDensity` volumes that need it and
+/// applying their CSG edits.
+///
+/// Every surface (each volume, and each surface of a multi-surface volume) goes
+/// into the one pass, stage by stage, so a kernel is bound once per stage and
+/// shared by all surfaces using it rather than once per surface.
+fn dispatch_surface_nets(pass: &mut ComputePass, world: &World) {
+    let pipeline_cache = world.resource::<PipelineCache>();
+    let pipelines = world.resource::<SurfaceNetsPipelines>();
 
-            // Stage 3: Compact Vertices
-            if let Some(pipeline) =
-                pipeline_cache.get_compute_pipeline(pipelines.compact_vertices_pipeline)
-            {
-                pass.set_bind_group(0, &bind_groups.compact_vertices, &[]);
+    // Query all entities with both buffers and bind groups ready
+    let mut query = world
+        .try_query::<(
+            &SurfaceNetsBuffers,
+            &SurfaceNetsBindGroups,
+            &MeshingRegion,
+            Has<StageSchedule>,
+            Option<&StageCursor>,
+            Option<&DensityGeneration>,
+            Option<&GeneratedDensity>,
+        )>()
+        .unwrap();
+
+    // Stage 0: Generate Density, before anything reads the density buffers
+    let mut generations = world
+        .try_query::<(&SurfaceNetsBuffers, &DensityGenerationBindGroup)>()
+        .unwrap();
+    for (buffers, bind_group) in generations.iter(world) {
+        let Some(pipeline) = pipeline_cache.get_compute_pipeline(bind_group.1) else {
+            continue;
+        };
+        let workgroups = workgroups_3d(buffers.dimensions.0);
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group.0, &[]);
+        pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+    }
+
+    // Stage 0b: CSG edits, composited into the generated densities
+    if let Some(pipeline) = pipeline_cache.get_compute_pipeline(pipelines.csg_pipeline) {
+        let mut edits = world.try_query::<&CsgEditBindGroups>().unwrap();
+        let mut bound = false;
+        for (bind_group, workgroups) in edits.iter(world).flat_map(|edits| &edits.0) {
+            if !bound {
                 pass.set_pipeline(pipeline);
-                pass.dispatch_workgroups(workgroup_count_1d, 1, 1);
+                bound = true;
             }
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+        }
+    }
 
-            // Stage 4: Generate Faces
-            if let Some(pipeline) =
-                pipeline_cache.get_compute_pipeline(pipelines.generate_faces_pipeline)
-            {
-                pass.set_bind_group(0, &bind_groups.generate_faces, &[]);
-                pass.set_pipeline(pipeline);
-                pass.dispatch_workgroups(
-                    workgroup_count_3d.0,
-                    workgroup_count_3d.1,
-                    workgroup_count_3d.2,
-                );
+    // Stage 0c: Sculpt brushes, on top of generation and CSG edits
+    if let Some(snapshot_pipeline) =
+        pipeline_cache.get_compute_pipeline(pipelines.brush_snapshot_pipeline)
+    {
+        let mut strokes = world.try_query::<&BrushStrokeBindGroups>().unwrap();
+        for stroke in strokes.iter(world).flat_map(|strokes| &strokes.0) {
+            let Some(pipeline) = pipeline_cache.get_compute_pipeline(stroke.pipeline) else {
+                continue;
+            };
+            pass.set_bind_group(0, &stroke.bind_group, &[]);
+            if let Some(workgroups) = stroke.snapshot {
+                pass.set_pipeline(snapshot_pipeline);
+                pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
             }
+            let workgroups = stroke.workgroups;
+            pass.set_pipeline(pipeline);
+            pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
+        }
+    }
 
-            // Stage 5: Prefix Sum (faces)
-            if let Some(pipeline) =
-                pipeline_cache.get_compute_pipeline(pipelines.prefix_sum_pipeline)
-            {
-                pass.set_bind_group(0, &bind_groups.prefix_sum_faces, &[]);
-                pass.set_pipeline(pipeline);
-                let max_faces = cell_count * 3;
-                let face_workgroups = (max_faces + 255) / 256;
-                pass.dispatch_workgroups(face_workgroups, 1, 1);
+    let mut surfaces: Vec<SurfaceDispatch> = query
+        .iter(world)
+        // Generated volumes wait until their densities are up to date
+        .filter(|(.., generation, generated)| match generation {
+            Some(generation) => generated.is_some_and(|generated| &generated.0 == *generation),
+            None => true,
+        })
+        .map(|(buffers, bind_groups, region, amortized, cursor, ..)| {
+            // Amortized volumes only dispatch the stages scheduled for this frame
+            let runs = std::array::from_fn(|stage| match cursor {
+                Some(cursor) if amortized => cursor.runs(stage as u32 + 1),
+                _ => !amortized,
+            });
+            SurfaceDispatch {
+                bind_groups,
+                stages: surface_stages(buffers, region, pipelines),
+                runs,
             }
+        })
+        .collect();
+
+    // Group surfaces sharing kernels so each stage switches pipeline as little as possible
+    surfaces.sort_by_key(|surface| (surface.stages[0].0.id(), surface.stages[3].0.id()));
 
-            // Stage 6: Compact Faces
-            if let Some(pipeline) =
-                pipeline_cache.get_compute_pipeline(pipelines.compact_faces_pipeline)
-            {
-                pass.set_bind_group(0, &bind_groups.compact_faces, &[]);
+    for stage in 0..STAGE_COUNT as usize {
+        let mut bound = None;
+        for surface in surfaces.iter().filter(|surface| surface.runs[stage]) {
+            let (pipeline_id, workgroups) = surface.stages[stage];
+            let Some(pipeline) = pipeline_cache.get_compute_pipeline(pipeline_id) else {
+                continue;
+            };
+            if bound != Some(pipeline_id) {
                 pass.set_pipeline(pipeline);
-                let max_faces = cell_count * 3;
-                let face_workgroups = (max_faces + 255) / 256;
-                pass.dispatch_workgroups(face_workgroups, 1, 1);
+                bound = Some(pipeline_id);
             }
+            pass.set_bind_group(0, surface.bind_group(stage), &[]);
+            pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
         }
-        Ok(())
     }
 }
+
+/// Kernels and workgroup counts of the seven stages for one surface.
+fn surface_stages(
+    buffers: &SurfaceNetsBuffers,
+    region: &MeshingRegion,
+    pipelines: &SurfaceNetsPipelines,
+) -> [(CachedComputePipelineId, (u32, u32, u32)); STAGE_COUNT as usize] {
+    // Stages 1 and 4 come from the volume's backend
+    let backend = buffers.algorithm.backend();
+    let [generate_vertices_pipeline, generate_faces_pipeline] =
+        pipelines.backends[&buffers.algorithm].pipelines(buffers.density_texture.is_some());
+    let [vertex_workgroups_3d, face_workgroups_3d] = backend.workgroups(region.size());
+
+    // The 1D stages cover every slot
+    let vertex_workgroups_1d = (buffers.vertex_slots().div_ceil(256), 1, 1);
+    let face_workgroups_1d = (buffers.face_slots().div_ceil(256), 1, 1);
+    let vertex_normals_pipeline = match buffers.density_texture {
+        Some(_) => pipelines.vertex_normals_texture_pipeline,
+        None => pipelines.vertex_normals_pipeline,
+    };
+
+    [
+        // Stage 1: Generate Vertices
+        (generate_vertices_pipeline, vertex_workgroups_3d),
+        // Stage 2: Prefix Sum (vertices)
+        (pipelines.prefix_sum_pipeline, vertex_workgroups_1d),
+        // Stage 3: Compact Vertices
+        (pipelines.compact_vertices_pipeline, vertex_workgroups_1d),
+        // Stage 4: Generate Faces
+        (generate_faces_pipeline, face_workgroups_3d),
+        // Stage 5: Prefix Sum (faces)
+        (pipelines.prefix_sum_pipeline, face_workgroups_1d),
+        // Stage 6: Compact Faces
+        (pipelines.compact_faces_pipeline, face_workgroups_1d),
+        // Stage 7: Vertex Normals
+        (vertex_normals_pipeline, vertex_workgroups_1d),
+    ]
+}
@@ -0,0 +1,96 @@
+use bevy::{
+    mesh::{MeshVertexAttribute, MeshVertexBufferLayoutRef, VertexAttributeValues, VertexFormat},
+    pbr::{ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline},
+    prelude::*,
+    render::render_resource::{
+        AsBindGroup, RenderPipelineDescriptor, SpecializedMeshPipelineError,
+    },
+    shader::ShaderRef,
+};
+
+const PACKED_VERTEX_SHADER: &str = "shaders/packed_vertex.wgsl";
+
+/// Per-vertex position as half floats x, y, z and 1, two to a `u32`, replacing
+/// `Mesh::ATTRIBUTE_POSITION` with `SculptSettings::packed_vertices`.
+pub const ATTRIBUTE_PACKED_POSITION: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_PackedPosition", 0x5c17_0004, VertexFormat::Uint32x2);
+
+/// Per-vertex octahedral encoded normal, replacing `Mesh::ATTRIBUTE_NORMAL` with
+/// `SculptSettings::packed_vertices`.
+pub const ATTRIBUTE_PACKED_NORMAL: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_PackedNormal", 0x5c17_0005, VertexFormat::Unorm16x2);
+
+/// `StandardMaterial` drawing meshes built with `SculptSettings::packed_vertices`,
+/// decoding their positions and normals in its vertex shader. Volumes with
+/// packed vertices and no material get a grey one, or give them your own
+/// `MeshMaterial3d<PackedVertexMaterial>`.
+pub type PackedVertexMaterial = ExtendedMaterial<StandardMaterial, PackedVertexExtension>;
+
+/// Vertex decoding part of a `PackedVertexMaterial`.
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone, Default)]
+pub struct PackedVertexExtension {}
+
+impl MaterialExtension for PackedVertexExtension {
+    fn vertex_shader() -> ShaderRef {
+        PACKED_VERTEX_SHADER.into()
+    }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        PACKED_VERTEX_SHADER.into()
+    }
+
+    // The same locations serve the main pass and the prepasses
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let mut attributes = vec![
+            ATTRIBUTE_PACKED_POSITION.at_shader_location(0),
+            ATTRIBUTE_PACKED_NORMAL.at_shader_location(1),
+        ];
+        for (attribute, location) in [
+            (Mesh::ATTRIBUTE_UV_0, 2),
+            (Mesh::ATTRIBUTE_UV_1, 3),
+            (Mesh::ATTRIBUTE_TANGENT, 4),
+            (Mesh::ATTRIBUTE_COLOR, 5),
+        ] {
+            if layout.0.contains(attribute) {
+                attributes.push(attribute.at_shader_location(location));
+            }
+        }
+        descriptor.vertex.buffers = vec![layout.0.get_layout(&attributes)?];
+        Ok(())
+    }
+}
+
+/// Swap the positions and normals of `mesh` for their packed attributes.
+pub(crate) fn pack_vertices(mesh: &mut Mesh) {
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.remove_attribute(Mesh::ATTRIBUTE_POSITION)
+    {
+        let packed: Vec<[u32; 2]> = positions
+            .iter()
+            .map(|&[x, y, z]| {
+                [
+                    sculpter_core::pack_half2(x, y),
+                    sculpter_core::pack_half2(z, 1.0),
+                ]
+            })
+            .collect();
+        mesh.insert_attribute(ATTRIBUTE_PACKED_POSITION, packed);
+    }
+    if let Some(VertexAttributeValues::Float32x3(normals)) =
+        mesh.remove_attribute(Mesh::ATTRIBUTE_NORMAL)
+    {
+        let packed: Vec<[u16; 2]> = normals
+            .iter()
+            .map(|&normal| sculpter_core::octahedral_encode(Vec3::from_array(normal)))
+            .collect();
+        mesh.insert_attribute(
+            ATTRIBUTE_PACKED_NORMAL,
+            VertexAttributeValues::Unorm16x2(packed),
+        );
+    }
+}
@@ -0,0 +1,670 @@
+use std::{
+    fmt, fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{
+    platform::collections::HashMap,
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
+};
+
+use crate::{
+    DensityField, DensityFieldSize,
+    chunk::Chunk,
+    dirty_region::{DensityEdited, DensityFieldDirtyRegion},
+    material::MaterialField,
+};
+
+const MAGIC: &[u8; 4] = b"SCHK";
+const VERSION: u32 = 1;
+const HAS_MATERIALS: u32 = 1;
+
+/// A chunk read by `load_chunk`, ready to spawn as `(chunk, field)` plus its
+/// materials if it had any.
+#[derive(Clone, Debug)]
+pub struct SavedChunk {
+    pub chunk: Chunk,
+    pub field: DensityField,
+    pub materials: Option<MaterialField>,
+}
+
+/// Save the chunk to this file with `save_chunk` in the background, once it
+/// has a `DensityField`. Removed when the save starts, so insert it again to
+/// save again; `ChunkSaved` or `ChunkIoFailed` is triggered when it's done.
+#[derive(Component, Clone, Debug)]
+pub struct SaveChunk(pub PathBuf);
+
+/// Load a chunk saved with `save_chunk` from this file into the entity in the
+/// background, inserting its `Chunk`, `DensityField` and `MaterialField`.
+/// Removed when the load starts; `ChunkLoaded` or `ChunkIoFailed` is triggered
+/// when it's done.
+#[derive(Component, Clone, Debug)]
+pub struct LoadChunk(pub PathBuf);
+
+/// `SaveChunk` running on the `IoTaskPool`.
+#[derive(Component)]
+pub struct ChunkSaveTask(Task<(PathBuf, io::Result<()>)>);
+
+/// Marks a chunk edited since its last save: flagged with a
+/// `DensityFieldDirtyRegion` or given new materials. Cleared when a save of it
+/// starts.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct UnsavedEdits;
+
+/// Save chunks with `UnsavedEdits` to `directory` in the background, one file
+/// per coordinate, while this resource is present.
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkAutosave {
+    pub directory: PathBuf,
+    /// Time between saves, `None` to not save on a timer
+    pub interval: Option<Duration>,
+    /// Save chunks as they're despawned or streamed out for another coordinate
+    pub on_unload: bool,
+    /// Save chunks when the app exits, waiting for every save to finish
+    pub on_exit: bool,
+}
+
+impl ChunkAutosave {
+    /// Save every minute, on unload and on exit.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            interval: Some(Duration::from_secs(60)),
+            on_unload: true,
+            on_exit: true,
+        }
+    }
+
+    /// File chunk `coord` is saved to, and can be loaded from with `LoadChunk`.
+    pub fn path(&self, coord: IVec3) -> PathBuf {
+        self.directory
+            .join(format!("{}_{}_{}.schk", coord.x, coord.y, coord.z))
+    }
+}
+
+/// A save of a chunk that was unloaded, whose events are triggered on an entity
+/// of its own, despawned right after.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct UnloadedChunkSave;
+
+/// The saves running for each file. Saves of a file are numbered as they're
+/// taken, and a save finishing after a newer one was moved into place is
+/// dropped rather than overwriting it. A file's entry goes once none of its
+/// saves are running.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct ChunkSaves(Arc<Mutex<HashMap<PathBuf, FileSaves>>>);
+
+#[derive(Clone, Copy, Default)]
+struct FileSaves {
+    // Number of the last save taken and of the last one moved into place
+    taken: u64,
+    written: u64,
+    running: usize,
+}
+
+impl ChunkSaves {
+    /// Number the next save of `path`.
+    fn start(&self, path: &Path) -> u64 {
+        let mut files = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let file = files.entry(path.to_path_buf()).or_default();
+        file.taken += 1;
+        file.running += 1;
+        file.taken
+    }
+
+    /// Finish save `save` of `path` with `commit`, told whether it's newer
+    /// than the save last moved into place.
+    fn finish(
+        &self,
+        path: &Path,
+        save: u64,
+        commit: impl FnOnce(bool) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut files = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let file = files.entry(path.to_path_buf()).or_default();
+        let newest = save > file.written;
+        let result = commit(newest);
+        if newest && result.is_ok() {
+            file.written = save;
+        }
+        file.running = file.running.saturating_sub(1);
+        if file.running == 0 {
+            files.remove(path);
+        }
+        result
+    }
+}
+
+/// `LoadChunk` running on the `IoTaskPool`.
+#[derive(Component)]
+pub struct ChunkLoadTask(Task<(PathBuf, Result<SavedChunk, ChunkLoadError>)>);
+
+/// Triggered on a chunk once its `SaveChunk` file is written.
+#[derive(EntityEvent, Clone, Debug)]
+pub struct ChunkSaved {
+    pub entity: Entity,
+    pub path: PathBuf,
+}
+
+/// Triggered on an entity once its `LoadChunk` file is read into it.
+#[derive(EntityEvent, Clone, Debug)]
+pub struct ChunkLoaded {
+    pub entity: Entity,
+    pub coord: IVec3,
+}
+
+/// Triggered on an entity whose `SaveChunk` or `LoadChunk` failed.
+#[derive(EntityEvent, Clone, Debug)]
+pub struct ChunkIoFailed {
+    pub entity: Entity,
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Why a chunk couldn't be loaded.
+#[derive(Debug)]
+pub enum ChunkLoadError {
+    Io(io::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ChunkLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read chunk: {error}"),
+            Self::Invalid(reason) => write!(f, "invalid chunk: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ChunkLoadError {}
+
+impl From<io::Error> for ChunkLoadError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Write a chunk's densities and materials, run-length encoded and compressed
+/// with zstd, so edited worlds can be saved and restored with `load_chunk`. The
+/// field and materials hold a sample per point of `dimensions`.
+///
+/// The format is little endian: magic `SCHK`, format version (`u32`, 1), the
+/// chunk coordinate (`i32` × 3), the dimensions (`u32` × 3) and flags (`u32`,
+/// bit 0 set with materials), then a zstd frame holding the densities' and
+/// then the materials' runs, each a length (`u32`) and value (`f32` or `u32`).
+pub fn save_chunk(
+    mut writer: impl Write,
+    chunk: Chunk,
+    dimensions: DensityFieldSize,
+    field: &DensityField,
+    materials: Option<&MaterialField>,
+) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    for component in chunk.to_array() {
+        writer.write_all(&component.to_le_bytes())?;
+    }
+    for component in dimensions.to_array() {
+        writer.write_all(&component.to_le_bytes())?;
+    }
+    let flags = if materials.is_some() {
+        HAS_MATERIALS
+    } else {
+        0
+    };
+    writer.write_all(&flags.to_le_bytes())?;
+
+    let mut runs = Vec::new();
+    encode_runs(field.iter().map(|density| density.to_bits()), &mut runs);
+    if let Some(materials) = materials {
+        encode_runs(materials.iter().copied(), &mut runs);
+    }
+    writer.write_all(&zstd::encode_all(
+        runs.as_slice(),
+        zstd::DEFAULT_COMPRESSION_LEVEL,
+    )?)
+}
+
+/// Read a chunk written by `save_chunk` with the current `dimensions`. Files
+/// saved with other dimensions are rejected before anything is decompressed.
+pub fn load_chunk(
+    mut reader: impl Read,
+    dimensions: DensityFieldSize,
+) -> Result<SavedChunk, ChunkLoadError> {
+    let mut header = [0; 36];
+    reader.read_exact(&mut header)?;
+    let word = |index: usize| u32::from_le_bytes(header[index * 4..][..4].try_into().unwrap());
+    if &header[..4] != MAGIC {
+        return Err(ChunkLoadError::Invalid("not a saved chunk"));
+    }
+    if word(1) != VERSION {
+        return Err(ChunkLoadError::Invalid("unsupported version"));
+    }
+    let chunk = Chunk(ivec3(word(2) as i32, word(3) as i32, word(4) as i32));
+    if uvec3(word(5), word(6), word(7)) != dimensions.0 {
+        return Err(ChunkLoadError::Invalid(
+            "saved with a different DensityFieldSize",
+        ));
+    }
+    let count = dimensions.density_count() as usize;
+    let has_materials = word(8) & HAS_MATERIALS != 0;
+
+    // Every run holds at least one value, so anything past the longest the
+    // runs can be is trailing data, and is left compressed
+    let fields = if has_materials { 2 } else { 1 };
+    let limit = count as u64 * fields * 8;
+    let mut runs = Vec::new();
+    zstd::Decoder::new(reader)?
+        .take(limit + 1)
+        .read_to_end(&mut runs)?;
+    let mut input = runs.as_slice();
+    let densities = decode_runs(&mut input, count)?;
+    let materials = if has_materials {
+        Some(MaterialField(decode_runs(&mut input, count)?))
+    } else {
+        None
+    };
+    if !input.is_empty() {
+        return Err(ChunkLoadError::Invalid("trailing data"));
+    }
+
+    Ok(SavedChunk {
+        chunk,
+        field: DensityField(densities.into_iter().map(f32::from_bits).collect()),
+        materials,
+    })
+}
+
+/// Append the runs of equal values, each a length and the value.
+fn encode_runs(values: impl Iterator<Item = u32>, output: &mut Vec<u8>) {
+    let mut run: Option<(u32, u32)> = None;
+    for value in values {
+        match &mut run {
+            Some((length, run_value)) if *run_value == value && *length < u32::MAX => {
+                *length += 1;
+            }
+            _ => {
+                if let Some((length, run_value)) = run {
+                    output.extend_from_slice(&length.to_le_bytes());
+                    output.extend_from_slice(&run_value.to_le_bytes());
+                }
+                run = Some((1, value));
+            }
+        }
+    }
+    if let Some((length, value)) = run {
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Read runs off the front of `input` until they hold `count` values.
+fn decode_runs(input: &mut &[u8], count: usize) -> Result<Vec<u32>, ChunkLoadError> {
+    let mut values = Vec::new();
+    while values.len() < count {
+        let Some((run, rest)) = input.split_first_chunk::<8>() else {
+            return Err(ChunkLoadError::Invalid("unexpected end of data"));
+        };
+        *input = rest;
+        let length = u32::from_le_bytes(run[..4].try_into().unwrap()) as usize;
+        let value = u32::from_le_bytes(run[4..].try_into().unwrap());
+        if length > count - values.len() {
+            return Err(ChunkLoadError::Invalid(
+                "more values than the dimensions hold",
+            ));
+        }
+        values.extend(std::iter::repeat_n(value, length));
+    }
+    Ok(values)
+}
+
+/// Start the requested saves and loads on the `IoTaskPool`. Saves take a copy
+/// of the chunk, so it can be edited while it's written.
+pub fn start_chunk_io(
+    mut commands: Commands,
+    saves: Query<
+        (
+            Entity,
+            &SaveChunk,
+            &Chunk,
+            &DensityField,
+            Option<&MaterialField>,
+        ),
+        // Waiting for the previous save, which writes the same file
+        Without<ChunkSaveTask>,
+    >,
+    loads: Query<(Entity, &LoadChunk)>,
+    dimensions: Res<DensityFieldSize>,
+    chunk_saves: Res<ChunkSaves>,
+) {
+    for (entity, SaveChunk(path), &chunk, field, materials) in &saves {
+        let task = spawn_save(
+            &chunk_saves,
+            path.clone(),
+            chunk,
+            *dimensions,
+            field,
+            materials,
+        );
+        commands
+            .entity(entity)
+            .try_remove::<(SaveChunk, UnsavedEdits)>()
+            .try_insert(ChunkSaveTask(task));
+    }
+    for (entity, LoadChunk(path)) in &loads {
+        let path = path.clone();
+        let dimensions = *dimensions;
+        let task = IoTaskPool::get().spawn(async move {
+            let result = fs::File::open(&path)
+                .map_err(ChunkLoadError::from)
+                .and_then(|file| load_chunk(BufReader::new(file), dimensions));
+            (path, result)
+        });
+        commands
+            .entity(entity)
+            .try_remove::<LoadChunk>()
+            .try_insert(ChunkLoadTask(task));
+    }
+}
+
+/// Finish saves and loads, inserting loaded chunks and triggering their events.
+pub fn poll_chunk_io(
+    mut commands: Commands,
+    mut saves: Query<(Entity, &mut ChunkSaveTask, Has<UnloadedChunkSave>)>,
+    mut loads: Query<(Entity, &mut ChunkLoadTask)>,
+) {
+    for (entity, mut task, unloaded) in &mut saves {
+        let Some((path, result)) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        commands.entity(entity).try_remove::<ChunkSaveTask>();
+        match result {
+            Ok(()) => commands.trigger(ChunkSaved { entity, path }),
+            Err(error) => commands.trigger(ChunkIoFailed {
+                entity,
+                path,
+                error: error.to_string(),
+            }),
+        }
+        if unloaded {
+            commands.entity(entity).try_despawn();
+        }
+    }
+
+    for (entity, mut task) in &mut loads {
+        let Some((path, result)) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_remove::<ChunkLoadTask>();
+        match result {
+            Ok(saved) => {
+                entity_commands.try_insert((saved.chunk, saved.field));
+                if let Some(materials) = saved.materials {
+                    entity_commands.try_insert(materials);
+                }
+                commands.trigger(ChunkLoaded {
+                    entity,
+                    coord: *saved.chunk,
+                });
+            }
+            Err(error) => commands.trigger(ChunkIoFailed {
+                entity,
+                path,
+                error: error.to_string(),
+            }),
+        }
+    }
+}
+
+/// Flag chunks edited this frame, including the neighbours their edits were
+/// copied into and those sculpted on the GPU, as having `UnsavedEdits`.
+pub fn track_unsaved_edits(
+    mut commands: Commands,
+    dirty: Query<Entity, (With<Chunk>, Changed<DensityFieldDirtyRegion>)>,
+    materials: Query<(Entity, Ref<MaterialField>), With<Chunk>>,
+    chunks: Query<(), With<Chunk>>,
+    mut sculpted: MessageReader<DensityEdited>,
+) {
+    let repainted = materials
+        .iter()
+        .filter(|(_, materials)| materials.is_changed() && !materials.is_added())
+        .map(|(entity, _)| entity);
+    let sculpted = sculpted
+        .read()
+        .map(|sculpted| sculpted.entity)
+        .filter(|&entity| chunks.contains(entity));
+    for entity in dirty.iter().chain(repainted).chain(sculpted) {
+        commands.entity(entity).try_insert(UnsavedEdits);
+    }
+}
+
+/// Request saves of every chunk with `UnsavedEdits` once per
+/// `ChunkAutosave::interval`.
+pub fn autosave_chunks(
+    mut commands: Commands,
+    autosave: Option<Res<ChunkAutosave>>,
+    edited: Query<(Entity, &Chunk), (With<UnsavedEdits>, Without<SaveChunk>)>,
+    time: Res<Time<Real>>,
+    mut last_save: Local<Duration>,
+) {
+    let Some((autosave, interval)) = autosave
+        .as_ref()
+        .and_then(|autosave| Some((autosave, autosave.interval?)))
+    else {
+        return;
+    };
+    if time.elapsed().saturating_sub(*last_save) < interval {
+        return;
+    }
+    *last_save = time.elapsed();
+    for (entity, &Chunk(coord)) in &edited {
+        commands
+            .entity(entity)
+            .try_insert(SaveChunk(autosave.path(coord)));
+    }
+}
+
+/// Save a chunk with `UnsavedEdits` as it's despawned or streamed out, when
+/// `ChunkAutosave::on_unload` is set. The save gets an entity of its own.
+pub fn save_unloaded_chunk(
+    replaced: On<Replace, Chunk>,
+    mut commands: Commands,
+    autosave: Option<Res<ChunkAutosave>>,
+    chunks: Query<(&Chunk, &DensityField, Option<&MaterialField>), With<UnsavedEdits>>,
+    dimensions: Res<DensityFieldSize>,
+    chunk_saves: Res<ChunkSaves>,
+) {
+    let Some(autosave) = autosave.filter(|autosave| autosave.on_unload) else {
+        return;
+    };
+    let Ok((&chunk, field, materials)) = chunks.get(replaced.entity) else {
+        return;
+    };
+    let task = spawn_save(
+        &chunk_saves,
+        autosave.path(*chunk),
+        chunk,
+        *dimensions,
+        field,
+        materials,
+    );
+    commands.spawn((ChunkSaveTask(task), UnloadedChunkSave));
+    commands
+        .entity(replaced.entity)
+        .try_remove::<UnsavedEdits>();
+}
+
+/// Save every chunk with `UnsavedEdits` when the app exits, if
+/// `ChunkAutosave::on_exit` is set, blocking until these and the saves already
+/// running are written.
+pub fn save_chunks_on_exit(
+    mut exit: MessageReader<AppExit>,
+    autosave: Option<Res<ChunkAutosave>>,
+    mut running: Query<&mut ChunkSaveTask>,
+    edited: Query<(&Chunk, &DensityField, Option<&MaterialField>), With<UnsavedEdits>>,
+    dimensions: Res<DensityFieldSize>,
+    chunk_saves: Res<ChunkSaves>,
+) {
+    if exit.read().count() == 0 {
+        return;
+    }
+    let Some(autosave) = autosave.filter(|autosave| autosave.on_exit) else {
+        return;
+    };
+    let saves: Vec<_> = edited
+        .iter()
+        .map(|(&chunk, field, materials)| {
+            spawn_save(
+                &chunk_saves,
+                autosave.path(*chunk),
+                chunk,
+                *dimensions,
+                field,
+                materials,
+            )
+        })
+        .collect();
+    let running = running.iter_mut().map(|mut task| block_on(&mut task.0));
+    for (path, result) in running.chain(saves.into_iter().map(block_on)) {
+        if let Err(error) = result {
+            error!("Failed to save chunk to {}: {error}", path.display());
+        }
+    }
+}
+
+/// Write a copy of the chunk on the `IoTaskPool`.
+fn spawn_save(
+    saves: &ChunkSaves,
+    path: PathBuf,
+    chunk: Chunk,
+    dimensions: DensityFieldSize,
+    field: &DensityField,
+    materials: Option<&MaterialField>,
+) -> Task<(PathBuf, io::Result<()>)> {
+    let (field, materials) = (field.clone(), materials.cloned());
+    let saves = saves.clone();
+    let save = saves.start(&path);
+    IoTaskPool::get().spawn(async move {
+        let partial = path.with_extension(format!("{save}.partial"));
+        let written = write_chunk_file(&partial, chunk, dimensions, &field, materials.as_ref());
+        // Moved into place so a crash mid-write leaves the previous file
+        // intact, unless a newer save got there first
+        let result = saves.finish(&path, save, |newest| {
+            written?;
+            if newest {
+                fs::rename(&partial, &path)
+            } else {
+                fs::remove_file(&partial)
+            }
+        });
+        (path, result)
+    })
+}
+
+/// Save a chunk to `path`, creating its directory.
+fn write_chunk_file(
+    path: &Path,
+    chunk: Chunk,
+    dimensions: DensityFieldSize,
+    field: &DensityField,
+    materials: Option<&MaterialField>,
+) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    save_chunk(&mut writer, chunk, dimensions, field, materials)?;
+    writer.into_inner()?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMENSIONS: DensityFieldSize = DensityFieldSize(uvec3(5, 3, 4));
+
+    fn saved(chunk: Chunk, materials: Option<&MaterialField>) -> (DensityField, Vec<u8>) {
+        let count = DIMENSIONS.density_count() as usize;
+        // Long runs of air and ground, with a few distinct values and a negative zero
+        let field = DensityField(
+            (0..count)
+                .map(|i| match i {
+                    0..20 => 1.0,
+                    20 => -0.0,
+                    21..25 => i as f32 * -0.25,
+                    _ => -1.0,
+                })
+                .collect(),
+        );
+        let mut file = Vec::new();
+        save_chunk(&mut file, chunk, DIMENSIONS, &field, materials).unwrap();
+        (field, file)
+    }
+
+    #[test]
+    fn chunk_round_trip() {
+        let chunk = Chunk(ivec3(-3, 0, 7));
+        let materials = MaterialField((0..60).map(|i| i / 25).collect());
+        let (field, file) = saved(chunk, Some(&materials));
+
+        let loaded = load_chunk(file.as_slice(), DIMENSIONS).unwrap();
+        assert_eq!(loaded.chunk, chunk);
+        let bits = |field: &DensityField| field.iter().map(|d| d.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&loaded.field), bits(&field));
+        assert_eq!(
+            loaded.materials.map(|materials| materials.0),
+            Some(materials.0)
+        );
+    }
+
+    #[test]
+    fn chunk_round_trip_without_materials() {
+        let (field, file) = saved(Chunk(IVec3::ZERO), None);
+
+        let loaded = load_chunk(file.as_slice(), DIMENSIONS).unwrap();
+        assert_eq!(loaded.field.0, field.0);
+        assert!(loaded.materials.is_none());
+    }
+
+    #[test]
+    fn load_rejects_invalid_chunks() {
+        let (_, file) = saved(Chunk(IVec3::ONE), None);
+
+        let mut wrong_magic = file.clone();
+        wrong_magic[0] = b'X';
+        assert!(matches!(
+            load_chunk(wrong_magic.as_slice(), DIMENSIONS),
+            Err(ChunkLoadError::Invalid(_))
+        ));
+        assert!(matches!(
+            load_chunk(file.as_slice(), DensityFieldSize(uvec3(6, 3, 4))),
+            Err(ChunkLoadError::Invalid(_))
+        ));
+        assert!(load_chunk(&file[..30], DIMENSIONS).is_err());
+    }
+
+    #[test]
+    fn load_stops_decompressing_past_the_dimensions() {
+        let (_, file) = saved(Chunk(IVec3::ONE), None);
+
+        // Far more data than the header's dimensions can hold
+        let mut bomb = file[..36].to_vec();
+        bomb.extend(zstd::encode_all([0; 1 << 20].as_slice(), 0).unwrap());
+        assert!(matches!(
+            load_chunk(bomb.as_slice(), DIMENSIONS),
+            Err(ChunkLoadError::Invalid(_))
+        ));
+    }
+}
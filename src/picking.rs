@@ -0,0 +1,128 @@
+use bevy::{
+    picking::mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize, gpu_density::GpuDensity,
+    units::VoxelSpacing,
+};
+
+/// Where the primary window's cursor meets the nearest volume's surface, for
+/// placing brushes, updated every frame after transforms propagate.
+///
+/// The ray comes from the camera with a `SculptCursorCamera`, or the first
+/// active camera when none has one, and is cast against the `DensityField` of
+/// every volume. Brush strokes reach the field once they are read back from
+/// the GPU, a few frames after they are applied.
+///
+/// `GpuDensity` volumes have no field and are picked on their mesh instead,
+/// so their strokes only show up once they are remeshed, and the hit lies on
+/// the mesh's triangles rather than the interpolated surface.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct SculptCursor {
+    /// Farthest surface picked, in metres from the camera
+    pub max_distance: f32,
+    /// `None` while the cursor is outside the window or over no surface
+    pub hit: Option<CursorHit>,
+}
+
+impl Default for SculptCursor {
+    fn default() -> Self {
+        Self {
+            max_distance: 1000.0,
+            hit: None,
+        }
+    }
+}
+
+/// The surface under the cursor, in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CursorHit {
+    pub volume: Entity,
+    pub position: Vec3,
+    /// Out of the solid
+    pub normal: Vec3,
+    /// From the camera, in metres
+    pub distance: f32,
+}
+
+/// The camera `SculptCursor` picks through.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct SculptCursorCamera;
+
+/// Cast the cursor's ray into every volume and keep the nearest hit.
+pub fn update_sculpt_cursor(
+    mut cursor: ResMut<SculptCursor>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform, Has<SculptCursorCamera>)>,
+    volumes: Query<(
+        Entity,
+        &DensityField,
+        &GlobalTransform,
+        Option<&VoxelSpacing>,
+    )>,
+    gpu_volumes: Query<(), (With<GpuDensity>, Without<DensityField>)>,
+    mut mesh_ray_cast: MeshRayCast,
+    dimensions: Res<DensityFieldSize>,
+    mesh_size: Res<DensityFieldMeshSize>,
+) {
+    let ray = windows
+        .single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .and_then(|position| {
+            let (camera, camera_transform, _) = cameras
+                .iter()
+                .find(|(_, _, marked)| *marked)
+                .or_else(|| cameras.iter().find(|(camera, ..)| camera.is_active))?;
+            camera.viewport_to_world(camera_transform, position).ok()
+        });
+    let Some(ray) = ray else {
+        cursor.hit = None;
+        return;
+    };
+
+    let dims = dimensions.0;
+    let max_distance = cursor.max_distance;
+    let hit = volumes
+        .iter()
+        .filter_map(|(volume, density_field, transform, spacing)| {
+            let scale = VoxelSpacing::volume_scale(spacing, *mesh_size, dims);
+            let hit = density_field.raycast_world(
+                dims,
+                scale,
+                transform,
+                ray.origin,
+                *ray.direction,
+                max_distance,
+            )?;
+            Some(CursorHit {
+                volume,
+                position: hit.position,
+                normal: hit.normal,
+                distance: hit.distance,
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    let filter = |entity| gpu_volumes.contains(entity);
+    let settings = MeshRayCastSettings::default().with_filter(&filter);
+    let mesh_hit = mesh_ray_cast
+        .cast_ray(ray, &settings)
+        .iter()
+        .filter(|(_, hit)| hit.distance <= max_distance)
+        .map(|(volume, hit)| CursorHit {
+            volume: *volume,
+            position: hit.point,
+            normal: hit.normal,
+            distance: hit.distance,
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance));
+    let hit = [hit, mesh_hit]
+        .into_iter()
+        .flatten()
+        .min_by(|a, b| a.distance.total_cmp(&b.distance));
+    cursor.set_if_neq(SculptCursor { max_distance, hit });
+}
@@ -1,28 +1,145 @@
 // pipeline.rs
+use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 use bevy::render::render_resource::*;
 use bevy::render::renderer::RenderDevice;
 
-use crate::bind_group::SurfaceNetsBindGroupLayouts;
+use crate::{
+    backend::{BackendInit, BackendKernels},
+    bind_group::SurfaceNetsBindGroupLayouts,
+    brush::BrushParams,
+    csg::CsgParams,
+    gpu_density::{DensityGeneration, DensityGenerationParams, DensityKernel},
+    quantize::DensityDecode,
+    sdf::GpuSdfPart,
+    settings::{MeshingAlgorithm, SurfaceNetsParams},
+};
 
-// Shader paths
-const GENERATE_VERTICES_SHADER: &str = "shaders/generate_vertices.wgsl";
+// Shader paths of the stages shared by every backend
 const PREFIX_SUM_SHADER: &str = "shaders/prefix_sum.wgsl";
 const COMPACT_VERTICES_SHADER: &str = "shaders/compact_vertices.wgsl";
-const GENERATE_FACES_SHADER: &str = "shaders/generate_faces.wgsl";
 const COMPACT_FACES_SHADER: &str = "shaders/compact_faces.wgsl";
+const VERTEX_NORMALS_SHADER: &str = "shaders/vertex_normals.wgsl";
+const GENERATE_DENSITY_SHADER: &str = "shaders/generate_density.wgsl";
+const DENSITY_SHADER: &str = "shaders/density.wgsl";
+const DENSITY_GENERATION_SHADER: &str = "shaders/density_generation.wgsl";
+const NOISE_SHADER: &str = "shaders/noise.wgsl";
+const CSG_SHADER: &str = "shaders/csg.wgsl";
+const SDF_SHADER: &str = "shaders/sdf.wgsl";
+const SCULPT_BRUSH_SHADER: &str = "shaders/sculpt_brush.wgsl";
 
 #[derive(Resource)]
 pub struct SurfaceNetsPipelines {
-    pub generate_vertices_pipeline: CachedComputePipelineId,
-
     pub prefix_sum_pipeline: CachedComputePipelineId,
 
     pub compact_vertices_pipeline: CachedComputePipelineId,
 
-    pub generate_faces_pipeline: CachedComputePipelineId,
-
     pub compact_faces_pipeline: CachedComputePipelineId,
+
+    // Gradient normals from the density buffer or a density texture
+    pub vertex_normals_pipeline: CachedComputePipelineId,
+    pub vertex_normals_texture_pipeline: CachedComputePipelineId,
+
+    // Density generation of `GpuDensity` volumes, from the noise stack, the
+    // user's function (which never compiles if the app doesn't load one), a
+    // heightmap or a signed distance shape
+    pub generate_density_pipeline: CachedComputePipelineId,
+    pub generate_density_custom_pipeline: CachedComputePipelineId,
+    pub generate_density_heightmap_pipeline: CachedComputePipelineId,
+    pub generate_density_sdf_pipeline: CachedComputePipelineId,
+
+    // Composites into the densities of `GpuDensity` volumes
+    pub csg_pipeline: CachedComputePipelineId,
+    // Sculpt brushes, after a copy of the densities around them for those
+    // reading neighbours
+    pub brush_snapshot_pipeline: CachedComputePipelineId,
+    pub brush_pipeline: CachedComputePipelineId,
+    pub brush_smooth_pipeline: CachedComputePipelineId,
+    pub brush_flatten_pipeline: CachedComputePipelineId,
+    // `sculpter::density`, `sculpter::noise`, `sculpter::sdf` and
+    // `sculpter::density_generation`, kept loaded so shaders can import them
+    _density_shader: Handle<Shader>,
+    _noise_shader: Handle<Shader>,
+    _sdf_shader: Handle<Shader>,
+    _density_generation_shader: Handle<Shader>,
+
+    // Stage 1 and 4 kernels of each meshing backend
+    pub backends: HashMap<MeshingAlgorithm, BackendKernels>,
+
+    // Trilinear sampler for texture density inputs
+    pub density_sampler: Sampler,
+}
+
+impl SurfaceNetsPipelines {
+    /// The kernel generating a `GpuDensity` volume's densities.
+    pub fn generate_density(&self, generation: &DensityGeneration) -> CachedComputePipelineId {
+        match (generation.custom, &generation.heightmap, &generation.sdf) {
+            (true, ..) => self.generate_density_custom_pipeline,
+            (false, Some(_), _) => self.generate_density_heightmap_pipeline,
+            (false, None, Some(_)) => self.generate_density_sdf_pipeline,
+            (false, None, None) => self.generate_density_pipeline,
+        }
+    }
+
+    /// The kernel applying a sculpt brush.
+    pub fn brush(&self, params: &BrushParams) -> CachedComputePipelineId {
+        if params.smooths() {
+            self.brush_smooth_pipeline
+        } else if params.flattens() {
+            self.brush_flatten_pipeline
+        } else {
+            self.brush_pipeline
+        }
+    }
+
+    /// Every pipeline, shared and per backend.
+    pub fn all(&self) -> impl Iterator<Item = CachedComputePipelineId> + '_ {
+        [
+            self.prefix_sum_pipeline,
+            self.compact_vertices_pipeline,
+            self.compact_faces_pipeline,
+            self.vertex_normals_pipeline,
+            self.vertex_normals_texture_pipeline,
+            self.generate_density_pipeline,
+            self.generate_density_heightmap_pipeline,
+            self.generate_density_sdf_pipeline,
+            self.csg_pipeline,
+            self.brush_snapshot_pipeline,
+            self.brush_pipeline,
+            self.brush_smooth_pipeline,
+            self.brush_flatten_pipeline,
+        ]
+        .into_iter()
+        .chain(
+            self.backends
+                .values()
+                .flat_map(|kernels| kernels.buffer.into_iter().chain(kernels.texture)),
+        )
+    }
+}
+
+/// Pipelines of compiled `DensityKernel`s, queued the first time a volume runs
+/// them. They recompile in place when their shader is replaced.
+#[derive(Resource, Default)]
+pub struct DensityKernelPipelines(HashMap<AssetId<Shader>, CachedComputePipelineId>);
+
+impl DensityKernelPipelines {
+    pub fn get_or_queue(
+        &mut self,
+        kernel: &DensityKernel,
+        layouts: &SurfaceNetsBindGroupLayouts,
+        pipeline_cache: &PipelineCache,
+    ) -> CachedComputePipelineId {
+        *self.0.entry(kernel.shader.id()).or_insert_with(|| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some("generate_density_kernel_pipeline".into()),
+                layout: vec![layouts.generate_density.clone()],
+                shader: kernel.shader.clone(),
+                entry_point: Some("generate_density".into()),
+                ..default()
+            })
+        })
+    }
 }
 
 pub fn init_surface_nets_pipelines(
@@ -33,20 +150,6 @@ pub fn init_surface_nets_pipelines(
 ) {
     use binding_types::*;
 
-    // Layout 1: Generate Vertices
-    let generate_vertices_layout = render_device.create_bind_group_layout(
-        "GenerateVerticesLayout",
-        &BindGroupLayoutEntries::sequential(
-            ShaderStages::COMPUTE,
-            (
-                storage_buffer_read_only::<Vec<f32>>(false), // density_field
-                storage_buffer::<Vec<f32>>(false),           // vertices (output)
-                storage_buffer::<Vec<u32>>(false),           // vertex_valid (output)
-                uniform_buffer::<UVec3>(false),              // dimensions
-            ),
-        ),
-    );
-
     // Layout 2: Prefix Sum
     let prefix_sum_layout = render_device.create_bind_group_layout(
         "PrefixSumLayout",
@@ -74,45 +177,144 @@ pub fn init_surface_nets_pipelines(
         ),
     );
 
-    // Layout 4: Generate Faces
-    let generate_faces_layout = render_device.create_bind_group_layout(
-        "GenerateFacesLayout",
+    // Layout 5: Compact Faces
+    let compact_faces_layout = render_device.create_bind_group_layout(
+        "CompactFacesLayout",
         &BindGroupLayoutEntries::sequential(
             ShaderStages::COMPUTE,
             (
-                storage_buffer_read_only::<Vec<u32>>(false), // vertex_valid
+                storage_buffer_read_only::<Vec<u32>>(false), // faces (input)
+                storage_buffer_read_only::<Vec<u32>>(false), // face_valid
+                storage_buffer_read_only::<Vec<u32>>(false), // face_indices
+                storage_buffer::<Vec<u32>>(false),           // compacted_faces (output)
                 storage_buffer_read_only::<Vec<u32>>(false), // vertex_indices
-                storage_buffer::<Vec<u32>>(false),           // faces (output)
-                storage_buffer::<Vec<u32>>(false),           // face_valid (output)
+            ),
+        ),
+    );
+
+    // Layout 7: Vertex Normals
+    let vertex_normals_layout = render_device.create_bind_group_layout(
+        "VertexNormalsLayout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                storage_buffer_read_only::<Vec<u32>>(false), // density_field (f32 bits or packed bytes)
+                storage_buffer_read_only::<Vec<f32>>(false), // compacted_vertices
+                storage_buffer_read_only::<u32>(false),      // vertex_count
+                storage_buffer::<Vec<f32>>(false),           // compacted_normals (output)
                 uniform_buffer::<UVec3>(false),              // dimensions
+                uniform_buffer::<DensityDecode>(false),      // density_decode
+                uniform_buffer::<SurfaceNetsParams>(false),  // params
+                storage_buffer::<Vec<f32>>(false),           // compacted_occlusion (output)
+                storage_buffer_read_only::<Vec<u32>>(false), // material_field
+                storage_buffer::<Vec<u32>>(false),           // compacted_material_ids (output)
+                storage_buffer::<Vec<f32>>(false),           // compacted_material_weights (output)
             ),
         ),
     );
 
-    // Layout 5: Compact Faces
-    let compact_faces_layout = render_device.create_bind_group_layout(
-        "CompactFacesLayout",
+    // Layout 7b: Vertex Normals from a 3D density texture
+    let vertex_normals_texture_layout = render_device.create_bind_group_layout(
+        "VertexNormalsTextureLayout",
         &BindGroupLayoutEntries::sequential(
             ShaderStages::COMPUTE,
             (
-                storage_buffer_read_only::<Vec<u32>>(false), // faces (input)
-                storage_buffer_read_only::<Vec<u32>>(false), // face_valid
-                storage_buffer_read_only::<Vec<u32>>(false), // face_indices
-                storage_buffer::<Vec<u32>>(false),           // compacted_faces (output)
+                texture_3d(TextureSampleType::Float { filterable: true }), // density_texture
+                storage_buffer_read_only::<Vec<f32>>(false),               // compacted_vertices
+                storage_buffer_read_only::<u32>(false),                    // vertex_count
+                storage_buffer::<Vec<f32>>(false), // compacted_normals (output)
+                uniform_buffer::<UVec3>(false),    // dimensions
+                uniform_buffer::<DensityDecode>(false), // density_decode
+                uniform_buffer::<SurfaceNetsParams>(false), // params
+                storage_buffer::<Vec<f32>>(false), // compacted_occlusion (output)
+                sampler(SamplerBindingType::Filtering), // density_sampler
             ),
         ),
     );
 
-    // Queue compute pipelines
-    let generate_vertices_pipeline =
-        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-            label: Some("generate_vertices_pipeline".into()),
-            layout: vec![generate_vertices_layout.clone()],
-            shader: asset_server.load(GENERATE_VERTICES_SHADER),
-            entry_point: Some("generate_vertices".into()),
-            ..default()
-        });
+    // Layout 0: Generate Density
+    let generate_density_layout = render_device.create_bind_group_layout(
+        "GenerateDensityLayout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                storage_buffer::<Vec<f32>>(false), // density_field (output)
+                uniform_buffer::<DensityGenerationParams>(false), // params
+            ),
+        ),
+    );
+    let generate_density_heightmap_layout = render_device.create_bind_group_layout(
+        "GenerateDensityHeightmapLayout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                storage_buffer::<Vec<f32>>(false), // density_field (output)
+                uniform_buffer::<DensityGenerationParams>(false), // params
+                texture_2d(TextureSampleType::Float { filterable: true }), // heightmap
+                sampler(SamplerBindingType::Filtering), // heightmap_sampler
+            ),
+        ),
+    );
+
+    let generate_density_sdf_layout = render_device.create_bind_group_layout(
+        "GenerateDensitySdfLayout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                storage_buffer::<Vec<f32>>(false), // density_field (output)
+                uniform_buffer::<DensityGenerationParams>(false), // params
+                storage_buffer_read_only::<Vec<GpuSdfPart>>(false), // sdf_parts
+            ),
+        ),
+    );
+
+    // Layout 0b: CSG
+    let csg_layout = render_device.create_bind_group_layout(
+        "CsgLayout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                storage_buffer::<Vec<f32>>(false),  // density_field (output)
+                uniform_buffer::<CsgParams>(false), // params
+                storage_buffer_read_only::<Vec<f32>>(false), // source_field
+                storage_buffer_read_only::<Vec<GpuSdfPart>>(false), // parts
+            ),
+        ),
+    );
+
+    // Layout 0c: Sculpt Brush
+    let brush_layout = render_device.create_bind_group_layout(
+        "BrushLayout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                storage_buffer::<Vec<f32>>(false),    // density_field (output)
+                uniform_buffer::<BrushParams>(false), // params
+                storage_buffer::<Vec<f32>>(false),    // snapshot
+                storage_buffer_read_only::<Vec<f32>>(false), // curve
+            ),
+        ),
+    );
 
+    // Each backend creates its own layouts and kernels for stages 1 and 4
+    let init = BackendInit {
+        asset_server: &asset_server,
+        pipeline_cache: &pipeline_cache,
+        render_device: &render_device,
+    };
+    let backends = MeshingAlgorithm::ALL
+        .into_iter()
+        .map(|algorithm| (algorithm, algorithm.backend().init_kernels(&init)))
+        .collect();
+
+    let density_sampler = render_device.create_sampler(&SamplerDescriptor {
+        label: Some("density_sampler"),
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..default()
+    });
+
+    // Queue the shared compute pipelines
     let prefix_sum_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
         label: Some("prefix_sum_pipeline".into()),
         layout: vec![prefix_sum_layout.clone()],
@@ -130,15 +332,6 @@ pub fn init_surface_nets_pipelines(
             ..default()
         });
 
-    let generate_faces_pipeline =
-        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-            label: Some("generate_faces_pipeline".into()),
-            layout: vec![generate_faces_layout.clone()],
-            shader: asset_server.load(GENERATE_FACES_SHADER),
-            entry_point: Some("generate_faces".into()),
-            ..default()
-        });
-
     let compact_faces_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
         label: Some("compact_faces_pipeline".into()),
         layout: vec![compact_faces_layout.clone()],
@@ -147,20 +340,139 @@ pub fn init_surface_nets_pipelines(
         ..default()
     });
 
+    let vertex_normals_pipeline =
+        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("vertex_normals_pipeline".into()),
+            layout: vec![vertex_normals_layout.clone()],
+            shader: asset_server.load(VERTEX_NORMALS_SHADER),
+            entry_point: Some("vertex_normals".into()),
+            ..default()
+        });
+
+    let vertex_normals_texture_pipeline =
+        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("vertex_normals_texture_pipeline".into()),
+            layout: vec![vertex_normals_texture_layout.clone()],
+            shader: asset_server.load(VERTEX_NORMALS_SHADER),
+            shader_defs: vec!["DENSITY_TEXTURE".into()],
+            entry_point: Some("vertex_normals".into()),
+            ..default()
+        });
+
+    let generate_density_pipeline =
+        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("generate_density_pipeline".into()),
+            layout: vec![generate_density_layout.clone()],
+            shader: asset_server.load(GENERATE_DENSITY_SHADER),
+            entry_point: Some("generate_density".into()),
+            ..default()
+        });
+
+    let generate_density_custom_pipeline =
+        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("generate_density_custom_pipeline".into()),
+            layout: vec![generate_density_layout.clone()],
+            shader: asset_server.load(GENERATE_DENSITY_SHADER),
+            shader_defs: vec!["CUSTOM_DENSITY".into()],
+            entry_point: Some("generate_density".into()),
+            ..default()
+        });
+
+    let generate_density_heightmap_pipeline =
+        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("generate_density_heightmap_pipeline".into()),
+            layout: vec![generate_density_heightmap_layout.clone()],
+            shader: asset_server.load(GENERATE_DENSITY_SHADER),
+            shader_defs: vec!["HEIGHTMAP".into()],
+            entry_point: Some("generate_density".into()),
+            ..default()
+        });
+
+    let generate_density_sdf_pipeline =
+        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("generate_density_sdf_pipeline".into()),
+            layout: vec![generate_density_sdf_layout.clone()],
+            shader: asset_server.load(GENERATE_DENSITY_SHADER),
+            shader_defs: vec!["SDF_DENSITY".into()],
+            entry_point: Some("generate_density".into()),
+            ..default()
+        });
+
+    let csg_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: Some("csg_pipeline".into()),
+        layout: vec![csg_layout.clone()],
+        shader: asset_server.load(CSG_SHADER),
+        entry_point: Some("csg".into()),
+        ..default()
+    });
+
+    let brush_snapshot_pipeline =
+        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("brush_snapshot_pipeline".into()),
+            layout: vec![brush_layout.clone()],
+            shader: asset_server.load(SCULPT_BRUSH_SHADER),
+            entry_point: Some("brush_snapshot".into()),
+            ..default()
+        });
+
+    let brush_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: Some("brush_pipeline".into()),
+        layout: vec![brush_layout.clone()],
+        shader: asset_server.load(SCULPT_BRUSH_SHADER),
+        entry_point: Some("brush".into()),
+        ..default()
+    });
+
+    let brush_smooth_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: Some("brush_smooth_pipeline".into()),
+        layout: vec![brush_layout.clone()],
+        shader: asset_server.load(SCULPT_BRUSH_SHADER),
+        entry_point: Some("brush_smooth".into()),
+        ..default()
+    });
+
+    let brush_flatten_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: Some("brush_flatten_pipeline".into()),
+        layout: vec![brush_layout.clone()],
+        shader: asset_server.load(SCULPT_BRUSH_SHADER),
+        entry_point: Some("brush_flatten".into()),
+        ..default()
+    });
+
     commands.insert_resource(SurfaceNetsPipelines {
-        generate_vertices_pipeline,
         prefix_sum_pipeline,
         compact_vertices_pipeline,
-        generate_faces_pipeline,
         compact_faces_pipeline,
+        vertex_normals_pipeline,
+        vertex_normals_texture_pipeline,
+        generate_density_pipeline,
+        generate_density_custom_pipeline,
+        generate_density_heightmap_pipeline,
+        generate_density_sdf_pipeline,
+        csg_pipeline,
+        brush_snapshot_pipeline,
+        brush_pipeline,
+        brush_smooth_pipeline,
+        brush_flatten_pipeline,
+        _density_shader: asset_server.load(DENSITY_SHADER),
+        _noise_shader: asset_server.load(NOISE_SHADER),
+        _sdf_shader: asset_server.load(SDF_SHADER),
+        _density_generation_shader: asset_server.load(DENSITY_GENERATION_SHADER),
+        backends,
+        density_sampler,
     });
 
     // Store bind group layouts
     commands.insert_resource(SurfaceNetsBindGroupLayouts {
-        generate_vertices: generate_vertices_layout,
         prefix_sum: prefix_sum_layout,
         compact_vertices: compact_vertices_layout,
-        generate_faces: generate_faces_layout,
         compact_faces: compact_faces_layout,
+        vertex_normals: vertex_normals_layout,
+        vertex_normals_texture: vertex_normals_texture_layout,
+        generate_density: generate_density_layout,
+        generate_density_heightmap: generate_density_heightmap_layout,
+        generate_density_sdf: generate_density_sdf_layout,
+        csg: csg_layout,
+        brush: brush_layout,
     });
 }
@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::render::render_resource::ShaderType;
+
+/// 8-bit storage formats for densities uploaded to the GPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum QuantizedFormat {
+    /// Unsigned bytes, decoded as `byte * scale + offset`.
+    U8,
+    /// Signed bytes, decoded as `byte * scale + offset`. Best for clamped SDFs centred on zero.
+    #[default]
+    I8,
+}
+
+/// Upload a field's densities as 8-bit values instead of `f32`, cutting the GPU
+/// density buffer to a quarter of its size.
+///
+/// Values are encoded as `round((density - offset) / scale)` and decoded by
+/// `sculpter::density`, so densities outside the representable range are
+/// clamped.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct DensityQuantization {
+    pub format: QuantizedFormat,
+    pub scale: f32,
+    pub offset: f32,
+}
+
+impl DensityQuantization {
+    /// Signed quantization of an SDF clamped to `[-max_distance, max_distance]`.
+    pub fn clamped_sdf(max_distance: f32) -> Self {
+        Self {
+            format: QuantizedFormat::I8,
+            scale: max_distance / i8::MAX as f32,
+            offset: 0.0,
+        }
+    }
+
+    pub fn quantize(&self, density: f32) -> u8 {
+        let q = ((density - self.offset) / self.scale).round();
+        match self.format {
+            QuantizedFormat::U8 => q.clamp(0.0, u8::MAX as f32) as u8,
+            QuantizedFormat::I8 => q.clamp(i8::MIN as f32, i8::MAX as f32) as i8 as u8,
+        }
+    }
+
+    pub fn dequantize(&self, byte: u8) -> f32 {
+        let q = match self.format {
+            QuantizedFormat::U8 => byte as f32,
+            QuantizedFormat::I8 => byte as i8 as f32,
+        };
+        q * self.scale + self.offset
+    }
+
+    /// Quantize a whole field, padded to a multiple of four bytes so it can be
+    /// bound as `array<u32>`.
+    pub fn encode(&self, densities: &[f32]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = densities.iter().map(|&d| self.quantize(d)).collect();
+        bytes.resize(bytes.len().next_multiple_of(4), 0);
+        bytes
+    }
+}
+
+// Must match `DENSITY_FORMAT_*` in density.wgsl
+#[cfg(feature = "gpu")]
+pub(crate) const DENSITY_FORMAT_F32: u32 = 0;
+#[cfg(feature = "gpu")]
+pub(crate) const DENSITY_FORMAT_U8: u32 = 1;
+#[cfg(feature = "gpu")]
+pub(crate) const DENSITY_FORMAT_I8: u32 = 2;
+
+/// Uniform telling the meshing kernels how to decode the density buffer.
+#[cfg(feature = "gpu")]
+#[derive(ShaderType, Clone, Copy, Debug)]
+pub struct DensityDecode {
+    pub format: u32,
+    pub scale: f32,
+    pub offset: f32,
+}
+
+#[cfg(feature = "gpu")]
+impl Default for DensityDecode {
+    fn default() -> Self {
+        Self {
+            format: DENSITY_FORMAT_F32,
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+#[cfg(feature = "gpu")]
+impl From<&DensityQuantization> for DensityDecode {
+    fn from(quantization: &DensityQuantization) -> Self {
+        Self {
+            format: match quantization.format {
+                QuantizedFormat::U8 => DENSITY_FORMAT_U8,
+                QuantizedFormat::I8 => DENSITY_FORMAT_I8,
+            },
+            scale: quantization.scale,
+            offset: quantization.offset,
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use bevy::{
+    ecs::entity::EntityHashSet,
+    prelude::*,
+    tasks::{Task, block_on, futures_lite::future},
+};
+use bevy_rapier3d::{
+    parry::shape::SharedShape,
+    prelude::{Collider, CollisionGroups, TriMeshFlags},
+};
+
+use crate::{
+    DensityFieldMeshSize, DensityFieldSize,
+    collision::{CollisionMesh, ConvexDecomposition, spawn_convex_decomposition},
+    settings::Simplification,
+    units::VoxelSpacing,
+};
+
+/// How a volume's rapier `Collider` is built. Volumes without it collide with
+/// everything through their `CollisionMesh` as is.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct RapierColliderSettings {
+    /// Inserted next to the collider
+    pub collision_groups: CollisionGroups,
+    pub mesh: ColliderMesh,
+}
+
+/// Geometry a volume's collider is built from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColliderMesh {
+    /// The volume's `CollisionMesh`: its own mesh, or the low resolution one
+    /// asked for by `GenerateCollisionMesh`
+    #[default]
+    Collision,
+    /// The `CollisionMesh` decimated further. Errors are measured in voxels, as
+    /// for `SculptSettings::simplification`.
+    Simplified(Simplification),
+}
+
+/// `ConvexDecomposition` of a volume's rapier `Collider` running on the
+/// `AsyncComputeTaskPool`.
+#[derive(Component)]
+pub struct RapierDecompositionTask(Task<Option<SharedShape>>);
+
+/// Give volumes whose `CollisionMesh`, `RapierColliderSettings` or
+/// `ConvexDecomposition` changed a trimesh `Collider`, or take it away when the
+/// mesh is empty. Volumes with a `ConvexDecomposition` start decomposing
+/// instead, dropping a stale decomposition still running.
+pub fn update_rapier_colliders(
+    mut commands: Commands,
+    mesh_size: Res<DensityFieldMeshSize>,
+    dimensions: Res<DensityFieldSize>,
+    meshes: Query<(
+        Entity,
+        &CollisionMesh,
+        Option<&RapierColliderSettings>,
+        Option<&ConvexDecomposition>,
+        Option<&VoxelSpacing>,
+    )>,
+    changed: Query<
+        Entity,
+        Or<(
+            Changed<CollisionMesh>,
+            Changed<RapierColliderSettings>,
+            Changed<ConvexDecomposition>,
+        )>,
+    >,
+    // Back to a trimesh
+    mut removed: RemovedComponents<ConvexDecomposition>,
+    #[cfg(feature = "gpu")] buffers: Query<&crate::buffers::SurfaceNetsBuffers>,
+) {
+    let entities: EntityHashSet = changed.iter().chain(removed.read()).collect();
+    for (entity, mesh, settings, decomposition, spacing) in meshes.iter_many(entities) {
+        let settings = settings.copied().unwrap_or_default();
+        let simplified;
+        let mesh = match settings.mesh {
+            ColliderMesh::Collision => &mesh.0,
+            ColliderMesh::Simplified(simplification) => {
+                // Decimated in grid units, like the volume's own mesh
+                #[cfg(feature = "gpu")]
+                let dimensions = buffers
+                    .get(entity)
+                    .map_or(*dimensions, |buffers| buffers.dimensions);
+                #[cfg(not(feature = "gpu"))]
+                let dimensions = *dimensions;
+                let scale = VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions.0);
+                let mut grid_mesh = mesh.0.clone();
+                grid_mesh.scale(scale.recip());
+                sculpter_core::simplify(&mut grid_mesh, simplification);
+                grid_mesh.scale(scale);
+                simplified = grid_mesh;
+                &simplified
+            }
+        };
+
+        let mut entity_commands = commands.entity(entity);
+        if let Some(decomposition) = decomposition {
+            let task = spawn_convex_decomposition(mesh, *decomposition);
+            entity_commands.try_insert(RapierDecompositionTask(task));
+            continue;
+        }
+        entity_commands.try_remove::<RapierDecompositionTask>();
+
+        let indices: Vec<_> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+        match Collider::trimesh_with_flags(
+            mesh.positions.clone(),
+            indices,
+            TriMeshFlags::MERGE_DUPLICATE_VERTICES | TriMeshFlags::DELETE_DEGENERATE_TRIANGLES,
+        ) {
+            Ok(collider) => {
+                entity_commands.try_insert((collider, settings.collision_groups));
+            }
+            Err(error) => {
+                if !mesh.indices.is_empty() {
+                    warn!("Could not build a collider for {entity}: {error}");
+                }
+                entity_commands.try_remove::<(Collider, CollisionGroups)>();
+            }
+        }
+    }
+}
+
+/// Give volumes whose decomposition finished a compound `Collider` of its
+/// convex hulls, or take it away when there are none.
+pub fn poll_rapier_decompositions(
+    mut commands: Commands,
+    mut tasks: Query<(
+        Entity,
+        &mut RapierDecompositionTask,
+        &CollisionMesh,
+        Option<&RapierColliderSettings>,
+    )>,
+) {
+    for (entity, mut task, mesh, settings) in &mut tasks {
+        let Some(shape) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_remove::<RapierDecompositionTask>();
+        match shape {
+            Some(shape) => {
+                let settings = settings.copied().unwrap_or_default();
+                entity_commands.try_insert((Collider::from(shape), settings.collision_groups));
+            }
+            None => {
+                if !mesh.indices.is_empty() {
+                    warn!("Could not build a collider for {entity}: no convex hulls were found");
+                }
+                entity_commands.try_remove::<(Collider, CollisionGroups)>();
+            }
+        }
+    }
+}
@@ -3,131 +3,387 @@ use bevy::{
     render::gpu_readback::{Readback, ReadbackComplete},
 };
 
-use crate::buffers::SurfaceNetsBuffers;
+use sculpter_core::{
+    AMBIENT_OCCLUSION_ATTRIBUTE, MATERIAL_ID_ATTRIBUTE, MATERIAL_WEIGHTS_ATTRIBUTE,
+};
+
+use crate::{
+    DensityFieldSize,
+    amortize::{StageSchedule, StagesDispatched},
+    buffers::SurfaceNetsBuffers,
+    dirty_region::Remesh,
+    mesh::{GeneratedMesh, add_skirts, apply_gradient_normals},
+    mesh_data::{MeshData, VertexAttributeData},
+    settings::{MeshingAlgorithm, SculptSettings},
+    watchdog::{MeshingFailed, ReadbackStarted},
+};
 
 #[derive(Component, Default)]
 pub struct ReadbackBuffers {
     pub vertex_count: Option<u32>,
     pub vertices: Option<Vec<f32>>,
+    pub normals: Option<Vec<f32>>,
+    /// Empty when the volume bakes no ambient occlusion
+    pub occlusion: Option<Vec<f32>>,
+    /// Empty when the volume has no `MaterialField`
+    pub material_ids: Option<Vec<u32>>,
+    /// Four per vertex, empty when the volume has no `MaterialField`
+    pub material_weights: Option<Vec<f32>>,
     pub face_count: Option<u32>,
     pub faces: Option<Vec<u32>>,
 }
 
+/// Read back the outputs of volumes being generated on the GPU. Amortized volumes
+/// wait until the render world reports their last stage dispatched.
 pub fn setup_readback_for_new_fields(
     mut commands: Commands,
     new_buffers: Query<
-        (Entity, &SurfaceNetsBuffers),
-        (Added<SurfaceNetsBuffers>, Without<ReadbackBuffers>),
+        (
+            Entity,
+            &SurfaceNetsBuffers,
+            Option<&StageSchedule>,
+            Option<&StagesDispatched>,
+        ),
+        (
+            Or<(Without<Mesh3d>, With<Remesh>)>,
+            Without<ReadbackBuffers>,
+            Without<MeshingFailed>,
+        ),
     >,
+    time: Res<Time<Real>>,
 ) {
-    for (parent_entity, buffers) in new_buffers {
-        let vertex_count_entity = commands
-            .spawn(Readback::buffer(buffers.vertex_count.clone()))
+    for (parent_entity, buffers, schedule, dispatched) in new_buffers {
+        if let Some(schedule) = schedule
+            && dispatched != Some(&StagesDispatched(schedule.start_frame))
+        {
+            continue;
+        }
+
+        commands
+            .spawn((
+                Readback::buffer(buffers.vertex_count.clone()),
+                ChildOf(parent_entity),
+            ))
             .observe(
                 |event: On<ReadbackComplete>,
                  children_of: Query<&ChildOf>,
                  mut commands: Commands,
                  mut readback_buffers: Query<&mut ReadbackBuffers>| {
-                    let parent = children_of
+                    // The volume was despawned or its readback abandoned meanwhile
+                    let Some(mut buffers) = children_of
                         .get(event.entity)
-                        .expect("Readback is not a child of anything")
-                        .parent();
-
-                    let mut buffers = readback_buffers
-                        .get_mut(parent)
-                        .expect("parent of readback does not have ReadbackBuffers");
+                        .ok()
+                        .and_then(|child_of| readback_buffers.get_mut(child_of.parent()).ok())
+                    else {
+                        commands.entity(event.entity).try_despawn();
+                        return;
+                    };
 
                     let data: Vec<u32> = event.to_shader_type();
                     //get the vertex count and if there is none set it to 0
-                    let vertex_count = data.get(0).copied().unwrap_or(0);
+                    let vertex_count = data.first().copied().unwrap_or(0);
 
                     buffers.vertex_count = Some(vertex_count);
 
-                    commands.entity(event.entity).despawn();
+                    commands.entity(event.entity).try_despawn();
                 },
-            )
-            .id();
+            );
 
-        let vertices_entity = commands
-            .spawn(Readback::buffer(buffers.vertices.clone()))
+        commands
+            .spawn((
+                Readback::buffer(buffers.vertices.clone()),
+                ChildOf(parent_entity),
+            ))
             .observe(
                 |event: On<ReadbackComplete>,
                  children_of: Query<&ChildOf>,
                  mut commands: Commands,
                  mut readback_buffers: Query<&mut ReadbackBuffers>| {
-                    let parent = children_of
+                    // The volume was despawned or its readback abandoned meanwhile
+                    let Some(mut buffers) = children_of
                         .get(event.entity)
-                        .expect("Readback is not a child of anything")
-                        .parent();
-
-                    let mut buffers = readback_buffers
-                        .get_mut(parent)
-                        .expect("parent of readback does not have ReadbackBuffers");
+                        .ok()
+                        .and_then(|child_of| readback_buffers.get_mut(child_of.parent()).ok())
+                    else {
+                        commands.entity(event.entity).try_despawn();
+                        return;
+                    };
 
                     let vertices: Vec<f32> = event.to_shader_type();
                     buffers.vertices = Some(vertices);
 
-                    commands.entity(event.entity).despawn();
+                    commands.entity(event.entity).try_despawn();
                 },
-            )
-            .id();
-        let face_count_entity = commands
-            .spawn(Readback::buffer(buffers.face_count.clone()))
+            );
+
+        commands
+            .spawn((
+                Readback::buffer(buffers.compacted_normals.clone()),
+                ChildOf(parent_entity),
+            ))
             .observe(
                 |event: On<ReadbackComplete>,
                  children_of: Query<&ChildOf>,
                  mut commands: Commands,
                  mut readback_buffers: Query<&mut ReadbackBuffers>| {
-                    let parent = children_of
+                    // The volume was despawned or its readback abandoned meanwhile
+                    let Some(mut buffers) = children_of
                         .get(event.entity)
-                        .expect("Readback is not a child of anything")
-                        .parent();
+                        .ok()
+                        .and_then(|child_of| readback_buffers.get_mut(child_of.parent()).ok())
+                    else {
+                        commands.entity(event.entity).try_despawn();
+                        return;
+                    };
+
+                    let normals: Vec<f32> = event.to_shader_type();
+                    buffers.normals = Some(normals);
+
+                    commands.entity(event.entity).try_despawn();
+                },
+            );
+
+        // Ambient occlusion is only read back when the volume bakes it
+        let occlusion = if buffers.params.occlusion_rays > 0 {
+            commands
+                .spawn((
+                    Readback::buffer(buffers.compacted_occlusion.clone()),
+                    ChildOf(parent_entity),
+                ))
+                .observe(
+                    |event: On<ReadbackComplete>,
+                     children_of: Query<&ChildOf>,
+                     mut commands: Commands,
+                     mut readback_buffers: Query<&mut ReadbackBuffers>| {
+                        // The volume was despawned or its readback abandoned meanwhile
+                        let Some(mut buffers) = children_of
+                            .get(event.entity)
+                            .ok()
+                            .and_then(|child_of| readback_buffers.get_mut(child_of.parent()).ok())
+                        else {
+                            commands.entity(event.entity).try_despawn();
+                            return;
+                        };
+
+                        let occlusion: Vec<f32> = event.to_shader_type();
+                        buffers.occlusion = Some(occlusion);
+
+                        commands.entity(event.entity).try_despawn();
+                    },
+                );
+            None
+        } else {
+            Some(Vec::new())
+        };
+
+        // Likewise for the material channel, only bound for volumes with one
+        let (material_ids, material_weights) = if buffers.params.materials > 0 {
+            commands
+                .spawn((
+                    Readback::buffer(buffers.compacted_material_ids.clone()),
+                    ChildOf(parent_entity),
+                ))
+                .observe(
+                    |event: On<ReadbackComplete>,
+                     children_of: Query<&ChildOf>,
+                     mut commands: Commands,
+                     mut readback_buffers: Query<&mut ReadbackBuffers>| {
+                        // The volume was despawned or its readback abandoned meanwhile
+                        let Some(mut buffers) = children_of
+                            .get(event.entity)
+                            .ok()
+                            .and_then(|child_of| readback_buffers.get_mut(child_of.parent()).ok())
+                        else {
+                            commands.entity(event.entity).try_despawn();
+                            return;
+                        };
+
+                        let material_ids: Vec<u32> = event.to_shader_type();
+                        buffers.material_ids = Some(material_ids);
 
-                    let mut buffers = readback_buffers
-                        .get_mut(parent)
-                        .expect("parent of readback does not have ReadbackBuffers");
+                        commands.entity(event.entity).try_despawn();
+                    },
+                );
+            commands
+                .spawn((
+                    Readback::buffer(buffers.compacted_material_weights.clone()),
+                    ChildOf(parent_entity),
+                ))
+                .observe(
+                    |event: On<ReadbackComplete>,
+                     children_of: Query<&ChildOf>,
+                     mut commands: Commands,
+                     mut readback_buffers: Query<&mut ReadbackBuffers>| {
+                        // The volume was despawned or its readback abandoned meanwhile
+                        let Some(mut buffers) = children_of
+                            .get(event.entity)
+                            .ok()
+                            .and_then(|child_of| readback_buffers.get_mut(child_of.parent()).ok())
+                        else {
+                            commands.entity(event.entity).try_despawn();
+                            return;
+                        };
+
+                        let material_weights: Vec<f32> = event.to_shader_type();
+                        buffers.material_weights = Some(material_weights);
+
+                        commands.entity(event.entity).try_despawn();
+                    },
+                );
+            (None, None)
+        } else {
+            (Some(Vec::new()), Some(Vec::new()))
+        };
+        commands
+            .spawn((
+                Readback::buffer(buffers.face_count.clone()),
+                ChildOf(parent_entity),
+            ))
+            .observe(
+                |event: On<ReadbackComplete>,
+                 children_of: Query<&ChildOf>,
+                 mut commands: Commands,
+                 mut readback_buffers: Query<&mut ReadbackBuffers>| {
+                    // The volume was despawned or its readback abandoned meanwhile
+                    let Some(mut buffers) = children_of
+                        .get(event.entity)
+                        .ok()
+                        .and_then(|child_of| readback_buffers.get_mut(child_of.parent()).ok())
+                    else {
+                        commands.entity(event.entity).try_despawn();
+                        return;
+                    };
                     let data: Vec<u32> = event.to_shader_type();
                     //get the vertex count and if there is none set it to 0
-                    let face_count = data.get(0).copied().unwrap_or(0);
+                    let face_count = data.first().copied().unwrap_or(0);
 
                     buffers.face_count = Some(face_count);
 
-                    commands.entity(event.entity).despawn();
+                    commands.entity(event.entity).try_despawn();
                 },
-            )
-            .id();
-        let faces_entity = commands
-            .spawn(Readback::buffer(buffers.faces.clone()))
+            );
+        commands
+            .spawn((
+                Readback::buffer(buffers.faces.clone()),
+                ChildOf(parent_entity),
+            ))
             .observe(
                 |event: On<ReadbackComplete>,
                  children_of: Query<&ChildOf>,
                  mut commands: Commands,
                  mut readback_buffers: Query<&mut ReadbackBuffers>| {
-                    let parent = children_of
+                    // The volume was despawned or its readback abandoned meanwhile
+                    let Some(mut buffers) = children_of
                         .get(event.entity)
-                        .expect("Readback is not a child of anything")
-                        .parent();
-
-                    let mut buffers = readback_buffers
-                        .get_mut(parent)
-                        .expect("parent of readback does not have ReadbackBuffers");
+                        .ok()
+                        .and_then(|child_of| readback_buffers.get_mut(child_of.parent()).ok())
+                    else {
+                        commands.entity(event.entity).try_despawn();
+                        return;
+                    };
                     let faces: Vec<u32> = event.to_shader_type();
 
                     buffers.faces = Some(faces);
 
-                    commands.entity(event.entity).despawn();
+                    commands.entity(event.entity).try_despawn();
                 },
-            )
-            .id();
+            );
+
+        // The volume may be despawned before these apply, the readbacks then
+        // find no parent and despawn themselves
+        commands.entity(parent_entity).try_insert((
+            ReadbackBuffers {
+                occlusion,
+                material_ids,
+                material_weights,
+                ..default()
+            },
+            ReadbackStarted(time.elapsed()),
+        ));
+    }
+}
+
+/// Turn completed readbacks into a `GeneratedMesh`, triangulated by the backend
+/// that generated the faces, with the volume's skirts.
+pub fn assemble_readback_mesh(
+    mut commands: Commands,
+    query: Query<(
+        Entity,
+        &ReadbackBuffers,
+        Option<&SurfaceNetsBuffers>,
+        Option<&MeshingAlgorithm>,
+        Option<&SculptSettings>,
+    )>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    for (entity, data, buffers, algorithm, settings) in &query {
+        let (
+            Some(vertex_count),
+            Some(vertices),
+            Some(normals),
+            Some(occlusion),
+            Some(material_ids),
+            Some(material_weights),
+            Some(face_count),
+            Some(faces),
+        ) = (
+            data.vertex_count,
+            data.vertices.as_deref(),
+            data.normals.as_deref(),
+            data.occlusion.as_deref(),
+            data.material_ids.as_deref(),
+            data.material_weights.as_deref(),
+            data.face_count,
+            data.faces.as_deref(),
+        )
+        else {
+            continue;
+        };
+
+        // The buffers are sized for the worst case, only the counted prefix is output
+        let vertices = &vertices[..(vertex_count as usize * 3).min(vertices.len())];
+        let faces = &faces[..(face_count as usize * 4).min(faces.len())];
+        let algorithm = algorithm.copied().unwrap_or_default();
+        let backend = algorithm.backend();
+        let mut mesh = MeshData::from_faces(vertices, faces, |face, indices| {
+            backend.triangulate(face, indices)
+        });
+        let gradients: Vec<Vec3> = normals
+            .chunks_exact(3)
+            .take(mesh.positions.len())
+            .map(|n| vec3(n[0], n[1], n[2]))
+            .collect();
+        apply_gradient_normals(&mut mesh, &gradients, algorithm);
+        if !occlusion.is_empty() {
+            let occlusion = occlusion[..mesh.positions.len().min(occlusion.len())].to_vec();
+            mesh.attributes.insert(
+                AMBIENT_OCCLUSION_ATTRIBUTE,
+                VertexAttributeData::Float32(occlusion),
+            );
+        }
+        if !material_ids.is_empty() {
+            let count = mesh.positions.len();
+            mesh.attributes.insert(
+                MATERIAL_ID_ATTRIBUTE,
+                VertexAttributeData::Uint32(material_ids[..count.min(material_ids.len())].to_vec()),
+            );
+            let weights = material_weights
+                .chunks_exact(4)
+                .take(count)
+                .map(|w| vec4(w[0], w[1], w[2], w[3]))
+                .collect();
+            mesh.attributes.insert(
+                MATERIAL_WEIGHTS_ATTRIBUTE,
+                VertexAttributeData::Float32x4(weights),
+            );
+        }
+        let dimensions = buffers.map_or(*dimensions, |buffers| buffers.dimensions);
+        let settings = settings.cloned().unwrap_or_default();
+        add_skirts(&mut mesh, dimensions.0, algorithm, &settings);
 
         commands
-            .entity(parent_entity)
-            .insert(ReadbackBuffers::default())
-            .add_children(&[
-                vertex_count_entity,
-                vertices_entity,
-                face_count_entity,
-                faces_entity,
-            ]);
+            .entity(entity)
+            .try_insert(GeneratedMesh(mesh))
+            .try_remove::<ReadbackBuffers>();
     }
 }
@@ -0,0 +1,117 @@
+pub use sculpter_core::RayHit;
+
+use bevy::prelude::*;
+
+use crate::DensityField;
+
+impl DensityField {
+    /// Density at `position` in grid space, where sample `(x, y, z)` sits at
+    /// `(x, y, z)`, interpolated trilinearly as the meshers do. Positions outside
+    /// the grid are clamped to it.
+    pub fn sample(&self, dimensions: UVec3, position: Vec3) -> f32 {
+        sculpter_core::sample_trilinear(&self.0, dimensions, position)
+    }
+
+    /// Gradient of the interpolated density at `position` in grid space, per
+    /// sample, pointing out of the solid.
+    pub fn gradient(&self, dimensions: UVec3, position: Vec3) -> Vec3 {
+        sculpter_core::density_gradient(&self.0, dimensions, position)
+    }
+
+    /// Density at a world `position`, for a volume placed by `transform` whose
+    /// samples are `scale` apart, in metres: `VoxelSpacing::in_meters`, or else
+    /// the `DensityFieldMeshSize` over the dimensions.
+    pub fn sample_world(
+        &self,
+        dimensions: UVec3,
+        scale: Vec3,
+        transform: &GlobalTransform,
+        position: Vec3,
+    ) -> f32 {
+        self.sample(dimensions, to_grid(scale, transform, position))
+    }
+
+    /// Gradient of the density at a world `position`, per metre in world
+    /// space, for a volume placed as in `sample_world`.
+    pub fn gradient_world(
+        &self,
+        dimensions: UVec3,
+        scale: Vec3,
+        transform: &GlobalTransform,
+        position: Vec3,
+    ) -> Vec3 {
+        let gradient = self.gradient(dimensions, to_grid(scale, transform, position)) / scale;
+        // Gradients transform with the inverse transpose
+        Mat3::from(transform.affine().inverse().matrix3).transpose() * gradient
+    }
+
+    /// First hit of a ray from `origin` along `direction` in grid space with the
+    /// surface, within `max_distance` samples, sphere traced on the calling
+    /// thread without waiting for the mesh or a collider.
+    pub fn raycast(
+        &self,
+        dimensions: UVec3,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        sculpter_core::raycast(&self.0, dimensions, origin, direction, max_distance)
+    }
+
+    /// `raycast` in world space, for a volume placed as in `sample_world`, e.g.
+    /// to pick the sculpt point under the cursor. Distances are in metres.
+    pub fn raycast_world(
+        &self,
+        dimensions: UVec3,
+        scale: Vec3,
+        transform: &GlobalTransform,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        let inverse = transform.affine().inverse();
+        // Samples per metre along the ray
+        let grid_direction = inverse.transform_vector3(direction.normalize_or_zero()) / scale;
+        let samples_per_meter = grid_direction.length();
+        let hit = self.raycast(
+            dimensions,
+            to_grid(scale, transform, origin),
+            grid_direction,
+            max_distance * samples_per_meter,
+        )?;
+        Some(RayHit {
+            position: transform.transform_point(hit.position * scale),
+            normal: (Mat3::from(inverse.matrix3).transpose() * (hit.normal / scale))
+                .normalize_or_zero(),
+            distance: hit.distance / samples_per_meter,
+        })
+    }
+
+    /// Whether the sphere, in grid space, reaches into the solid, read from the
+    /// samples rather than the mesh or a collider.
+    pub fn sphere_overlaps(&self, dimensions: UVec3, center: Vec3, radius: f32) -> bool {
+        sculpter_core::sphere_overlaps(&self.0, dimensions, center, radius)
+    }
+
+    /// Whether the box from `min` to `max`, in grid space, reaches into the
+    /// solid, e.g. whether a cell is free for an agent.
+    pub fn aabb_overlaps(&self, dimensions: UVec3, min: Vec3, max: Vec3) -> bool {
+        sculpter_core::aabb_overlaps(&self.0, dimensions, min, max)
+    }
+
+    /// How deep the capsule around the segment from `a` to `b`, in grid space,
+    /// is buried, in samples, or 0 if it stays clear.
+    pub fn capsule_penetration_depth(
+        &self,
+        dimensions: UVec3,
+        a: Vec3,
+        b: Vec3,
+        radius: f32,
+    ) -> f32 {
+        sculpter_core::capsule_penetration_depth(&self.0, dimensions, a, b, radius)
+    }
+}
+
+fn to_grid(scale: Vec3, transform: &GlobalTransform, position: Vec3) -> Vec3 {
+    transform.affine().inverse().transform_point3(position) / scale
+}
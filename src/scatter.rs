@@ -0,0 +1,45 @@
+pub use sculpter_core::{ScatterDistribution, SurfacePoint, scatter_points};
+
+use bevy::prelude::*;
+
+use crate::{dirty_region::Remesh, mesh::Meshed};
+
+/// Also scatter `SurfacePoints` over this volume's mesh whenever it is meshed,
+/// so grass, rocks and decals follow edits to the terrain.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GenerateSurfacePoints {
+    pub count: usize,
+    /// Blue noise distances are in metres, like the mesh
+    pub distribution: ScatterDistribution,
+    pub seed: u64,
+}
+
+impl Default for GenerateSurfacePoints {
+    fn default() -> Self {
+        Self {
+            count: 256,
+            distribution: ScatterDistribution::default(),
+            seed: 0,
+        }
+    }
+}
+
+/// Points on the volume's mesh with their normals and materials, placed as its
+/// `GenerateSurfacePoints` asks and scaled like the mesh.
+#[derive(Component, Clone, Debug, Default, Deref)]
+pub struct SurfacePoints(pub Vec<SurfacePoint>);
+
+/// Remesh volumes whose `GenerateSurfacePoints` was added or changed. Points of
+/// volumes that dropped it are removed.
+pub fn remesh_changed_surface_points(
+    mut commands: Commands,
+    changed: Query<Entity, (Changed<GenerateSurfacePoints>, With<Meshed>)>,
+    mut removed: RemovedComponents<GenerateSurfacePoints>,
+) {
+    for entity in removed.read() {
+        commands.entity(entity).try_remove::<SurfacePoints>();
+    }
+    for entity in &changed {
+        commands.entity(entity).try_insert(Remesh);
+    }
+}
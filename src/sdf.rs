@@ -0,0 +1,126 @@
+pub use sculpter_core::{SdfPart, SdfPrimitive, SdfShape};
+
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::{math::Affine3A, render::render_resource::ShaderType};
+#[cfg(feature = "gpu")]
+use sculpter_core::CsgOperation;
+
+use crate::DensityField;
+
+// Must match `SHAPE_*` in sdf.wgsl
+#[cfg(feature = "gpu")]
+const SHAPE_SPHERE: u32 = 0;
+#[cfg(feature = "gpu")]
+const SHAPE_CUBOID: u32 = 1;
+#[cfg(feature = "gpu")]
+const SHAPE_ROUNDED_CUBOID: u32 = 2;
+#[cfg(feature = "gpu")]
+const SHAPE_CAPSULE: u32 = 3;
+#[cfg(feature = "gpu")]
+const SHAPE_CYLINDER: u32 = 4;
+#[cfg(feature = "gpu")]
+const SHAPE_TORUS: u32 = 5;
+#[cfg(feature = "gpu")]
+const SHAPE_CONE: u32 = 6;
+#[cfg(feature = "gpu")]
+const SHAPE_PLANE: u32 = 7;
+
+// Must match `OPERATION_*` in sdf.wgsl, the smooth variants sharing them
+#[cfg(feature = "gpu")]
+const OPERATION_UNION: u32 = 0;
+#[cfg(feature = "gpu")]
+const OPERATION_SUBTRACTION: u32 = 1;
+#[cfg(feature = "gpu")]
+const OPERATION_INTERSECTION: u32 = 2;
+
+impl DensityField {
+    /// Evaluate `shape` on the calling thread, with sample `(x, y, z)` at
+    /// `origin + (x, y, z) * spacing`, as a `GpuDensity` running
+    /// `DensityFunction::Sdf` does on the GPU.
+    pub fn from_sdf(dimensions: UVec3, shape: &SdfShape, origin: Vec3, spacing: Vec3) -> Self {
+        Self(sculpter_core::evaluate_sdf(
+            shape, dimensions, origin, spacing,
+        ))
+    }
+}
+
+/// A part of an `SdfShape` as the GPU evaluates it.
+#[cfg(feature = "gpu")]
+#[derive(ShaderType, Clone, Copy, Debug, Default, PartialEq)]
+pub struct GpuSdfPart {
+    /// Shape space to the primitive's space
+    pub inverse: Mat4,
+    pub size: Vec4,
+    pub shape: u32,
+    pub operation: u32,
+    /// Blend radius, 0 for the sharp operations
+    pub radius: f32,
+    /// Primitive distances to the shape's
+    pub scale: f32,
+}
+
+#[cfg(feature = "gpu")]
+impl GpuSdfPart {
+    pub fn new(primitive: SdfPrimitive, transform: Affine3A, operation: CsgOperation) -> Self {
+        let (shape, size) = match primitive {
+            SdfPrimitive::Sphere { radius } => (SHAPE_SPHERE, Vec4::new(radius, 0.0, 0.0, 0.0)),
+            SdfPrimitive::Cuboid { half_extents } => (SHAPE_CUBOID, half_extents.extend(0.0)),
+            SdfPrimitive::RoundedCuboid {
+                half_extents,
+                radius,
+            } => (SHAPE_ROUNDED_CUBOID, half_extents.extend(radius)),
+            SdfPrimitive::Capsule {
+                half_height,
+                radius,
+            } => (SHAPE_CAPSULE, Vec4::new(radius, half_height, 0.0, 0.0)),
+            SdfPrimitive::Cylinder {
+                half_height,
+                radius,
+            } => (SHAPE_CYLINDER, Vec4::new(radius, half_height, 0.0, 0.0)),
+            SdfPrimitive::Torus {
+                major_radius,
+                minor_radius,
+            } => (SHAPE_TORUS, Vec4::new(major_radius, minor_radius, 0.0, 0.0)),
+            SdfPrimitive::Cone {
+                half_height,
+                radius,
+            } => (SHAPE_CONE, Vec4::new(radius, half_height, 0.0, 0.0)),
+            SdfPrimitive::Plane => (SHAPE_PLANE, Vec4::ZERO),
+        };
+        Self {
+            inverse: Mat4::from(transform.inverse()),
+            size,
+            shape,
+            operation: gpu_operation(operation),
+            radius: operation.radius(),
+            scale: sculpter_core::csg_scale(transform),
+        }
+    }
+
+    /// The parts of `shape` to upload, never empty since storage bindings
+    /// can't be: shapes without parts upload an unused placeholder.
+    pub fn from_shape(shape: &SdfShape) -> Vec<Self> {
+        let parts: Vec<Self> = shape
+            .parts
+            .iter()
+            .map(|part| Self::new(part.primitive, part.transform, part.operation))
+            .collect();
+        if parts.is_empty() {
+            return vec![Self::default()];
+        }
+        parts
+    }
+}
+
+/// The kernel's `OPERATION_*` for `operation`, its radius set apart.
+#[cfg(feature = "gpu")]
+pub fn gpu_operation(operation: CsgOperation) -> u32 {
+    match operation {
+        CsgOperation::Union | CsgOperation::SmoothUnion { .. } => OPERATION_UNION,
+        CsgOperation::Subtraction | CsgOperation::SmoothSubtraction { .. } => OPERATION_SUBTRACTION,
+        CsgOperation::Intersection | CsgOperation::SmoothIntersection { .. } => {
+            OPERATION_INTERSECTION
+        }
+    }
+}
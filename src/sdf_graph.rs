@@ -0,0 +1,370 @@
+use std::fmt::{self, Write};
+
+use bevy::{
+    asset::{AssetEvent, AssetLoader, LoadContext, io::Reader},
+    math::Affine3A,
+    platform::collections::HashSet,
+    prelude::*,
+    shader::Shader,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    csg::CsgOperation,
+    gpu_density::{DensityFunction, DensityKernel, GpuDensity},
+    sdf::{GpuSdfPart, SdfPrimitive, gpu_operation},
+};
+
+/// A density function authored as data: primitives, booleans, noise and domain
+/// warps wired together, compiled into a density generation kernel that a
+/// `GpuDensity` runs through `DensityFunction::Graph`.
+///
+/// Loaded from `.sdf.ron` files, whose edits hot reload into the volumes using
+/// them. Densities are distances in the units of the sample positions.
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SdfGraph {
+    /// Nodes, each reading only nodes before it; the last is the output
+    pub nodes: Vec<SdfNode>,
+    /// The compiled kernel, filled in when the graph is loaded
+    #[serde(skip)]
+    pub shader: Handle<Shader>,
+}
+
+/// A node of an `SdfGraph`, reading the nodes it names by index.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum SdfNode {
+    Primitive(SdfPrimitive),
+    /// `input` moved, rotated and uniformly scaled
+    Transform {
+        input: usize,
+        #[serde(default)]
+        translation: Vec3,
+        #[serde(default)]
+        rotation: Quat,
+        #[serde(default = "unit_scale")]
+        scale: f32,
+    },
+    /// `b` combined into `a`
+    Boolean {
+        a: usize,
+        b: usize,
+        operation: CsgOperation,
+    },
+    /// `input` with fractal noise added, roughening its surface by up to
+    /// `amplitude`
+    Noise {
+        input: usize,
+        amplitude: f32,
+        frequency: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u32,
+    },
+    /// `input` looked up at positions displaced by up to `amplitude` by
+    /// fractal noise, for twisted, organic shapes
+    Warp {
+        input: usize,
+        amplitude: f32,
+        frequency: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u32,
+    },
+}
+
+fn unit_scale() -> f32 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    3
+}
+
+impl SdfNode {
+    fn inputs(&self) -> impl Iterator<Item = usize> {
+        let (first, second) = match *self {
+            Self::Primitive(_) => (None, None),
+            Self::Transform { input, .. }
+            | Self::Noise { input, .. }
+            | Self::Warp { input, .. } => (Some(input), None),
+            Self::Boolean { a, b, .. } => (Some(a), Some(b)),
+        };
+        first.into_iter().chain(second)
+    }
+
+    /// Every number the node writes into the compiled kernel.
+    fn parameters(&self) -> Vec<f32> {
+        match *self {
+            Self::Primitive(primitive) => {
+                GpuSdfPart::new(primitive, Affine3A::IDENTITY, CsgOperation::Union)
+                    .size
+                    .to_array()
+                    .to_vec()
+            }
+            Self::Transform {
+                translation,
+                rotation,
+                scale,
+                ..
+            } => [
+                translation.to_array().as_slice(),
+                &rotation.to_array(),
+                &[scale],
+            ]
+            .concat(),
+            Self::Boolean { operation, .. } => vec![operation.radius()],
+            Self::Noise {
+                amplitude,
+                frequency,
+                ..
+            }
+            | Self::Warp {
+                amplitude,
+                frequency,
+                ..
+            } => vec![amplitude, frequency],
+        }
+    }
+}
+
+/// Why an `SdfGraph` couldn't be loaded.
+#[derive(Debug)]
+pub enum SdfGraphError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for SdfGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read .sdf.ron file: {error}"),
+            Self::Ron(error) => write!(f, "could not parse .sdf.ron file: {error}"),
+            Self::Invalid(reason) => write!(f, "invalid SDF graph: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SdfGraphError {}
+
+impl From<std::io::Error> for SdfGraphError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl SdfGraph {
+    /// Check that the graph has an output, that nodes only read nodes before
+    /// them, so it compiles without cycles, that every parameter is finite, as
+    /// WGSL has no literal for NaN or infinity, and that transforms can be
+    /// undone.
+    pub fn validate(&self) -> Result<(), SdfGraphError> {
+        if self.nodes.is_empty() {
+            return Err(SdfGraphError::Invalid("no nodes".into()));
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let Some(input) = node.inputs().find(|&input| input >= index) {
+                return Err(SdfGraphError::Invalid(format!(
+                    "node {index} reads node {input}, which doesn't come before it"
+                )));
+            }
+            if let Some(value) = node
+                .parameters()
+                .into_iter()
+                .find(|value| !value.is_finite())
+            {
+                return Err(SdfGraphError::Invalid(format!(
+                    "node {index} has a parameter of {value}"
+                )));
+            }
+            if let SdfNode::Transform {
+                rotation, scale, ..
+            } = *node
+                && (scale == 0.0 || rotation.length_squared() == 0.0)
+            {
+                return Err(SdfGraphError::Invalid(format!(
+                    "node {index} scales by {scale} and rotates by {rotation}, which can't be undone"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The density generation kernel running the graph, a `generate_density`
+    /// entry point binding the density buffer and parameters as
+    /// `sculpter::density_generation` does.
+    pub fn to_wgsl(&self) -> String {
+        let mut wgsl = String::from(
+            "// Compiled from an SdfGraph\n\
+             #import sculpter::density_generation::{params, sample_position, store_density}\n\
+             #import sculpter::noise::{fbm, NOISE_BASIS_PERLIN}\n\
+             #import sculpter::sdf::{combine, primitive_distance}\n",
+        );
+        for (index, node) in self.nodes.iter().enumerate() {
+            let body = match *node {
+                SdfNode::Primitive(primitive) => {
+                    let part = GpuSdfPart::new(primitive, Affine3A::IDENTITY, CsgOperation::Union);
+                    format!(
+                        "primitive_distance({}u, {}, p)",
+                        part.shape,
+                        vec4_literal(part.size)
+                    )
+                }
+                SdfNode::Transform {
+                    input,
+                    translation,
+                    rotation,
+                    scale,
+                } => {
+                    let inverse = Mat4::from_scale_rotation_translation(
+                        Vec3::splat(scale),
+                        rotation,
+                        translation,
+                    )
+                    .inverse();
+                    format!(
+                        "node_{input}(({} * vec4<f32>(p, 1.0)).xyz) * {}",
+                        mat4_literal(inverse),
+                        float_literal(scale.abs())
+                    )
+                }
+                SdfNode::Boolean { a, b, operation } => format!(
+                    "combine({}u, node_{a}(p), node_{b}(p), {})",
+                    gpu_operation(operation),
+                    float_literal(operation.radius())
+                ),
+                SdfNode::Noise {
+                    input,
+                    amplitude,
+                    frequency,
+                    octaves,
+                    seed,
+                } => format!(
+                    "node_{input}(p) + {} * {}",
+                    float_literal(amplitude),
+                    fbm_call("p", seed, octaves, frequency)
+                ),
+                SdfNode::Warp {
+                    input,
+                    amplitude,
+                    frequency,
+                    octaves,
+                    seed,
+                } => format!(
+                    "node_{input}(p + {} * vec3<f32>({}, {}, {}))",
+                    float_literal(amplitude),
+                    fbm_call("p", seed, octaves, frequency),
+                    fbm_call("p + 5.2", seed.wrapping_add(1), octaves, frequency),
+                    fbm_call("p + 1.3", seed.wrapping_add(2), octaves, frequency)
+                ),
+            };
+            let _ = write!(
+                wgsl,
+                "\nfn node_{index}(p: vec3<f32>) -> f32 {{\n    return {body};\n}}\n"
+            );
+        }
+        let _ = write!(
+            wgsl,
+            "\n@compute @workgroup_size(8, 8, 8)\n\
+             fn generate_density(@builtin(global_invocation_id) global_id: vec3<u32>) {{\n    \
+                 if any(global_id >= params.dimensions) {{\n        return;\n    }}\n    \
+                 store_density(global_id, node_{}(sample_position(global_id)));\n\
+             }}\n",
+            self.nodes.len() - 1
+        );
+        wgsl
+    }
+}
+
+fn float_literal(value: f32) -> String {
+    format!("{value:?}")
+}
+
+fn vec4_literal(value: Vec4) -> String {
+    let [x, y, z, w] = value.to_array().map(float_literal);
+    format!("vec4<f32>({x}, {y}, {z}, {w})")
+}
+
+fn mat4_literal(value: Mat4) -> String {
+    let columns = value.to_cols_array().map(float_literal).join(", ");
+    format!("mat4x4<f32>({columns})")
+}
+
+fn fbm_call(p: &str, seed: u32, octaves: u32, frequency: f32) -> String {
+    format!(
+        "fbm(NOISE_BASIS_PERLIN, {p}, {seed}u, {octaves}u, {}, 2.0, 0.5)",
+        float_literal(frequency)
+    )
+}
+
+/// Loads `.sdf.ron` files as `SdfGraph`s, along with their compiled kernel as
+/// the `Shader` labeled `shader`.
+#[derive(Default)]
+pub struct SdfGraphLoader;
+
+impl AssetLoader for SdfGraphLoader {
+    type Asset = SdfGraph;
+    type Settings = ();
+    type Error = SdfGraphError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<SdfGraph, SdfGraphError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut graph: SdfGraph = ron::de::from_bytes(&bytes).map_err(SdfGraphError::Ron)?;
+        graph.validate()?;
+        let path = format!("{}#shader", load_context.path().display());
+        graph.shader = load_context
+            .add_labeled_asset("shader".into(), Shader::from_wgsl(graph.to_wgsl(), path));
+        Ok(graph)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sdf.ron"]
+    }
+}
+
+/// Give `GpuDensity` volumes running a graph its compiled kernel once it has
+/// loaded, a new revision of it whenever the file changes, and take it away
+/// from volumes no longer running one.
+pub fn sync_sdf_graphs(
+    mut commands: Commands,
+    mut graph_events: MessageReader<AssetEvent<SdfGraph>>,
+    graphs: Res<Assets<SdfGraph>>,
+    volumes: Query<(Entity, Ref<GpuDensity>, Has<DensityKernel>)>,
+    mut revision: Local<u32>,
+) {
+    let changed_graphs: HashSet<AssetId<SdfGraph>> = graph_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (entity, density, has_kernel) in &volumes {
+        let DensityFunction::Graph(handle) = &density.function else {
+            if has_kernel && density.is_changed() {
+                commands.entity(entity).remove::<DensityKernel>();
+            }
+            continue;
+        };
+        if !density.is_changed() && !changed_graphs.contains(&handle.id()) {
+            continue;
+        }
+        // Not loaded yet, the load event comes back to it
+        let Some(graph) = graphs.get(handle) else {
+            continue;
+        };
+        *revision = revision.wrapping_add(1);
+        commands.entity(entity).try_insert(DensityKernel {
+            shader: graph.shader.clone(),
+            revision: *revision,
+        });
+    }
+}
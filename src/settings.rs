@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::render::render_resource::ShaderType;
+pub use sculpter_core::{
+    AmbientOcclusion, DegenerateFilter, Simplification, Smoothing, VertexPlacement,
+    VertexRelaxation,
+};
+
+/// Which implementation meshes a volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum SculptBackend {
+    /// Compute shader pipeline with GPU readback. Best for large fields such as terrain.
+    #[default]
+    Gpu,
+    /// Meshing on the CPU, in the background on the `AsyncComputeTaskPool`. Avoids
+    /// the readback round trip, which suits tiny, frequently-updated volumes, and
+    /// builds the same meshes as the GPU.
+    Cpu,
+}
+
+/// How texture coordinates are generated for a volume's mesh, as `Mesh::ATTRIBUTE_UV_0`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum UvGeneration {
+    /// No UVs, textures are projected in the shader instead, as `TriplanarMaterial`
+    /// does.
+    #[default]
+    Triplanar,
+    /// World-space positions projected onto the plane facing `direction`, one
+    /// texture repeat per `tile_size` meters, so neighbouring volumes line up.
+    Planar { direction: Vec3, tile_size: f32 },
+    /// Each volume unwrapped onto a 3x2 atlas, one tile per side of its bounding
+    /// box, every triangle going to the side it faces most.
+    BoxAtlas,
+}
+
+/// Which side of the surface a volume's mesh faces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum SurfaceSides {
+    /// Faces point out of the solid, as seen from the open space around it.
+    #[default]
+    Outside,
+    /// Only the interior surface, facing into the solid, for cave interiors and
+    /// hollow shells viewed from within.
+    Inside,
+    /// Both sides, each with its own vertices and normals, so thin or open
+    /// surfaces are lit correctly from either side. Doubles the geometry.
+    Both,
+}
+
+/// Isosurface extraction algorithm for a volume. Volumes without this component
+/// use surface nets.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum MeshingAlgorithm {
+    /// One vertex per surface cell, joined by quads. Smooth and compact.
+    #[default]
+    SurfaceNets,
+    /// Every cell is split into six tetrahedra, which have no ambiguous cases,
+    /// so the topology is always consistent. Produces more triangles.
+    MarchingTetrahedra,
+    /// Classic marching cubes, up to five triangles per cell from a lookup table.
+    /// Ambiguous cube faces are resolved the same way from both cells sharing
+    /// them, so the mesh is watertight.
+    MarchingCubes,
+    /// Surface nets topology with each vertex placed by solving the quadric error
+    /// of the cell's edge crossings and normals, so hard edges from boxes and CSG
+    /// cuts stay crisp instead of being rounded off.
+    DualContouring,
+    /// Every voxel with a negative density drawn as a cube, Minecraft style, with
+    /// the exposed faces merged into large quads. Any edit remeshes the whole
+    /// volume, since merged quads span it.
+    Blocky,
+    /// For heightfields, solid below a single height per column: each column is
+    /// binary searched for its surface rather than every cell visited, and the
+    /// mesh is a regular grid of quads with one vertex per column. Overhangs and
+    /// caves are lost.
+    Heightfield,
+}
+
+/// Per-volume meshing options. Volumes without this component use the defaults.
+#[derive(Component, Clone, Debug, Default)]
+pub struct SculptSettings {
+    pub backend: SculptBackend,
+    pub vertex_placement: VertexPlacement,
+    pub relaxation: VertexRelaxation,
+    /// Spread the seven GPU compute stages over several frames, dispatching at most
+    /// this many per frame. Bounds per-frame GPU time on weak hardware at the cost
+    /// of latency. `None` runs every stage in one frame.
+    pub stages_per_frame: Option<u32>,
+    /// Hang skirts this many voxels deep below the border edges of the mesh, so
+    /// cracks against neighbours meshed at another resolution are hidden. A
+    /// cheaper alternative to `LodTransitions` for any algorithm but blocky,
+    /// which has no cracks. 0 disables them.
+    pub skirt_depth: f32,
+    /// With `MeshingAlgorithm::DualContouring`, contour an adaptive octree instead
+    /// of the uniform grid: cells merge while the quadric error of the merged cell
+    /// stays under this threshold (squared voxels), so smooth and flat stretches
+    /// take far fewer triangles. The octree is built on the CPU, so such volumes
+    /// are meshed there whatever their `backend`.
+    pub adaptive_error: Option<f32>,
+    /// Faces meeting at more than this angle (radians) get their own copies of
+    /// the vertices between them with face normals, keeping intentional hard
+    /// edges sharp. `None` smooths every vertex.
+    pub crease_angle: Option<f32>,
+    /// Merge coincident vertices and drop zero-area and sliver triangles, which
+    /// surface nets occasionally produces at boundaries and physics engines
+    /// reject. `None` keeps every triangle.
+    pub degenerate_filter: Option<DegenerateFilter>,
+    /// Smooth the mesh, ironing out voxel-grid ripples on organic shapes. Unlike
+    /// `relaxation`, which pulls vertices back onto the surface, this averages
+    /// them with their neighbours on the CPU. `None` keeps the raw positions.
+    pub smoothing: Option<Smoothing>,
+    /// Decimate the mesh by quadric error edge collapses, merging the near-coplanar
+    /// faces of flat stretches. Borders stay put, so neighbours still line up.
+    /// `None` keeps every face.
+    pub simplification: Option<Simplification>,
+    /// Which side of the surface gets faces. Combined with `InvertWinding`,
+    /// `Inside` faces out again.
+    pub sides: SurfaceSides,
+    /// Always give the `Mesh` 32-bit indices. Otherwise meshes of up to 65536
+    /// vertices get 16-bit ones, halving their index memory.
+    pub force_u32_indices: bool,
+    /// Store positions as half floats and normals octahedral encoded, about
+    /// halving the vertex buffer. Volumes without a material then draw with a
+    /// `PackedVertexMaterial`, the only material reading them. Ignored with
+    /// `MaterialSubmeshes` or a `StandardMaterial` or `TriplanarMaterial`.
+    pub packed_vertices: bool,
+    /// Texture coordinates written to the mesh.
+    pub uv_generation: UvGeneration,
+    /// Bake ambient occlusion into each vertex, as `ATTRIBUTE_AMBIENT_OCCLUSION`
+    /// (`AMBIENT_OCCLUSION_ATTRIBUTE` in plain mesh data), darkening caves and
+    /// overhangs. `None` skips the rays.
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+}
+
+impl SculptSettings {
+    /// Whether volumes with these settings are meshed on the CPU.
+    pub(crate) fn meshes_on_cpu(&self, algorithm: MeshingAlgorithm) -> bool {
+        self.backend == SculptBackend::Cpu
+            || (algorithm == MeshingAlgorithm::DualContouring && self.adaptive_error.is_some())
+    }
+}
+
+// Must match `VERTEX_PLACEMENT_*` in generate_vertices.wgsl
+#[cfg(feature = "gpu")]
+pub(crate) const VERTEX_PLACEMENT_CROSSING_AVERAGE: u32 = 0;
+#[cfg(feature = "gpu")]
+pub(crate) const VERTEX_PLACEMENT_CELL_CENTROID: u32 = 1;
+#[cfg(feature = "gpu")]
+pub(crate) const VERTEX_PLACEMENT_GRADIENT_PROJECTED: u32 = 2;
+
+/// Uniform passed to the meshing shaders, built from `SculptSettings`.
+#[cfg(feature = "gpu")]
+#[derive(ShaderType, Clone, Copy, Debug, Default)]
+pub struct SurfaceNetsParams {
+    pub vertex_placement: u32,
+    pub relaxation_iterations: u32,
+    pub relaxation_strength: f32,
+    pub occlusion_rays: u32,
+    pub occlusion_distance: f32,
+    /// 1 when the volume's `MaterialField` is bound, set by `SurfaceNetsBuffers`
+    pub materials: u32,
+    /// Samples skipped per grid step, above 1 for LOD levels meshed from the
+    /// volume's own density buffer
+    pub stride: u32,
+    /// Dimensions of the bound density field, set by `SurfaceNetsBuffers`
+    pub source_dimensions: UVec3,
+}
+
+#[cfg(feature = "gpu")]
+impl From<&SculptSettings> for SurfaceNetsParams {
+    fn from(settings: &SculptSettings) -> Self {
+        Self {
+            vertex_placement: match settings.vertex_placement {
+                VertexPlacement::CrossingAverage => VERTEX_PLACEMENT_CROSSING_AVERAGE,
+                VertexPlacement::CellCentroid => VERTEX_PLACEMENT_CELL_CENTROID,
+                VertexPlacement::GradientProjected => VERTEX_PLACEMENT_GRADIENT_PROJECTED,
+            },
+            relaxation_iterations: settings.relaxation.iterations,
+            relaxation_strength: settings.relaxation.strength,
+            occlusion_rays: settings
+                .ambient_occlusion
+                .map_or(0, |occlusion| occlusion.rays),
+            occlusion_distance: settings
+                .ambient_occlusion
+                .map_or(0.0, |occlusion| occlusion.distance),
+            materials: 0,
+            stride: 1,
+            source_dimensions: UVec3::ZERO,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use bevy::{
+    ecs::entity::EntityHashSet,
+    prelude::*,
+    tasks::{Task, block_on, futures_lite::future},
+};
+
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize,
+    buffers::SurfaceNetsBuffers,
+    cpu::spawn_downsampled_mesh,
+    mesh::GeneratedMesh,
+    mesh_data::{MeshData, ToMesh},
+    settings::MeshingAlgorithm,
+    units::VoxelSpacing,
+};
+
+/// Also build a `ShadowProxy` whenever this volume is meshed, from its
+/// `DensityField` downsampled `downsample` times along each axis.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GenerateShadowProxy {
+    pub downsample: u32,
+}
+
+impl Default for GenerateShadowProxy {
+    fn default() -> Self {
+        Self { downsample: 4 }
+    }
+}
+
+/// Low resolution stand-in of the volume's mesh, scaled like it, for shadow
+/// casting or far away drawing. Nothing draws it by default: e.g. give it to a
+/// child with `NotShadowReceiver` and the volume a `NotShadowCaster`.
+#[derive(Component, Clone, Debug)]
+pub struct ShadowProxy(pub Handle<Mesh>);
+
+/// Shadow proxy meshing running on the `AsyncComputeTaskPool`.
+#[derive(Component)]
+pub struct ShadowProxyTask(Task<MeshData>);
+
+/// Start meshing the shadow proxies of freshly meshed volumes, from their field
+/// at the size their GPU buffers were made for. A volume remeshed while its task
+/// runs starts over, dropping the stale task. Proxies of volumes that dropped
+/// `GenerateShadowProxy` are removed.
+pub fn build_shadow_proxies(
+    mut commands: Commands,
+    mesh_size: Res<DensityFieldMeshSize>,
+    dimensions: Res<DensityFieldSize>,
+    query: Query<(
+        Entity,
+        &DensityField,
+        &GenerateShadowProxy,
+        Option<&SurfaceNetsBuffers>,
+        Option<&MeshingAlgorithm>,
+        Option<&VoxelSpacing>,
+    )>,
+    changed: Query<Entity, Changed<GenerateShadowProxy>>,
+    // `build_mesh_from_readback` takes the `GeneratedMesh` of every volume it meshes
+    mut built: RemovedComponents<GeneratedMesh>,
+    mut removed: RemovedComponents<GenerateShadowProxy>,
+) {
+    for entity in removed.read() {
+        commands
+            .entity(entity)
+            .try_remove::<(ShadowProxy, ShadowProxyTask)>();
+    }
+
+    let entities: EntityHashSet = built.read().chain(&changed).collect();
+    for (entity, density_field, proxy, buffers, algorithm, spacing) in query.iter_many(entities) {
+        let dimensions = buffers.map_or(*dimensions, |buffers| buffers.dimensions);
+        if density_field.len() != dimensions.density_count() as usize {
+            continue;
+        }
+
+        let task = spawn_downsampled_mesh(
+            density_field,
+            dimensions,
+            proxy.downsample,
+            algorithm.copied().unwrap_or_default(),
+            VoxelSpacing::volume_scale(spacing, *mesh_size, dimensions.0),
+        );
+        commands.entity(entity).try_insert(ShadowProxyTask(task));
+    }
+}
+
+/// Attach finished shadow proxies, reusing the asset of the previous one.
+pub fn poll_shadow_proxy_tasks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut tasks: Query<(Entity, &mut ShadowProxyTask, Option<&ShadowProxy>)>,
+) {
+    for (entity, mut task, existing) in &mut tasks {
+        let Some(mesh_data) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        let mesh = mesh_data.to_mesh();
+        let handle = match existing {
+            Some(ShadowProxy(handle)) => {
+                let _ = meshes.insert(handle, mesh);
+                handle.clone()
+            }
+            None => meshes.add(mesh),
+        };
+        commands
+            .entity(entity)
+            .try_remove::<ShadowProxyTask>()
+            .try_insert(ShadowProxy(handle));
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::{
+    asset::{AssetEvent, AssetLoader, LoadContext, io::Reader},
+    platform::collections::HashSet,
+    prelude::*,
+};
+use sculpter_core::{CoordinateSystem, SparseVolume, SparseVolumeError};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DensityField, DensityFieldSize,
+    chunk::{Chunk, ChunkMap},
+    dirty_region::DensityFieldDirtyRegion,
+    mesh::Meshed,
+};
+
+/// A sparse volume loaded from an `.svol` file or a NanoVDB `.nvdb` float grid,
+/// whose layout and supported subset are described in `sculpter_core::sparse`.
+#[derive(Asset, TypePath, Clone, Debug, Deref)]
+pub struct SparseVolumeAsset(pub SparseVolume);
+
+impl SparseVolumeAsset {
+    /// Coordinates of the chunks of `map` covering the volume's active leaves.
+    /// Spawn a `Chunk` with a `SparseVolumeSource` at each to import all of it.
+    pub fn chunks(
+        &self,
+        map: &ChunkMap,
+        dimensions: DensityFieldSize,
+    ) -> impl Iterator<Item = IVec3> + use<> {
+        let (min, max) = self
+            .bounds()
+            .map(|(min, max)| (map.chunk_at(min, dimensions), map.chunk_at(max, dimensions)))
+            .unwrap_or((IVec3::ONE, IVec3::ZERO));
+        (min.z..=max.z).flat_map(move |z| {
+            (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| ivec3(x, y, z)))
+        })
+    }
+}
+
+/// Densities of a `SparseVolumeAsset` filling the volume's `DensityField`: a
+/// `Chunk` from its place in the `ChunkMap`, any other volume from the sparse
+/// volume's sample 0. Filled once the asset has loaded and again whenever it
+/// changes.
+#[derive(Component, Clone, Debug)]
+pub struct SparseVolumeSource(pub Handle<SparseVolumeAsset>);
+
+/// Loads `.svol` and `.nvdb` files as `SparseVolumeAsset`s.
+#[derive(Default)]
+pub struct SparseVolumeLoader;
+
+/// How `SparseVolumeLoader` reads a file.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct SparseVolumeLoaderSettings {
+    /// Axes of the file, Bevy's own by default as `sparse_volume_from_chunks` writes them
+    pub coordinate_system: CoordinateSystem,
+}
+
+impl AssetLoader for SparseVolumeLoader {
+    type Asset = SparseVolumeAsset;
+    type Settings = SparseVolumeLoaderSettings;
+    type Error = SparseVolumeError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &SparseVolumeLoaderSettings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<SparseVolumeAsset, SparseVolumeError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let volume = if bytes.starts_with(b"NanoVDB") {
+            SparseVolume::read_nanovdb(&bytes)?
+        } else {
+            SparseVolume::read(&bytes)?
+        };
+        Ok(SparseVolumeAsset(
+            volume.to_bevy(settings.coordinate_system),
+        ))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["svol", "nvdb"]
+    }
+}
+
+/// The densities of every chunk of `map` in `coordinate_system`'s axes, samples
+/// equal to `background` left inactive, to save with `SparseVolume::write`.
+pub fn sparse_volume_from_chunks(
+    map: &ChunkMap,
+    fields: &Query<&DensityField>,
+    dimensions: DensityFieldSize,
+    background: f32,
+    coordinate_system: CoordinateSystem,
+) -> SparseVolume {
+    let mut volume = SparseVolume::new(background);
+    for (coord, entity) in map.iter() {
+        if let Ok(field) = fields.get(entity) {
+            volume.insert_dense(field, dimensions.0, map.sample_origin(coord, dimensions));
+        }
+    }
+    volume.from_bevy(coordinate_system)
+}
+
+/// Fill volumes whose `SparseVolumeSource` or sparse volume changed, remeshing
+/// volumes that were already meshed.
+pub fn sync_sparse_volume_sources(
+    mut commands: Commands,
+    mut volume_events: MessageReader<AssetEvent<SparseVolumeAsset>>,
+    sources: Query<(Entity, Ref<SparseVolumeSource>, Option<&Chunk>, Has<Meshed>)>,
+    volumes: Res<Assets<SparseVolumeAsset>>,
+    map: Res<ChunkMap>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let changed_volumes: HashSet<AssetId<SparseVolumeAsset>> = volume_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, source, chunk, meshed) in &sources {
+        if !source.is_changed() && !changed_volumes.contains(&source.0.id()) {
+            continue;
+        }
+        // Filled once it has loaded
+        let Some(volume) = volumes.get(&source.0) else {
+            continue;
+        };
+
+        let origin = chunk.map_or(IVec3::ZERO, |chunk| map.sample_origin(**chunk, *dimensions));
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert(DensityField(volume.to_dense(origin, dimensions.0)));
+        if meshed {
+            entity_commands.try_insert(DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0));
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use bevy::{platform::collections::HashSet, prelude::*};
+
+#[cfg(feature = "gpu")]
+use crate::mesh::GeneratedMesh;
+use crate::{
+    DensityField, DensityFieldMeshSize, DensityFieldSize,
+    chunk::{Chunk, ChunkMap},
+    units::VoxelSpacing,
+};
+
+/// Keep the `ChunkMap` streamed in around this entity, e.g. the player's camera.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct StreamingAnchor;
+
+/// Marks chunks spawned by streaming, which despawns or reuses them once no
+/// `StreamingAnchor` is near. Chunks spawned otherwise are left alone.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct StreamedChunk;
+
+/// How the `ChunkMap` is streamed around `StreamingAnchor`s.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ChunkStreaming {
+    /// Chunks whose coordinate lies within this many chunks of an anchor's are
+    /// spawned
+    pub radius: u32,
+    /// Chunks spawned or reused per frame at most, nearest first, so entering a
+    /// new area doesn't stall a frame
+    pub budget: usize,
+}
+
+impl Default for ChunkStreaming {
+    fn default() -> Self {
+        Self {
+            radius: 4,
+            budget: 16,
+        }
+    }
+}
+
+/// Triggered on a streamed chunk that needs densities, fresh or reused for
+/// another coordinate. Insert its `DensityField`, sampled from `origin` on, to
+/// have it meshed, or let a `ChunkGenerator` fill it.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct ChunkRequested {
+    pub entity: Entity,
+    pub coord: IVec3,
+    /// Map-wide sample the chunk's first sample lies at
+    pub origin: IVec3,
+}
+
+/// Spawn the chunks in range of the anchors and request their densities, and
+/// take those out of range away. Chunks leaving range are reused for the ones
+/// entering it, so their GPU buffers are rewritten instead of freed and
+/// allocated again, and despawned only once every chunk in range is spawned.
+pub fn stream_chunks(
+    mut commands: Commands,
+    streaming: Res<ChunkStreaming>,
+    map: Res<ChunkMap>,
+    anchors: Query<&GlobalTransform, With<StreamingAnchor>>,
+    streamed: Query<(Entity, &Chunk), With<StreamedChunk>>,
+    dimensions: Res<DensityFieldSize>,
+    mesh_size: Res<DensityFieldMeshSize>,
+) {
+    let scale = VoxelSpacing::volume_scale(None, *mesh_size, dimensions.0);
+    let centers: Vec<IVec3> = anchors
+        .iter()
+        .map(|anchor| {
+            let sample = (anchor.translation() / scale).floor().as_ivec3();
+            map.chunk_at(sample, *dimensions)
+        })
+        .collect();
+    let radius = streaming.radius as i32;
+    let distance = |coord: IVec3| {
+        centers
+            .iter()
+            .map(|center| (coord - center).as_vec3().length())
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    let mut wanted = HashSet::new();
+    for center in &centers {
+        for z in -radius..=radius {
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    let coord = center + ivec3(x, y, z);
+                    if distance(coord) <= radius as f32 {
+                        wanted.insert(coord);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut missing: Vec<IVec3> = wanted
+        .iter()
+        .copied()
+        .filter(|&coord| map.get(coord).is_none())
+        .collect();
+    missing.sort_by(|a, b| distance(*a).total_cmp(&distance(*b)));
+    // Chunks still left to stream in next frame can reuse the leaving ones
+    let backlog = missing.len() > streaming.budget;
+    missing.truncate(streaming.budget);
+
+    let mut leaving = streamed
+        .iter()
+        .filter(|(_, chunk)| !wanted.contains(&chunk.0))
+        .map(|(entity, _)| entity);
+    for coord in missing {
+        let entity = match leaving.next() {
+            Some(entity) => {
+                let mut entity_commands = commands.entity(entity);
+                entity_commands
+                    .remove::<(Chunk, DensityField)>()
+                    .insert(Chunk(coord));
+                // Its old mesh shows until the new densities are meshed
+                #[cfg(feature = "gpu")]
+                entity_commands.insert(Visibility::Hidden);
+                entity
+            }
+            None => commands.spawn((Chunk(coord), StreamedChunk)).id(),
+        };
+        commands.trigger(ChunkRequested {
+            entity,
+            coord,
+            origin: map.sample_origin(coord, *dimensions),
+        });
+    }
+    if !backlog {
+        for entity in leaving {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Show reused chunks again once their new densities are meshed.
+#[cfg(feature = "gpu")]
+pub fn reveal_streamed_chunks(
+    mut built: RemovedComponents<GeneratedMesh>,
+    mut chunks: Query<&mut Visibility, With<StreamedChunk>>,
+) {
+    let mut iter = chunks.iter_many_mut(built.read());
+    while let Some(mut visibility) = iter.fetch_next() {
+        if *visibility == Visibility::Hidden {
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
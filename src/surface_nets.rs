@@ -0,0 +1,210 @@
+#[cfg(feature = "gpu")]
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::render::{
+    render_resource::{binding_types::*, *},
+    renderer::RenderDevice,
+};
+
+use crate::backend::MeshingBackend;
+#[cfg(feature = "gpu")]
+use crate::{
+    backend::{BackendBindings, BackendInit, BackendKernels},
+    dirty_region::MeshingRegion,
+    quantize::DensityDecode,
+    settings::SurfaceNetsParams,
+};
+
+#[cfg(feature = "gpu")]
+const GENERATE_VERTICES_SHADER: &str = "shaders/generate_vertices.wgsl";
+#[cfg(feature = "gpu")]
+const GENERATE_FACES_SHADER: &str = "shaders/generate_faces.wgsl";
+
+// Indices into `BackendKernels::layouts`
+#[cfg(feature = "gpu")]
+const GENERATE_VERTICES_LAYOUT: usize = 0;
+#[cfg(feature = "gpu")]
+const GENERATE_VERTICES_TEXTURE_LAYOUT: usize = 1;
+#[cfg(feature = "gpu")]
+const GENERATE_FACES_LAYOUT: usize = 2;
+
+/// One vertex per surface cell, joined by quads.
+pub struct SurfaceNets;
+
+#[cfg(feature = "gpu")]
+impl SurfaceNets {
+    /// Surface nets' layouts and face kernel, with vertices placed by `entry_point`
+    /// of `shader` (bound like generate_vertices.wgsl). `labels` name the buffer
+    /// and texture input vertex pipelines.
+    pub(crate) fn kernels_with_vertex_stage(
+        init: &BackendInit,
+        labels: [&'static str; 2],
+        shader: &'static str,
+        entry_point: &'static str,
+    ) -> BackendKernels {
+        // Layout 1: Generate Vertices
+        let generate_vertices_layout = init.render_device.create_bind_group_layout(
+            "GenerateVerticesLayout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    storage_buffer_read_only::<Vec<u32>>(false), // density_field (f32 bits or packed bytes)
+                    storage_buffer::<Vec<f32>>(false),           // vertices (output)
+                    storage_buffer::<Vec<u32>>(false),           // vertex_valid (output)
+                    uniform_buffer::<UVec3>(false),              // dimensions
+                    uniform_buffer::<DensityDecode>(false),      // density_decode
+                    uniform_buffer::<MeshingRegion>(false),      // region
+                    uniform_buffer::<SurfaceNetsParams>(false),  // params
+                ),
+            ),
+        );
+
+        // Layout 1b: Generate Vertices from a 3D density texture
+        let generate_vertices_texture_layout = init.render_device.create_bind_group_layout(
+            "GenerateVerticesTextureLayout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    texture_3d(TextureSampleType::Float { filterable: true }), // density_texture
+                    storage_buffer::<Vec<f32>>(false),                         // vertices (output)
+                    storage_buffer::<Vec<u32>>(false), // vertex_valid (output)
+                    uniform_buffer::<UVec3>(false),    // dimensions
+                    uniform_buffer::<DensityDecode>(false), // density_decode
+                    uniform_buffer::<MeshingRegion>(false), // region
+                    uniform_buffer::<SurfaceNetsParams>(false), // params
+                    sampler(SamplerBindingType::Filtering), // density_sampler
+                ),
+            ),
+        );
+
+        // Layout 4: Generate Faces
+        let generate_faces_layout = init.render_device.create_bind_group_layout(
+            "GenerateFacesLayout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    storage_buffer_read_only::<Vec<u32>>(false), // vertex_valid
+                    storage_buffer_read_only::<Vec<u32>>(false), // vertex_indices
+                    storage_buffer::<Vec<u32>>(false),           // faces (output)
+                    storage_buffer::<Vec<u32>>(false),           // face_valid (output)
+                    uniform_buffer::<UVec3>(false),              // dimensions
+                    uniform_buffer::<MeshingRegion>(false),      // region
+                ),
+            ),
+        );
+
+        let generate_vertices_pipeline = init.queue_pipeline(
+            labels[0],
+            &generate_vertices_layout,
+            shader,
+            entry_point,
+            vec![],
+        );
+        let generate_vertices_texture_pipeline = init.queue_pipeline(
+            labels[1],
+            &generate_vertices_texture_layout,
+            shader,
+            entry_point,
+            vec!["DENSITY_TEXTURE".into()],
+        );
+        // Faces only read the vertex flags, so both inputs share this kernel
+        let generate_faces_pipeline = init.queue_pipeline(
+            "generate_faces_pipeline",
+            &generate_faces_layout,
+            GENERATE_FACES_SHADER,
+            "generate_faces",
+            vec![],
+        );
+
+        BackendKernels {
+            buffer: [generate_vertices_pipeline, generate_faces_pipeline],
+            texture: [generate_vertices_texture_pipeline, generate_faces_pipeline],
+            layouts: vec![
+                generate_vertices_layout,
+                generate_vertices_texture_layout,
+                generate_faces_layout,
+            ],
+            buffers: vec![],
+        }
+    }
+}
+
+impl MeshingBackend for SurfaceNets {
+    #[cfg(feature = "gpu")]
+    fn vertices_per_point(&self) -> u32 {
+        1
+    }
+
+    #[cfg(feature = "gpu")]
+    fn faces_per_point(&self) -> u32 {
+        3
+    }
+
+    #[cfg(feature = "gpu")]
+    fn init_kernels(&self, init: &BackendInit) -> BackendKernels {
+        Self::kernels_with_vertex_stage(
+            init,
+            [
+                "generate_vertices_pipeline",
+                "generate_vertices_texture_pipeline",
+            ],
+            GENERATE_VERTICES_SHADER,
+            "generate_vertices",
+        )
+    }
+
+    #[cfg(feature = "gpu")]
+    fn bind_groups(
+        &self,
+        kernels: &BackendKernels,
+        render_device: &RenderDevice,
+        bindings: &BackendBindings,
+    ) -> [BindGroup; 2] {
+        // Bind Group 1: Generate Vertices (from the density buffer or texture)
+        let generate_vertices_bg = match &bindings.density_sampler {
+            Some(density_sampler) => render_device.create_bind_group(
+                Some("generate_vertices_texture_bind_group"),
+                &kernels.layouts[GENERATE_VERTICES_TEXTURE_LAYOUT],
+                &BindGroupEntries::sequential((
+                    bindings.density.clone(),
+                    bindings.vertices.clone(),
+                    bindings.vertex_valid.clone(),
+                    bindings.dimensions.clone(),
+                    bindings.density_decode.clone(),
+                    bindings.region.clone(),
+                    bindings.params.clone(),
+                    density_sampler.clone(),
+                )),
+            ),
+            None => render_device.create_bind_group(
+                Some("generate_vertices_bind_group"),
+                &kernels.layouts[GENERATE_VERTICES_LAYOUT],
+                &BindGroupEntries::sequential((
+                    bindings.density.clone(),
+                    bindings.vertices.clone(),
+                    bindings.vertex_valid.clone(),
+                    bindings.dimensions.clone(),
+                    bindings.density_decode.clone(),
+                    bindings.region.clone(),
+                    bindings.params.clone(),
+                )),
+            ),
+        };
+
+        // Bind Group 4: Generate Faces
+        let generate_faces_bg = render_device.create_bind_group(
+            Some("generate_faces_bind_group"),
+            &kernels.layouts[GENERATE_FACES_LAYOUT],
+            &BindGroupEntries::sequential((
+                bindings.vertex_valid.clone(),
+                bindings.vertex_indices.clone(),
+                bindings.faces.clone(),
+                bindings.face_valid.clone(),
+                bindings.dimensions.clone(),
+                bindings.region.clone(),
+            )),
+        );
+
+        [generate_vertices_bg, generate_faces_bg]
+    }
+}
@@ -0,0 +1,264 @@
+use std::io;
+
+use bevy::prelude::*;
+#[cfg(feature = "gpu")]
+use bevy::{
+    asset::RenderAssetUsages,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::DensityField;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+// Header, index and the level index of a single level
+const KTX2_DFD_OFFSET: u32 = 12 + 9 * 4 + 4 * 4 + 2 * 8 + 3 * 8;
+// Total size, block header and one sample
+const KTX2_DFD_LENGTH: u32 = 4 + 24 + 16;
+
+/// Texel format a `DensityField` is baked to as a 3D texture, one red channel
+/// holding the density.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DensityTextureFormat {
+    /// Exact densities, but not filterable on every device
+    R32Float,
+    /// Half precision, filterable everywhere
+    R16Float,
+    /// Densities from `-range` to `range` mapped to [-1, 1], clamped outside
+    /// it. A quarter of the size and filterable, with the surface kept at 0.
+    R8Snorm { range: f32 },
+}
+
+impl DensityTextureFormat {
+    /// Bytes per texel.
+    pub fn texel_size(&self) -> u32 {
+        match self {
+            Self::R32Float => 4,
+            Self::R16Float => 2,
+            Self::R8Snorm { .. } => 1,
+        }
+    }
+
+    /// The matching `VkFormat`, as stored in KTX2 files.
+    fn vk_format(&self) -> u32 {
+        match self {
+            Self::R32Float => 100,
+            Self::R16Float => 76,
+            Self::R8Snorm { .. } => 10,
+        }
+    }
+
+    /// The matching texture format, accepted by `DensityTexture`.
+    #[cfg(feature = "gpu")]
+    pub fn texture_format(&self) -> TextureFormat {
+        match self {
+            Self::R32Float => TextureFormat::R32Float,
+            Self::R16Float => TextureFormat::R16Float,
+            Self::R8Snorm { .. } => TextureFormat::R8Snorm,
+        }
+    }
+
+    /// Texel data of a `dimensions` field, little endian.
+    fn encode(&self, densities: &[f32], dimensions: UVec3) -> Vec<u8> {
+        assert_eq!(
+            densities.len(),
+            dimensions.element_product() as usize,
+            "density count doesn't match the dimensions"
+        );
+        match *self {
+            Self::R32Float => densities.iter().flat_map(|d| d.to_le_bytes()).collect(),
+            Self::R16Float => densities
+                .iter()
+                .flat_map(|&d| sculpter_core::f32_to_f16_bits(d).to_le_bytes())
+                .collect(),
+            Self::R8Snorm { range } => densities
+                .iter()
+                .map(|&d| ((d / range).clamp(-1.0, 1.0) * 127.0).round() as i8 as u8)
+                .collect(),
+        }
+    }
+}
+
+impl DensityField {
+    /// Bake the field into a single-level KTX2 3D texture of the given
+    /// dimensions, for raymarching, volumetric fog and other GPU consumers.
+    pub fn write_ktx2(
+        &self,
+        dimensions: UVec3,
+        format: DensityTextureFormat,
+        mut writer: impl io::Write,
+    ) -> io::Result<()> {
+        let data = format.encode(&self.0, dimensions);
+        let u32s = |writer: &mut dyn io::Write, values: &[u32]| {
+            values
+                .iter()
+                .try_for_each(|value| writer.write_all(&value.to_le_bytes()))
+        };
+
+        writer.write_all(&KTX2_IDENTIFIER)?;
+        u32s(
+            &mut writer,
+            &[
+                format.vk_format(),
+                format.texel_size(),
+                dimensions.x,
+                dimensions.y,
+                dimensions.z,
+                // No array layers, one face, one level, no supercompression
+                0,
+                1,
+                1,
+                0,
+            ],
+        )?;
+        // Data format descriptor, no key/value data
+        u32s(&mut writer, &[KTX2_DFD_OFFSET, KTX2_DFD_LENGTH, 0, 0])?;
+        // No supercompression global data, then the level's offset and sizes
+        let level_offset = u64::from(KTX2_DFD_OFFSET + KTX2_DFD_LENGTH);
+        let level_length = data.len() as u64;
+        for value in [0, 0, level_offset, level_length, level_length] {
+            writer.write_all(&u64::to_le_bytes(value))?;
+        }
+        write_ktx2_dfd(&mut writer, format)?;
+        writer.write_all(&data)
+    }
+
+    /// The field as a 3D `Image` of the given dimensions, for a
+    /// `DensityTexture` or the app's own shaders.
+    #[cfg(feature = "gpu")]
+    pub fn to_image(&self, dimensions: UVec3, format: DensityTextureFormat) -> Image {
+        Image::new(
+            Extent3d {
+                width: dimensions.x,
+                height: dimensions.y,
+                depth_or_array_layers: dimensions.z,
+            },
+            TextureDimension::D3,
+            format.encode(&self.0, dimensions),
+            format.texture_format(),
+            RenderAssetUsages::default(),
+        )
+    }
+}
+
+/// Basic data format descriptor of a single linear red channel.
+fn write_ktx2_dfd(writer: &mut impl io::Write, format: DensityTextureFormat) -> io::Result<()> {
+    const SIGNED: u32 = 0x40;
+    const FLOAT: u32 = 0x80;
+    let bits = format.texel_size() * 8;
+    let (qualifiers, lower, upper) = match format {
+        DensityTextureFormat::R32Float | DensityTextureFormat::R16Float => {
+            (FLOAT | SIGNED, (-1.0f32).to_bits(), 1.0f32.to_bits())
+        }
+        DensityTextureFormat::R8Snorm { .. } => (SIGNED, -127i32 as u32, 127),
+    };
+    let words = [
+        KTX2_DFD_LENGTH,
+        // Khronos basic descriptor block, version 2
+        0,
+        2 | (KTX2_DFD_LENGTH - 4) << 16,
+        // RGBSDA color model, BT.709 primaries, linear transfer
+        1 | 1 << 8 | 1 << 16,
+        // One texel per block
+        0,
+        format.texel_size(),
+        0,
+        // The red channel, over every bit of the texel
+        (bits - 1) << 16 | qualifiers << 24,
+        0,
+        lower,
+        upper,
+    ];
+    for word in words {
+        writer.write_all(&word.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn ktx2(format: DensityTextureFormat, densities: Vec<f32>, dimensions: UVec3) -> Vec<u8> {
+        let mut file = Vec::new();
+        DensityField(densities)
+            .write_ktx2(dimensions, format, &mut file)
+            .unwrap();
+        file
+    }
+
+    #[test]
+    fn ktx2_header_describes_the_level() {
+        let dimensions = uvec3(3, 2, 4);
+        let count = dimensions.element_product() as usize;
+        let file = ktx2(DensityTextureFormat::R16Float, vec![0.5; count], dimensions);
+
+        assert_eq!(file[..12], KTX2_IDENTIFIER);
+        // vkFormat, typeSize, pixel size, layers, faces, levels, supercompression
+        let header: Vec<u32> = (0..9).map(|i| u32_at(&file, 12 + i * 4)).collect();
+        assert_eq!(header, [76, 2, 3, 2, 4, 0, 1, 1, 0]);
+        // DFD offset and length, no key/value data
+        assert_eq!(u32_at(&file, 48), KTX2_DFD_OFFSET);
+        assert_eq!(u32_at(&file, 52), KTX2_DFD_LENGTH);
+        assert_eq!(u64_at(&file, 56), 0);
+        // No supercompression global data, then the level
+        assert_eq!(u64_at(&file, 64), 0);
+        assert_eq!(u64_at(&file, 72), 0);
+        let level_offset = u64_at(&file, 80) as usize;
+        let level_length = u64_at(&file, 88) as usize;
+        assert_eq!(level_offset, (KTX2_DFD_OFFSET + KTX2_DFD_LENGTH) as usize);
+        assert_eq!(level_length, count * 2);
+        assert_eq!(u64_at(&file, 96), level_length as u64);
+        assert_eq!(file.len(), level_offset + level_length);
+        assert_eq!(
+            file[level_offset..level_offset + 2],
+            sculpter_core::f32_to_f16_bits(0.5).to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn ktx2_dfd_describes_one_red_channel() {
+        for (format, bits, qualifiers) in [
+            (DensityTextureFormat::R32Float, 32, 0xC0),
+            (DensityTextureFormat::R16Float, 16, 0xC0),
+            (DensityTextureFormat::R8Snorm { range: 1.0 }, 8, 0x40),
+        ] {
+            let file = ktx2(format, vec![0.0; 8], UVec3::splat(2));
+            let dfd = KTX2_DFD_OFFSET as usize;
+            let word = |i: usize| u32_at(&file, dfd + i * 4);
+
+            assert_eq!(word(0), KTX2_DFD_LENGTH);
+            // Khronos basic block, version 2, sized without the total size
+            assert_eq!(word(1), 0);
+            assert_eq!(word(2), 2 | (KTX2_DFD_LENGTH - 4) << 16);
+            assert_eq!(word(3) & 0xFF, 1);
+            assert_eq!(word(5), format.texel_size());
+            // Red channel (id 0) over the whole texel, with its qualifiers
+            assert_eq!(word(7) & 0xFF, 0);
+            assert_eq!(word(7) >> 16 & 0xFF, bits - 1);
+            assert_eq!(word(7) >> 24, qualifiers);
+        }
+    }
+
+    #[test]
+    fn ktx2_snorm_maps_the_range() {
+        let range = 2.0;
+        let file = ktx2(
+            DensityTextureFormat::R8Snorm { range },
+            vec![-4.0, -2.0, -1.0, 0.0, 1.0, 2.0, 4.0, 0.01],
+            UVec3::new(2, 2, 2),
+        );
+        let data = &file[(KTX2_DFD_OFFSET + KTX2_DFD_LENGTH) as usize..];
+        let texels: Vec<i8> = data.iter().map(|&byte| byte as i8).collect();
+        assert_eq!(texels, [-127, -127, -64, 0, 64, 127, 127, 1]);
+    }
+}
@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use sculpter_core::transition::transition_faces;
+
+use crate::{
+    DensityField, DensityFieldSize,
+    dirty_region::Remesh,
+    mesh::{GeneratedMesh, Meshed},
+    quantize::DensityQuantization,
+    settings::MeshingAlgorithm,
+};
+
+/// Faces of a volume whose neighbour is meshed at half its resolution, e.g. the
+/// next LOD level of a chunked terrain. Each of them gets transition cells that
+/// join this volume's surface to the coarser one without cracks.
+///
+/// Only marching cubes volumes are stitched, and the neighbour has to be meshed
+/// with marching cubes from every other sample of the shared face. Changing the
+/// mask remeshes the volume.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Deref, DerefMut)]
+pub struct LodTransitions(pub u8);
+
+impl LodTransitions {
+    pub const POSITIVE_X: Self = Self(1 << 0);
+    pub const NEGATIVE_X: Self = Self(1 << 1);
+    pub const POSITIVE_Y: Self = Self(1 << 2);
+    pub const NEGATIVE_Y: Self = Self(1 << 3);
+    pub const POSITIVE_Z: Self = Self(1 << 4);
+    pub const NEGATIVE_Z: Self = Self(1 << 5);
+    pub const ALL: Self = Self(0b11_1111);
+
+    pub fn contains(self, faces: Self) -> bool {
+        self.0 & faces.0 == faces.0
+    }
+}
+
+impl std::ops::BitOr for LodTransitions {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Remesh volumes whose transition faces changed.
+pub fn remesh_changed_transitions(
+    mut commands: Commands,
+    query: Query<Entity, (Changed<LodTransitions>, With<Meshed>)>,
+) {
+    for entity in &query {
+        commands.entity(entity).try_insert(Remesh);
+    }
+}
+
+/// Add transition cells to freshly generated meshes, between readback and mesh
+/// building so GPU and CPU output are stitched alike.
+pub fn stitch_lod_transitions(
+    mut query: Query<(
+        &mut GeneratedMesh,
+        &LodTransitions,
+        &DensityField,
+        Option<&MeshingAlgorithm>,
+        Option<&DensityQuantization>,
+    )>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    for (mut generated, transitions, density_field, algorithm, quantization) in &mut query {
+        if transitions.0 == 0 {
+            continue;
+        }
+        if algorithm.copied().unwrap_or_default() != MeshingAlgorithm::MarchingCubes {
+            warn_once!("LodTransitions only stitch marching cubes volumes");
+            continue;
+        }
+
+        // Stitch against the densities the mesher saw
+        let densities: Vec<f32> = match quantization {
+            Some(q) => density_field
+                .iter()
+                .map(|&d| q.dequantize(q.quantize(d)))
+                .collect(),
+            None => density_field.0.clone(),
+        };
+        let faces = transition_faces(&densities, dimensions.0, transitions.0);
+        if !faces.faces.is_empty() {
+            generated.append(faces.to_mesh_data());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use bevy::{
+    mesh::MeshVertexBufferLayoutRef,
+    pbr::{ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline},
+    prelude::*,
+    render::render_resource::{
+        AsBindGroup, RenderPipelineDescriptor, SpecializedMeshPipelineError,
+    },
+    shader::ShaderRef,
+};
+
+use crate::mesh_data::ATTRIBUTE_MATERIAL_WEIGHTS;
+
+const TRIPLANAR_SHADER: &str = "shaders/triplanar.wgsl";
+
+/// `StandardMaterial` textured by triplanar projection of a texture array, one
+/// layer per material 0 to 3, blended by the mesh's `ATTRIBUTE_MATERIAL_WEIGHTS`.
+///
+/// Give volumes with a `MaterialField` a `MeshMaterial3d<TriplanarMaterial>` and
+/// they keep it across remeshes. The projected texture replaces the base color,
+/// lighting and the other `StandardMaterial` options apply as usual. Forward
+/// rendering only, the deferred path draws the plain `StandardMaterial`.
+pub type TriplanarMaterial = ExtendedMaterial<StandardMaterial, TriplanarExtension>;
+
+/// Triplanar splatting part of a `TriplanarMaterial`.
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct TriplanarExtension {
+    /// 2D array texture with (at least) 4 layers
+    #[texture(100, dimension = "2d_array")]
+    #[sampler(101)]
+    pub layers: Handle<Image>,
+    /// Texture repeats per world unit
+    #[uniform(102)]
+    pub scale: f32,
+    /// Exponent sharpening the blend between the three projection axes
+    #[uniform(103)]
+    pub sharpness: f32,
+}
+
+impl TriplanarExtension {
+    pub fn new(layers: Handle<Image>) -> Self {
+        Self {
+            layers,
+            scale: 0.25,
+            sharpness: 4.0,
+        }
+    }
+}
+
+impl MaterialExtension for TriplanarExtension {
+    fn fragment_shader() -> ShaderRef {
+        TRIPLANAR_SHADER.into()
+    }
+
+    // The weights ride in the vertex color slot, so the standard vertex shader
+    // forwards them. Prepasses don't need them.
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let prepass = descriptor
+            .vertex
+            .shader_defs
+            .contains(&"PREPASS_PIPELINE".into());
+        if prepass || !layout.0.contains(ATTRIBUTE_MATERIAL_WEIGHTS) {
+            return Ok(());
+        }
+
+        // Same locations as the mesh pipeline, see `MeshPipeline::specialize`
+        let mut attributes = vec![
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+        ];
+        if layout.0.contains(Mesh::ATTRIBUTE_UV_0) {
+            attributes.push(Mesh::ATTRIBUTE_UV_0.at_shader_location(2));
+        }
+        if layout.0.contains(Mesh::ATTRIBUTE_UV_1) {
+            attributes.push(Mesh::ATTRIBUTE_UV_1.at_shader_location(3));
+        }
+        if layout.0.contains(Mesh::ATTRIBUTE_TANGENT) {
+            attributes.push(Mesh::ATTRIBUTE_TANGENT.at_shader_location(4));
+        }
+        attributes.push(ATTRIBUTE_MATERIAL_WEIGHTS.at_shader_location(5));
+        descriptor.vertex.buffers = vec![layout.0.get_layout(&attributes)?];
+
+        descriptor.vertex.shader_defs.push("VERTEX_COLORS".into());
+        if let Some(fragment) = &mut descriptor.fragment {
+            fragment.shader_defs.push("VERTEX_COLORS".into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+use crate::{DensityFieldMeshSize, DensityFieldSize};
+
+/// Unit of physical spacing metadata.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum LengthUnit {
+    Micrometers,
+    Millimeters,
+    Centimeters,
+    #[default]
+    Meters,
+    Inches,
+    Feet,
+}
+
+impl LengthUnit {
+    /// Length of one unit in metres.
+    pub fn meters(self) -> f32 {
+        match self {
+            Self::Micrometers => 1e-6,
+            Self::Millimeters => 1e-3,
+            Self::Centimeters => 1e-2,
+            Self::Meters => 1.0,
+            Self::Inches => 0.0254,
+            Self::Feet => 0.3048,
+        }
+    }
+}
+
+/// Physical distance between neighbouring density samples, such as NRRD spacing
+/// in millimetres.
+///
+/// Volumes with this component are meshed at real-world scale (one Bevy unit is
+/// one metre) instead of being stretched to `DensityFieldMeshSize`. The original
+/// unit is kept so exports can be written back in it.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct VoxelSpacing {
+    /// Distance between samples along each axis, in `unit`
+    pub spacing: Vec3,
+    pub unit: LengthUnit,
+}
+
+impl VoxelSpacing {
+    pub fn new(spacing: Vec3, unit: LengthUnit) -> Self {
+        Self { spacing, unit }
+    }
+
+    /// Same spacing along every axis.
+    pub fn uniform(spacing: f32, unit: LengthUnit) -> Self {
+        Self::new(Vec3::splat(spacing), unit)
+    }
+
+    /// Spacing in metres, the scale applied to grid-space mesh positions.
+    pub fn in_meters(&self) -> Vec3 {
+        self.spacing * self.unit.meters()
+    }
+
+    /// Distance between samples in metres of a volume with `dimensions`
+    /// samples along each axis: its `spacing`, or else `mesh_size` spread over
+    /// the samples. The scale every grid-space position of the volume is
+    /// placed in its local space by.
+    pub fn volume_scale(
+        spacing: Option<&Self>,
+        mesh_size: DensityFieldMeshSize,
+        dimensions: UVec3,
+    ) -> Vec3 {
+        spacing.map_or(*mesh_size / dimensions.as_vec3(), Self::in_meters)
+    }
+
+    /// The same spacing expressed in another unit.
+    pub fn to_unit(&self, unit: LengthUnit) -> Self {
+        Self::new(self.in_meters() / unit.meters(), unit)
+    }
+
+    /// Mesh size giving the same scale when set as the `DensityFieldMeshSize`
+    /// resource, for fields of the given dimensions.
+    pub fn mesh_size(&self, dimensions: DensityFieldSize) -> DensityFieldMeshSize {
+        DensityFieldMeshSize(self.in_meters() * dimensions.as_vec3())
+    }
+}
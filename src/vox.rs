@@ -0,0 +1,231 @@
+use std::fmt;
+
+use bevy::{
+    asset::{AssetEvent, AssetLoader, LoadContext, io::Reader},
+    platform::collections::HashSet,
+    prelude::*,
+};
+use sculpter_core::{CoordinateSystem, grid};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DensityFieldSize,
+    material::{MaterialField, MaterialPalette},
+    settings::MeshingAlgorithm,
+    voxel_grid::VoxelGrid,
+};
+
+/// A model from a MagicaVoxel `.vox` file, in Bevy's axes.
+///
+/// Loading `model.vox` gives the file's first model, and `model.vox#Model1`
+/// and so on the others. Scene transforms between the models are ignored.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct VoxModel {
+    pub size: UVec3,
+    /// Palette index per voxel, x fastest, 0 for empty voxels
+    pub voxels: Vec<u8>,
+    /// Color per palette index, empty if the file has no palette
+    pub palette: Vec<Color>,
+}
+
+/// Voxels of a `VoxModel`, filling the volume's `VoxelGrid` from its minimum
+/// corner with the palette index as material. Models larger than
+/// `DensityFieldSize` are cut off.
+#[derive(Component, Clone, Debug)]
+#[require(MeshingAlgorithm = MeshingAlgorithm::Blocky)]
+pub struct VoxDensitySource(pub Handle<VoxModel>);
+
+/// Loads `.vox` files as `VoxModel`s.
+#[derive(Default)]
+pub struct VoxLoader;
+
+/// How `VoxLoader` reads a `.vox` file.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct VoxLoaderSettings {
+    /// Axes of the file, Z-up and right-handed as MagicaVoxel saves them by default
+    pub coordinate_system: CoordinateSystem,
+}
+
+impl Default for VoxLoaderSettings {
+    fn default() -> Self {
+        Self {
+            coordinate_system: CoordinateSystem::Z_UP_RIGHT_HANDED,
+        }
+    }
+}
+
+/// Why a `.vox` file couldn't be loaded.
+#[derive(Debug)]
+pub enum VoxError {
+    Io(std::io::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read .vox file: {error}"),
+            Self::Invalid(reason) => write!(f, "invalid .vox file: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+impl From<std::io::Error> for VoxError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl AssetLoader for VoxLoader {
+    type Asset = VoxModel;
+    type Settings = VoxLoaderSettings;
+    type Error = VoxError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &VoxLoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<VoxModel, VoxError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut models = parse_vox(&bytes, settings.coordinate_system)?.into_iter();
+        let first = models.next().ok_or(VoxError::Invalid("no models"))?;
+        for (index, model) in models.enumerate() {
+            load_context.add_labeled_asset(format!("Model{}", index + 1), model);
+        }
+        Ok(first)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vox"]
+    }
+}
+
+/// Split `count` bytes off the front of `input`.
+fn take<'a>(input: &mut &'a [u8], count: usize) -> Result<&'a [u8], VoxError> {
+    if input.len() < count {
+        return Err(VoxError::Invalid("unexpected end of file"));
+    }
+    let (taken, rest) = input.split_at(count);
+    *input = rest;
+    Ok(taken)
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, VoxError> {
+    let bytes = take(input, 4)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// The next chunk's id, content and children.
+fn chunk<'a>(input: &mut &'a [u8]) -> Result<(&'a [u8], &'a [u8], &'a [u8]), VoxError> {
+    let id = take(input, 4)?;
+    let content_size = read_u32(input)? as usize;
+    let children_size = read_u32(input)? as usize;
+    Ok((id, take(input, content_size)?, take(input, children_size)?))
+}
+
+/// Every model of a `.vox` file, with the file's palette, converted from
+/// `coordinate_system` to Bevy's axes.
+fn parse_vox(bytes: &[u8], coordinate_system: CoordinateSystem) -> Result<Vec<VoxModel>, VoxError> {
+    let mut input = bytes;
+    if take(&mut input, 4)? != b"VOX " {
+        return Err(VoxError::Invalid("not a MagicaVoxel file"));
+    }
+    let _version = read_u32(&mut input)?;
+    let (id, _, mut children) = chunk(&mut input)?;
+    if id != b"MAIN" {
+        return Err(VoxError::Invalid("no MAIN chunk"));
+    }
+
+    let mut models = Vec::new();
+    let mut size = None;
+    let mut palette = Vec::new();
+    while !children.is_empty() {
+        let (id, mut content, _) = chunk(&mut children)?;
+        match id {
+            b"SIZE" => {
+                size = Some(uvec3(
+                    read_u32(&mut content)?,
+                    read_u32(&mut content)?,
+                    read_u32(&mut content)?,
+                ));
+            }
+            b"XYZI" => {
+                let size = size
+                    .take()
+                    .ok_or(VoxError::Invalid("XYZI chunk without SIZE"))?;
+                let mut voxels = vec![0; size.element_product() as usize];
+                let count = read_u32(&mut content)? as usize;
+                for voxel in take(&mut content, count * 4)?.chunks_exact(4) {
+                    let [x, y, z, index] = [voxel[0], voxel[1], voxel[2], voxel[3]].map(u32::from);
+                    if x >= size.x || y >= size.y || z >= size.z {
+                        continue;
+                    }
+                    voxels[grid::index(size, x, y, z) as usize] = index as u8;
+                }
+                let (voxels, size) = coordinate_system.volume_to_bevy(&voxels, size);
+                models.push(VoxModel {
+                    size,
+                    voxels,
+                    palette: Vec::new(),
+                });
+            }
+            b"RGBA" => {
+                // Palette index i is stored at i - 1, index 0 is empty
+                palette = std::iter::once(Color::NONE)
+                    .chain(
+                        content
+                            .chunks_exact(4)
+                            .take(255)
+                            .map(|rgba| Color::srgba_u8(rgba[0], rgba[1], rgba[2], rgba[3])),
+                    )
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    for model in &mut models {
+        model.palette = palette.clone();
+    }
+    Ok(models)
+}
+
+/// Fill volumes whose `VoxDensitySource` or model changed with the model's
+/// voxels, materials and palette.
+pub fn sync_vox_models(
+    mut commands: Commands,
+    mut model_events: MessageReader<AssetEvent<VoxModel>>,
+    sources: Query<(Entity, Ref<VoxDensitySource>)>,
+    models: Res<Assets<VoxModel>>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let changed_models: HashSet<AssetId<VoxModel>> = model_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, source) in &sources {
+        if !source.is_changed() && !changed_models.contains(&source.0.id()) {
+            continue;
+        }
+        // Filled once it has loaded
+        let Some(model) = models.get(&source.0) else {
+            continue;
+        };
+
+        let voxels = grid::resize(&model.voxels, model.size, dimensions.0, 0);
+        let materials = voxels.iter().map(|&index| index as u32).collect();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert((VoxelGrid(voxels), MaterialField(materials)));
+        if !model.palette.is_empty() {
+            entity_commands.try_insert(MaterialPalette(model.palette.clone()));
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+
+use crate::{
+    DensityField, DensityFieldSize, dirty_region::DensityFieldDirtyRegion, mesh::Meshed,
+    settings::MeshingAlgorithm,
+};
+
+/// A value stored per voxel of a `VoxelGrid`.
+pub trait Voxel: Copy + Default + PartialEq + Send + Sync + 'static {
+    /// Whether the voxel is filled. The default value is empty.
+    fn is_solid(self) -> bool {
+        self != Self::default()
+    }
+}
+
+impl Voxel for u8 {}
+impl Voxel for u16 {}
+
+/// Discrete voxels, e.g. block ids, meshed as cubes with `MeshingAlgorithm::Blocky`.
+///
+/// Laid out like a `DensityField` (x fastest, `DensityFieldSize` dimensions), with
+/// zero for empty voxels. The plugin keeps a `DensityField` in sync with it, so
+/// it goes through the usual upload, readback and mesh building, and editing the
+/// grid remeshes the volume.
+#[derive(Component, Clone, Debug, Deref, DerefMut)]
+#[require(MeshingAlgorithm = MeshingAlgorithm::Blocky)]
+pub struct VoxelGrid<T: Voxel = u16>(pub Vec<T>);
+
+/// Mirror changed voxel grids into their volume's `DensityField`, solid voxels
+/// negative, and remesh volumes that were already meshed.
+pub fn sync_voxel_grids<T: Voxel>(
+    mut commands: Commands,
+    grids: Query<(Entity, &VoxelGrid<T>, Has<Meshed>), Changed<VoxelGrid<T>>>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    for (entity, grid, meshed) in &grids {
+        let densities = grid
+            .iter()
+            .map(|voxel| if voxel.is_solid() { -1.0 } else { 1.0 })
+            .collect();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_insert(DensityField(densities));
+        if meshed {
+            entity_commands.try_insert(DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0));
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::sync::Arc;
+
+use bevy::{
+    asset::AssetEvent,
+    mesh::{PrimitiveTopology, VertexAttributeValues},
+    platform::collections::HashSet,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on},
+};
+use sculpter_core::{voxelize, voxelize_slab};
+
+use crate::{
+    DensityField, DensityFieldSize,
+    clipboard::FieldClipboard,
+    csg::{CsgOperation, CsgSource},
+    dirty_region::DensityFieldDirtyRegion,
+    mesh::Meshed,
+    units::{LengthUnit, VoxelSpacing},
+};
+
+// Distances are exact this many voxels from the surface, plenty for meshing
+// and for sculpting brushes that blend into the field
+const DISTANCE_BAND: f32 = 3.0;
+
+/// Voxelize a closed triangle mesh into the volume's `DensityField`, as the
+/// signed distance to its surface, making any model sculptable.
+///
+/// The mesh's bounds are centered in the field and the volume gets the
+/// `VoxelSpacing` that keeps the model its size, with mesh units taken as
+/// metres. The conversion runs in the background once the mesh has loaded, and
+/// again whenever it changes.
+#[derive(Component, Clone, Debug)]
+pub struct VoxelizeMesh {
+    pub mesh: Handle<Mesh>,
+    /// Distance between samples in mesh units, or `None` to fit the mesh into
+    /// `DensityFieldSize` with a sample of margin on every side
+    pub voxel_size: Option<f32>,
+}
+
+impl VoxelizeMesh {
+    pub fn new(mesh: Handle<Mesh>) -> Self {
+        Self {
+            mesh,
+            voxel_size: None,
+        }
+    }
+}
+
+/// Voxelization running on the `AsyncComputeTaskPool`, a task per slab of the
+/// field along z.
+#[derive(Component)]
+pub struct VoxelizationTask {
+    slabs: Vec<Task<Vec<f32>>>,
+    voxel_size: f32,
+}
+
+/// Start voxelizing meshes whose `VoxelizeMesh` or mesh changed. A volume
+/// revoxelized while its conversion runs starts over, dropping the stale tasks.
+pub fn start_voxelization(
+    mut commands: Commands,
+    mut mesh_events: MessageReader<AssetEvent<Mesh>>,
+    sources: Query<(Entity, Ref<VoxelizeMesh>)>,
+    meshes: Res<Assets<Mesh>>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let changed_meshes: HashSet<AssetId<Mesh>> = mesh_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, source) in &sources {
+        if !source.is_changed() && !changed_meshes.contains(&source.mesh.id()) {
+            continue;
+        }
+        // Voxelized once it has loaded
+        let Some(mesh) = meshes.get(&source.mesh) else {
+            continue;
+        };
+        let Some(triangles) = mesh_triangles(mesh) else {
+            warn!("Can't voxelize the mesh of {entity}: it needs triangles with positions");
+            continue;
+        };
+
+        let dims = dimensions.0;
+        let (min, max) = triangles
+            .iter()
+            .flatten()
+            .fold((Vec3::MAX, Vec3::MIN), |(min, max), &p| {
+                (min.min(p), max.max(p))
+            });
+        let voxel_size = source.voxel_size.unwrap_or_else(|| {
+            let room = dims
+                .saturating_sub(UVec3::splat(3))
+                .max(UVec3::ONE)
+                .as_vec3();
+            ((max - min) / room).max_element().max(f32::EPSILON)
+        });
+        // Mesh space to grid space, the bounds' center on the field's
+        let origin = (min + max) / 2.0 - (dims.as_vec3() - 1.0) / 2.0 * voxel_size;
+        let triangles: Arc<Vec<[Vec3; 3]>> = Arc::new(
+            triangles
+                .into_iter()
+                .map(|triangle| triangle.map(|p| (p - origin) / voxel_size))
+                .collect(),
+        );
+
+        let pool = AsyncComputeTaskPool::get();
+        let depth = dims.z.div_ceil(pool.thread_num().max(1) as u32).max(1);
+        let slabs = (0..dims.z)
+            .step_by(depth as usize)
+            .map(|start| {
+                let triangles = triangles.clone();
+                let slab = start..(start + depth).min(dims.z);
+                pool.spawn(async move { voxelize_slab(&triangles, dims, slab, DISTANCE_BAND) })
+            })
+            .collect();
+        commands
+            .entity(entity)
+            .try_insert(VoxelizationTask { slabs, voxel_size });
+    }
+}
+
+/// Write finished voxelizations into their volumes, remeshing volumes that were
+/// already meshed.
+pub fn poll_voxelization_tasks(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut VoxelizationTask, Has<Meshed>)>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    for (entity, mut task, meshed) in &mut tasks {
+        if !task.slabs.iter().all(Task::is_finished) {
+            continue;
+        }
+        let densities: Vec<f32> = task.slabs.drain(..).flat_map(block_on).collect();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_remove::<VoxelizationTask>();
+        // Stale if the field was resized meanwhile, a new task is on its way
+        if densities.len() != dimensions.density_count() as usize {
+            continue;
+        }
+        entity_commands.try_insert((
+            DensityField(densities),
+            VoxelSpacing::uniform(task.voxel_size, LengthUnit::Meters),
+        ));
+        if meshed {
+            entity_commands.try_insert(DensityFieldDirtyRegion::new(UVec3::ZERO, dimensions.0));
+        }
+    }
+}
+
+/// Trigger on a volume to press a closed triangle mesh into its densities with
+/// a CSG operation, e.g. a prefab arch or statue added to terrain or a tunnel
+/// carved out of it, and remesh it. The mesh must have loaded.
+#[derive(EntityEvent, Clone, Debug)]
+pub struct StampMesh {
+    pub entity: Entity,
+    pub mesh: Handle<Mesh>,
+    /// Placement of the mesh in the volume's grid space, mesh units scaled
+    /// into samples
+    pub transform: Transform,
+    pub operation: CsgOperation,
+}
+
+/// Voxelize `mesh`, placed in the `dimensions` field's grid space by
+/// `transform`, and composite it into the field with `operation`, on the
+/// calling thread.
+///
+/// Returns the region to mark dirty for the volume to be remeshed, or `None`
+/// if the mesh has no triangles or misses the field.
+pub fn stamp_mesh(
+    density_field: &mut DensityField,
+    dimensions: UVec3,
+    mesh: &Mesh,
+    transform: Transform,
+    operation: CsgOperation,
+) -> Option<DensityFieldDirtyRegion> {
+    let (clipboard, min) = voxelize_stamp(mesh, transform, dimensions)?;
+    density_field.composite(
+        dimensions,
+        &CsgSource::Clipboard(Arc::new(clipboard)),
+        Transform::from_translation(min.as_vec3()),
+        operation,
+    )
+}
+
+/// The mesh voxelized over the box of the field around it, and the box's
+/// first sample, or `None` if it has no triangles or misses the field.
+fn voxelize_stamp(
+    mesh: &Mesh,
+    transform: Transform,
+    dimensions: UVec3,
+) -> Option<(FieldClipboard, UVec3)> {
+    let affine = transform.compute_affine();
+    let triangles: Vec<[Vec3; 3]> = mesh_triangles(mesh)?
+        .into_iter()
+        .map(|triangle| triangle.map(|p| affine.transform_point3(p)))
+        .collect();
+    let (min, max) = triangles
+        .iter()
+        .flatten()
+        .fold((Vec3::MAX, Vec3::MIN), |(min, max), &p| {
+            (min.min(p), max.max(p))
+        });
+    // Room for the distance band, within the field
+    let min = (min - DISTANCE_BAND).floor().max(Vec3::ZERO).as_uvec3();
+    let max = ((max + DISTANCE_BAND).ceil() + 1.0)
+        .min(dimensions.as_vec3())
+        .as_uvec3();
+    let size = max.saturating_sub(min);
+    if size.cmplt(UVec3::splat(2)).any() {
+        return None;
+    }
+    let offset = min.as_vec3();
+    let triangles: Vec<[Vec3; 3]> = triangles
+        .into_iter()
+        .map(|triangle| triangle.map(|p| p - offset))
+        .collect();
+    let densities = voxelize(&triangles, size, DISTANCE_BAND);
+    Some((
+        FieldClipboard {
+            dimensions: size,
+            densities: Arc::new(DensityField(densities)),
+        },
+        min,
+    ))
+}
+
+/// Stamp the mesh of a `StampMesh` into its volume, compositing it as a
+/// `CompositeDensity` so `GpuDensity` volumes are stamped too.
+pub fn stamp_mesh_into_volume(
+    stamp: On<StampMesh>,
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    dimensions: Res<DensityFieldSize>,
+) {
+    let entity = stamp.entity;
+    let Some(mesh) = meshes.get(&stamp.mesh) else {
+        warn!("Can't stamp a mesh into {entity}: it hasn't loaded");
+        return;
+    };
+    let Some((clipboard, min)) = voxelize_stamp(mesh, stamp.transform, dimensions.0) else {
+        return;
+    };
+    commands.trigger(Arc::new(clipboard).paste(entity, min, stamp.operation));
+}
+
+/// The positions of every triangle of a triangle list mesh.
+fn mesh_triangles(mesh: &Mesh) -> Option<Vec<[Vec3; 3]>> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return None;
+    }
+    let positions = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(VertexAttributeValues::as_float3)?;
+    let position = |index: usize| positions.get(index).copied().map(Vec3::from);
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(indices) => indices.iter().collect(),
+        None => (0..positions.len()).collect(),
+    };
+    indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            Some([
+                position(triangle[0])?,
+                position(triangle[1])?,
+                position(triangle[2])?,
+            ])
+        })
+        .collect()
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, render::gpu_readback::Readback};
+
+use crate::{
+    backpressure::{CriticalRemesh, GpuGenerationStarted},
+    dirty_region::Remesh,
+    readback::ReadbackBuffers,
+};
+
+/// Limits on how long a GPU readback may take before it is considered lost
+/// (device lost, swapped adapter) and retried.
+#[derive(Resource, Clone, Debug)]
+pub struct ReadbackWatchdog {
+    /// Time allowed for a readback to complete
+    pub timeout: Duration,
+    /// Readbacks requested again after a timeout before the generation fails
+    pub max_retries: u32,
+}
+
+impl Default for ReadbackWatchdog {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max_retries: 2,
+        }
+    }
+}
+
+/// Time (since startup) at which the current readback was requested.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ReadbackStarted(pub Duration);
+
+/// Readbacks of the current generation that timed out.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ReadbackRetries(pub u32);
+
+/// Marks a volume whose generation failed. It is skipped until this is removed;
+/// remove it to try again (and insert `Remesh` if the volume already had a mesh).
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct MeshingFailed;
+
+/// Triggered on a volume when its readback timed out more than
+/// `ReadbackWatchdog::max_retries` times.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct GenerationFailed {
+    pub entity: Entity,
+    /// Readbacks requested, including the first
+    pub attempts: u32,
+}
+
+/// Retry readbacks that didn't complete in time, failing the generation after
+/// too many attempts.
+pub fn watch_readbacks(
+    mut commands: Commands,
+    pending: Query<
+        (
+            Entity,
+            &ReadbackStarted,
+            Option<&ReadbackRetries>,
+            Option<&Children>,
+        ),
+        With<ReadbackBuffers>,
+    >,
+    readbacks: Query<(), With<Readback>>,
+    watchdog: Res<ReadbackWatchdog>,
+    time: Res<Time<Real>>,
+) {
+    for (entity, started, retries, children) in &pending {
+        if time.elapsed().saturating_sub(started.0) < watchdog.timeout {
+            continue;
+        }
+
+        // Drop the lost readbacks, their observers would never fire
+        for child in children.into_iter().flatten() {
+            if readbacks.contains(*child) {
+                commands.entity(*child).try_despawn();
+            }
+        }
+
+        let retries = retries.map_or(0, |retries| retries.0);
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.try_remove::<(ReadbackBuffers, ReadbackStarted)>();
+        if retries < watchdog.max_retries {
+            // Without `ReadbackBuffers` a new readback is requested next frame
+            warn!("Readback for {entity} timed out, retrying");
+            entity_commands.try_insert(ReadbackRetries(retries + 1));
+        } else {
+            warn!(
+                "Readback for {entity} timed out {} times, giving up",
+                retries + 1
+            );
+            entity_commands
+                .try_remove::<(
+                    ReadbackRetries,
+                    GpuGenerationStarted,
+                    Remesh,
+                    CriticalRemesh,
+                )>()
+                .try_insert(MeshingFailed);
+            commands.trigger(GenerationFailed {
+                entity,
+                attempts: retries + 1,
+            });
+        }
+    }
+}
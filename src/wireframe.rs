@@ -0,0 +1,93 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    mesh::{Indices, PrimitiveTopology},
+    prelude::*,
+};
+
+use crate::{
+    dirty_region::Remesh,
+    mesh::{Meshed, update_bounds},
+    mesh_data::MeshData,
+};
+
+/// Also draw the volume's mesh edges as lines on a child entity, to debug surface
+/// nets connectivity. Quads show as their four cell-dual edges, without the
+/// diagonal splitting them.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct DebugWireframe {
+    /// Unlit line color
+    pub color: Color,
+}
+
+impl Default for DebugWireframe {
+    fn default() -> Self {
+        Self {
+            color: Color::srgb(1.0, 0.8, 0.0),
+        }
+    }
+}
+
+/// Child entity drawing its parent volume's `DebugWireframe`.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct DebugWireframeMesh;
+
+/// Remesh volumes whose `DebugWireframe` was added, changed or removed.
+pub fn remesh_changed_wireframes(
+    mut commands: Commands,
+    changed: Query<Entity, (Changed<DebugWireframe>, With<Meshed>)>,
+    mut removed: RemovedComponents<DebugWireframe>,
+    meshed: Query<(), With<Meshed>>,
+) {
+    let removed = removed.read().filter(|&entity| meshed.contains(entity));
+    for entity in changed.iter().chain(removed) {
+        commands.entity(entity).try_insert(Remesh);
+    }
+}
+
+/// Update, spawn or despawn the wireframe child of `volume` to match
+/// `wireframe`, drawing the edges of `mesh_data`.
+pub(crate) fn build_debug_wireframe(
+    commands: &mut Commands,
+    volume: Entity,
+    mesh_data: &MeshData,
+    wireframe: Option<&DebugWireframe>,
+    children: Option<&Children>,
+    wireframes: &Query<&Mesh3d, With<DebugWireframeMesh>>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    let mut existing = children
+        .into_iter()
+        .flatten()
+        .filter_map(|&child| Some((child, wireframes.get(child).ok()?)));
+    let Some(wireframe) = wireframe else {
+        for (child, _) in existing {
+            commands.entity(child).try_despawn();
+        }
+        return;
+    };
+
+    let edges = sculpter_core::wireframe_edges(&mesh_data.positions, &mesh_data.indices);
+    let mut lines = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default());
+    lines.insert_attribute(Mesh::ATTRIBUTE_POSITION, mesh_data.positions.clone());
+    lines.insert_indices(Indices::U32(edges.into_iter().flatten().collect()));
+    let material = MeshMaterial3d(materials.add(StandardMaterial {
+        base_color: wireframe.color,
+        unlit: true,
+        ..default()
+    }));
+
+    match existing.next() {
+        Some((child, Mesh3d(handle))) => {
+            let mut child = commands.entity(child);
+            update_bounds(&mut child, &lines);
+            child.try_insert(material);
+            let _ = meshes.insert(handle, lines);
+        }
+        None => {
+            let mut child = commands.spawn((material, DebugWireframeMesh, ChildOf(volume)));
+            update_bounds(&mut child, &lines);
+            child.insert(Mesh3d(meshes.add(lines)));
+        }
+    }
+}